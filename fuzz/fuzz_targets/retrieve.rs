@@ -0,0 +1,24 @@
+//! Fuzz the handling of entry names by `Store::retrieve()`
+//!
+//! Run with `cargo +nightly fuzz run retrieve` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use libpass::{names, Store, StoreEntry};
+
+fuzz_target!(|pass_name: &str| {
+    let store = Store::open(concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/simple")).unwrap();
+
+    if let Ok(normalized) = names::normalize_name(pass_name) {
+        assert_eq!(names::normalize_name(&normalized).unwrap(), normalized);
+        assert!(!normalized.split('/').any(|segment| segment == ".."));
+    }
+
+    // whatever is retrieved must be located inside of the store
+    match store.retrieve(pass_name) {
+        Ok(StoreEntry::File(file)) => assert!(file.path.starts_with(store.root())),
+        Ok(StoreEntry::Directory(dir)) => assert!(dir.path.starts_with(store.root())),
+        Err(_) => {}
+    }
+});
@@ -0,0 +1,200 @@
+//! Description of who can decrypt which parts of a store
+//!
+//! Team stores usually grant access to subtrees by placing `.gpg-id` files with different recipients in their
+//! directories.
+//! [`Store::access_map()`](crate::Store::access_map) walks the whole tree and reports for every directory
+//! which `.gpg-id` file governs it and which keys (and user ids) are able to decrypt its entries.
+//! Only the local keyring is consulted so recipients whose keys are not imported are reported as unresolved.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::Store;
+//!
+//! let access = Store::from_env().unwrap().access_map().unwrap();
+//! for dir in access.directories() {
+//!     let users = dir.keys.iter().flat_map(|key| &key.user_ids).collect::<Vec<_>>();
+//!     println!("{}/: {:?}", dir.name, users);
+//! }
+//! ```
+
+use crate::crypto::CryptoOptions;
+use crate::{names, GpgIdFile, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A key which is able to decrypt the entries of a directory
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct AccessKey {
+    /// The id of the key
+    pub key_id: String,
+    /// The fingerprint of the key
+    pub fingerprint: String,
+    /// All user ids of the key, e.g. `John Doe <john@example.com>`
+    pub user_ids: Vec<String>,
+}
+
+impl AccessKey {
+    /// Describe *key* of the local keyring
+    pub(crate) fn of(key: &gpgme::Key) -> Self {
+        Self {
+            key_id: key.id().unwrap_or_default().to_string(),
+            fingerprint: key.fingerprint().unwrap_or_default().to_string(),
+            user_ids: key
+                .user_ids()
+                .filter_map(|uid| uid.id().ok().map(str::to_string))
+                .collect(),
+        }
+    }
+}
+
+/// The recipients which govern a directory of a store
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DirectoryAccess {
+    /// The name of the directory relative to the store root (empty for the root itself)
+    pub name: String,
+    /// The `.gpg-id` file governing the directory or `None` if the directory is not covered by any
+    pub gpg_id: Option<PathBuf>,
+    /// The recipients as listed in the governing `.gpg-id` file
+    pub recipients: Vec<String>,
+    /// The keys to which the recipients resolve in the local keyring
+    pub keys: Vec<AccessKey>,
+    /// The recipients which could not be found in the local keyring
+    pub unresolved: Vec<String>,
+}
+
+/// The access matrix of a store as returned by [`Store::access_map()`](crate::Store::access_map)
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct AccessMap {
+    directories: Vec<DirectoryAccess>,
+}
+
+impl AccessMap {
+    /// All directories of the store sorted by their name
+    pub fn directories(&self) -> &[DirectoryAccess] {
+        &self.directories
+    }
+
+    /// The access description of the directory *name*
+    pub fn directory(&self, name: &str) -> Option<&DirectoryAccess> {
+        let name = names::normalize_name(name).ok()?;
+        self.directories.iter().find(|dir| dir.name == name)
+    }
+
+    /// The names of all directories whose entries can be decrypted by the key *key_id*
+    ///
+    /// *key_id* may be a key id or fingerprint with or without a `0x` prefix.
+    pub fn directories_of(&self, key_id: &str) -> Vec<&str> {
+        let key_id = key_id.trim_start_matches("0x").to_uppercase();
+        self.directories
+            .iter()
+            .filter(|dir| {
+                dir.keys.iter().any(|key| {
+                    key.key_id.eq_ignore_ascii_case(&key_id)
+                        || key.fingerprint.to_uppercase().ends_with(&key_id)
+                })
+            })
+            .map(|dir| dir.name.as_str())
+            .collect()
+    }
+}
+
+/// Resolves recipients to keys of the local keyring and caches the results
+struct Resolver {
+    gpg_ctx: gpgme::Context,
+    cache: HashMap<String, Vec<AccessKey>>,
+}
+
+impl Resolver {
+    /// All keys of the local keyring which match *recipient*
+    fn resolve(&mut self, recipient: &str) -> Result<Vec<AccessKey>> {
+        if let Some(keys) = self.cache.get(recipient) {
+            return Ok(keys.clone());
+        }
+        let keys = self
+            .gpg_ctx
+            .find_keys([recipient])?
+            .filter_map(|key| key.ok())
+            .map(|key| AccessKey::of(&key))
+            .collect::<Vec<_>>();
+        self.cache.insert(recipient.to_string(), keys.clone());
+        Ok(keys)
+    }
+}
+
+/// Describe the access to every directory below *root*
+/// using gpg contexts configured according to *crypto*
+pub(crate) fn access_map(root: &Path, crypto: &CryptoOptions) -> Result<AccessMap> {
+    let mut resolver = Resolver {
+        gpg_ctx: crypto.new_context()?,
+        cache: HashMap::new(),
+    };
+    let mut directories = Vec::new();
+    visit(root, root, None, &mut resolver, &mut directories)?;
+    directories.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    Ok(AccessMap { directories })
+}
+
+/// Describe *dir* and all of its subdirectories which inherit the recipients of *inherited* unless they
+/// have their own `.gpg-id` file
+fn visit(
+    root: &Path,
+    dir: &Path,
+    inherited: Option<&DirectoryAccess>,
+    resolver: &mut Resolver,
+    directories: &mut Vec<DirectoryAccess>,
+) -> Result<()> {
+    let gpg_id_path = dir.join(".gpg-id");
+    let name = if dir == root {
+        String::new()
+    } else {
+        names::path_to_name(root, dir)?
+    };
+    let access = if gpg_id_path.is_file() {
+        let recipients = GpgIdFile::read(&gpg_id_path)?.recipients().to_vec();
+        let mut keys = Vec::new();
+        let mut unresolved = Vec::new();
+        for recipient in &recipients {
+            let resolved = resolver.resolve(recipient)?;
+            if resolved.is_empty() {
+                unresolved.push(recipient.clone());
+            }
+            for key in resolved {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+        DirectoryAccess {
+            name,
+            gpg_id: Some(gpg_id_path),
+            recipients,
+            keys,
+            unresolved,
+        }
+    } else {
+        match inherited {
+            Some(inherited) => DirectoryAccess {
+                name,
+                ..inherited.clone()
+            },
+            None => DirectoryAccess {
+                name,
+                gpg_id: None,
+                recipients: Vec::new(),
+                keys: Vec::new(),
+                unresolved: Vec::new(),
+            },
+        }
+    };
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() && path.file_name() != Some(".git".as_ref()) {
+            visit(root, &path, Some(&access), resolver, directories)?;
+        }
+    }
+    directories.push(access);
+    Ok(())
+}
@@ -0,0 +1,398 @@
+//! A shared cache of decrypted entries for short-lived processes
+//!
+//! Every decryption goes through gpg-agent which can be slow, especially with smartcards.
+//! Applications which are started many times in a row (like command line tools) can avoid that by running an
+//! [`Agent`] which keeps recently decrypted entries in locked memory for a limited time and serves them over a
+//! unix socket.
+//! A store which is configured via [`Store::with_agent()`](crate::Store::with_agent) transparently consults
+//! the agent before decrypting entries itself and hands newly decrypted entries to it.
+//!
+//! Entries are cached by path and SHA-256 hash of their ciphertext so that changed entries are never served
+//! from the cache, and they are erased from memory at most a second after they expired.
+//! The socket is only accessible by the current user, however every process of that user can retrieve
+//! cached entries while they are cached.
+//!
+//! This module is only available with the `agent` feature on unix systems.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::agent::{self, Agent};
+//! use libpass::Store;
+//! use std::time::Duration;
+//!
+//! let socket = agent::default_socket_path().unwrap();
+//!
+//! // in short-lived processes
+//! let store = Store::from_env().unwrap().with_agent(&socket);
+//!
+//! // in a long-running process
+//! let agent = Agent::bind(&socket, Duration::from_secs(300)).unwrap();
+//! agent.serve().unwrap();
+//! ```
+
+use crate::context_pool::ContextPool;
+use crate::crypto::CryptoOptions;
+use crate::secure_mem::{self, LockedRegion};
+use crate::snapshot::hash_of;
+use crate::telemetry::debug;
+use crate::{utils, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Upper bound for the size of a single message so that clients can not exhaust the agents memory
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Time after which clients give up waiting for the agent
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time after which the agent closes connections of clients which stopped talking to it
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Upper bound for the number of connections which the agent serves at the same time
+const MAX_CONNECTIONS: usize = 16;
+
+/// Bounds for the interval in which expired entries are erased
+const MIN_SWEEP_INTERVAL: Duration = Duration::from_millis(10);
+const MAX_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The socket path which is used by default (`$XDG_RUNTIME_DIR/libpass/agent.sock`)
+///
+/// `None` is returned if the platform has no per-user runtime directory.
+pub fn default_socket_path() -> Option<PathBuf> {
+    directories::BaseDirs::new()
+        .and_then(|dirs| dirs.runtime_dir().map(Path::to_path_buf))
+        .map(|dir| dir.join("libpass").join("agent.sock"))
+}
+
+/// A decrypted entry which is held by the agent
+#[derive(Debug)]
+struct CachedEntry {
+    plaintext: Vec<u8>,
+    locked: LockedRegion,
+    expires: Instant,
+}
+
+impl Drop for CachedEntry {
+    fn drop(&mut self) {
+        secure_mem::release(&mut self.plaintext, &mut self.locked);
+    }
+}
+
+type Cache = Arc<Mutex<HashMap<String, CachedEntry>>>;
+
+/// Lock *cache*, ignoring poisoning since the cache is still usable after a thread panicked while holding it
+fn lock(
+    cache: &Mutex<HashMap<String, CachedEntry>>,
+) -> MutexGuard<'_, HashMap<String, CachedEntry>> {
+    cache
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Erase expired entries from *cache* every *interval* until the agent and all its connections are gone
+fn sweep(cache: Weak<Mutex<HashMap<String, CachedEntry>>>, interval: Duration) {
+    loop {
+        thread::sleep(interval);
+        let cache = match cache.upgrade() {
+            Some(cache) => cache,
+            None => return,
+        };
+        let now = Instant::now();
+        lock(&cache).retain(|_, entry| entry.expires > now);
+    }
+}
+
+/// Counts an open connection of an [`Agent`] until it is dropped
+struct Connection(Arc<AtomicUsize>);
+
+impl Connection {
+    /// Count a new connection in *open* or return `None` if the maximum number of connections is reached
+    fn open(open: &Arc<AtomicUsize>) -> Option<Self> {
+        open.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+            (count < MAX_CONNECTIONS).then_some(count + 1)
+        })
+        .ok()
+        .map(|_| Self(open.clone()))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Server which caches decrypted entries in locked memory and serves them over a unix socket
+#[derive(Debug)]
+pub struct Agent {
+    listener: UnixListener,
+    socket_path: PathBuf,
+    ttl: Duration,
+    cache: Cache,
+}
+
+impl Agent {
+    /// Listen on the unix socket at *socket_path* and cache entries for *ttl*
+    ///
+    /// Missing parent directories are created so that only the current user can access them and the socket
+    /// itself is only accessible by the current user.
+    /// A stale socket which was left behind by a previous agent is replaced.
+    pub fn bind(socket_path: impl AsRef<Path>, ttl: Duration) -> Result<Self> {
+        let socket_path = socket_path.as_ref().to_owned();
+        let parent = socket_path.parent().unwrap_or(Path::new(""));
+        utils::create_dir_all(parent, 0o077)?;
+        if UnixStream::connect(&socket_path).is_err() && socket_path.exists() {
+            debug!("Removing stale agent socket {}", socket_path.display());
+            fs::remove_file(&socket_path)?;
+        }
+
+        // the socket is created inside a private directory and only moved into place once its permissions are
+        // restricted so that other users can never connect to it
+        let mut suffix = [0u8; 8];
+        getrandom::getrandom(&mut suffix).map_err(io::Error::from)?;
+        let staging = parent.join(format!(
+            ".libpass-agent-{}",
+            suffix
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        ));
+        fs::DirBuilder::new().mode(0o700).create(&staging)?;
+        let staged = staging.join("agent.sock");
+        let listener = UnixListener::bind(&staged).and_then(|listener| {
+            fs::set_permissions(&staged, fs::Permissions::from_mode(0o600))?;
+            fs::rename(&staged, &socket_path)?;
+            Ok(listener)
+        });
+        let _ = fs::remove_file(&staged);
+        fs::remove_dir(&staging)?;
+
+        let cache = Cache::default();
+        let interval = ttl.clamp(MIN_SWEEP_INTERVAL, MAX_SWEEP_INTERVAL);
+        let weak = Arc::downgrade(&cache);
+        thread::Builder::new()
+            .name("libpass-agent-sweeper".to_string())
+            .spawn(move || sweep(weak, interval))?;
+        Ok(Self {
+            listener: listener?,
+            socket_path,
+            ttl,
+            cache,
+        })
+    }
+
+    /// The path of the socket this agent listens on
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// The time for which entries are cached
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Serve clients until accepting connections fails
+    ///
+    /// Every connection is handled on its own thread.
+    /// At most 16 connections are served at the same time; further connections are closed right away and
+    /// connections of clients which stay silent for a few seconds are closed as well.
+    pub fn serve(&self) -> Result<()> {
+        let open = Arc::new(AtomicUsize::new(0));
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let connection = match Connection::open(&open) {
+                Some(connection) => connection,
+                None => {
+                    debug!("Refusing agent connection because too many are open");
+                    continue;
+                }
+            };
+            let cache = self.cache.clone();
+            let ttl = self.ttl;
+            thread::spawn(move || {
+                let _connection = connection;
+                if let Err(e) = handle_connection(stream, &cache, ttl) {
+                    debug!("Agent connection failed: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Agent {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.socket_path);
+    }
+}
+
+/// Answer all requests that are sent over *stream*
+fn handle_connection(mut stream: UnixStream, cache: &Cache, ttl: Duration) -> io::Result<()> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+    loop {
+        let op = match read_frame(&mut stream) {
+            Ok(op) => op,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        match op.as_slice() {
+            b"get" => {
+                let key = String::from_utf8_lossy(&read_frame(&mut stream)?).into_owned();
+                let mut cache = lock(cache);
+                let now = Instant::now();
+                cache.retain(|_, entry| entry.expires > now);
+                match cache.get(&key) {
+                    Some(entry) => {
+                        write_frame(&mut stream, b"hit")?;
+                        write_frame(&mut stream, &entry.plaintext)?;
+                    }
+                    None => write_frame(&mut stream, b"miss")?,
+                }
+            }
+            b"put" => {
+                let key = String::from_utf8_lossy(&read_frame(&mut stream)?).into_owned();
+                let mut entry = CachedEntry {
+                    plaintext: read_frame(&mut stream)?,
+                    locked: LockedRegion::default(),
+                    expires: Instant::now() + ttl,
+                };
+                entry.locked.lock(&entry.plaintext);
+                lock(cache).insert(key, entry);
+                write_frame(&mut stream, b"ok")?;
+            }
+            b"clear" => {
+                lock(cache).clear();
+                write_frame(&mut stream, b"ok")?;
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unknown agent request",
+                ))
+            }
+        }
+    }
+}
+
+/// Connection details of an [`Agent`]
+///
+/// Every request opens a new connection so that clients never hold on to an agent.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct AgentClient {
+    socket_path: PathBuf,
+}
+
+impl AgentClient {
+    /// Talk to the agent listening on *socket_path*
+    pub fn new(socket_path: impl Into<PathBuf>) -> Self {
+        Self {
+            socket_path: socket_path.into(),
+        }
+    }
+
+    /// The path of the socket the agent listens on
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Retrieve the cached plaintext stored under *key*
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let mut stream = self.connect()?;
+        write_frame(&mut stream, b"get")?;
+        write_frame(&mut stream, key.as_bytes())?;
+        match read_frame(&mut stream)?.as_slice() {
+            b"hit" => Ok(Some(read_frame(&mut stream)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Cache *plaintext* under *key*
+    pub fn put(&self, key: &str, plaintext: &[u8]) -> Result<()> {
+        let mut stream = self.connect()?;
+        write_frame(&mut stream, b"put")?;
+        write_frame(&mut stream, key.as_bytes())?;
+        write_frame(&mut stream, plaintext)?;
+        read_frame(&mut stream)?;
+        Ok(())
+    }
+
+    /// Remove all entries from the cache of the agent
+    pub fn clear(&self) -> Result<()> {
+        let mut stream = self.connect()?;
+        write_frame(&mut stream, b"clear")?;
+        read_frame(&mut stream)?;
+        Ok(())
+    }
+
+    /// Decrypt *ciphertext* of the file at *path* with *crypto*, preferring the plaintext cached by the agent
+    ///
+    /// Failures to talk to the agent are logged and otherwise ignored so that a missing agent only costs
+    /// performance.
+    pub(crate) fn decrypt(
+        &self,
+        crypto: &CryptoOptions,
+        pool: &ContextPool,
+        path: &Path,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>> {
+        let key = cache_key(path, ciphertext);
+        match self.get(&key) {
+            Ok(Some(plaintext)) => return Ok(plaintext),
+            Ok(None) => {}
+            Err(e) => debug!("Could not query agent: {}", e),
+        }
+
+        let plaintext = crypto.decrypt(pool, ciphertext)?;
+        if let Err(e) = self.put(&key, &plaintext) {
+            debug!("Could not hand decrypted entry to agent: {}", e);
+        }
+        Ok(plaintext)
+    }
+
+    fn connect(&self) -> io::Result<UnixStream> {
+        let stream = UnixStream::connect(&self.socket_path)?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+        Ok(stream)
+    }
+}
+
+/// The key under which the plaintext of *ciphertext* stored at *path* is cached
+pub(crate) fn cache_key(path: &Path, ciphertext: &[u8]) -> String {
+    format!("{}:{}", path.display(), hash_of(ciphertext))
+}
+
+fn write_frame(stream: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len())
+        .ok()
+        .filter(|len| *len as usize <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "agent message is too large"))?;
+    stream.write_all(&len.to_be_bytes())?;
+    stream.write_all(data)
+}
+
+fn read_frame(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "agent message is too large",
+        ));
+    }
+
+    let mut data = vec![0; len];
+    stream.read_exact(&mut data)?;
+    Ok(data)
+}
@@ -0,0 +1,25 @@
+//! The alias convention which lets an entry redirect to another entry
+//!
+//! An alias is a regular entry whose first line is `@alias: <name of the target entry>`.
+//! Since aliases are encrypted like all other entries, the name of their target is not revealed by the store.
+
+/// Prefix of the line which marks an entry as alias
+const PREFIX: &str = "@alias:";
+
+/// The name of the entry which the entry with *plaintext* content redirects to, if it is an alias
+pub(crate) fn target(plaintext: &[u8]) -> Option<String> {
+    let first_line = plaintext.split(|b| *b == b'\n').next()?;
+    let target = std::str::from_utf8(first_line)
+        .ok()?
+        .strip_prefix(PREFIX)?
+        .trim();
+    match target {
+        "" => None,
+        target => Some(target.to_string()),
+    }
+}
+
+/// The plaintext content of an alias which redirects to *target*
+pub(crate) fn content(target: &str) -> String {
+    format!("{} {}\n", PREFIX, target)
+}
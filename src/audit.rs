@@ -0,0 +1,119 @@
+//! Observation of accesses to secrets for compliance logging
+//!
+//! A [`Store`](crate::Store) can be given an [`AccessLogger`] via
+//! [`Store::set_access_logger()`](crate::Store::set_access_logger) which is then notified whenever an entry is
+//! decrypted, written or deleted through that store.
+//!
+//! ## Example
+//! ```
+//! use libpass::audit::AccessEvent;
+//! use libpass::Store;
+//!
+//! let mut store = Store::open("tests/simple").unwrap().with_access_context("backup job");
+//! store.set_access_logger(|event: &AccessEvent| {
+//!     println!("{:?} of {} by {:?}", event.operation, event.pass_name, event.context)
+//! });
+//! ```
+
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// The kind of access to an entry
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum AccessOperation {
+    /// The entry was decrypted
+    Decrypt,
+    /// New content was encrypted and written into the entry
+    Write,
+    /// The entry was removed from the store
+    Delete,
+}
+
+/// A single access to an entry of a store
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct AccessEvent {
+    /// Name of the accessed entry relative to the store root (without `.gpg` suffix)
+    pub pass_name: String,
+    /// The time at which the access happened
+    pub timestamp: SystemTime,
+    /// What was done with the entry
+    pub operation: AccessOperation,
+    /// The context which was configured via [`Store::with_access_context()`](crate::Store::with_access_context)
+    pub context: Option<String>,
+}
+
+/// An observer which is notified about accesses to entries
+///
+/// Loggers are called synchronously after each successful access so they should not block for long.
+/// Any closure taking an [`AccessEvent`] can be used as logger.
+pub trait AccessLogger: Send + Sync {
+    /// Record that *event* happened
+    fn log(&self, event: &AccessEvent);
+}
+
+impl<F> AccessLogger for F
+where
+    F: Fn(&AccessEvent) + Send + Sync,
+{
+    fn log(&self, event: &AccessEvent) {
+        self(event)
+    }
+}
+
+/// The logger and context with which accesses of a store are recorded
+///
+/// Loggers can not be compared, so all recorders are equal and hash alike.
+#[derive(Clone, Default)]
+pub(crate) struct AccessRecorder {
+    logger: Option<Arc<dyn AccessLogger>>,
+    context: Option<String>,
+}
+
+impl AccessRecorder {
+    pub(crate) fn set_logger(&mut self, logger: Arc<dyn AccessLogger>) {
+        self.logger = Some(logger);
+    }
+
+    pub(crate) fn set_context(&mut self, context: Option<String>) {
+        self.context = context;
+    }
+
+    pub(crate) fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    /// Notify the logger (if there is one) that *operation* was done with *pass_name*
+    pub(crate) fn record(&self, pass_name: &str, operation: AccessOperation) {
+        if let Some(logger) = &self.logger {
+            logger.log(&AccessEvent {
+                pass_name: pass_name.to_string(),
+                timestamp: SystemTime::now(),
+                operation,
+                context: self.context.clone(),
+            });
+        }
+    }
+}
+
+impl Debug for AccessRecorder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessRecorder")
+            .field("logger", &self.logger.as_ref().map(|_| "AccessLogger"))
+            .field("context", &self.context)
+            .finish()
+    }
+}
+
+impl PartialEq for AccessRecorder {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for AccessRecorder {}
+
+impl Hash for AccessRecorder {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
@@ -0,0 +1,147 @@
+//! Ciphertext bundles for moving entries between air-gapped machines
+//!
+//! [`Store::pack()`](crate::Store::pack) writes the encrypted files of selected entries into a single
+//! bundle together with the `.gpg-id` files (and their signatures) that govern them.
+//! The bundle can be carried over to another machine (e.g. on a USB drive) and unpacked into a store there
+//! with [`Store::unpack()`](crate::Store::unpack).
+//! Entries are never decrypted so no plaintext is exposed on the way.
+//!
+//! ## Format
+//! A bundle starts with the line `libpass-bundle 1` followed by a manifest with one line
+//! `<sha256> <size> <path>` per file (where `%`, CR and LF in the path are percent-encoded), an empty line
+//! and the contents of all files in the order of the manifest.
+//! Paths are relative to the store root and use `/` as separator.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::Store;
+//! use std::fs::File;
+//!
+//! let store = Store::from_env().unwrap();
+//! store.pack(&["servers/"], File::create("/media/usb/servers.bundle").unwrap()).unwrap();
+//!
+//! // on the other machine
+//! let target = Store::from_env().unwrap();
+//! target.unpack(File::open("/media/usb/servers.bundle").unwrap()).unwrap();
+//! ```
+
+use crate::snapshot::hash_of;
+use crate::{index, names, PassError, Result};
+use std::io::{BufRead, BufReader, Read, Write};
+
+const HEADER: &str = "libpass-bundle 1";
+
+/// A file contained in a bundle
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub(crate) struct BundleFile {
+    /// The path of the file relative to the store root with `/` as separator
+    pub(crate) path: String,
+    /// The encrypted content of the file
+    pub(crate) content: Vec<u8>,
+}
+
+impl BundleFile {
+    /// The name of the entry stored in this file or `None` if it is a `.gpg-id` file or signature
+    pub(crate) fn pass_name(&self) -> Option<&str> {
+        self.path.strip_suffix(".gpg")
+    }
+}
+
+/// Write *files* as bundle into *writer*
+pub(crate) fn write(files: &[BundleFile], mut writer: impl Write) -> Result<()> {
+    let mut manifest = format!("{}\n", HEADER);
+    for file in files {
+        manifest.push_str(&format!(
+            "{} {} {}\n",
+            hash_of(&file.content),
+            file.content.len(),
+            index::escape(&file.path)
+        ));
+    }
+    manifest.push('\n');
+    writer.write_all(manifest.as_bytes())?;
+    for file in files {
+        writer.write_all(&file.content)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read all files of the bundle from *reader* and verify their integrity
+///
+/// Only entry files (`*.gpg`), `.gpg-id` files and their signatures with valid names are accepted.
+pub(crate) fn read(reader: impl Read) -> Result<Vec<BundleFile>> {
+    let invalid = |reason: &str| PassError::InvalidBundle(reason.to_string());
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim_end_matches('\n') != HEADER {
+        return Err(invalid(
+            "the header is missing or of an unsupported version",
+        ));
+    }
+
+    let mut manifest = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("the manifest is not terminated"));
+        }
+        let line = line.trim_end_matches('\n');
+        if line.is_empty() {
+            break;
+        }
+        let mut parts = line.splitn(3, ' ');
+        match (
+            parts.next(),
+            parts.next().map(str::parse::<u64>),
+            parts.next(),
+        ) {
+            (Some(hash), Some(Ok(size)), Some(path)) => {
+                let path = index::unescape(path).ok_or_else(|| invalid("a path is malformed"))?;
+                check_path(&path)?;
+                manifest.push((hash.to_string(), size, path));
+            }
+            _ => return Err(invalid("a manifest line is malformed")),
+        }
+    }
+
+    let mut files = Vec::with_capacity(manifest.len());
+    for (hash, size, path) in manifest {
+        // the content grows while reading so that a forged size does not allocate huge buffers up front
+        let mut content = Vec::new();
+        reader.by_ref().take(size).read_to_end(&mut content)?;
+        if content.len() as u64 != size {
+            return Err(invalid("the bundle is truncated"));
+        }
+        if hash_of(&content) != hash {
+            return Err(PassError::InvalidBundle(format!(
+                "the content of {} is corrupted",
+                path
+            )));
+        }
+        files.push(BundleFile { path, content });
+    }
+    Ok(files)
+}
+
+/// Check that *path* of a bundle file names an entry file, `.gpg-id` file or signature inside the store
+fn check_path(path: &str) -> Result<()> {
+    let (dir, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+    let is_normalized =
+        |name: &str| names::normalize_name(name).is_ok_and(|normalized| normalized == name);
+    let is_valid = match file_name {
+        ".gpg-id" | ".gpg-id.sig" => is_normalized(dir),
+        _ => path
+            .strip_suffix(".gpg")
+            .is_some_and(|name| !name.is_empty() && is_normalized(name)),
+    };
+    if is_valid {
+        Ok(())
+    } else {
+        Err(PassError::InvalidBundle(format!(
+            "{} is not a valid path inside of a store",
+            path
+        )))
+    }
+}
@@ -0,0 +1,266 @@
+//! Delegation of operations to the real *pass* binary
+//!
+//! Some features of *pass* (most notably its extensions like `pass otp`) are not implemented by this library.
+//! [`PassCli`] invokes the `pass` program of the system with the environment of a [`Store`] and parses its
+//! output into the types of this library so that applications can mix native and CLI-backed operations on
+//! the same store.
+//!
+//! Since the output of *pass* is meant for humans, prefer the native API whenever it covers an operation.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::compat::PassCli;
+//! use libpass::Store;
+//!
+//! let store = Store::from_env().unwrap();
+//! let pass = PassCli::new(&store);
+//! let code = pass.run(&["otp", "code", "github.com"], None).unwrap();
+//! println!("{}", String::from_utf8_lossy(&code).trim());
+//! ```
+
+use crate::secure::SecretString;
+use crate::telemetry::debug;
+use crate::{ParsedEntry, PassError, Result, Store, PASSWORD_STORE_DIR_ENV};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Prefix of the environment variables which configure *pass*
+const ENV_PREFIX: &str = "PASSWORD_STORE_";
+
+/// A handle for invoking the `pass` program on a store
+///
+/// The program is run with `PASSWORD_STORE_DIR` pointing to the store and all other settings of the
+/// [`Config`](crate::Config) of the store exported as environment variables.
+/// `PASSWORD_STORE_*` variables of the current process are not passed on so that only the settings of the store
+/// apply.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PassCli {
+    program: PathBuf,
+    env: Vec<(String, String)>,
+}
+
+impl PassCli {
+    /// Invoke `pass` from the `PATH` on *store*
+    pub fn new(store: &Store) -> Self {
+        let mut env = store
+            .config()
+            .to_env()
+            .into_iter()
+            .filter(|(var, _)| *var != PASSWORD_STORE_DIR_ENV)
+            .map(|(var, value)| (var.to_string(), value))
+            .collect::<Vec<_>>();
+        env.push((
+            PASSWORD_STORE_DIR_ENV.to_string(),
+            store.root().to_string_lossy().into_owned(),
+        ));
+        Self {
+            program: PathBuf::from("pass"),
+            env,
+        }
+    }
+
+    /// Invoke *program* instead of `pass` from the `PATH`
+    pub fn with_program(mut self, program: impl Into<PathBuf>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// The program which is invoked
+    pub fn program(&self) -> &Path {
+        &self.program
+    }
+
+    /// Additionally set the environment variable *var* to *value*, e.g. to configure an extension
+    pub fn with_env(mut self, var: impl Into<String>, value: impl Into<String>) -> Self {
+        let var = var.into();
+        self.env.retain(|(existing, _)| *existing != var);
+        self.env.push((var, value.into()));
+        self
+    }
+
+    /// The environment variables with which the program is invoked
+    pub fn env(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    /// Run the program with *args*, write *stdin* to its standard input and return its standard output
+    ///
+    /// ## Errors
+    /// [`PassError::PassCliFailed`] is returned if the program exits unsuccessfully and an io error if it
+    /// cannot be started.
+    pub fn run(&self, args: &[&str], stdin: Option<&[u8]>) -> Result<Vec<u8>> {
+        debug!("Running {} {}", self.program.display(), args.join(" "));
+        let mut command = Command::new(&self.program);
+        for (var, _) in std::env::vars_os() {
+            if var.to_string_lossy().starts_with(ENV_PREFIX) {
+                command.env_remove(var);
+            }
+        }
+        let mut child = command
+            .args(args)
+            .envs(self.env.iter().map(|(var, value)| (var, value)))
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // the input is written concurrently because the program may fill its output pipes before reading all of it
+        let output = thread::scope(|scope| {
+            let writer = stdin
+                .zip(child.stdin.take())
+                .map(|(input, mut pipe)| scope.spawn(move || pipe.write_all(input)));
+            let output = child.wait_with_output()?;
+            match writer.map(|writer| writer.join().expect("writing to a pipe does not panic")) {
+                // programs may exit without reading their input which is reported by their exit status
+                Some(Err(e)) if e.kind() != io::ErrorKind::BrokenPipe => Err(e),
+                _ => Ok(output),
+            }
+        })?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(PassError::PassCliFailed {
+                status: output.status.code(),
+                stderr: strip_ansi(&String::from_utf8_lossy(&output.stderr))
+                    .trim()
+                    .to_string(),
+            })
+        }
+    }
+
+    /// The version of *pass* like `1.7.4`
+    pub fn version(&self) -> Result<String> {
+        let output = String::from_utf8_lossy(&self.run(&["version"], None)?).into_owned();
+        output
+            .split_whitespace()
+            .filter_map(|word| word.strip_prefix('v'))
+            .find(|version| version.starts_with(|c: char| c.is_ascii_digit()))
+            .map(str::to_string)
+            .ok_or(PassError::PassCliFailed {
+                status: Some(0),
+                stderr: "the output does not contain a version".to_string(),
+            })
+    }
+
+    /// The names of all entries as listed by `pass ls`
+    ///
+    /// *pass* does not mark directories in its listing so empty directories are reported like entries.
+    pub fn names(&self) -> Result<Vec<String>> {
+        let output = String::from_utf8_lossy(&self.run(&["ls"], None)?).into_owned();
+        Ok(parse_tree(&strip_ansi(&output)))
+    }
+
+    /// Decrypt the entry *pass_name* with `pass show`
+    pub fn show(&self, pass_name: &str) -> Result<ParsedEntry> {
+        let output = self.run(&["show", "--", pass_name], None)?;
+        let content = String::from_utf8(output).map_err(|_| PassError::PassCliFailed {
+            status: Some(0),
+            stderr: format!("the content of {} is not valid UTF-8", pass_name),
+        })?;
+        Ok(ParsedEntry::from(content))
+    }
+
+    /// Insert or overwrite the entry *pass_name* with *content* via `pass insert --multiline --force`
+    pub fn insert(&self, pass_name: &str, content: &str) -> Result<()> {
+        self.run(
+            &["insert", "--multiline", "--force", "--", pass_name],
+            Some(content.as_bytes()),
+        )?;
+        Ok(())
+    }
+
+    /// Generate a password of *length* characters (or the configured default length) for the entry
+    /// *pass_name* with `pass generate --force` and return it
+    pub fn generate(
+        &self,
+        pass_name: &str,
+        length: Option<usize>,
+        no_symbols: bool,
+    ) -> Result<SecretString> {
+        let length = length.map(|length| length.to_string());
+        let mut args = vec!["generate", "--force"];
+        if no_symbols {
+            args.push("--no-symbols");
+        }
+        args.extend(["--", pass_name]);
+        args.extend(length.as_deref());
+        let output = String::from_utf8_lossy(&self.run(&args, None)?).into_owned();
+        // the password is printed on the last line after "The generated password for ... is:"
+        strip_ansi(&output)
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .map(SecretString::from)
+            .ok_or(PassError::PassCliFailed {
+                status: Some(0),
+                stderr: "the output does not contain a password".to_string(),
+            })
+    }
+}
+
+/// Remove the ANSI escape sequences with which *pass* colors its output
+pub(crate) fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // control sequences are terminated by a character in the range @ to ~
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Extract the names of all leaves of the `tree` output which `pass ls` prints
+pub(crate) fn parse_tree(output: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    // the names of the directories leading to the current line
+    let mut path: Vec<&str> = Vec::new();
+    let mut previous: Option<(usize, &str)> = None;
+    // the first line is the title ("Password Store" or the name of a subfolder)
+    for line in output.lines().skip(1) {
+        // tree draws ASCII connectors in non-unicode locales
+        let (indent, name) = match line.split_once("── ").or_else(|| line.split_once("-- ")) {
+            Some((indent, name)) => (indent, name),
+            None => continue,
+        };
+        // every level is indented by four characters
+        let depth = indent.chars().count() / 4;
+        if let Some((previous_depth, previous_name)) = previous {
+            if depth > previous_depth {
+                path.push(previous_name);
+            } else {
+                names.push(join(&path, previous_name));
+                path.truncate(depth);
+            }
+        }
+        previous = Some((depth, name));
+    }
+    if let Some((_, name)) = previous {
+        names.push(join(&path, name));
+    }
+    names
+}
+
+/// Join the directory names *path* and *name* into the name of an entry
+fn join(path: &[&str], name: &str) -> String {
+    let mut joined = path.join("/");
+    if !joined.is_empty() {
+        joined.push('/');
+    }
+    joined.push_str(name);
+    joined
+}
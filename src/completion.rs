@@ -0,0 +1,74 @@
+//! Formatting of completion candidates for shells
+//!
+//! [`Store::completion_candidates()`](crate::Store::completion_candidates) finds the names which complete
+//! what a user typed so far and [`render()`] formats them in the way the completion system of a shell expects.
+//!
+//! ## Example
+//! ```
+//! use libpass::completion::{self, Shell};
+//! use libpass::Store;
+//!
+//! let store = Store::open("tests/simple").unwrap();
+//! let candidates = store.completion_candidates("fol").unwrap();
+//! assert_eq!(candidates, ["folder/", "folder2/"]);
+//! assert_eq!(
+//!     completion::render(Shell::Fish, &candidates),
+//!     "folder/\tDirectory\nfolder2/\tDirectory\n"
+//! );
+//! ```
+
+/// A shell for which completion candidates can be rendered
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Shell {
+    /// One candidate per line with special characters escaped by backslashes, suitable for
+    /// `mapfile -t COMPREPLY`
+    Bash,
+    /// One candidate per line in the `name:description` form of `_describe`
+    Zsh,
+    /// One candidate per line followed by a tab and its description
+    Fish,
+}
+
+/// Format *candidates* as returned by [`Store::completion_candidates()`](crate::Store::completion_candidates)
+/// for *shell*
+///
+/// Candidates ending with `/` are described as directories, all others as entries.
+pub fn render(shell: Shell, candidates: &[String]) -> String {
+    let mut output = String::new();
+    for candidate in candidates {
+        let description = match candidate.ends_with('/') {
+            true => "Directory",
+            false => "Entry",
+        };
+        match shell {
+            Shell::Bash => output.push_str(&escape(candidate, |c| {
+                c.is_whitespace() || "\\'\"`$&|;<>()[]*?!#~{}".contains(c)
+            })),
+            Shell::Zsh => {
+                output.push_str(&escape(candidate, |c| c == '\\' || c == ':'));
+                output.push(':');
+                output.push_str(description);
+            }
+            Shell::Fish => {
+                // fish does not interpret the candidate, only tabs and line breaks separate fields
+                output.push_str(&candidate.replace(['\t', '\n'], " "));
+                output.push('\t');
+                output.push_str(description);
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Prefix all characters of *candidate* for which *special* returns true with a backslash
+fn escape(candidate: &str, special: impl Fn(char) -> bool) -> String {
+    let mut escaped = String::with_capacity(candidate.len());
+    for c in candidate.chars() {
+        if special(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,392 @@
+//! Settings which *pass* reads from environment variables
+//!
+//! [`Config`] mirrors all environment variables that *pass* interprets so that applications built on this
+//! library behave like *pass* for users who configured it.
+//! Every setting can additionally be overridden programmatically.
+//!
+//! ## Example
+//! ```
+//! use libpass::Config;
+//!
+//! let config = Config::from_lookup(|var| match var {
+//!     "PASSWORD_STORE_GENERATED_LENGTH" => Some("32".to_string()),
+//!     _ => None,
+//! })
+//! .unwrap()
+//! .with_clip_time(std::time::Duration::from_secs(10));
+//!
+//! assert_eq!(config.generated_length(), 32);
+//! assert_eq!(config.umask(), 0o077);
+//! ```
+
+use crate::telemetry::warning;
+use crate::{utils, PassError, Result, PASSWORD_STORE_DIR_ENV};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Environment variable which overrides the keys for which entries are encrypted
+pub const KEY_ENV: &str = "PASSWORD_STORE_KEY";
+/// Environment variable with additional options for gpg
+pub const GPG_OPTS_ENV: &str = "PASSWORD_STORE_GPG_OPTS";
+/// Environment variable which selects the X selection that passwords are copied into
+pub const X_SELECTION_ENV: &str = "PASSWORD_STORE_X_SELECTION";
+/// Environment variable with the number of seconds after which the clipboard is cleared
+pub const CLIP_TIME_ENV: &str = "PASSWORD_STORE_CLIP_TIME";
+/// Environment variable with the octal umask that is applied to newly created files
+pub const UMASK_ENV: &str = "PASSWORD_STORE_UMASK";
+/// Environment variable with the default length of generated passwords
+pub const GENERATED_LENGTH_ENV: &str = "PASSWORD_STORE_GENERATED_LENGTH";
+/// Environment variable with the characters that generated passwords consist of
+pub const CHARACTER_SET_ENV: &str = "PASSWORD_STORE_CHARACTER_SET";
+/// Environment variable with the characters that generated passwords without symbols consist of
+pub const CHARACTER_SET_NO_SYMBOLS_ENV: &str = "PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS";
+/// Environment variable which enables store extensions if set to `true`
+pub const ENABLE_EXTENSIONS_ENV: &str = "PASSWORD_STORE_ENABLE_EXTENSIONS";
+/// Environment variable with the directory that store extensions are loaded from
+pub const EXTENSIONS_DIR_ENV: &str = "PASSWORD_STORE_EXTENSIONS_DIR";
+/// Environment variable with the keys of which one must have signed `.gpg-id` files
+pub const SIGNING_KEY_ENV: &str = "PASSWORD_STORE_SIGNING_KEY";
+
+/// The umask that *pass* applies to new files unless `PASSWORD_STORE_UMASK` is set
+pub(crate) const DEFAULT_UMASK: u32 = 0o077;
+
+/// Settings of *pass* with the same defaults that *pass* uses
+///
+/// Use [`Config::from_env()`] to read the settings from the environment and the `with_*` methods to
+/// override individual settings afterwards.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Config {
+    store_dir: Option<PathBuf>,
+    keys: Vec<String>,
+    gpg_opts: Option<String>,
+    x_selection: String,
+    clip_time: Duration,
+    umask: u32,
+    generated_length: usize,
+    character_set: String,
+    character_set_no_symbols: String,
+    enable_extensions: bool,
+    extensions_dir: Option<PathBuf>,
+    signing_keys: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            store_dir: None,
+            keys: Vec::new(),
+            gpg_opts: None,
+            x_selection: "clipboard".to_string(),
+            clip_time: Duration::from_secs(45),
+            umask: DEFAULT_UMASK,
+            generated_length: 25,
+            character_set: "[:punct:][:alnum:]".to_string(),
+            character_set_no_symbols: "[:alnum:]".to_string(),
+            enable_extensions: false,
+            extensions_dir: None,
+            signing_keys: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Read the configuration from the environment variables of the current process
+    ///
+    /// Variables with a value which *pass* would not accept are ignored with a warning and their settings
+    /// keep their defaults, so that a malformed variable does not prevent using a store whose operations do
+    /// not even depend on it.
+    /// Use [`Config::from_lookup()`] with [`std::env::var()`] to reject such values instead.
+    pub fn from_env() -> Self {
+        let (config, invalid) = Self::parse(|var| env::var(var).ok());
+        for error in invalid {
+            warning!("Ignoring environment variable: {}", error);
+        }
+        config
+    }
+
+    /// Read the configuration from variables that are looked up via *lookup*
+    ///
+    /// This reads the same variables as [`Config::from_env()`] but allows applications to provide them from
+    /// somewhere else, e.g. from the environment of a child process.
+    /// Empty variables are treated as if they were not set.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidConfig`] is returned if a variable has a value which *pass* would not accept.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let (config, invalid) = Self::parse(lookup);
+        match invalid.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(config),
+        }
+    }
+
+    /// Read the configuration from variables that are looked up via *lookup* and keep the defaults of
+    /// settings whose variables are invalid
+    ///
+    /// The errors describing the invalid variables are returned alongside the configuration.
+    pub(crate) fn parse(lookup: impl Fn(&str) -> Option<String>) -> (Self, Vec<PassError>) {
+        let get = |var: &str| lookup(var).filter(|value| !value.is_empty());
+        let mut invalid = Vec::new();
+        let defaults = Self::default();
+
+        let clip_time = parse_var(
+            get,
+            CLIP_TIME_ENV,
+            "expected a number of seconds",
+            &mut invalid,
+            |value| value.parse().ok(),
+        )
+        .map_or(defaults.clip_time, Duration::from_secs);
+        let umask = parse_var(
+            get,
+            UMASK_ENV,
+            "expected an octal umask like 077",
+            &mut invalid,
+            |value| {
+                u32::from_str_radix(value, 8)
+                    .ok()
+                    .filter(|umask| *umask <= 0o777)
+            },
+        )
+        .unwrap_or(defaults.umask);
+        let generated_length = parse_var(
+            get,
+            GENERATED_LENGTH_ENV,
+            "expected a positive number",
+            &mut invalid,
+            |value| value.parse().ok().filter(|length| *length > 0),
+        )
+        .unwrap_or(defaults.generated_length);
+        let split = |value: String| value.split_whitespace().map(str::to_string).collect();
+
+        let config = Self {
+            store_dir: get(PASSWORD_STORE_DIR_ENV).map(PathBuf::from),
+            keys: get(KEY_ENV).map(split).unwrap_or_default(),
+            gpg_opts: get(GPG_OPTS_ENV),
+            x_selection: get(X_SELECTION_ENV).unwrap_or(defaults.x_selection),
+            clip_time,
+            umask,
+            generated_length,
+            character_set: get(CHARACTER_SET_ENV).unwrap_or(defaults.character_set),
+            character_set_no_symbols: get(CHARACTER_SET_NO_SYMBOLS_ENV)
+                .unwrap_or(defaults.character_set_no_symbols),
+            enable_extensions: get(ENABLE_EXTENSIONS_ENV).as_deref() == Some("true"),
+            extensions_dir: get(EXTENSIONS_DIR_ENV).map(PathBuf::from),
+            signing_keys: get(SIGNING_KEY_ENV).map(split).unwrap_or_default(),
+        };
+        (config, invalid)
+    }
+
+    /// The environment variables which make *pass* behave according to this configuration
+    ///
+    /// This is the inverse of [`Config::from_lookup()`] and can be used to configure child processes.
+    /// Variables of settings which are not set are omitted.
+    pub fn to_env(&self) -> Vec<(&'static str, String)> {
+        let mut env = Vec::new();
+        if let Some(store_dir) = &self.store_dir {
+            env.push((
+                PASSWORD_STORE_DIR_ENV,
+                store_dir.to_string_lossy().into_owned(),
+            ));
+        }
+        if !self.keys.is_empty() {
+            env.push((KEY_ENV, self.keys.join(" ")));
+        }
+        if let Some(gpg_opts) = &self.gpg_opts {
+            env.push((GPG_OPTS_ENV, gpg_opts.clone()));
+        }
+        env.push((X_SELECTION_ENV, self.x_selection.clone()));
+        env.push((CLIP_TIME_ENV, self.clip_time.as_secs().to_string()));
+        env.push((UMASK_ENV, format!("{:03o}", self.umask)));
+        env.push((GENERATED_LENGTH_ENV, self.generated_length.to_string()));
+        env.push((CHARACTER_SET_ENV, self.character_set.clone()));
+        env.push((
+            CHARACTER_SET_NO_SYMBOLS_ENV,
+            self.character_set_no_symbols.clone(),
+        ));
+        env.push((ENABLE_EXTENSIONS_ENV, self.enable_extensions.to_string()));
+        if let Some(extensions_dir) = &self.extensions_dir {
+            env.push((
+                EXTENSIONS_DIR_ENV,
+                extensions_dir.to_string_lossy().into_owned(),
+            ));
+        }
+        if !self.signing_keys.is_empty() {
+            env.push((SIGNING_KEY_ENV, self.signing_keys.join(" ")));
+        }
+        env
+    }
+
+    /// Use the store located at *path*
+    pub fn with_store_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.store_dir = Some(path.into());
+        self
+    }
+
+    /// The location of the store (`PASSWORD_STORE_DIR`, default `~/.password-store`)
+    ///
+    /// ## Errors
+    /// Paths which begin with `~` are resolved to the current users home directory which can produce io
+    /// errors.
+    pub fn store_dir(&self) -> Result<PathBuf> {
+        let path = self
+            .store_dir
+            .as_deref()
+            .unwrap_or_else(|| Path::new("~/.password-store"));
+        Ok(utils::canonicalize_path(&path)?)
+    }
+
+    /// Encrypt entries for *keys* instead of the keys from `.gpg-id` files
+    pub fn with_keys(mut self, keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Keys which override the keys from `.gpg-id` files (`PASSWORD_STORE_KEY`, default none)
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    /// Use the given additional options for gpg
+    pub fn with_gpg_opts(mut self, opts: impl Into<String>) -> Self {
+        self.gpg_opts = Some(opts.into());
+        self
+    }
+
+    /// Additional options for gpg (`PASSWORD_STORE_GPG_OPTS`, default none)
+    ///
+    /// These are only relevant for applications which invoke gpg themselves since this library uses gpgme.
+    pub fn gpg_opts(&self) -> Option<&str> {
+        self.gpg_opts.as_deref()
+    }
+
+    /// Copy passwords into the given X selection
+    pub fn with_x_selection(mut self, selection: impl Into<String>) -> Self {
+        self.x_selection = selection.into();
+        self
+    }
+
+    /// The X selection passwords are copied into (`PASSWORD_STORE_X_SELECTION`, default `clipboard`)
+    pub fn x_selection(&self) -> &str {
+        &self.x_selection
+    }
+
+    /// Clear the clipboard after *clip_time*
+    pub fn with_clip_time(mut self, clip_time: Duration) -> Self {
+        self.clip_time = clip_time;
+        self
+    }
+
+    /// Time after which the clipboard is cleared (`PASSWORD_STORE_CLIP_TIME`, default 45 seconds)
+    pub fn clip_time(&self) -> Duration {
+        self.clip_time
+    }
+
+    /// Apply *umask* to newly created files
+    pub fn with_umask(mut self, umask: u32) -> Self {
+        self.umask = umask & 0o777;
+        self
+    }
+
+    /// The umask applied to newly created files (`PASSWORD_STORE_UMASK`, default `077`)
+    pub fn umask(&self) -> u32 {
+        self.umask
+    }
+
+    /// Generate passwords with *length* characters by default
+    pub fn with_generated_length(mut self, length: usize) -> Self {
+        self.generated_length = length;
+        self
+    }
+
+    /// The default length of generated passwords (`PASSWORD_STORE_GENERATED_LENGTH`, default 25)
+    pub fn generated_length(&self) -> usize {
+        self.generated_length
+    }
+
+    /// Generate passwords from the given character set
+    pub fn with_character_set(mut self, character_set: impl Into<String>) -> Self {
+        self.character_set = character_set.into();
+        self
+    }
+
+    /// The characters generated passwords consist of (`PASSWORD_STORE_CHARACTER_SET`, default
+    /// `[:punct:][:alnum:]`)
+    ///
+    /// Like for *pass*, this is a character set in the syntax of `tr`.
+    pub fn character_set(&self) -> &str {
+        &self.character_set
+    }
+
+    /// Generate passwords without symbols from the given character set
+    pub fn with_character_set_no_symbols(mut self, character_set: impl Into<String>) -> Self {
+        self.character_set_no_symbols = character_set.into();
+        self
+    }
+
+    /// The characters generated passwords without symbols consist of
+    /// (`PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS`, default `[:alnum:]`)
+    pub fn character_set_no_symbols(&self) -> &str {
+        &self.character_set_no_symbols
+    }
+
+    /// Enable or disable store extensions
+    pub fn with_extensions_enabled(mut self, enabled: bool) -> Self {
+        self.enable_extensions = enabled;
+        self
+    }
+
+    /// Whether store extensions are enabled (`PASSWORD_STORE_ENABLE_EXTENSIONS`, default `false`)
+    pub fn extensions_enabled(&self) -> bool {
+        self.enable_extensions
+    }
+
+    /// Load store extensions from *path*
+    pub fn with_extensions_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.extensions_dir = Some(path.into());
+        self
+    }
+
+    /// The directory store extensions are loaded from (`PASSWORD_STORE_EXTENSIONS_DIR`, default
+    /// `.extensions` inside the store)
+    pub fn extensions_dir(&self) -> Result<PathBuf> {
+        match &self.extensions_dir {
+            Some(path) => Ok(utils::canonicalize_path(path)?),
+            None => Ok(self.store_dir()?.join(".extensions")),
+        }
+    }
+
+    /// Require `.gpg-id` files to be signed by one of *keys*
+    pub fn with_signing_keys(mut self, keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.signing_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Keys of which one must have signed `.gpg-id` files (`PASSWORD_STORE_SIGNING_KEY`, default none)
+    pub fn signing_keys(&self) -> &[String] {
+        &self.signing_keys
+    }
+}
+
+/// Look up the variable *var* via *get* and interpret it with *parse*
+///
+/// `None` is returned if the variable is not set or invalid and invalid values are recorded in *invalid*.
+fn parse_var<T>(
+    get: impl Fn(&str) -> Option<String>,
+    var: &'static str,
+    reason: &str,
+    invalid: &mut Vec<PassError>,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Option<T> {
+    let value = get(var)?;
+    let parsed = parse(&value);
+    if parsed.is_none() {
+        invalid.push(PassError::InvalidConfig {
+            variable: var.to_string(),
+            value,
+            reason: reason.to_string(),
+        });
+    }
+    parsed
+}
@@ -0,0 +1,137 @@
+//! Reuse of gpgme contexts across operations
+//!
+//! Creating a gpgme context is comparatively expensive, so a [`Store`](crate::Store) keeps a small number of
+//! idle contexts around and hands them out to its operations.
+
+use crate::{utils, Result};
+use gpgme::Context;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// The number of idle contexts a store keeps by default
+pub(crate) const DEFAULT_POOL_SIZE: usize = 4;
+
+/// A pool of idle gpgme contexts which is shared between clones
+///
+/// A pool with size 0 (the default) creates a new context for every operation.
+/// Comparison and hashing only consider the size of the pool.
+#[derive(Clone, Default)]
+pub(crate) struct ContextPool {
+    idle: Arc<Mutex<Vec<Context>>>,
+    size: usize,
+}
+
+impl ContextPool {
+    /// Create a pool which keeps at most *size* idle contexts
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            idle: Default::default(),
+            size,
+        }
+    }
+
+    /// The maximum number of idle contexts
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Take an idle context from the pool or create a new one if there is none
+    ///
+    /// The context is configured with *offline* and *gpg_home* as described in
+    /// [`utils::configure_gpg_context()`].
+    /// It is returned to the pool once the returned guard is dropped.
+    pub(crate) fn get(&self, offline: bool, gpg_home: Option<&Path>) -> Result<PooledContext<'_>> {
+        let idle = match self.size {
+            0 => None,
+            _ => self.lock().pop(),
+        };
+        let ctx = match idle {
+            Some(mut ctx) => {
+                // idle contexts may have been used with other options before
+                utils::configure_gpg_context(&mut ctx, offline, gpg_home)?;
+                ctx
+            }
+            None => utils::create_gpg_context(offline, gpg_home)?,
+        };
+        Ok(PooledContext {
+            ctx: Some(ctx),
+            pool: self,
+        })
+    }
+
+    /// The number of contexts which are currently idle
+    #[cfg(test)]
+    pub(crate) fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Context>> {
+        // contexts do not hold state between operations so a panic while holding the lock is harmless
+        self.idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl std::fmt::Debug for ContextPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextPool")
+            .field("idle", &self.lock().len())
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+impl PartialEq for ContextPool {
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size
+    }
+}
+
+impl Eq for ContextPool {}
+
+impl Hash for ContextPool {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.size.hash(state);
+    }
+}
+
+/// A context which is borrowed from a [`ContextPool`]
+pub(crate) struct PooledContext<'a> {
+    ctx: Option<Context>,
+    pool: &'a ContextPool,
+}
+
+impl PooledContext<'_> {
+    /// Drop the context instead of returning it to the pool, e.g. because an operation on it was cancelled
+    pub(crate) fn discard(mut self) {
+        self.ctx = None;
+    }
+}
+
+impl Deref for PooledContext<'_> {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        self.ctx.as_ref().expect("context is only taken on drop")
+    }
+}
+
+impl DerefMut for PooledContext<'_> {
+    fn deref_mut(&mut self) -> &mut Context {
+        self.ctx.as_mut().expect("context is only taken on drop")
+    }
+}
+
+impl Drop for PooledContext<'_> {
+    fn drop(&mut self) {
+        if let Some(ctx) = self.ctx.take() {
+            let mut idle = self.pool.lock();
+            if idle.len() < self.pool.size {
+                idle.push(ctx);
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! Typed access to SSH keys and X.509 certificates which are stored as entries
+//!
+//! Infrastructure tooling often keeps private keys and certificates in pass.
+//! [`StoreFileRef::as_ssh_key()`](crate::StoreFileRef::as_ssh_key) and
+//! [`StoreFileRef::as_x509()`](crate::StoreFileRef::as_x509) find such a PEM block in the content of an entry
+//! and extract the information which is usually needed without a separate crypto library.
+//! Only the structure is inspected; keys are never decrypted and signatures are not verified.
+
+use crate::utils;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Magic bytes at the beginning of keys in the OpenSSH private key format
+const OPENSSH_MAGIC: &[u8] = b"openssh-key-v1\0";
+
+/// The encoding of a private key
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SshKeyFormat {
+    /// The format written by `ssh-keygen` since OpenSSH 7.8 (`BEGIN OPENSSH PRIVATE KEY`)
+    OpenSsh,
+    /// A traditional PEM key like `BEGIN RSA PRIVATE KEY`
+    Pem,
+    /// A PKCS#8 key (`BEGIN PRIVATE KEY` or `BEGIN ENCRYPTED PRIVATE KEY`)
+    Pkcs8,
+}
+
+/// A private key which can be used with SSH
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SshKey {
+    /// How the key is encoded
+    pub format: SshKeyFormat,
+    /// The type of the key like `ssh-ed25519` or `None` if it can not be determined without decrypting
+    /// the key
+    pub key_type: Option<String>,
+    /// Whether the key is protected by a passphrase
+    pub encrypted: bool,
+    /// The public key in the format of `authorized_keys` (only known for the OpenSSH format)
+    pub public_key: Option<String>,
+}
+
+/// An X.509 certificate
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Certificate {
+    /// The distinguished name of the subject like `C=DE, O=Example Org, CN=example.com`
+    pub subject: String,
+    /// The distinguished name of the issuer
+    pub issuer: String,
+    /// The serial number in hexadecimal notation
+    pub serial: String,
+    /// The time from which on the certificate is valid
+    pub not_before: SystemTime,
+    /// The time at which the certificate expires
+    pub not_after: SystemTime,
+}
+
+impl Certificate {
+    /// Whether the certificate is expired at *time*
+    pub fn is_expired_at(&self, time: SystemTime) -> bool {
+        time > self.not_after
+    }
+
+    /// The time which is left until the certificate expires or `None` if it already expired
+    pub fn remaining_validity(&self) -> Option<Duration> {
+        self.not_after.duration_since(SystemTime::now()).ok()
+    }
+}
+
+/// Find a private key in *content*
+pub(crate) fn parse_ssh_key(content: &[u8]) -> Result<SshKey, String> {
+    let block = find_pem_block(content, |label| label.ends_with("PRIVATE KEY"))
+        .ok_or("The entry does not contain a private key")?;
+    let key_type = |name: &str| Some(name.to_string());
+    match block.label.as_str() {
+        "OPENSSH PRIVATE KEY" => parse_openssh_key(&block.data),
+        "ENCRYPTED PRIVATE KEY" | "PRIVATE KEY" => Ok(SshKey {
+            format: SshKeyFormat::Pkcs8,
+            key_type: None,
+            encrypted: block.label == "ENCRYPTED PRIVATE KEY",
+            public_key: None,
+        }),
+        label => Ok(SshKey {
+            format: SshKeyFormat::Pem,
+            key_type: match label {
+                "RSA PRIVATE KEY" => key_type("ssh-rsa"),
+                "DSA PRIVATE KEY" => key_type("ssh-dss"),
+                "EC PRIVATE KEY" => key_type("ecdsa"),
+                _ => None,
+            },
+            encrypted: block
+                .headers
+                .iter()
+                .any(|(key, value)| key == "Proc-Type" && value.ends_with("ENCRYPTED")),
+            public_key: None,
+        }),
+    }
+}
+
+fn parse_openssh_key(data: &[u8]) -> Result<SshKey, String> {
+    let mut reader = SshReader(
+        data.strip_prefix(OPENSSH_MAGIC)
+            .ok_or("The key is not in the OpenSSH format")?,
+    );
+    let cipher = reader.string()?;
+    let _kdf = reader.string()?;
+    let _kdf_options = reader.string()?;
+    if reader.u32()? == 0 {
+        return Err("The key file does not contain a key".to_string());
+    }
+    let public_key = reader.string()?;
+    let key_type = String::from_utf8(SshReader(public_key).string()?.to_vec())
+        .map_err(|_| "The key type is not valid UTF-8")?;
+    Ok(SshKey {
+        format: SshKeyFormat::OpenSsh,
+        encrypted: cipher != b"none",
+        public_key: Some(format!("{} {}", key_type, utils::base64_encode(public_key))),
+        key_type: Some(key_type),
+    })
+}
+
+/// Reader of the binary encoding of SSH (RFC 4251)
+struct SshReader<'a>(&'a [u8]);
+
+impl<'a> SshReader<'a> {
+    fn u32(&mut self) -> Result<u32, String> {
+        let value = self.0.get(..4).ok_or("Unexpected end of key")?;
+        self.0 = &self.0[4..];
+        Ok(u32::from_be_bytes([value[0], value[1], value[2], value[3]]))
+    }
+
+    fn string(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        if self.0.len() < len {
+            return Err("Unexpected end of key".to_string());
+        }
+        let (value, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(value)
+    }
+}
+
+/// Find a certificate in *content* which is either PEM encoded or consists only of the DER encoding
+pub(crate) fn parse_certificate(content: &[u8]) -> Result<Certificate, String> {
+    let der = match find_pem_block(content, |label| label == "CERTIFICATE") {
+        Some(block) => block.data,
+        None if content.first() == Some(&0x30) => content.to_vec(),
+        None => return Err("The entry does not contain a certificate".to_string()),
+    };
+
+    let (certificate, _) = Der::read(&der, TAG_SEQUENCE)?;
+    let (tbs, _) = Der::read(certificate, TAG_SEQUENCE)?;
+    let mut rest = tbs;
+    // the version is an explicitly tagged, optional field
+    if rest.first() == Some(&0xa0) {
+        rest = Der::any(rest)?.1;
+    }
+    let (serial, rest) = Der::read(rest, TAG_INTEGER)?;
+    let (_signature_algorithm, rest) = Der::read(rest, TAG_SEQUENCE)?;
+    let (issuer, rest) = Der::read(rest, TAG_SEQUENCE)?;
+    let (validity, rest) = Der::read(rest, TAG_SEQUENCE)?;
+    let (subject, _) = Der::read(rest, TAG_SEQUENCE)?;
+
+    let (not_before, validity) = Der::any(validity)?;
+    let (not_after, _) = Der::any(validity)?;
+    Ok(Certificate {
+        subject: format_name(subject)?,
+        issuer: format_name(issuer)?,
+        serial: serial.iter().map(|b| format!("{:02x}", b)).collect(),
+        not_before: parse_time(not_before)?,
+        not_after: parse_time(not_after)?,
+    })
+}
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+
+/// A single DER encoded value
+struct Der<'a> {
+    tag: u8,
+    value: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    /// Split the first value off *data*
+    fn any(data: &'a [u8]) -> Result<(Der<'a>, &'a [u8]), String> {
+        let truncated = || "The certificate is truncated".to_string();
+        let tag = *data.first().ok_or_else(truncated)?;
+        let first_len = *data.get(1).ok_or_else(truncated)? as usize;
+        let (len, header_len) = match first_len {
+            len if len < 0x80 => (len, 2),
+            0x81..=0x84 => {
+                let count = first_len - 0x80;
+                let bytes = data.get(2..2 + count).ok_or_else(truncated)?;
+                (
+                    bytes.iter().fold(0usize, |len, b| (len << 8) | *b as usize),
+                    2 + count,
+                )
+            }
+            _ => return Err("The certificate has an unsupported length encoding".to_string()),
+        };
+        let value = data
+            .get(header_len..header_len + len)
+            .ok_or_else(truncated)?;
+        Ok((Der { tag, value }, &data[header_len + len..]))
+    }
+
+    /// Split the first value off *data* and require it to have *tag*
+    fn read(data: &'a [u8], tag: u8) -> Result<(&'a [u8], &'a [u8]), String> {
+        let (value, rest) = Self::any(data)?;
+        if value.tag != tag {
+            return Err(format!(
+                "Expected the tag {:#04x} but found {:#04x} in the certificate",
+                tag, value.tag
+            ));
+        }
+        Ok((value.value, rest))
+    }
+}
+
+/// Format a distinguished name like `C=DE, O=Example Org, CN=example.com`
+fn format_name(mut name: &[u8]) -> Result<String, String> {
+    let mut parts = Vec::new();
+    while !name.is_empty() {
+        let (mut rdn, rest) = Der::read(name, TAG_SET)?;
+        name = rest;
+        while !rdn.is_empty() {
+            let (attribute, rest) = Der::read(rdn, TAG_SEQUENCE)?;
+            rdn = rest;
+            let (oid, attribute) = Der::read(attribute, TAG_OID)?;
+            let (value, _) = Der::any(attribute)?;
+            parts.push(format!(
+                "{}={}",
+                attribute_name(oid),
+                String::from_utf8_lossy(value.value)
+            ));
+        }
+    }
+    Ok(parts.join(", "))
+}
+
+/// The short name of the attribute type *oid* or its dotted notation if it is uncommon
+fn attribute_name(oid: &[u8]) -> String {
+    match oid {
+        [0x55, 0x04, 0x03] => "CN".to_string(),
+        [0x55, 0x04, 0x06] => "C".to_string(),
+        [0x55, 0x04, 0x07] => "L".to_string(),
+        [0x55, 0x04, 0x08] => "ST".to_string(),
+        [0x55, 0x04, 0x0a] => "O".to_string(),
+        [0x55, 0x04, 0x0b] => "OU".to_string(),
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01] => "emailAddress".to_string(),
+        _ => {
+            let mut arcs = Vec::new();
+            if let Some(first) = oid.first() {
+                arcs.push((first / 40) as u64);
+                arcs.push((first % 40) as u64);
+            }
+            let mut arc = 0u64;
+            for b in oid.iter().skip(1) {
+                arc = (arc << 7) | (b & 0x7f) as u64;
+                if b & 0x80 == 0 {
+                    arcs.push(arc);
+                    arc = 0;
+                }
+            }
+            arcs.iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(".")
+        }
+    }
+}
+
+/// Parse a UTCTime or GeneralizedTime in UTC
+fn parse_time(time: Der<'_>) -> Result<SystemTime, String> {
+    let invalid = || "The certificate contains an invalid time".to_string();
+    let text = std::str::from_utf8(time.value).map_err(|_| invalid())?;
+    let text = text.strip_suffix('Z').ok_or_else(invalid)?;
+    let (year, rest) = match time.tag {
+        TAG_UTC_TIME => {
+            let year: i64 = text
+                .get(..2)
+                .ok_or_else(invalid)?
+                .parse()
+                .map_err(|_| invalid())?;
+            // RFC 5280: two-digit years below 50 are in the 21st century
+            (
+                if year < 50 { 2000 + year } else { 1900 + year },
+                &text[2..],
+            )
+        }
+        TAG_GENERALIZED_TIME => (
+            text.get(..4)
+                .ok_or_else(invalid)?
+                .parse()
+                .map_err(|_| invalid())?,
+            &text[4..],
+        ),
+        _ => return Err(invalid()),
+    };
+    let field = |i: usize| -> Result<i64, String> {
+        rest.get(i * 2..i * 2 + 2)
+            .ok_or_else(invalid)?
+            .parse()
+            .map_err(|_| invalid())
+    };
+    let days = utils::days_from_civil(year, field(0)?, field(1)?);
+    let seconds = days * 86400 + field(2)? * 3600 + field(3)? * 60 + field(4)?;
+    let seconds = u64::try_from(seconds).map_err(|_| invalid())?;
+    Ok(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// A decoded PEM block
+struct PemBlock {
+    label: String,
+    /// Headers like `Proc-Type: 4,ENCRYPTED` of traditional PEM keys
+    headers: Vec<(String, String)>,
+    data: Vec<u8>,
+}
+
+/// Find the first PEM block in *content* whose label satisfies *accept*
+fn find_pem_block(content: &[u8], accept: impl Fn(&str) -> bool) -> Option<PemBlock> {
+    let content = String::from_utf8_lossy(content);
+    let mut lines = content.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let label = match line
+            .strip_prefix("-----BEGIN ")
+            .and_then(|line| line.strip_suffix("-----"))
+        {
+            Some(label) if accept(label) => label,
+            _ => continue,
+        };
+
+        let end = format!("-----END {}-----", label);
+        let mut headers = Vec::new();
+        let mut encoded = String::new();
+        for line in lines.by_ref() {
+            if line == end {
+                return Some(PemBlock {
+                    label: label.to_string(),
+                    headers,
+                    data: utils::base64_decode(&encoded)?,
+                });
+            }
+            match line.split_once(": ") {
+                Some((key, value)) => headers.push((key.to_string(), value.to_string())),
+                None => encoded.push_str(line),
+            }
+        }
+        return None;
+    }
+    None
+}
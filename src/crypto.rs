@@ -0,0 +1,666 @@
+//! Configuration and execution of the gpg operations that are performed on store content
+
+use crate::context_pool::{ContextPool, PooledContext};
+use crate::telemetry::{span, warning};
+use crate::{utils, PassError, Result};
+use gpgme::{Context, Data, EncryptFlags, IntoData, Validity};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Policy that decides which recipient keys are acceptable during encryption
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum EncryptionPolicy {
+    /// Encrypt for all configured keys regardless of their trust level (like *pass* does)
+    #[default]
+    AlwaysTrust,
+    /// Only encrypt for keys that have a full or ultimate validity in the gpg web of trust
+    ///
+    /// Encryption operations fail with [`PassError::UnusableRecipient`] if a recipient key is not
+    /// sufficiently trusted.
+    RequireValidTrust,
+}
+
+/// Whether encrypted payloads are compressed before encrypting them
+///
+/// Uncompressed entries are slightly larger but similar content yields similar ciphertext sizes which can
+/// help delta based synchronization tools like rsync or Syncthing.
+/// gpgme does not allow choosing a specific algorithm (ZIP, ZLIB or BZIP2) so if compression is enabled,
+/// gpg picks the algorithm from the preferences of the recipient keys.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum Compression {
+    /// Let gpg decide whether and how to compress (like *pass* does)
+    #[default]
+    Default,
+    /// Never compress payloads
+    None,
+}
+
+/// Outcome of verifying the signature of an encrypted entry
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum SignatureStatus {
+    /// The entry is not signed
+    Unsigned,
+    /// All signatures of the entry are valid
+    Valid {
+        /// Fingerprints of the keys which signed the entry
+        fingerprints: Vec<String>,
+    },
+    /// At least one signature of the entry is invalid, e.g. because the content was tampered with
+    Invalid {
+        /// Fingerprint of the key which produced the invalid signature
+        fingerprint: String,
+        /// Description of why the signature is invalid
+        reason: String,
+    },
+}
+
+/// A gpg operation whose duration can be limited with
+/// [`Store::with_gpg_timeout()`](crate::Store::with_gpg_timeout)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum GpgOperation {
+    /// Decrypting an entry, which may wait for pinentry or a smartcard
+    Decrypt,
+    /// Encrypting an entry, which may wait for pinentry if entries are signed
+    Encrypt,
+    /// Looking up the keys of recipients in the keyring, which may wait for a locked keyring
+    KeyLookup,
+}
+
+impl fmt::Display for GpgOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Decrypt => "decryption",
+            Self::Encrypt => "encryption",
+            Self::KeyLookup => "key lookup",
+        })
+    }
+}
+
+/// Options that are applied to encryption operations
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub(crate) struct CryptoOptions {
+    pub(crate) policy: EncryptionPolicy,
+    /// Whether content is signed with the default secret key while encrypting it
+    pub(crate) sign: bool,
+    /// Whether ciphertext is written in ASCII armored form instead of binary
+    pub(crate) armor: bool,
+    pub(crate) compression: Compression,
+    pub(crate) decrypt_timeout: Option<Duration>,
+    pub(crate) encrypt_timeout: Option<Duration>,
+    pub(crate) key_lookup_timeout: Option<Duration>,
+    /// Whether gpg is prevented from accessing the network (e.g. key servers or Web Key Directories)
+    pub(crate) offline: bool,
+    /// The directory containing the keyring which is used instead of the default one
+    pub(crate) gpg_home: Option<Arc<Path>>,
+}
+
+impl CryptoOptions {
+    /// The time after which *operation* is aborted or `None` if it may take arbitrarily long
+    pub(crate) fn timeout(&self, operation: GpgOperation) -> Option<Duration> {
+        match operation {
+            GpgOperation::Decrypt => self.decrypt_timeout,
+            GpgOperation::Encrypt => self.encrypt_timeout,
+            GpgOperation::KeyLookup => self.key_lookup_timeout,
+        }
+    }
+
+    /// Abort *operation* after *timeout* or never if it is `None`
+    pub(crate) fn set_timeout(&mut self, operation: GpgOperation, timeout: Option<Duration>) {
+        match operation {
+            GpgOperation::Decrypt => self.decrypt_timeout = timeout,
+            GpgOperation::Encrypt => self.encrypt_timeout = timeout,
+            GpgOperation::KeyLookup => self.key_lookup_timeout = timeout,
+        }
+    }
+
+    /// Take a context from *pool* which is configured according to these options
+    pub(crate) fn context<'a>(&self, pool: &'a ContextPool) -> Result<PooledContext<'a>> {
+        pool.get(self.offline, self.gpg_home.as_deref())
+    }
+
+    /// Create a new context which is configured according to these options
+    ///
+    /// All gpg operations use contexts created by this or [`CryptoOptions::context()`] so that no operation
+    /// ignores the options of its store.
+    pub(crate) fn new_context(&self) -> Result<Context> {
+        utils::create_gpg_context(self.offline, self.gpg_home.as_deref())
+    }
+
+    /// Encrypt *plaintext* for all *keys* according to these options using a context of *pool*
+    ///
+    /// ## Errors
+    /// [`PassError::Timeout`] is returned if an encryption timeout is configured and exceeded.
+    pub(crate) fn encrypt(
+        &self,
+        pool: &ContextPool,
+        keys: &[gpgme::Key],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>> {
+        let _span = span!(
+            "encrypt",
+            bytes = plaintext.len(),
+            recipients = keys.len(),
+            sign = self.sign,
+            armor = self.armor,
+            compression = ?self.compression
+        );
+        let flags = self.flags(keys)?;
+
+        match self.encrypt_timeout {
+            None => self.encrypt_in(&mut *self.context(pool)?, keys, plaintext, flags),
+            Some(timeout) => {
+                let (options, pool, keys) = (self.clone(), pool.clone(), keys.to_vec());
+                let mut plaintext = plaintext.to_vec();
+                with_timeout(GpgOperation::Encrypt, timeout, move |cancellation| {
+                    let mut gpg_ctx = options.context(&pool)?;
+                    let result = options.encrypt_in(&mut gpg_ctx, &keys, &plaintext, flags);
+                    // the worker holds its own copy of the plaintext
+                    plaintext.fill(0);
+                    if cancellation.is_cancelled() {
+                        gpg_ctx.discard();
+                    }
+                    result
+                })
+            }
+        }
+    }
+
+    /// Encrypt *plaintext* for all *keys* with *flags* according to these options using *gpg_ctx*
+    fn encrypt_in(
+        &self,
+        gpg_ctx: &mut Context,
+        keys: &[gpgme::Key],
+        plaintext: &[u8],
+        flags: EncryptFlags,
+    ) -> Result<Vec<u8>> {
+        let mut ciphertext = Vec::new();
+        self.encrypt_into(gpg_ctx, keys, plaintext, &mut ciphertext, flags)?;
+        Ok(ciphertext)
+    }
+
+    /// Encrypt *plaintext* for all *keys* with *flags* into *ciphertext* according to these options using
+    /// *gpg_ctx*
+    fn encrypt_into<'p, 'c>(
+        &self,
+        gpg_ctx: &mut Context,
+        keys: &[gpgme::Key],
+        plaintext: impl IntoData<'p>,
+        ciphertext: impl IntoData<'c>,
+        flags: EncryptFlags,
+    ) -> Result<()> {
+        // pooled contexts may have been used with other options before
+        gpg_ctx.set_armor(self.armor);
+        if self.sign {
+            gpg_ctx.sign_and_encrypt_with_flags(keys, plaintext, ciphertext, flags)?;
+        } else {
+            gpg_ctx.encrypt_with_flags(keys, plaintext, ciphertext, flags)?;
+        }
+        Ok(())
+    }
+
+    /// Look up the keys *key_ids* in the keyring using a context of *pool*
+    ///
+    /// ## Errors
+    /// [`PassError::GpgKeyNotFoundError`] is returned for the first key which cannot be found and
+    /// [`PassError::Timeout`] if a key lookup timeout is configured and exceeded.
+    pub(crate) fn lookup_keys(
+        &self,
+        pool: &ContextPool,
+        key_ids: &[String],
+    ) -> Result<Vec<gpgme::Key>> {
+        match self.key_lookup_timeout {
+            None => lookup_keys_in(&mut *self.context(pool)?, key_ids),
+            Some(timeout) => {
+                let (options, pool, key_ids) = (self.clone(), pool.clone(), key_ids.to_vec());
+                with_timeout(GpgOperation::KeyLookup, timeout, move |cancellation| {
+                    let mut gpg_ctx = options.context(&pool)?;
+                    let result = lookup_keys_in(&mut gpg_ctx, &key_ids);
+                    if cancellation.is_cancelled() {
+                        gpg_ctx.discard();
+                    }
+                    result
+                })
+            }
+        }
+    }
+
+    /// Decrypt *ciphertext* into its plaintext using a context of *pool*
+    ///
+    /// ## Errors
+    /// [`PassError::Timeout`] is returned if a decryption timeout is configured and exceeded.
+    pub(crate) fn decrypt(&self, pool: &ContextPool, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let _span = span!("decrypt", bytes = ciphertext.len());
+        match self.decrypt_timeout {
+            None => decrypt_in(&mut *self.context(pool)?, ciphertext),
+            Some(timeout) => {
+                let (options, pool, ciphertext) = (self.clone(), pool.clone(), ciphertext.to_vec());
+                with_timeout(GpgOperation::Decrypt, timeout, move |cancellation| {
+                    let mut gpg_ctx = options.context(&pool)?;
+                    let mut result = decrypt_in(&mut gpg_ctx, &ciphertext);
+                    if cancellation.is_cancelled() {
+                        gpg_ctx.discard();
+                        // nobody receives the plaintext anymore
+                        if let Ok(plaintext) = &mut result {
+                            plaintext.fill(0);
+                        }
+                    }
+                    result
+                })
+            }
+        }
+    }
+
+    /// Decrypt *ciphertext* and encrypt its plaintext for all *keys* into *output* without ever holding the
+    /// whole plaintext in memory
+    ///
+    /// Decryption runs on a separate thread and passes the plaintext to the encryption in chunks through a
+    /// bounded pipe, so at most [`STREAM_CHUNKS_IN_FLIGHT`] chunks of [`STREAM_CHUNK_SIZE`] bytes are buffered
+    /// at any time and every chunk is zeroed once it was consumed.
+    /// Timeouts are not applied since the duration grows with the size of the entry.
+    ///
+    /// *output* may contain an incomplete message if an error is returned, so it should only be committed
+    /// once this succeeds.
+    pub(crate) fn reencrypt_stream(
+        &self,
+        pool: &ContextPool,
+        keys: &[gpgme::Key],
+        ciphertext: impl Read + Send,
+        output: impl Write + Send,
+    ) -> Result<()> {
+        let _span = span!("reencrypt_stream", recipients = keys.len());
+        let flags = self.flags(keys)?;
+        let (writer, reader) = pipe();
+        thread::scope(|scope| {
+            let decryption = thread::Builder::new()
+                .name("libpass-stream-decryption".to_string())
+                .spawn_scoped(scope, || -> Result<()> {
+                    let mut gpg_ctx = self.context(pool)?;
+                    let ciphertext = Data::from_reader(ciphertext).map_err(|e| e.error())?;
+                    let plaintext = Data::from_writer(writer).map_err(|e| e.error())?;
+                    gpg_ctx.decrypt(ciphertext, plaintext)?;
+                    Ok(())
+                })?;
+            let encryption = (|| {
+                let mut gpg_ctx = self.context(pool)?;
+                let plaintext = Data::from_reader(reader).map_err(|e| e.error())?;
+                let ciphertext = Data::from_writer(output).map_err(|e| e.error())?;
+                self.encrypt_into(&mut gpg_ctx, keys, plaintext, ciphertext, flags)
+            })();
+            let decryption = match decryption.join() {
+                Ok(result) => result,
+                Err(panic) => std::panic::resume_unwind(panic),
+            };
+            // a failed decryption closes the pipe early which looks like a complete plaintext to the encryption
+            // while a failed encryption breaks the pipe and thereby fails the decryption as well
+            match encryption {
+                Ok(()) => decryption,
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    /// Create a detached signature of *data* with the secret key *signing_key*
+    pub(crate) fn sign_detached(&self, signing_key: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let mut gpg_ctx = self.new_context()?;
+        let key = gpg_ctx
+            .get_secret_key(signing_key)
+            .map_err(|_| PassError::GpgKeyNotFoundError(signing_key.to_string()))?;
+        gpg_ctx.add_signer(&key)?;
+        let mut signature = Vec::new();
+        gpg_ctx.sign_detached(data, &mut signature)?;
+        Ok(signature)
+    }
+
+    /// Verify the detached *signature* of *data*
+    ///
+    /// Keys of unknown signers are never retrieved from the network while offline.
+    pub(crate) fn verify_detached(&self, signature: &[u8], data: &[u8]) -> Result<SignatureStatus> {
+        let mut gpg_ctx = self.new_context()?;
+        let verification = gpg_ctx.verify_detached(signature, data)?;
+        Ok(signature_status(&verification))
+    }
+
+    /// Decrypt *ciphertext* and verify the signatures that are contained in it
+    pub(crate) fn verify_signature(&self, ciphertext: &[u8]) -> Result<SignatureStatus> {
+        let mut gpg_ctx = self.new_context()?;
+        let mut plaintext = Vec::new();
+        let (_, verification) = gpg_ctx.decrypt_and_verify(ciphertext, &mut plaintext)?;
+        plaintext.fill(0);
+        Ok(signature_status(&verification))
+    }
+
+    /// The flags with which content is encrypted for *keys* according to these options
+    ///
+    /// ## Errors
+    /// [`PassError::UnusableRecipient`] is returned if a key is not trusted as required by the policy.
+    pub(crate) fn flags(&self, keys: &[gpgme::Key]) -> Result<EncryptFlags> {
+        let mut flags = match self.policy {
+            EncryptionPolicy::AlwaysTrust => EncryptFlags::ALWAYS_TRUST,
+            EncryptionPolicy::RequireValidTrust => {
+                keys.iter().try_for_each(check_key_trusted)?;
+                EncryptFlags::empty()
+            }
+        };
+        if self.compression == Compression::None {
+            flags |= EncryptFlags::NO_COMPRESS;
+        }
+        Ok(flags)
+    }
+
+    /// Encrypt *plaintext* for all *keys* according to these options into an ASCII armored message
+    /// regardless of whether armoring is enabled
+    pub(crate) fn encrypt_armored(
+        &self,
+        pool: &ContextPool,
+        keys: &[gpgme::Key],
+        plaintext: &[u8],
+    ) -> Result<String> {
+        let options = Self {
+            armor: true,
+            ..self.clone()
+        };
+        let ciphertext = options.encrypt(pool, keys, plaintext)?;
+        // armored messages only consist of ASCII characters
+        Ok(String::from_utf8_lossy(&ciphertext).into_owned())
+    }
+}
+
+/// Decrypt *ciphertext* into its plaintext using a context of *pool* without a timeout
+pub(crate) fn decrypt(pool: &ContextPool, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    CryptoOptions::default().decrypt(pool, ciphertext)
+}
+
+/// Decrypt *ciphertext* into its plaintext using *gpg_ctx*
+fn decrypt_in(gpg_ctx: &mut Context, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    let mut plaintext = Vec::new();
+    gpg_ctx.decrypt(ciphertext, &mut plaintext)?;
+    Ok(plaintext)
+}
+
+/// The size from which entries are streamed when they are re-encrypted unless configured otherwise with
+/// [`Store::with_streaming_threshold()`](crate::Store::with_streaming_threshold)
+pub const DEFAULT_STREAMING_THRESHOLD: u64 = 1024 * 1024;
+
+/// Size of the chunks in which plaintext is passed from decryption to encryption by
+/// [`CryptoOptions::reencrypt_stream()`]
+pub(crate) const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Number of chunks which may be buffered between decryption and encryption by
+/// [`CryptoOptions::reencrypt_stream()`]
+pub(crate) const STREAM_CHUNKS_IN_FLIGHT: usize = 4;
+
+/// Create a bounded in-memory pipe which passes plaintext between two gpg operations on different threads
+pub(crate) fn pipe() -> (PipeWriter, PipeReader) {
+    let (sender, receiver) = mpsc::sync_channel(STREAM_CHUNKS_IN_FLIGHT);
+    (
+        PipeWriter(sender),
+        PipeReader {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+        },
+    )
+}
+
+/// The writing half of a [`pipe()`] which blocks while the pipe is full
+#[derive(Debug)]
+pub(crate) struct PipeWriter(mpsc::SyncSender<Vec<u8>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(STREAM_CHUNK_SIZE);
+        self.0.send(buf[..len].to_vec()).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "the reading half was closed")
+        })?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The reading half of a [`pipe()`] which reaches its end once the writing half is dropped
+#[derive(Debug)]
+pub(crate) struct PipeReader {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            self.chunk.fill(0);
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.position);
+        buf[..len].copy_from_slice(&self.chunk[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.chunk.fill(0);
+        // chunks which were never read still contain plaintext
+        while let Ok(mut chunk) = self.receiver.try_recv() {
+            chunk.fill(0);
+        }
+    }
+}
+
+/// Look up the keys *key_ids* in the keyring using *gpg_ctx*
+fn lookup_keys_in(gpg_ctx: &mut Context, key_ids: &[String]) -> Result<Vec<gpgme::Key>> {
+    key_ids
+        .iter()
+        .map(|key_id| {
+            gpg_ctx
+                .get_key(key_id)
+                .map_err(|_| PassError::GpgKeyNotFoundError(key_id.to_string()))
+        })
+        .collect()
+}
+
+/// Signals a worker of [`with_timeout()`] that nobody waits for its result anymore
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the operation was cancelled because its timeout elapsed
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// The maximum number of worker threads of [`with_timeout()`] which exist at once
+///
+/// Cancelled workers keep running until gpg returns, so without a bound every hung operation would leave
+/// another thread behind.
+pub(crate) const MAX_TIMEOUT_WORKERS: usize = 8;
+
+static WORKERS: Workers = Workers {
+    running: Mutex::new(0),
+    finished: Condvar::new(),
+};
+
+/// The number of running workers of [`with_timeout()`], including cancelled ones
+struct Workers {
+    running: Mutex<usize>,
+    finished: Condvar,
+}
+
+impl Workers {
+    /// Reserve a slot for a new worker or return `None` if none becomes free before *deadline*
+    fn acquire(&'static self, deadline: Instant) -> Option<WorkerSlot> {
+        let mut running = self
+            .running
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        while *running >= MAX_TIMEOUT_WORKERS {
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            running = self
+                .finished
+                .wait_timeout(running, remaining)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+        *running += 1;
+        Some(WorkerSlot(self))
+    }
+}
+
+/// A slot of [`Workers`] which is released once the worker holding it finishes
+struct WorkerSlot(&'static Workers);
+
+impl Drop for WorkerSlot {
+    fn drop(&mut self) {
+        *self
+            .0
+            .running
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) -= 1;
+        self.0.finished.notify_one();
+    }
+}
+
+/// Run *operation* on a worker thread and stop waiting for it once *timeout* elapses
+///
+/// gpgme offers no way to interrupt a blocking call (e.g. one that waits for a hung pinentry), so a worker
+/// which exceeds its timeout is cancelled instead: it runs to completion in the background and then discards
+/// its result and gpg context instead of handing them back.
+/// At most [`MAX_TIMEOUT_WORKERS`] workers exist at once; further operations wait for one of them to
+/// finish within their own timeout.
+///
+/// ## Errors
+/// [`PassError::Timeout`] is returned if the worker does not finish within *timeout*.
+pub(crate) fn with_timeout<T: Send + 'static>(
+    operation: GpgOperation,
+    timeout: Duration,
+    run: impl FnOnce(&Cancellation) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let deadline = Instant::now() + timeout;
+    let slot = match WORKERS.acquire(deadline) {
+        Some(slot) => slot,
+        None => {
+            warning!(
+                "Gave up on gpg {} after {:?} because too many earlier operations are still hung",
+                operation,
+                timeout
+            );
+            return Err(PassError::Timeout { operation, timeout });
+        }
+    };
+    let cancellation = Cancellation::default();
+    let (sender, receiver) = mpsc::sync_channel(1);
+    let worker = {
+        let cancellation = cancellation.clone();
+        thread::Builder::new()
+            .name(format!("libpass-{}", operation).replace(' ', "-"))
+            .spawn(move || {
+                let _slot = slot;
+                // the receiver is gone if the operation was cancelled
+                let _ = sender.send(run(&cancellation));
+            })?
+    };
+    match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            cancellation.cancel();
+            warning!("Cancelled gpg {} after {:?}", operation, timeout);
+            Err(PassError::Timeout { operation, timeout })
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => match worker.join() {
+            Err(panic) => std::panic::resume_unwind(panic),
+            Ok(()) => unreachable!("the worker sends a result before it finishes"),
+        },
+    }
+}
+
+fn signature_status(verification: &gpgme::VerificationResult) -> SignatureStatus {
+    let mut fingerprints = Vec::new();
+    for signature in verification.signatures() {
+        let fingerprint = signature.fingerprint().unwrap_or_default().to_string();
+        if let Err(e) = signature.status() {
+            return SignatureStatus::Invalid {
+                fingerprint,
+                reason: e.to_string(),
+            };
+        }
+        fingerprints.push(fingerprint);
+    }
+
+    if fingerprints.is_empty() {
+        SignatureStatus::Unsigned
+    } else {
+        SignatureStatus::Valid { fingerprints }
+    }
+}
+
+/// *key* as upper case fingerprint without spaces and `0x` prefix or `None` if it is no full fingerprint
+///
+/// Key ids and other short forms are rejected because they can easily be forged.
+pub(crate) fn full_fingerprint(key: &str) -> Option<String> {
+    let key = key.split_whitespace().collect::<String>();
+    let hex = key
+        .strip_prefix("0x")
+        .or_else(|| key.strip_prefix("0X"))
+        .unwrap_or(&key);
+    match matches!(hex.len(), 40 | 64) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => Some(hex.to_ascii_uppercase()),
+        false => None,
+    }
+}
+
+impl SignatureStatus {
+    /// Whether all signatures are valid and one of them was made by a key whose full fingerprint is listed
+    /// in *trusted_keys*
+    ///
+    /// Entries of *trusted_keys* which are no full fingerprints never match.
+    pub(crate) fn is_valid_from(&self, trusted_keys: &[String]) -> bool {
+        let fingerprints = match self {
+            Self::Valid { fingerprints } => fingerprints,
+            _ => return false,
+        };
+        fingerprints
+            .iter()
+            .filter_map(|fingerprint| full_fingerprint(fingerprint))
+            .any(|fingerprint| {
+                trusted_keys
+                    .iter()
+                    .filter_map(|key| full_fingerprint(key))
+                    .any(|key| key == fingerprint)
+            })
+    }
+}
+
+/// Check that *key* has at least one user id with a full or ultimate validity
+fn check_key_trusted(key: &gpgme::Key) -> Result<()> {
+    if key
+        .user_ids()
+        .any(|uid| matches!(uid.validity(), Validity::Full | Validity::Ultimate))
+    {
+        Ok(())
+    } else {
+        Err(PassError::UnusableRecipient {
+            key_id: key.id().unwrap_or_default().to_string(),
+            reason: "key is not sufficiently trusted".to_string(),
+        })
+    }
+}
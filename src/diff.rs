@@ -0,0 +1,241 @@
+//! Field-level comparison of two versions of an entry and comparison of whole stores
+//!
+//! The result of a comparison describes *what* changed without revealing secret values (unless explicitly
+//! requested) which makes it suitable for audit logs and for generating git commit messages.
+//!
+//! [`diff_stores()`] compares all entries of two stores, e.g. to verify a backup or the result of syncing
+//! two machines.
+//!
+//! ## Example
+//! ```
+//! use libpass::{diff, ParsedEntry};
+//!
+//! let old: ParsedEntry = "hunter2\nuser: john\n".parse().unwrap();
+//! let new: ParsedEntry = "correct horse\nuser: john\nurl: https://example.com\n".parse().unwrap();
+//! assert_eq!(diff::diff(&old, &new).to_string(), "password changed, url added");
+//! ```
+
+use crate::{ParsedEntry, Result, Store, StoreFileRef};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+
+/// A single change between two versions of an entry
+///
+/// Field values are only contained if the diff was created by [`diff_with_values()`].
+/// The password is never contained.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Change {
+    /// The password on the first line was changed
+    PasswordChanged,
+    /// A field was added
+    FieldAdded {
+        /// Key of the field as written in the new version
+        key: String,
+        /// The new value
+        value: Option<String>,
+    },
+    /// A field was removed
+    FieldRemoved {
+        /// Key of the field as written in the old version
+        key: String,
+        /// The old value
+        value: Option<String>,
+    },
+    /// The value of a field was changed
+    FieldChanged {
+        /// Key of the field as written in the new version
+        key: String,
+        /// The old value
+        old: Option<String>,
+        /// The new value
+        new: Option<String>,
+    },
+    /// Lines which are not fields (like free-form notes) were changed
+    OtherContentChanged,
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PasswordChanged => write!(f, "password changed"),
+            Self::FieldAdded { key, .. } => write!(f, "{} added", key),
+            Self::FieldRemoved { key, .. } => write!(f, "{} removed", key),
+            Self::FieldChanged { key, .. } => write!(f, "{} changed", key),
+            Self::OtherContentChanged => write!(f, "notes changed"),
+        }
+    }
+}
+
+/// All changes between two versions of an entry
+///
+/// The `Display` implementation renders a short summary like `password changed, url added` which never
+/// contains any values.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct EntryDiff {
+    /// The changes in the order password, fields (in the order of the new version), removed fields and
+    /// other content
+    pub changes: Vec<Change>,
+}
+
+impl EntryDiff {
+    /// Whether both versions are equal
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl Display for EntryDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare *old* and *new* without including any values in the result
+pub fn diff(old: &ParsedEntry, new: &ParsedEntry) -> EntryDiff {
+    compare(old, new, false)
+}
+
+/// Compare *old* and *new* and include the old and new values of changed fields in the result
+///
+/// The password is still never included.
+pub fn diff_with_values(old: &ParsedEntry, new: &ParsedEntry) -> EntryDiff {
+    compare(old, new, true)
+}
+
+fn compare(old: &ParsedEntry, new: &ParsedEntry, include_values: bool) -> EntryDiff {
+    let value = |value: &str| include_values.then(|| value.to_string());
+    let mut changes = Vec::new();
+
+    if old.password() != new.password() {
+        changes.push(Change::PasswordChanged);
+    }
+
+    for (key, new_value) in first_fields(new) {
+        match old.field(key) {
+            None => changes.push(Change::FieldAdded {
+                key: key.to_string(),
+                value: value(new_value),
+            }),
+            Some(old_value) if old_value != new_value => changes.push(Change::FieldChanged {
+                key: key.to_string(),
+                old: value(old_value),
+                new: value(new_value),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (key, old_value) in first_fields(old) {
+        if new.field(key).is_none() {
+            changes.push(Change::FieldRemoved {
+                key: key.to_string(),
+                value: value(old_value),
+            });
+        }
+    }
+
+    let other_lines = |entry: &ParsedEntry| {
+        entry
+            .body_lines()
+            .filter(|line| crate::parsed_entry::parse_field(line).is_none())
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    if other_lines(old) != other_lines(new) {
+        changes.push(Change::OtherContentChanged);
+    }
+
+    EntryDiff { changes }
+}
+
+/// The first occurrence of every field of *entry* because only those are returned by [`ParsedEntry::field()`]
+fn first_fields(entry: &ParsedEntry) -> Vec<(&str, &str)> {
+    let mut fields: Vec<(&str, &str)> = Vec::new();
+    for (key, value) in entry.fields() {
+        if !fields.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            fields.push((key, value));
+        }
+    }
+    fields
+}
+
+/// How entries are compared by [`diff_stores()`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum StoreComparison {
+    /// Entries are changed if their encrypted files differ
+    ///
+    /// Nothing is decrypted but entries which were only re-encrypted (e.g. for other recipients) are
+    /// reported as changed.
+    #[default]
+    Ciphertext,
+    /// Entries whose encrypted files differ are decrypted and only reported as changed if their content
+    /// differs
+    Plaintext,
+}
+
+/// The entries which differ between two stores
+///
+/// All lists contain entry names and are sorted.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct StoreDiff {
+    /// Entries which only exist in the second store
+    pub added: Vec<String>,
+    /// Entries which only exist in the first store
+    pub removed: Vec<String>,
+    /// Entries which exist in both stores but differ
+    pub changed: Vec<String>,
+}
+
+impl StoreDiff {
+    /// Whether both stores contain the same entries
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare the entries of the stores *a* and *b*
+///
+/// Added and removed entries are reported from the perspective of *a*, so an entry which only exists in *b*
+/// is added.
+/// Decryptions of [plaintext comparisons](StoreComparison::Plaintext) go through the respective store, so
+/// their rate limits and access loggers apply.
+pub fn diff_stores(a: &Store, b: &Store, comparison: StoreComparison) -> Result<StoreDiff> {
+    let named_files = |store: &Store| -> Result<BTreeMap<String, StoreFileRef>> {
+        store
+            .files()?
+            .into_iter()
+            .map(|file| Ok((store.relative_name(&file.path)?, file)))
+            .collect()
+    };
+    let files_a = named_files(a)?;
+    let mut files_b = named_files(b)?;
+
+    let mut diff = StoreDiff::default();
+    for (name, file_a) in files_a {
+        let file_b = match files_b.remove(&name) {
+            Some(file_b) => file_b,
+            None => {
+                diff.removed.push(name);
+                continue;
+            }
+        };
+        let changed = fs::read(&file_a.path)? != fs::read(&file_b.path)?
+            && match comparison {
+                StoreComparison::Ciphertext => true,
+                StoreComparison::Plaintext => {
+                    a.read_plaintext(&file_a)? != b.read_plaintext(&file_b)?
+                }
+            };
+        if changed {
+            diff.changed.push(name);
+        }
+    }
+    diff.added = files_b.into_keys().collect();
+    Ok(diff)
+}
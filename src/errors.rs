@@ -1,7 +1,9 @@
+use crate::GpgOperation;
 #[cfg(nightly)]
 use std::backtrace::Backtrace;
 use std::io;
 use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error;
 
 /// Errors that may returned by library functions
@@ -15,6 +17,32 @@ pub enum PassError {
     #[error("The pass store at {0} is incorrectly formatted: {1}")]
     InvalidStoreFormat(PathBuf, String),
 
+    /// No `.gpg-id` file governs a location of the store so that the recipients of its entries are unknown
+    #[error(
+        "The pass store at {0} is not initialized because no .gpg-id file governs the location"
+    )]
+    StoreNotInitialized(PathBuf),
+
+    /// A file or directory of the store could have been tampered with by other users
+    #[error("Refusing to use {path} because {reason}")]
+    InsecurePermissions {
+        /// The affected file or directory
+        path: PathBuf,
+        /// What is insecure about it
+        reason: String,
+    },
+
+    /// A configuration variable has a value which can not be interpreted
+    #[error("The value {value:?} of {variable} is invalid: {reason}")]
+    InvalidConfig {
+        /// Name of the variable
+        variable: String,
+        /// The value which could not be interpreted
+        value: String,
+        /// What was expected instead
+        reason: String,
+    },
+
     /// The requested entry could not be clearly identified because it is ambiguous
     #[error("Password name {0} is ambiguous because it references a directory as well as a file inside the store")]
     AmbiguousPassName(String),
@@ -23,6 +51,48 @@ pub enum PassError {
     #[error("The requested entry ({0}) was not found in the password store")]
     EntryNotFound(String),
 
+    /// A file was requested but the name references a directory
+    #[error("The entry {0} is a directory and not a file")]
+    NotAFile(String),
+
+    /// A directory was requested but the name references a file
+    #[error("The entry {0} is a file and not a directory")]
+    NotADirectory(String),
+
+    /// An entry could not be created because another one with the same name already exists
+    #[error("The entry {0} already exists in the password store")]
+    EntryAlreadyExists(String),
+
+    /// An entry could not be removed or replaced because it is [protected](crate::Store::protect)
+    #[error("The entry {0} is protected")]
+    EntryProtected(String),
+
+    /// A password name was given which can not be used to reference an entry
+    #[error("The password name {0} is invalid: {1}")]
+    InvalidPassName(String, String),
+
+    /// A field of an entry can not be set to the given key or value
+    #[error("The field {key} can not be set: {reason}")]
+    InvalidField {
+        /// Key of the field
+        key: String,
+        /// Why the field can not be set
+        reason: String,
+    },
+
+    /// Values that were given to render a template do not match its fields
+    #[error("The values do not match the template {template}: {reason}")]
+    InvalidTemplateValues {
+        /// Name of the template
+        template: String,
+        /// Description of the mismatch
+        reason: String,
+    },
+
+    /// The decrypted content of an entry does not have the format that was expected
+    #[error("The content of the entry is malformed: {0}")]
+    MalformedEntry(String),
+
     /// An on-disk path could not be correctly interpreted by this program
     ///
     /// This can happen because rust imposes that all strings must be valid UTF-8 but some operating systems
@@ -32,10 +102,24 @@ pub enum PassError {
     #[error("Could not decode the path {0} as UTF-8 string")]
     PathDecodingError(PathBuf),
 
+    /// A file in the store does not contain a valid encrypted OpenPGP message, e.g. because it is empty or
+    /// corrupted
+    #[error("The file {0} does not contain a valid encrypted OpenPGP message: {1}")]
+    InvalidCiphertext(PathBuf, String),
+
     /// A gpg key was tried to be loaded but it could not be
     #[error("Could not load the gpg key {0}")]
     GpgKeyNotFoundError(String),
 
+    /// A gpg key was loaded but it can not be used to encrypt content for it
+    #[error("The gpg key {key_id} can not be used as a recipient: {reason}")]
+    UnusableRecipient {
+        /// Id of the affected key
+        key_id: String,
+        /// Description of why the key is unusable
+        reason: String,
+    },
+
     /// Some IO error occurred that is preserved as `source`
     #[error("IO Error")]
     IOError {
@@ -46,13 +130,261 @@ pub enum PassError {
         backtrace: Backtrace,
     },
 
+    /// The smartcard (e.g. a YubiKey) which holds the required secret key is not present
+    #[error("The smartcard holding the required secret key is not present")]
+    CardMissing {
+        /// The underlying error
+        source: gpgme::Error,
+    },
+
+    /// A PIN or passphrase is required to unlock the secret key but it could not be obtained
+    ///
+    /// This happens when no pinentry program is available, the user cancelled the prompt or entered a wrong
+    /// PIN.
+    #[error("A PIN or passphrase is required to unlock the secret key but could not be obtained")]
+    PinRequired {
+        /// The underlying error
+        source: gpgme::Error,
+    },
+
+    /// The gpg-agent is not running and could not be started
+    #[error("The gpg-agent is unavailable")]
+    AgentUnavailable {
+        /// The underlying error
+        source: gpgme::Error,
+    },
+
+    /// The signature of a `.gpg-id` file is invalid or missing although it is required
+    #[error("The signature of {path} could not be verified: {reason}")]
+    InvalidGpgIdSignature {
+        /// Path of the `.gpg-id` file
+        path: PathBuf,
+        /// Why the signature is not acceptable
+        reason: String,
+    },
+
+    /// Too many entries were decrypted recently so that a configured rate limit would be exceeded
+    #[error("Refusing to decrypt {pass_name} because the rate limit is exceeded, retry in {retry_after:?}")]
+    RateLimited {
+        /// Name of the entry which was to be decrypted
+        pass_name: String,
+        /// The time after which the decryption would be allowed again
+        retry_after: Duration,
+    },
+
+    /// A gpg operation did not finish in time, e.g. because gpg-agent or pinentry hangs
+    ///
+    /// See [`Store::with_gpg_timeout()`](crate::Store::with_gpg_timeout).
+    #[error("The gpg {operation} did not finish within {timeout:?}")]
+    Timeout {
+        /// The operation which was aborted
+        operation: GpgOperation,
+        /// The configured timeout of the operation
+        timeout: Duration,
+    },
+
+    /// An entry outside of the subpaths of a [`StoreView`](crate::view::StoreView) was accessed
+    #[error("Access to {0} is denied because it is outside of the store view")]
+    AccessDenied(String),
+
+    /// A bundle of entries could not be read because it is malformed or corrupted
+    #[error("The bundle is invalid: {0}")]
+    InvalidBundle(String),
+
+    /// The `pass` program which was invoked via [`PassCli`](crate::compat::PassCli) failed
+    #[error("pass failed with exit status {status:?}: {stderr}")]
+    PassCliFailed {
+        /// The exit status of the program or `None` if it was terminated by a signal
+        status: Option<i32>,
+        /// The error output of the program
+        stderr: String,
+    },
+
+    /// Resolving an alias led back to an alias which was already visited
+    #[error("The alias {} forms a loop", .0.join(" -> "))]
+    AliasLoop(Vec<String>),
+
+    /// Resolving a reference led back to an entry which was already visited
+    #[error("The reference {} forms a loop", .0.join(" -> "))]
+    ReferenceLoop(Vec<String>),
+
+    /// References are nested deeper than allowed
+    #[error("The reference {} is nested deeper than {max_depth} levels", .chain.join(" -> "))]
+    ReferenceTooDeep {
+        /// The entries which were visited, ending with the one which was not resolved anymore
+        chain: Vec<String>,
+        /// The maximum number of nested references
+        max_depth: usize,
+    },
+
+    /// A pattern or placeholder of a layout spec is malformed
+    #[cfg(feature = "layout")]
+    #[error("The layout rule {rule:?} is invalid: {reason}")]
+    InvalidLayoutRule {
+        /// The malformed pattern or regular expression
+        rule: String,
+        /// Why it is malformed
+        reason: String,
+    },
+
+    /// No `otpauth://` URI could be read from an image
+    #[cfg(feature = "otp-qr")]
+    #[error("No otpauth:// URI could be read from the image: {0}")]
+    NoOtpQrCode(String),
+
+    /// A serialized message was produced by a newer, incompatible version of the wire format
+    #[cfg(feature = "serde")]
+    #[error("The schema version {0} is not supported")]
+    UnsupportedSchemaVersion(u32),
+
+    /// An operation on the git repository of the store failed
+    #[cfg(feature = "git")]
+    #[error("Git error: {0}")]
+    GitError(#[from] git2::Error),
+
+    /// A conflict between local and remote changes could not be resolved because the file is not an entry
+    #[cfg(feature = "git")]
+    #[error("The conflict in {0} can not be resolved because it is not an entry of the store")]
+    GitConflict(PathBuf),
+
     /// Some error occurred during entry interaction that is preserved as `source`
     #[error("GPG error")]
     GpgError {
         /// The underlying error
-        #[from]
         source: gpgme::Error,
         #[cfg(nightly)]
         backtrace: Backtrace,
     },
 }
+
+/// A remediation that a user can perform to resolve an error
+///
+/// Front-ends can use this to show actionable messages instead of the raw error message.
+/// Get an instance of this by calling [`PassError::suggestion()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Suggestion {
+    /// Create a password store (e.g. by running `pass init <gpg-id>`)
+    RunPassInit,
+    /// Insert the smartcard which holds the secret key
+    InsertSmartcard,
+    /// Unlock the secret key by entering its PIN or passphrase (requires a working pinentry program)
+    EnterPin,
+    /// Start the gpg-agent (e.g. by running `gpgconf --launch gpg-agent`)
+    StartGpgAgent,
+    /// Restrict the permissions of the store so that only the current user can modify it
+    FixPermissions,
+    /// Import the missing gpg key into the keyring
+    ImportKey,
+    /// Assign sufficient trust to the recipient key or use another key
+    TrustKey,
+    /// Use another name for the entry
+    ChooseDifferentName,
+    /// Correct the configuration (e.g. the environment variables)
+    FixConfig,
+    /// Wait until the rate limit allows more decryptions
+    WaitAndRetry,
+    /// Resolve the conflict in the git repository of the store manually
+    ResolveGitConflict,
+    /// Restore the affected file from a backup because it is corrupted
+    RestoreFromBackup,
+}
+
+impl PassError {
+    /// A remediation which could resolve this error or `None` if there is no specific one
+    ///
+    /// ## Example
+    /// ```
+    /// use libpass::{PassError, Store, Suggestion};
+    ///
+    /// match Store::open("/does/not/exist") {
+    ///     Err(e) => assert_eq!(e.suggestion(), Some(Suggestion::RunPassInit)),
+    ///     Ok(_) => panic!(),
+    /// }
+    /// ```
+    pub fn suggestion(&self) -> Option<Suggestion> {
+        match self {
+            Self::PasswordStoreNotFound(_) | Self::StoreNotInitialized(_) => {
+                Some(Suggestion::RunPassInit)
+            }
+            Self::InsecurePermissions { .. } => Some(Suggestion::FixPermissions),
+            Self::InvalidConfig { .. } => Some(Suggestion::FixConfig),
+            #[cfg(feature = "layout")]
+            Self::InvalidLayoutRule { .. } => Some(Suggestion::FixConfig),
+            Self::AmbiguousPassName(_)
+            | Self::EntryAlreadyExists(_)
+            | Self::InvalidPassName(..) => Some(Suggestion::ChooseDifferentName),
+            Self::InvalidCiphertext(..) | Self::InvalidBundle(_) => {
+                Some(Suggestion::RestoreFromBackup)
+            }
+            Self::GpgKeyNotFoundError(_) => Some(Suggestion::ImportKey),
+            Self::UnusableRecipient { .. } => Some(Suggestion::TrustKey),
+            Self::CardMissing { .. } => Some(Suggestion::InsertSmartcard),
+            Self::PinRequired { .. } => Some(Suggestion::EnterPin),
+            Self::AgentUnavailable { .. } => Some(Suggestion::StartGpgAgent),
+            Self::RateLimited { .. } => Some(Suggestion::WaitAndRetry),
+            #[cfg(feature = "git")]
+            Self::GitConflict(_) => Some(Suggestion::ResolveGitConflict),
+            // listed explicitly so that new variants have to be considered here
+            Self::InvalidStoreFormat(..)
+            | Self::EntryNotFound(_)
+            | Self::NotAFile(_)
+            | Self::NotADirectory(_)
+            | Self::EntryProtected(_)
+            | Self::InvalidField { .. }
+            | Self::InvalidTemplateValues { .. }
+            | Self::MalformedEntry(_)
+            | Self::PathDecodingError(_)
+            | Self::IOError { .. }
+            | Self::InvalidGpgIdSignature { .. }
+            | Self::Timeout { .. }
+            | Self::AccessDenied(_)
+            | Self::PassCliFailed { .. }
+            | Self::AliasLoop(_)
+            | Self::ReferenceLoop(_)
+            | Self::ReferenceTooDeep { .. }
+            | Self::GpgError { .. } => None,
+            #[cfg(feature = "serde")]
+            Self::UnsupportedSchemaVersion(_) => None,
+            #[cfg(feature = "otp-qr")]
+            Self::NoOtpQrCode(_) => None,
+            #[cfg(feature = "git")]
+            Self::GitError(_) => None,
+        }
+    }
+}
+
+impl From<gpgme::Error> for PassError {
+    fn from(source: gpgme::Error) -> Self {
+        let is_any = |codes: &[gpgme::Error]| codes.iter().any(|e| e.code() == source.code());
+
+        if is_any(&[gpgme::Error::CARD_NOT_PRESENT, gpgme::Error::CARD_REMOVED]) {
+            Self::CardMissing { source }
+        } else if is_any(&[
+            gpgme::Error::NO_PIN_ENTRY,
+            gpgme::Error::PIN_ENTRY,
+            gpgme::Error::NO_PIN,
+            gpgme::Error::BAD_PIN,
+            gpgme::Error::BAD_PASSPHRASE,
+            gpgme::Error::CANCELED,
+        ]) {
+            Self::PinRequired { source }
+        } else if is_any(&[
+            gpgme::Error::NO_AGENT,
+            gpgme::Error::AGENT,
+            gpgme::Error::ASS_CONNECT_FAILED,
+        ]) {
+            Self::AgentUnavailable { source }
+        } else {
+            Self::GpgError {
+                source,
+                #[cfg(nightly)]
+                backtrace: Backtrace::capture(),
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Export of entries into formats which other tools consume
+//!
+//! ## Environment variables
+//! Like the `pass env` extension, every entry of a directory becomes one variable whose name is derived from the entry name with
+//! [`variable_name()`] and whose value is the first line (the password) of the entry.
+//! See [`StoreDirectoryRef::as_env_map()`](crate::StoreDirectoryRef::as_env_map) and
+//! [`StoreDirectoryRef::spawn_with_env()`](crate::StoreDirectoryRef::spawn_with_env).
+//!
+//! ### Example
+//! ```
+//! assert_eq!(libpass::export::variable_name("db-password"), "DB_PASSWORD");
+//! assert_eq!(libpass::export::variable_name("2fa.secret"), "_2FA_SECRET");
+//! ```
+//!
+//! ## dotenv files
+//! The same variables can be written as `.env` file with
+//! [`StoreDirectoryRef::render_dotenv()`](crate::StoreDirectoryRef::render_dotenv).
+//! In the other direction, [`Store::import_dotenv()`](crate::Store::import_dotenv) creates one entry per
+//! variable of a `.env` file so that pass can be the source of truth for such files.
+
+use crate::secure::SecretString;
+use crate::{PassError, Result};
+use std::io::Write;
+
+/// The name of the environment variable for the entry with the file name *name*
+///
+/// The name is uppercased and every character which is not an ASCII letter, digit or `_` is replaced by
+/// `_`.
+/// Names starting with a digit get a `_` prefix so that they can be used in shells.
+pub fn variable_name(name: &str) -> String {
+    let mut variable = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect::<String>();
+    if variable.starts_with(|c: char| c.is_ascii_digit()) || variable.is_empty() {
+        variable.insert(0, '_');
+    }
+    variable
+}
+
+/// Write *variables* as lines like `KEY=value` in the format of `.env` files
+///
+/// Values which contain anything but letters, digits and `_-.,:/@+` are double-quoted with `\\`, `"`, `$` and
+/// `` ` `` escaped.
+pub(crate) fn write_dotenv<'a>(
+    mut writer: impl Write,
+    variables: impl IntoIterator<Item = (&'a String, &'a SecretString)>,
+) -> Result<()> {
+    for (variable, value) in variables {
+        let value = value.expose();
+        let plain = value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.,:/@+".contains(c));
+        if plain {
+            writeln!(writer, "{}={}", variable, value)?;
+        } else {
+            let mut quoted = String::with_capacity(value.len() + 2);
+            for c in value.chars() {
+                match c {
+                    '\\' | '"' | '$' | '`' => {
+                        quoted.push('\\');
+                        quoted.push(c);
+                    }
+                    '\n' => quoted.push_str("\\n"),
+                    c => quoted.push(c),
+                }
+            }
+            writeln!(writer, "{}=\"{}\"", variable, quoted)?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse the variables of a `.env` file in the order in which they are defined
+///
+/// Empty lines, comments and an `export` prefix are ignored.
+/// Values can be unquoted, single-quoted (taken literally) or double-quoted (with `\\` escapes).
+///
+/// ## Errors
+/// [`PassError::InvalidField`] is returned for lines which do not define a variable.
+pub fn parse_dotenv(content: &str) -> Result<Vec<(String, SecretString)>> {
+    let mut variables = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let invalid = |reason: &str| PassError::InvalidField {
+            key: format!("line {}", i + 1),
+            reason: reason.to_string(),
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (variable, value) = line
+            .split_once('=')
+            .ok_or_else(|| invalid("The line is not of the form KEY=value"))?;
+        let variable = variable.trim();
+        if variable.is_empty() || variable.contains(char::is_whitespace) {
+            return Err(invalid("The variable name is invalid"));
+        }
+
+        let value = value.trim_start();
+        let value = if let Some(quoted) = value.strip_prefix('\'') {
+            let (value, _) = quoted
+                .split_once('\'')
+                .ok_or_else(|| invalid("The single quote is not closed"))?;
+            value.to_string()
+        } else if let Some(quoted) = value.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.chars();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => value.push('\n'),
+                        Some(c) => value.push(c),
+                        None => return Err(invalid("The double quote is not closed")),
+                    },
+                    Some(c) => value.push(c),
+                    None => return Err(invalid("The double quote is not closed")),
+                }
+            }
+            value
+        } else {
+            // unquoted values end at a comment
+            let value = match value.find(" #") {
+                Some(i) => &value[..i],
+                None => value,
+            };
+            value.trim_end().to_string()
+        };
+        variables.push((variable.to_string(), SecretString::from(value)));
+    }
+    Ok(variables)
+}
@@ -1,10 +1,17 @@
 //! Different handles and utilities for working with files
 
-use crate::{utils, Result};
+use crate::audit::{AccessOperation, AccessRecorder};
+use crate::config;
+use crate::context_pool::ContextPool;
+use crate::crypto::{self, CryptoOptions};
+use crate::secure_mem::{self, LockedRegion};
+use crate::telemetry::{span, trace, warning};
+use crate::text::TextOptions;
+use crate::{packets, utils, PassError, Result};
 
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::Path;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 
 /// A file handle that operates on encrypted content
 ///
@@ -35,21 +42,67 @@ use std::path::Path;
 /// # cipher_file.as_mut().seek(SeekFrom::Start(0)).unwrap();
 /// cipher_file.as_mut().write_all(&buffer).unwrap();
 /// ```
+///
+/// To swap in ciphertext that was produced elsewhere (e.g. received during a sync), use
+/// [`CipherFile::replace_ciphertext()`] which validates the new content and replaces the file atomically.
 #[derive(Debug)]
 pub struct CipherFile {
+    /// The underlying file which this handle wraps
     file: File,
+
+    /// Path of the underlying file
+    path: PathBuf,
+
+    /// Whether the underlying file was opened with write access
+    writable: bool,
 }
 
 impl CipherFile {
-    pub(crate) fn new(path: &Path) -> Result<Self> {
+    /// Open the file at *path*, only requesting write access if *writable* is set
+    ///
+    /// Read-only handles also work on read-only filesystems but writing to them fails.
+    pub(crate) fn new(path: &Path, writable: bool) -> Result<Self> {
         Ok(Self {
-            file: File::options()
-                .read(true)
-                .write(true)
-                .create(false)
-                .open(path)?,
+            file: Self::open(path, writable)?,
+            path: path.to_owned(),
+            writable,
         })
     }
+
+    fn open(path: &Path, writable: bool) -> io::Result<File> {
+        File::options()
+            .read(true)
+            .write(writable)
+            .create(false)
+            .open(path)
+    }
+
+    /// Replace the encrypted content of this file with the ciphertext read from *reader*
+    ///
+    /// The new content is validated to be an OpenPGP message before anything is changed.
+    /// It is then written to a temporary file which atomically replaces the original one so that the entry
+    /// is never left in a partially written state.
+    /// Afterwards, this handle refers to the new content.
+    pub fn replace_ciphertext(&mut self, mut reader: impl Read) -> Result<()> {
+        if !self.writable {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "cipher file handle was opened read-only",
+            )
+            .into());
+        }
+
+        let mut ciphertext = Vec::new();
+        reader.read_to_end(&mut ciphertext)?;
+        packets::validate_message(&ciphertext)
+            .map_err(|e| PassError::InvalidCiphertext(self.path.to_owned(), e.to_string()))?;
+
+        trace!("Replacing ciphertext of {}", self.path.display());
+        // the file exists so that its permissions are kept and the umask is irrelevant
+        utils::write_atomically(&self.path, &ciphertext, config::DEFAULT_UMASK)?;
+        self.file = Self::open(&self.path, self.writable)?;
+        Ok(())
+    }
 }
 
 impl AsRef<File> for CipherFile {
@@ -70,11 +123,15 @@ impl AsMut<File> for CipherFile {
 ///
 /// PlainFiles are automatically, encrypted synced and closed when they go out of scope.
 /// Errors detected on closing are logged and ignored by the implementation of Drop.
-/// Use the method [`RwPlainFile::sync()`] if these errors must be manually handled.
+/// Use the methods [`RwPlainFile::sync()`] or [`RwPlainFile::close()`] if these errors must be manually handled.
+/// To catch places where this is forgotten during development, the handle can be configured to panic in debug
+/// builds instead of only logging via [`RwPlainFile::set_panic_on_lost_changes()`].
 ///
 /// ## Usage
 /// This handle decrypts the entries content into an internal buffer when it is created.
 /// That buffer is intended as the access point to the decrypted content via `AsRef<Vec<u8>>` and `AsMut<Vec<u8>>`.
+/// Growing the buffer through `AsMut<Vec<u8>>` may leave copies of the content in freed memory while writes
+/// through the `Write` implementation release the old allocation first.
 ///
 /// For example, if you already have a [`StoreFileRef`](crate::StoreFileRef), you can use it to interact with
 /// the plaintext file content like so:
@@ -92,43 +149,129 @@ impl AsMut<File> for CipherFile {
 /// let content: &Vec<u8> = plain_file.as_ref();
 /// assert_eq!(content, "foobar123\n".as_bytes());
 /// ```
+///
+/// Additionally, the buffer can be accessed through the [`Read`], [`Write`] and [`Seek`] traits which
+/// operate on a cursor over the plaintext.
+/// Flushing the handle via [`Write::flush()`] is equivalent to calling [`RwPlainFile::sync()`]:
+/// ```
+/// # use std::io::{Read, Seek, SeekFrom};
+/// # use libpass::{StoreEntry};
+/// # std::env::set_var("PASSWORD_STORE_DIR", std::env::current_dir().unwrap().join("tests/simple"));
+/// # let store_file_ref = match libpass::retrieve("secret-a").unwrap() {
+/// #    StoreEntry::File(f) => f,
+/// #     _ => panic!()
+/// # };
+/// let mut plain_file = store_file_ref.plain_io_rw().unwrap();
+/// let mut password = String::new();
+/// plain_file.read_to_string(&mut password).unwrap();
+/// assert_eq!(password, "foobar123\n");
+///
+/// plain_file.seek(SeekFrom::Start(6)).unwrap();
+/// let mut digits = String::new();
+/// plain_file.read_to_string(&mut digits).unwrap();
+/// assert_eq!(digits, "123\n");
+/// ```
 #[derive(Debug)]
 pub struct RwPlainFile {
     /// The underlying file which this handle wraps
     file: File,
 
+    /// Path of the underlying file
+    path: PathBuf,
+
     /// The plaintext buffer that is exposed to the user to do their operations with
     buffer: Vec<u8>,
 
-    /// Backup buffer containing the last-synced plaintext content.
+    /// Whether the buffer may have been changed since it was last synced.
     /// This is used to decide whether an actual sync is needed or if it can be skipped because the content
     /// has not been changed.
-    last_synced_buffer: Vec<u8>,
+    /// It is set by every operation which gives mutable access to the buffer.
+    dirty: bool,
 
     /// Collection of keys which are used as gpg recipients during encryption
     encryption_keys: Vec<gpgme::Key>,
+
+    /// Options which are applied when encrypting the buffer
+    crypto: CryptoOptions,
+
+    /// Pool from which gpg contexts for decryption and encryption are taken
+    gpg_contexts: ContextPool,
+
+    /// Cursor position inside the buffer that is used by the `Read`, `Write` and `Seek` implementations
+    position: u64,
+
+    /// Whether the handle has already been closed explicitly so that Drop has nothing left to do
+    closed: bool,
+
+    /// Whether Drop panics in debug builds if changes can not be synced
+    panic_on_lost_changes: bool,
+
+    /// Lock of `buffer` into RAM
+    locked: LockedRegion,
+
+    /// Recorder and entry name with which writes of this handle are recorded
+    access: Option<(AccessRecorder, String)>,
+
+    /// Normalization which is applied to the buffer before it is encrypted
+    text: TextOptions,
 }
 
 impl RwPlainFile {
-    pub(crate) fn new(path: &Path, encryption_keys: Vec<gpgme::Key>) -> Result<Self> {
-        log::trace!("Opening {} as PlainFile", path.display());
-        let mut result = Self {
+    pub(crate) fn new(
+        path: &Path,
+        encryption_keys: Vec<gpgme::Key>,
+        crypto: CryptoOptions,
+    ) -> Result<Self> {
+        let mut result = Self::open(path, encryption_keys, crypto, ContextPool::default())?;
+        result.load_and_decrypt()?;
+        Ok(result)
+    }
+
+    /// Open the file at *path* with *plaintext* as its already decrypted content
+    pub(crate) fn with_plaintext(
+        path: &Path,
+        plaintext: Vec<u8>,
+        encryption_keys: Vec<gpgme::Key>,
+        crypto: CryptoOptions,
+        gpg_contexts: ContextPool,
+    ) -> Result<Self> {
+        let mut result = Self::open(path, encryption_keys, crypto, gpg_contexts)?;
+        result.buffer = plaintext;
+        result.locked.lock(&result.buffer);
+        Ok(result)
+    }
+
+    fn open(
+        path: &Path,
+        encryption_keys: Vec<gpgme::Key>,
+        crypto: CryptoOptions,
+        gpg_contexts: ContextPool,
+    ) -> Result<Self> {
+        trace!("Opening {} as PlainFile", path.display());
+        Ok(Self {
             file: File::options()
                 .read(true)
                 .write(true)
                 .create(false)
                 .open(path)?,
+            path: path.to_owned(),
             buffer: Vec::with_capacity(path.metadata()?.len() as usize),
-            last_synced_buffer: Vec::new(),
+            dirty: false,
             encryption_keys,
-        };
-        result.load_and_decrypt()?;
-        Ok(result)
+            crypto,
+            gpg_contexts,
+            position: 0,
+            closed: false,
+            panic_on_lost_changes: false,
+            locked: Default::default(),
+            access: None,
+            text: TextOptions::default(),
+        })
     }
 
     /// Load the content from filesystem and decrypt it into the internal buffer
     fn load_and_decrypt(&mut self) -> Result<()> {
-        log::trace!("Trying to load ciphertext and decrypt it to plaintext");
+        trace!("Trying to load ciphertext and decrypt it to plaintext");
 
         // read ciphertext from file
         let mut ciphertext = Vec::with_capacity(self.file.metadata()?.len() as usize);
@@ -136,37 +279,92 @@ impl RwPlainFile {
         self.file.read_to_end(&mut ciphertext)?;
 
         // decrypt ciphertext and store it in buffer
-        let mut gpg_ctx = utils::create_gpg_context()?;
-        gpg_ctx.decrypt(&mut ciphertext, &mut self.buffer)?;
-
-        self.last_synced_buffer = self.buffer.clone();
+        self.buffer = self.crypto.decrypt(&self.gpg_contexts, &ciphertext)?;
+        self.locked.lock(&self.buffer);
         Ok(())
     }
 
+    /// Record every write of this handle as access to *pass_name* with *recorder*
+    pub(crate) fn set_access_recorder(&mut self, recorder: AccessRecorder, pass_name: String) {
+        self.access = Some((recorder, pass_name));
+    }
+
+    /// Normalize the buffer with *text* before encrypting it
+    pub(crate) fn set_text_options(&mut self, text: TextOptions) {
+        self.text = text;
+    }
+
+    /// Make sure that the buffer can hold *additional* more bytes without being reallocated
+    ///
+    /// Call this before growing the buffer through [`as_mut()`](AsMut::as_mut) so that no copy of the
+    /// plaintext is left behind in freed memory (see [`secure_mem::reserve()`]).
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        secure_mem::reserve(&mut self.buffer, &mut self.locked, additional);
+    }
+
     /// Sync the buffer content into the file, encrypting it in the process
     ///
     /// Normally this operation only performs an actual content encryption and synchronization if necessary,
-    /// meaning if the buffer has been changed from the last time it was synced.
+    /// meaning if the buffer may have been changed (by writing to this handle or through
+    /// [`as_mut()`](AsMut::as_mut)) since the last time it was synced.
     /// To overwrite this behaviour and to force encryption and synchronization, set `force=true`.
     pub fn sync(&mut self, force: bool) -> Result<()> {
+        let _span = span!("plain_file_sync", force, dirty = self.dirty);
         // only do a content synchronization if the content has actually ben changed by the user
-        if !force && self.last_synced_buffer != self.buffer {
+        if force || self.dirty {
+            if let Some(normalized) = self.text.normalize(&self.buffer)? {
+                let mut original = std::mem::replace(&mut self.buffer, normalized);
+                secure_mem::release(&mut original, &mut self.locked);
+            }
+
             // encrypt the local buffer
-            let mut gpg_ctx = utils::create_gpg_context()?;
-            let mut ciphertext = Vec::new();
-            gpg_ctx.encrypt(&self.encryption_keys, &self.buffer, &mut ciphertext)?;
+            let ciphertext =
+                self.crypto
+                    .encrypt(&self.gpg_contexts, &self.encryption_keys, &self.buffer)?;
 
             // write it into the file
-            self.file.seek(SeekFrom::Start(0))?;
-            self.file.set_len(ciphertext.len() as u64)?;
-            self.file.write_all(&ciphertext)?;
-            self.last_synced_buffer = self.buffer.clone();
+            if is_hard_linked(&self.file)? {
+                // modifying the file in place would also modify other links to it (like snapshots)
+                trace!("Replacing hard-linked file {}", self.path.display());
+                // the file exists so that its permissions are kept and the umask is irrelevant
+                utils::write_atomically(&self.path, &ciphertext, config::DEFAULT_UMASK)?;
+                self.file = File::options().read(true).write(true).open(&self.path)?;
+            } else {
+                self.file.seek(SeekFrom::Start(0))?;
+                self.file.set_len(ciphertext.len() as u64)?;
+                self.file.write_all(&ciphertext)?;
+            }
+            self.dirty = false;
+            self.locked.lock(&self.buffer);
+
+            if let Some((recorder, pass_name)) = &self.access {
+                recorder.record(pass_name, AccessOperation::Write);
+            }
         }
 
         // also sync the internal file handle
         self.file.sync_all()?;
         Ok(())
     }
+
+    /// Sync the buffer content into the file and close this handle
+    ///
+    /// In contrast to dropping the handle, errors that occur during synchronization are returned instead of
+    /// being logged.
+    pub fn close(mut self) -> Result<()> {
+        let result = self.sync(false);
+        self.closed = true;
+        result
+    }
+
+    /// Configure whether dropping this handle panics if changes could not be synced and are therefore lost
+    ///
+    /// This only has an effect in debug builds and is intended to catch missing calls to
+    /// [`close()`](RwPlainFile::close) during development.
+    /// Release builds always only log the error.
+    pub fn set_panic_on_lost_changes(&mut self, enabled: bool) {
+        self.panic_on_lost_changes = enabled;
+    }
 }
 
 impl AsRef<Vec<u8>> for RwPlainFile {
@@ -177,18 +375,94 @@ impl AsRef<Vec<u8>> for RwPlainFile {
 
 impl AsMut<Vec<u8>> for RwPlainFile {
     fn as_mut(&mut self) -> &mut Vec<u8> {
+        // changes through the returned reference can not be observed so they are assumed to happen
+        self.dirty = true;
         &mut self.buffer
     }
 }
 
+impl Read for RwPlainFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = (self.position as usize).min(self.buffer.len());
+        let read = (&self.buffer[start..]).read(buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Write for RwPlainFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.dirty = true;
+
+        // writing past the end fills the gap with zeroes like files do
+        let start = self.position as usize;
+        if let Some(additional) = (start + buf.len()).checked_sub(self.buffer.len()) {
+            secure_mem::reserve(&mut self.buffer, &mut self.locked, additional);
+        }
+        if start > self.buffer.len() {
+            self.buffer.resize(start, 0);
+        }
+
+        let overwritten = buf.len().min(self.buffer.len() - start);
+        self.buffer[start..start + overwritten].copy_from_slice(&buf[..overwritten]);
+        self.buffer.extend_from_slice(&buf[overwritten..]);
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sync(false)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+}
+
+impl Seek for RwPlainFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => (self.buffer.len() as u64).checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        match new_position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
 impl Drop for RwPlainFile {
     fn drop(&mut self) {
-        if let Err(e) = self.sync(false) {
-            log::warn!(
-                "Error during drop of PlainFile, could not store encrypted content in file: {:?}",
-                e
-            )
+        if !self.closed {
+            if let Err(e) = self.sync(false) {
+                if cfg!(debug_assertions)
+                    && self.panic_on_lost_changes
+                    && self.dirty
+                    && !std::thread::panicking()
+                {
+                    panic!(
+                        "Unsynced changes of PlainFile are lost because they could not be stored: {:?}",
+                        e
+                    );
+                }
+
+                warning!(
+                    "Error during drop of PlainFile, could not store encrypted content in file: {:?}",
+                    e
+                )
+            }
         }
+
+        secure_mem::release(&mut self.buffer, &mut self.locked);
     }
 }
 
@@ -221,35 +495,40 @@ impl Drop for RwPlainFile {
 pub struct RoPlainFile {
     /// The plaintext buffer that is exposed to the user to do their operations with
     buffer: Vec<u8>,
+
+    /// Lock of `buffer` into RAM
+    locked: LockedRegion,
 }
 
 impl RoPlainFile {
     pub(crate) fn new(path: &Path) -> Result<Self> {
-        log::warn!("Opening {} as RoPlainFile", path.display());
+        warning!("Opening {} as RoPlainFile", path.display());
 
         let mut file = File::options().read(true).create(false).open(path)?;
-        Ok(Self {
-            buffer: Self::load_and_decrypt(&mut file)?,
-        })
+        Ok(Self::from_plaintext(Self::load_and_decrypt(&mut file)?))
+    }
+
+    /// Wrap *plaintext* which was already decrypted
+    pub(crate) fn from_plaintext(plaintext: Vec<u8>) -> Self {
+        let mut result = Self {
+            buffer: plaintext,
+            locked: LockedRegion::default(),
+        };
+        result.locked.lock(&result.buffer);
+        result
     }
 
     /// Load the content from filesystem and decrypt it into the internal buffer
     fn load_and_decrypt(file: &mut File) -> Result<Vec<u8>> {
-        log::trace!("Trying to load ciphertext and decrypt it to plaintext");
-
-        let file_len = file.metadata()?.len() as usize;
-        let mut buffer = Vec::with_capacity(file_len);
+        trace!("Trying to load ciphertext and decrypt it to plaintext");
 
         // read ciphertext from file
-        let mut ciphertext = Vec::with_capacity(file_len);
+        let mut ciphertext = Vec::with_capacity(file.metadata()?.len() as usize);
         file.seek(SeekFrom::Start(0))?;
         file.read_to_end(&mut ciphertext)?;
 
-        // decrypt ciphertext and store it in buffer
-        let mut gpg_ctx = utils::create_gpg_context()?;
-        gpg_ctx.decrypt(&mut ciphertext, &mut buffer)?;
-
-        Ok(buffer)
+        // decrypt ciphertext into buffer
+        crypto::decrypt(&ContextPool::default(), &ciphertext)
     }
 }
 
@@ -258,3 +537,70 @@ impl AsRef<Vec<u8>> for RoPlainFile {
         &self.buffer
     }
 }
+
+impl Drop for RoPlainFile {
+    fn drop(&mut self) {
+        secure_mem::release(&mut self.buffer, &mut self.locked);
+    }
+}
+
+/// The encrypted content of an entry file which is either read into memory or mapped into it
+#[derive(Debug)]
+pub(crate) enum Ciphertext {
+    Read(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Ciphertext {
+    /// Read the file at *path*, mapping it into memory instead if it is at least *mmap_threshold* bytes large
+    pub(crate) fn load(path: &Path, mmap_threshold: Option<u64>) -> Result<Self> {
+        #[cfg(feature = "mmap")]
+        if let Some(threshold) = mmap_threshold {
+            let file = File::open(path)?;
+            if file.metadata()?.len() >= threshold {
+                trace!("Mapping ciphertext of {} into memory", path.display());
+                return Ok(Self::Mapped(map(&file)?));
+            }
+        }
+        #[cfg(not(feature = "mmap"))]
+        let _ = mmap_threshold;
+
+        Ok(Self::Read(std::fs::read(path)?))
+    }
+}
+
+impl std::ops::Deref for Ciphertext {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Read(ciphertext) => ciphertext,
+            #[cfg(feature = "mmap")]
+            Self::Mapped(mapping) => mapping,
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+#[allow(unsafe_code)]
+fn map(file: &File) -> io::Result<memmap2::Mmap> {
+    // SAFETY: the mapping is only read while decrypting it. Concurrent modifications of the file can at worst
+    // make the decryption fail but truncating it while it is mapped terminates the process (SIGBUS), which is
+    // why mapping is opt-in and documented on Store::with_mmap_threshold().
+    unsafe { memmap2::Mmap::map(file) }
+}
+
+/// Whether other hard links to *file* exist
+fn is_hard_linked(file: &File) -> io::Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Ok(file.metadata()?.nlink() > 1)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = file;
+        Ok(false)
+    }
+}
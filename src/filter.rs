@@ -0,0 +1,140 @@
+//! Filtering of entries while traversing a store
+//!
+//! [`Store::list_filtered()`](crate::Store::list_filtered) only descends into the directories selected by a
+//! [`Filter`] and checks the remaining criteria on the files it encounters, so large stores do not pay for
+//! building entries which would be discarded afterwards anyway.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::filter::Filter;
+//! use libpass::Store;
+//! use std::time::{Duration, SystemTime};
+//!
+//! let last_week = SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+//! let recent = Store::from_env()
+//!     .unwrap()
+//!     .list_filtered(&Filter::new().under("work/").modified_since(last_week))
+//!     .unwrap();
+//! ```
+
+use crate::{names, walk, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Criteria which entries have to match to be listed by
+/// [`Store::list_filtered()`](crate::Store::list_filtered)
+///
+/// Criteria of different kinds must all match while multiple criteria of the same kind (e.g. two calls to
+/// [`under()`](Filter::under)) match if any of them does.
+/// An empty filter matches every entry.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct Filter {
+    extensions: Vec<String>,
+    under: Vec<String>,
+    modified_since: Option<SystemTime>,
+    modified_before: Option<SystemTime>,
+}
+
+impl Filter {
+    /// Create a filter which matches every entry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match entries stored in files with the extension *extension* (without the leading dot)
+    ///
+    /// Files which are no entries of the store (see [`Store::with_asc_entries()`](crate::Store::with_asc_entries))
+    /// are never matched.
+    pub fn extension(mut self, extension: impl Into<String>) -> Self {
+        self.extensions.push(extension.into());
+        self
+    }
+
+    /// Only match entries inside of the directory *dir*
+    ///
+    /// Other directories are not traversed at all.
+    pub fn under(mut self, dir: impl Into<String>) -> Self {
+        self.under.push(dir.into());
+        self
+    }
+
+    /// Only match entries which were modified at or after *time*
+    pub fn modified_since(mut self, time: SystemTime) -> Self {
+        self.modified_since = Some(time);
+        self
+    }
+
+    /// Only match entries which were modified before *time*
+    pub fn modified_before(mut self, time: SystemTime) -> Self {
+        self.modified_before = Some(time);
+        self
+    }
+
+    /// The directories inside of *root* which need to be traversed
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidPassName`](crate::PassError::InvalidPassName) is returned if a directory given to
+    /// [`under()`](Filter::under) is not a valid name.
+    fn base_dirs(&self, root: &Path) -> Result<Vec<PathBuf>> {
+        if self.under.is_empty() {
+            return Ok(vec![root.to_owned()]);
+        }
+        let mut dirs = Vec::new();
+        for dir in &self.under {
+            let path = names::dir_path(root, dir)?;
+            if path.is_dir() {
+                dirs.push(path);
+            }
+        }
+        Ok(dirs)
+    }
+
+    /// The extensions of *entry_extensions* which are accepted by this filter
+    fn extensions<'a>(&self, entry_extensions: &[&'a str]) -> Vec<&'a str> {
+        entry_extensions
+            .iter()
+            .filter(|extension| {
+                self.extensions.is_empty()
+                    || self
+                        .extensions
+                        .iter()
+                        .any(|candidate| candidate == *extension)
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Whether the entry file at *path* was modified in the range of this filter
+    fn matches_modified(&self, path: &Path) -> Result<bool> {
+        if self.modified_since.is_none() && self.modified_before.is_none() {
+            return Ok(true);
+        }
+        let modified = fs::metadata(path)?.modified()?;
+        Ok(self.modified_since.is_none_or(|since| modified >= since)
+            && self.modified_before.is_none_or(|before| modified < before))
+    }
+
+    /// Collect the paths of all entry files below *root* which match this filter
+    pub(crate) fn entry_files(
+        &self,
+        root: &Path,
+        entry_extensions: &[&str],
+    ) -> Result<Vec<PathBuf>> {
+        let extensions = self.extensions(entry_extensions);
+        if extensions.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut files = Vec::new();
+        for dir in self.base_dirs(root)? {
+            for path in walk::entry_files_with(&dir, &extensions)? {
+                if self.matches_modified(&path)? {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort_unstable();
+        files.dedup();
+        Ok(files)
+    }
+}
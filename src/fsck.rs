@@ -0,0 +1,244 @@
+//! Consistency checking of password stores
+//!
+//! Get a report by calling [`Store::fsck()`](crate::Store::fsck).
+
+use crate::crypto::CryptoOptions;
+use crate::telemetry::debug;
+use crate::{RecipientMismatch, Result, StoreFileRef};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+/// Files which are not entries but may legitimately be part of a store
+const METADATA_FILES: &[&str] = &[
+    ".gpg-id",
+    ".gpg-id.sig",
+    ".gitattributes",
+    ".gitignore",
+    ".gitmodules",
+    // submodules reference their repository with a .git file
+    ".git",
+];
+
+/// Directories which are not part of the entry hierarchy and are therefore not checked
+const IGNORED_DIRS: &[&str] = &[".git", ".extensions"];
+
+/// How severe an issue that was found during a consistency check is
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    /// The store works as expected but could be cleaned up
+    Info,
+    /// The store works but probably not as intended
+    Warning,
+    /// Entries of the store are not usable
+    Error,
+}
+
+/// The kind of issue that was found during a consistency check
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum FsckIssueKind {
+    /// A file that is neither an entry nor store metadata
+    OrphanFile,
+    /// A directory which contains nothing at all
+    EmptyDirectory,
+    /// A symlink whose target does not exist
+    BrokenSymlink,
+    /// An entry that could not be opened for reading
+    UnreadableFile,
+    /// An entry that does not contain a valid encrypted OpenPGP message
+    InvalidCiphertext,
+    /// An entry for which no `.gpg-id` file exists in its directory or any parent directory inside the store
+    MissingGpgId,
+    /// An entry for which the keys from its `.gpg-id` file could not be loaded so that its recipients could
+    /// not be checked
+    UnknownRecipients,
+    /// An entry whose recipients differ from the keys configured in its `.gpg-id` file
+    RecipientMismatch {
+        /// Ids of keys that are configured in `.gpg-id` but for which the entry is not encrypted
+        missing: Vec<String>,
+        /// Ids of keys for which the entry is encrypted but which are not configured in `.gpg-id`
+        unexpected: Vec<String>,
+    },
+}
+
+impl FsckIssueKind {
+    /// The severity of this kind of issue
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::OrphanFile | Self::EmptyDirectory => Severity::Info,
+            Self::BrokenSymlink | Self::UnknownRecipients | Self::RecipientMismatch { .. } => {
+                Severity::Warning
+            }
+            Self::UnreadableFile | Self::InvalidCiphertext | Self::MissingGpgId => Severity::Error,
+        }
+    }
+}
+
+/// A single issue that was found during a consistency check
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct FsckIssue {
+    /// Absolute path of the affected file or directory
+    pub path: PathBuf,
+    /// What kind of issue this is
+    pub kind: FsckIssueKind,
+    /// Human readable description of the issue
+    pub message: String,
+}
+
+impl FsckIssue {
+    /// How severe this issue is
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+/// The result of a consistency check of a store
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct FsckReport {
+    /// All issues that were found, in the order in which they were encountered
+    pub issues: Vec<FsckIssue>,
+}
+
+impl FsckReport {
+    /// Whether no issue with at least [`Severity::Warning`] was found
+    pub fn is_ok(&self) -> bool {
+        self.issues_at_least(Severity::Warning).next().is_none()
+    }
+
+    /// The severity of the most severe issue or `None` if no issues were found
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.issues.iter().map(FsckIssue::severity).max()
+    }
+
+    /// Iterate over all issues which have at least the given severity
+    pub fn issues_at_least(&self, severity: Severity) -> impl Iterator<Item = &FsckIssue> {
+        self.issues
+            .iter()
+            .filter(move |issue| issue.severity() >= severity)
+    }
+
+    fn push(&mut self, path: &Path, kind: FsckIssueKind, message: impl Into<String>) {
+        debug!("fsck found issue at {}: {:?}", path.display(), kind);
+        self.issues.push(FsckIssue {
+            path: path.to_owned(),
+            kind,
+            message: message.into(),
+        });
+    }
+}
+
+/// Check the whole store located at *root*
+///
+/// Keys are looked up according to *crypto*.
+pub(crate) fn check_store(root: &Path, crypto: &CryptoOptions) -> Result<FsckReport> {
+    let mut report = FsckReport::default();
+    check_dir(root, root, false, crypto, &mut report)?;
+    Ok(report)
+}
+
+/// Recursively check the directory at *dir*
+///
+/// *covered* indicates whether a parent directory contains a `.gpg-id` file.
+fn check_dir(
+    root: &Path,
+    dir: &Path,
+    covered: bool,
+    crypto: &CryptoOptions,
+    report: &mut FsckReport,
+) -> Result<()> {
+    let covered = covered || dir.join(".gpg-id").is_file();
+
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    if entries.is_empty() && dir != root {
+        report.push(
+            dir,
+            FsckIssueKind::EmptyDirectory,
+            "Directory does not contain anything",
+        );
+    }
+
+    for path in entries {
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+
+        // symlinks are followed but must point to something
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) if fs::symlink_metadata(&path).is_ok() => {
+                report.push(
+                    &path,
+                    FsckIssueKind::BrokenSymlink,
+                    "Symlink points to a non-existing target",
+                );
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if metadata.is_dir() {
+            if !IGNORED_DIRS.contains(&file_name) {
+                check_dir(root, &path, covered, crypto, report)?;
+            }
+        } else if file_name.ends_with(".gpg") {
+            check_file(root, &path, covered, crypto, report);
+        } else if !METADATA_FILES.contains(&file_name) {
+            report.push(
+                &path,
+                FsckIssueKind::OrphanFile,
+                "File is not an encrypted entry because it does not have a .gpg extension",
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the entry at *path* of the store at *root*
+fn check_file(
+    root: &Path,
+    path: &Path,
+    covered: bool,
+    crypto: &CryptoOptions,
+    report: &mut FsckReport,
+) {
+    if let Err(e) = File::open(path) {
+        report.push(path, FsckIssueKind::UnreadableFile, e.to_string());
+        return;
+    }
+
+    let file = StoreFileRef {
+        path: path.to_owned(),
+        root: root.to_owned(),
+    };
+    if let Err(e) = file.validate_ciphertext() {
+        report.push(path, FsckIssueKind::InvalidCiphertext, e.to_string());
+        return;
+    }
+
+    if !covered {
+        report.push(
+            path,
+            FsckIssueKind::MissingGpgId,
+            "No .gpg-id file exists in the directory of the entry or any of its parents",
+        );
+        return;
+    }
+
+    match RecipientMismatch::check(file, crypto) {
+        Ok(None) => {}
+        Ok(Some(mismatch)) => report.push(
+            path,
+            FsckIssueKind::RecipientMismatch {
+                missing: mismatch.missing,
+                unexpected: mismatch.unexpected,
+            },
+            "Entry is encrypted for different keys than its .gpg-id file specifies",
+        ),
+        Err(e) => report.push(path, FsckIssueKind::UnknownRecipients, e.to_string()),
+    }
+}
@@ -0,0 +1,411 @@
+//! Generation of random passwords like `pass generate`
+//!
+//! Passwords consist of characters which are drawn uniformly from a character set that is given in the syntax
+//! of `tr` (e.g. `[:alnum:]_-` or `a-z0-9`), using randomness of the operating system.
+//! Alternatively, memorable passphrases are built from words of a [`Wordlist`] (see
+//! [`PasswordGenerator::passphrase()`]), pronounceable passwords from syllables (see
+//! [`PasswordGenerator::pronounceable()`]) and passwords with a fixed structure from patterns (see
+//! [`PasswordGenerator::pattern()`]).
+//!
+//! ## Example
+//! ```
+//! use libpass::generate::PasswordGenerator;
+//!
+//! let generator = PasswordGenerator::new("[:digit:]").unwrap().with_length(6);
+//! let pin = generator.generate().unwrap();
+//! assert_eq!(pin.expose().len(), 6);
+//! assert!(pin.expose().chars().all(|c| c.is_ascii_digit()));
+//! ```
+
+use crate::secure::SecretString;
+use crate::{Config, PassError, Result};
+
+/// Generator of random passwords
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PasswordGenerator {
+    mode: Mode,
+    length: usize,
+}
+
+/// What generated passwords consist of
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+enum Mode {
+    Characters(Vec<char>),
+    Passphrase {
+        wordlist: Wordlist,
+        separator: String,
+    },
+    Pronounceable,
+    /// The characters that each position of the password is drawn from
+    Pattern(Vec<Vec<char>>),
+}
+
+/// Consonants of pronounceable passwords (without those that are easily confused or mispronounced)
+const CONSONANTS: &[char] = &[
+    'b', 'd', 'f', 'g', 'h', 'j', 'k', 'l', 'm', 'n', 'p', 'r', 's', 't', 'v', 'z',
+];
+
+/// Vowels of pronounceable passwords
+const VOWELS: &[char] = &['a', 'e', 'i', 'o', 'u'];
+
+/// The separator between words of passphrases by default
+const DEFAULT_SEPARATOR: &str = "-";
+
+/// The number of words of passphrases by default
+const DEFAULT_WORD_COUNT: usize = 6;
+
+impl PasswordGenerator {
+    /// Create a generator of passwords which consist of the characters of *character_set*
+    ///
+    /// The length of generated passwords defaults to the one of [`Config::default()`].
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidConfig`] is returned if *character_set* is malformed or empty.
+    pub fn new(character_set: &str) -> Result<Self> {
+        let characters =
+            parse_character_set(character_set).map_err(|reason| PassError::InvalidConfig {
+                variable: crate::config::CHARACTER_SET_ENV.to_string(),
+                value: character_set.to_string(),
+                reason,
+            })?;
+        Ok(Self {
+            mode: Mode::Characters(characters),
+            length: Config::default().generated_length(),
+        })
+    }
+
+    /// Create a generator of passphrases which consist of six words of *wordlist* separated by `-`
+    ///
+    /// With the `diceware` feature, [`Wordlist::eff_large()`] provides a suitable wordlist.
+    pub fn passphrase(wordlist: Wordlist) -> Self {
+        Self {
+            mode: Mode::Passphrase {
+                wordlist,
+                separator: DEFAULT_SEPARATOR.to_string(),
+            },
+            length: DEFAULT_WORD_COUNT,
+        }
+    }
+
+    /// Create a generator of pronounceable passwords which consist of lowercase syllables like `bakotiru`
+    ///
+    /// Pronounceable passwords are easier to type and remember but weaker than passwords of the same length
+    /// from a character set (about 3.2 bits per character, see [`entropy_bits()`](Self::entropy_bits)).
+    /// The length defaults to the one of [`Config::default()`].
+    pub fn pronounceable() -> Self {
+        Self {
+            mode: Mode::Pronounceable,
+            length: Config::default().generated_length(),
+        }
+    }
+
+    /// Create a generator of passwords which follow *pattern*, e.g. `AAaa99--`
+    ///
+    /// Every character of the pattern produces one character of the password:
+    /// - `A`: an uppercase letter
+    /// - `a`: a lowercase letter
+    /// - `9`: a digit
+    /// - `-`: a symbol (`[:punct:]`)
+    /// - `?`: any letter, digit or symbol (`[:graph:]`)
+    /// - `\x`: the character `x` itself
+    /// - any other character is kept as is
+    ///
+    /// The length of generated passwords is determined by the pattern so [`with_length()`](Self::with_length)
+    /// has no effect.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidConfig`] is returned if *pattern* ends with a single backslash.
+    pub fn pattern(pattern: &str) -> Result<Self> {
+        let mut positions = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            let class = match c {
+                'A' => "[:upper:]",
+                'a' => "[:lower:]",
+                '9' => "[:digit:]",
+                '-' => "[:punct:]",
+                '?' => "[:graph:]",
+                '\\' => match chars.next() {
+                    Some(literal) => {
+                        positions.push(vec![literal]);
+                        continue;
+                    }
+                    None => {
+                        return Err(PassError::InvalidConfig {
+                            variable: "pattern".to_string(),
+                            value: pattern.to_string(),
+                            reason: "Trailing backslash".to_string(),
+                        })
+                    }
+                },
+                literal => {
+                    positions.push(vec![literal]);
+                    continue;
+                }
+            };
+            positions.push(parse_character_set(class).expect("classes are valid"));
+        }
+        Ok(Self {
+            length: positions.len(),
+            mode: Mode::Pattern(positions),
+        })
+    }
+
+    /// Create a generator with the length and character set of *config*
+    ///
+    /// If *symbols* is false, the character set without symbols is used (like `pass generate --no-symbols`).
+    pub fn from_config(config: &Config, symbols: bool) -> Result<Self> {
+        let character_set = match symbols {
+            true => config.character_set(),
+            false => config.character_set_no_symbols(),
+        };
+        Ok(Self::new(character_set)?.with_length(config.generated_length()))
+    }
+
+    /// Generate passwords with *length* characters (or words for passphrases)
+    pub fn with_length(mut self, length: usize) -> Self {
+        if !matches!(self.mode, Mode::Pattern(_)) {
+            self.length = length;
+        }
+        self
+    }
+
+    /// The number of characters (or words for passphrases) of generated passwords
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Separate the words of passphrases by *separator*
+    ///
+    /// This has no effect on generators of character based passwords.
+    pub fn with_separator(mut self, separator: impl Into<String>) -> Self {
+        if let Mode::Passphrase {
+            separator: current, ..
+        } = &mut self.mode
+        {
+            *current = separator.into();
+        }
+        self
+    }
+
+    /// The distinct characters that generated passwords consist of or `None` if they are not drawn from a
+    /// character set
+    pub fn characters(&self) -> Option<&[char]> {
+        match &self.mode {
+            Mode::Characters(characters) => Some(characters),
+            _ => None,
+        }
+    }
+
+    /// The strength of generated passwords in bits
+    pub fn entropy_bits(&self) -> f64 {
+        let bits = |choices: usize| (choices as f64).log2();
+        match &self.mode {
+            Mode::Characters(characters) => self.length as f64 * bits(characters.len()),
+            Mode::Passphrase { wordlist, .. } => self.length as f64 * bits(wordlist.words.len()),
+            Mode::Pronounceable => (0..self.length).map(|i| bits(syllable_part(i).len())).sum(),
+            Mode::Pattern(positions) => positions.iter().map(|chars| bits(chars.len())).sum(),
+        }
+    }
+
+    /// Generate a new password
+    ///
+    /// ## Errors
+    /// An IO error is returned if the operating system does not provide randomness.
+    pub fn generate(&self) -> Result<SecretString> {
+        let mut password = String::new();
+        match &self.mode {
+            Mode::Characters(characters) => {
+                for _ in 0..self.length {
+                    password.push(characters[random_index(characters.len())?]);
+                }
+            }
+            Mode::Passphrase {
+                wordlist,
+                separator,
+            } => {
+                for i in 0..self.length {
+                    if i > 0 {
+                        password.push_str(separator);
+                    }
+                    password.push_str(&wordlist.words[random_index(wordlist.words.len())?]);
+                }
+            }
+            Mode::Pronounceable => {
+                for i in 0..self.length {
+                    let chars = syllable_part(i);
+                    password.push(chars[random_index(chars.len())?]);
+                }
+            }
+            Mode::Pattern(positions) => {
+                for chars in positions {
+                    password.push(chars[random_index(chars.len())?]);
+                }
+            }
+        }
+        Ok(SecretString::from(password))
+    }
+}
+
+/// The characters that position *i* of a pronounceable password is drawn from
+///
+/// Consonants and vowels alternate so that the password consists of syllables like `ba` or `ko`.
+fn syllable_part(i: usize) -> &'static [char] {
+    match i % 2 {
+        0 => CONSONANTS,
+        _ => VOWELS,
+    }
+}
+
+/// Words that passphrases are built from
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Wordlist {
+    words: Vec<String>,
+}
+
+impl Wordlist {
+    /// Create a wordlist of the distinct words of *words*
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidConfig`] is returned if there are less than two distinct words or a word contains
+    /// whitespace.
+    pub fn new(words: impl IntoIterator<Item = impl Into<String>>) -> Result<Self> {
+        let mut seen = std::collections::HashSet::new();
+        let words = words
+            .into_iter()
+            .map(Into::into)
+            .filter(|word| seen.insert(word.clone()))
+            .collect::<Vec<String>>();
+        let invalid = |reason: &str| PassError::InvalidConfig {
+            variable: "wordlist".to_string(),
+            value: format!("{} words", words.len()),
+            reason: reason.to_string(),
+        };
+        if words.len() < 2 {
+            return Err(invalid("A wordlist needs at least two distinct words"));
+        }
+        if words
+            .iter()
+            .any(|word| word.is_empty() || word.contains(char::is_whitespace))
+        {
+            return Err(invalid("Words must not be empty or contain whitespace"));
+        }
+        Ok(Self { words })
+    }
+
+    /// Parse a wordlist in diceware format (lines like `11111<tab>abacus`) or with one word per line
+    ///
+    /// Empty lines are ignored.
+    pub fn parse(content: &str) -> Result<Self> {
+        Self::new(
+            content
+                .lines()
+                .filter_map(|line| line.split_whitespace().last()),
+        )
+    }
+
+    /// The large wordlist of the EFF with 7776 words (about 12.9 bits per word)
+    ///
+    /// This is only available with the `diceware` feature.
+    #[cfg(feature = "diceware")]
+    pub fn eff_large() -> Self {
+        Self {
+            words: eff_wordlist::large::LIST
+                .iter()
+                .map(|(_, word)| word.to_string())
+                .collect(),
+        }
+    }
+
+    /// The words of this list
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+}
+
+/// A uniformly distributed random number in `0..bound`
+fn random_index(bound: usize) -> Result<usize> {
+    let bound = bound as u64;
+    // values above the largest multiple of bound are rejected so that the modulus is not biased
+    let zone = u64::MAX - (u64::MAX % bound);
+    loop {
+        let mut bytes = [0u8; 8];
+        getrandom::getrandom(&mut bytes).map_err(std::io::Error::from)?;
+        let value = u64::from_ne_bytes(bytes);
+        if value < zone {
+            return Ok((value % bound) as usize);
+        }
+    }
+}
+
+/// The characters of a `tr` style character set consisting of classes like `[:alnum:]`, ranges like `a-z`
+/// and single (possibly backslash escaped) characters
+fn parse_character_set(set: &str) -> std::result::Result<Vec<char>, String> {
+    let mut characters = Vec::new();
+    let mut rest = set;
+    while !rest.is_empty() {
+        if let Some(class) = rest.strip_prefix("[:") {
+            let (name, after) = class
+                .split_once(":]")
+                .ok_or_else(|| "Character class is not terminated".to_string())?;
+            characters.extend(class_characters(name)?);
+            rest = after;
+            continue;
+        }
+
+        let (first, after) = next_char(rest)?;
+        match after.strip_prefix('-').filter(|range| !range.is_empty()) {
+            Some(range) => {
+                let (last, after) = next_char(range)?;
+                if last < first {
+                    return Err(format!("The range {}-{} is reversed", first, last));
+                }
+                characters.extend(first..=last);
+                rest = after;
+            }
+            None => {
+                characters.push(first);
+                rest = after;
+            }
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    characters.retain(|c| seen.insert(*c));
+    if characters.is_empty() {
+        return Err("The character set is empty".to_string());
+    }
+    Ok(characters)
+}
+
+/// Split the first (possibly backslash escaped) character off *s*
+fn next_char(s: &str) -> std::result::Result<(char, &str), String> {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some('\\') => match chars.next() {
+            Some('n') => Ok(('\n', chars.as_str())),
+            Some('t') => Ok(('\t', chars.as_str())),
+            Some(c) => Ok((c, chars.as_str())),
+            None => Err("Trailing backslash".to_string()),
+        },
+        Some(c) => Ok((c, chars.as_str())),
+        None => Err("Unexpected end of character set".to_string()),
+    }
+}
+
+/// The characters of the POSIX character class *name* in the C locale
+fn class_characters(name: &str) -> std::result::Result<Vec<char>, String> {
+    let predicate: fn(&char) -> bool = match name {
+        "alnum" => char::is_ascii_alphanumeric,
+        "alpha" => char::is_ascii_alphabetic,
+        "digit" => char::is_ascii_digit,
+        "lower" => char::is_ascii_lowercase,
+        "upper" => char::is_ascii_uppercase,
+        "punct" => char::is_ascii_punctuation,
+        "xdigit" => char::is_ascii_hexdigit,
+        "graph" => char::is_ascii_graphic,
+        "print" => |c| c.is_ascii_graphic() || *c == ' ',
+        _ => return Err(format!("Unknown character class [:{}:]", name)),
+    };
+    Ok((0u8..128).map(char::from).filter(predicate).collect())
+}
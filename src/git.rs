@@ -0,0 +1,764 @@
+//! Synchronization of stores that are managed in a git repository
+//!
+//! This module is only available with the `git` feature enabled.
+//! Start a synchronization by calling [`Store::git_sync()`](crate::Store::git_sync) or set up a store from a
+//! remote via [`Store::clone_from()`](crate::Store::clone_from).
+//! The history of single entries is available through
+//! [`StoreFileRef::provenance()`](crate::StoreFileRef::provenance) and past states of the whole store through
+//! [`Store::list_at()`](crate::Store::list_at).
+
+use crate::audit::AccessOperation;
+use crate::crypto::{self, CryptoOptions};
+use crate::merge;
+use crate::telemetry::{debug, error, span, trace};
+use crate::{GpgIdFile, PassError, Result, SignatureStatus, Store, StoreFileRef};
+use git2::build::RepoBuilder;
+use git2::{
+    AnnotatedCommit, Commit, Cred, CredentialType, ErrorCode, FetchOptions, Index, IndexEntry,
+    ObjectType, Oid, PushOptions, RebaseOptions, RemoteCallbacks, Repository,
+    SubmoduleUpdateOptions, TreeWalkMode, TreeWalkResult,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How a conflicting entry is resolved during [`Store::git_sync()`](crate::Store::git_sync)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ConflictResolution {
+    /// Keep the local version of the entry
+    KeepOurs,
+    /// Keep the version of the entry from the remote
+    KeepTheirs,
+    /// Decrypt both versions, combine all of their lines and encrypt the result again
+    ///
+    /// The lines of the local version come first (so its password is kept) followed by all lines of the
+    /// remote version which the local version does not contain.
+    MergeUnion,
+}
+
+/// An entry which was changed locally as well as on the remote in incompatible ways
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Conflict {
+    /// Name of the conflicting entry
+    pub pass_name: String,
+    /// Ciphertext of the local version or `None` if the entry was deleted locally
+    pub ours: Option<Vec<u8>>,
+    /// Ciphertext of the remote version or `None` if the entry was deleted on the remote
+    pub theirs: Option<Vec<u8>>,
+}
+
+/// A conflict and how it was resolved
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ResolvedConflict {
+    /// Name of the conflicting entry
+    pub pass_name: String,
+    /// The resolution which was applied
+    pub resolution: ConflictResolution,
+}
+
+/// Outcome of a synchronization
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct SyncReport {
+    /// All conflicts which were resolved, in the order in which they were encountered
+    pub resolved: Vec<ResolvedConflict>,
+    /// Whether local commits were pushed to the remote
+    pub pushed: bool,
+}
+
+/// A commit which changed an entry
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Revision {
+    /// The id of the commit
+    pub commit: String,
+    /// The name of the author
+    pub author: String,
+    /// The email address of the author
+    pub email: String,
+    /// The time at which the change was authored
+    pub time: SystemTime,
+    /// The first line of the commit message
+    pub summary: String,
+}
+
+/// Who created and last changed an entry according to the git history of its store
+///
+/// Get an instance of this by calling [`StoreFileRef::provenance()`](crate::StoreFileRef::provenance).
+/// Changes which are not committed yet are not taken into account.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct Provenance {
+    /// The commit which added the entry or `None` if it was never committed
+    ///
+    /// If the entry was removed and added again, this is the commit which added it the last time.
+    pub created: Option<Revision>,
+    /// The most recent commit which changed the entry or `None` if it was never committed
+    pub last_changed: Option<Revision>,
+}
+
+/// An entry as it was recorded in a past commit
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PastEntry {
+    /// The name of the entry in that commit
+    pub pass_name: String,
+    /// The id of the git blob holding the encrypted content
+    pub blob: String,
+    /// The size of the encrypted content in bytes
+    pub size: u64,
+}
+
+/// The entries of a store as they were recorded in a past commit
+///
+/// Get an instance of this by calling [`Store::list_at()`](crate::Store::list_at) and decrypt its entries
+/// with [`Store::plain_io_at()`](crate::Store::plain_io_at).
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PastListing {
+    /// The commit whose tree was listed
+    pub revision: Revision,
+    /// All entries of the store in that commit, sorted by their name
+    pub entries: Vec<PastEntry>,
+}
+
+impl PastListing {
+    /// The entry named *pass_name* or `None` if it did not exist in the listed commit
+    pub fn entry(&self, pass_name: &str) -> Option<&PastEntry> {
+        let name = crate::normalize_name(pass_name).ok()?;
+        self.entries
+            .binary_search_by(|entry| entry.pass_name.as_str().cmp(&name))
+            .ok()
+            .map(|i| &self.entries[i])
+    }
+}
+
+/// Options for [`Store::clone_from()`](crate::Store::clone_from)
+///
+/// By default, the complete history of the default branch is cloned and every `.gpg-id` file must carry a
+/// valid `.gpg-id.sig` signature by one of the [signing keys](CloneOptions::with_signing_keys).
+/// Because no signing keys are configured initially, cloning fails until they are set or verification is
+/// explicitly [disabled](CloneOptions::with_gpg_id_verification).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct CloneOptions {
+    depth: Option<u32>,
+    branch: Option<String>,
+    signing_keys: Vec<String>,
+    skip_gpg_id_verification: bool,
+    offline: bool,
+}
+
+impl CloneOptions {
+    /// Only clone the given number of most recent commits
+    pub fn with_depth(mut self, depth: u32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Check out the given branch instead of the default branch of the remote
+    pub fn with_branch(mut self, branch: impl Into<String>) -> Self {
+        self.branch = Some(branch.into());
+        self
+    }
+
+    /// Require every `.gpg-id` file to be signed by one of the given keys
+    ///
+    /// Keys are identified by their full fingerprint, like *pass* does with its `PASSWORD_STORE_SIGNING_KEY`
+    /// environment variable.
+    /// Cloning fails if one of them is a shorter key id because those can be forged.
+    pub fn with_signing_keys(mut self, keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.signing_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The number of commits that are cloned or `None` if the whole history is cloned
+    pub fn depth(&self) -> Option<u32> {
+        self.depth
+    }
+
+    /// The branch that is checked out or `None` if the default branch of the remote is used
+    pub fn branch(&self) -> Option<&str> {
+        self.branch.as_deref()
+    }
+
+    /// The keys of which one must have signed every `.gpg-id` file
+    pub fn signing_keys(&self) -> &[String] {
+        &self.signing_keys
+    }
+
+    /// Verify the signatures of `.gpg-id` files after cloning
+    ///
+    /// Disabling verification allows a compromised remote to add its own recipients to the store and should
+    /// only be done for remotes which are trusted anyway.
+    ///
+    /// Enabled by default.
+    pub fn with_gpg_id_verification(mut self, enabled: bool) -> Self {
+        self.skip_gpg_id_verification = !enabled;
+        self
+    }
+
+    /// Whether the signatures of `.gpg-id` files are verified after cloning
+    pub fn gpg_id_verification(&self) -> bool {
+        !self.skip_gpg_id_verification
+    }
+
+    /// Prevent gpg from accessing the network while verifying `.gpg-id` files and in the cloned store
+    ///
+    /// See [`Store::with_offline()`].
+    ///
+    /// Disabled by default.
+    pub fn with_offline(mut self, enabled: bool) -> Self {
+        self.offline = enabled;
+        self
+    }
+
+    /// Whether gpg is prevented from accessing the network
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// The options for gpg operations during cloning
+    fn crypto(&self) -> CryptoOptions {
+        CryptoOptions {
+            offline: self.offline,
+            ..CryptoOptions::default()
+        }
+    }
+}
+
+/// Clone the store at *url* into *path* and verify its `.gpg-id` files
+///
+/// If verification fails, the clone is removed again (emptying *path* if it was an existing empty directory).
+pub(crate) fn clone(url: &str, path: &Path, options: &CloneOptions) -> Result<Store> {
+    if options.gpg_id_verification() {
+        check_signing_keys(&options.signing_keys)?;
+    }
+
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(remote_callbacks(git2::Config::open_default()?));
+    if let Some(depth) = options.depth {
+        fetch_options.depth(depth.try_into().unwrap_or(i32::MAX));
+    }
+    let mut builder = RepoBuilder::new();
+    builder.fetch_options(fetch_options);
+    if let Some(branch) = &options.branch {
+        builder.branch(branch);
+    }
+
+    debug!("Cloning {} into {}", url, path.display());
+    // git2 also clones into existing empty directories whose content then only stems from the clone
+    let existed = path.exists();
+    let was_empty = fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_none());
+    let verification = builder
+        .clone(url, path)
+        .map_err(PassError::from)
+        .and_then(|repo| update_submodules(&repo))
+        .and_then(|_| match options.gpg_id_verification() {
+            true => verify_gpg_ids(path, options),
+            false => Ok(()),
+        })
+        .and_then(|_| Store::open(path))
+        .map(|store| store.with_offline(options.offline));
+    if verification.is_err() && (!existed || was_empty) {
+        if let Err(e) = remove_clone(path, existed) {
+            error!(
+                "Could not remove rejected clone at {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+    verification
+}
+
+/// Remove the clone at *path*, keeping the (then empty) directory itself if *keep_dir* is set
+fn remove_clone(path: &Path, keep_dir: bool) -> std::io::Result<()> {
+    if !keep_dir {
+        return fs::remove_dir_all(path);
+    }
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        match entry.file_type()?.is_dir() {
+            true => fs::remove_dir_all(entry.path())?,
+            false => fs::remove_file(entry.path())?,
+        }
+    }
+    Ok(())
+}
+
+/// Check that *signing_keys* can be used to verify `.gpg-id` files
+///
+/// ## Errors
+/// [`PassError::InvalidConfig`] is returned if no keys are given or a key is no full fingerprint.
+fn check_signing_keys(signing_keys: &[String]) -> Result<()> {
+    let invalid = |value: &str, reason: &str| PassError::InvalidConfig {
+        variable: "signing_keys".to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    };
+    if signing_keys.is_empty() {
+        return Err(invalid(
+            "",
+            "signing keys are required to verify .gpg-id files unless verification is disabled",
+        ));
+    }
+    match signing_keys
+        .iter()
+        .find(|key| crypto::full_fingerprint(key).is_none())
+    {
+        Some(key) => Err(invalid(key, "signing keys must be full fingerprints")),
+        None => Ok(()),
+    }
+}
+
+/// Recursively verify that all `.gpg-id` files inside *dir* are validly signed by one of the signing keys
+fn verify_gpg_ids(dir: &Path, options: &CloneOptions) -> Result<()> {
+    let gpg_id = dir.join(".gpg-id");
+    if gpg_id.is_file() {
+        let error = |reason: String| PassError::InvalidGpgIdSignature {
+            path: gpg_id.clone(),
+            reason,
+        };
+        match GpgIdFile::verify_signature_in(&options.crypto(), &gpg_id)? {
+            status if status.is_valid_from(&options.signing_keys) => {}
+            SignatureStatus::Invalid {
+                fingerprint,
+                reason,
+            } => {
+                return Err(error(format!(
+                    "signature by {} is invalid: {}",
+                    fingerprint, reason
+                )))
+            }
+            SignatureStatus::Unsigned => return Err(error("file is not signed".to_string())),
+            SignatureStatus::Valid { fingerprints } => {
+                return Err(error(format!(
+                    "file is only signed by untrusted keys {}",
+                    fingerprints.join(", ")
+                )))
+            }
+        }
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() && path.file_name() != Some(".git".as_ref()) {
+            verify_gpg_ids(&path, options)?;
+        }
+    }
+    Ok(())
+}
+
+/// Pull the current branch of the repository at the store root with rebase and push it afterwards
+pub(crate) fn sync(
+    store: &Store,
+    resolve: &mut dyn FnMut(&Conflict) -> ConflictResolution,
+) -> Result<SyncReport> {
+    let _span = span!("git_sync", root = %store.root().display());
+    let repo = Repository::open(store.root())?;
+    let head = repo.head()?;
+    let head_name = head
+        .name()
+        .filter(|_| head.is_branch())
+        .ok_or_else(|| git2::Error::from_str("HEAD does not point to a branch"))?
+        .to_string();
+    let remote_name = buf_to_string(repo.branch_upstream_remote(&head_name)?)?;
+    let upstream_name = buf_to_string(repo.branch_upstream_name(&head_name)?)?;
+    let merge_name = repo.config()?.get_string(&format!(
+        "branch.{}.merge",
+        head_name.trim_start_matches("refs/heads/")
+    ))?;
+    let mut remote = repo.find_remote(&remote_name)?;
+
+    debug!("Fetching {} from {}", merge_name, remote_name);
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(remote_callbacks(repo.config()?));
+    let default_refspecs: &[&str] = &[];
+    remote.fetch(default_refspecs, Some(&mut fetch_options), None)?;
+
+    let previous_head = repo.head()?.peel_to_tree()?.id();
+    let upstream = repo.find_reference(&upstream_name)?;
+    let upstream_commit = repo.reference_to_annotated_commit(&upstream)?;
+    let (analysis, _) = repo.merge_analysis(&[&upstream_commit])?;
+
+    let mut report = SyncReport::default();
+    if analysis.is_fast_forward() {
+        debug!("Fast-forwarding {} to {}", head_name, upstream_name);
+        let target = repo.find_object(upstream_commit.id(), None)?;
+        repo.checkout_tree(&target, None)?;
+        repo.find_reference(&head_name)?
+            .set_target(upstream_commit.id(), "libpass: fast-forward")?;
+    } else if !analysis.is_up_to_date() {
+        rebase(store, &repo, &upstream_commit, resolve, &mut report)?;
+    }
+
+    update_submodules(&repo)?;
+    if store.manifest_maintenance() {
+        store.track_all_in_manifest(changed_paths(&repo, previous_head)?)?;
+    }
+
+    let local = repo.head()?.peel_to_commit()?.id();
+    let (ahead, _) = repo.graph_ahead_behind(local, upstream_commit.id())?;
+    if ahead > 0 {
+        debug!("Pushing {} commits to {}", ahead, remote_name);
+        let mut callbacks = remote_callbacks(repo.config()?);
+        callbacks.push_update_reference(|reference, status| match status {
+            Some(message) => Err(git2::Error::from_str(&format!(
+                "remote rejected {}: {}",
+                reference, message
+            ))),
+            None => Ok(()),
+        });
+        let mut push_options = PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+        remote.push(
+            &[format!("{}:{}", head_name, merge_name)],
+            Some(&mut push_options),
+        )?;
+        report.pushed = true;
+    }
+
+    Ok(report)
+}
+
+/// The paths of all files which differ between the tree *previous* and the tree of the current `HEAD` of
+/// *repo*
+fn changed_paths(repo: &Repository, previous: Oid) -> Result<Vec<PathBuf>> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("the repository of the store is bare"))?;
+    let previous = repo.find_tree(previous)?;
+    let current = repo.head()?.peel_to_tree()?;
+    let diff = repo.diff_tree_to_tree(Some(&previous), Some(&current), None)?;
+    Ok(diff
+        .deltas()
+        .flat_map(|delta| [delta.old_file().path(), delta.new_file().path()])
+        .flatten()
+        .map(|path| workdir.join(path))
+        .collect())
+}
+
+/// Initialize and update all submodules of *repo* recursively
+///
+/// Returns the working directories of all updated submodules.
+pub(crate) fn update_submodules(repo: &Repository) -> Result<Vec<PathBuf>> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("the repository of the store is bare"))?;
+
+    let mut updated = Vec::new();
+    for mut submodule in repo.submodules()? {
+        debug!("Updating submodule {}", submodule.path().display());
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.remote_callbacks(remote_callbacks(repo.config()?));
+        let mut update_options = SubmoduleUpdateOptions::new();
+        update_options.fetch(fetch_options);
+        submodule.update(true, Some(&mut update_options))?;
+
+        updated.push(workdir.join(submodule.path()));
+        updated.extend(update_submodules(&submodule.open()?)?);
+    }
+    Ok(updated)
+}
+
+/// The working directory of the innermost repository containing *path*
+pub(crate) fn repository_of(path: &Path) -> Result<PathBuf> {
+    let repo = Repository::discover(path.parent().unwrap_or(path))?;
+    Ok(repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("the repository of the store is bare"))?
+        .to_owned())
+}
+
+/// Find the commits which created and last changed the file at *path* in the repository containing it
+///
+/// Only the first parent of merge commits is followed, like `git log --first-parent` does.
+pub(crate) fn provenance(path: &Path) -> Result<Provenance> {
+    let path = fs::canonicalize(path)?;
+    let repo = Repository::discover(path.parent().unwrap_or(&path))?;
+    let workdir = fs::canonicalize(
+        repo.workdir()
+            .ok_or_else(|| git2::Error::from_str("the repository of the store is bare"))?,
+    )?;
+    let relative = path
+        .strip_prefix(&workdir)
+        .map_err(|_| git2::Error::from_str("the entry is not part of the repository"))?;
+    let blob_of = |commit: &Commit| -> Result<Option<Oid>> {
+        match commit.tree()?.get_path(relative) {
+            Ok(entry) => Ok(Some(entry.id())),
+            Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    };
+
+    let mut provenance = Provenance::default();
+    let mut commit = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?),
+        Err(e) if e.code() == ErrorCode::UnbornBranch => None,
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(current) = commit {
+        let blob = blob_of(&current)?;
+        if blob.is_none() {
+            break;
+        }
+        let parent = current.parents().next();
+        let parent_blob = match &parent {
+            Some(parent) => blob_of(parent)?,
+            None => None,
+        };
+        if parent_blob != blob {
+            let revision = revision(&current);
+            trace!("{} was changed in {}", relative.display(), revision.commit);
+            if provenance.last_changed.is_none() {
+                provenance.last_changed = Some(revision.clone());
+            }
+            if parent_blob.is_none() {
+                provenance.created = Some(revision);
+                break;
+            }
+        }
+        commit = parent;
+    }
+    Ok(provenance)
+}
+
+/// List the entries of *store* in the commit *rev* of the repository containing it
+pub(crate) fn list_at(store: &Store, rev: &str) -> Result<PastListing> {
+    let (repo, prefix) = open_repository(store)?;
+    let commit = repo.revparse_single(rev)?.peel_to_commit()?;
+    let tree = match prefix.as_os_str().is_empty() {
+        true => commit.tree()?,
+        false => match commit.tree()?.get_path(&prefix) {
+            Ok(entry) => entry.to_object(&repo)?.peel_to_tree()?,
+            // the store did not exist yet
+            Err(e) if e.code() == ErrorCode::NotFound => {
+                return Ok(PastListing {
+                    revision: revision(&commit),
+                    entries: Vec::new(),
+                })
+            }
+            Err(e) => return Err(e.into()),
+        },
+    };
+
+    let extensions = store.entry_extensions();
+    let mut files = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        // submodules are recorded as commits and are not descended into
+        if entry.kind() == Some(ObjectType::Blob) {
+            if let Some(name) = entry.name() {
+                let path = format!("{}{}", dir, name);
+                if crate::walk::has_extension(Path::new(&path), extensions) {
+                    files.push((path, entry.id()));
+                }
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+
+    let mut entries = Vec::new();
+    for (path, id) in files {
+        entries.push(PastEntry {
+            pass_name: store.relative_name(&store.root().join(&path))?,
+            blob: id.to_string(),
+            size: repo.find_blob(id)?.size() as u64,
+        });
+    }
+    entries.sort_by(|a, b| a.pass_name.cmp(&b.pass_name));
+    Ok(PastListing {
+        revision: revision(&commit),
+        entries,
+    })
+}
+
+/// Read the encrypted content of *entry* from the repository containing *store*
+pub(crate) fn past_ciphertext(store: &Store, entry: &PastEntry) -> Result<Vec<u8>> {
+    let (repo, _) = open_repository(store)?;
+    let blob = repo.find_blob(Oid::from_str(&entry.blob)?)?;
+    Ok(blob.content().to_vec())
+}
+
+/// Open the repository containing *store* and determine the path of the store root inside of it
+fn open_repository(store: &Store) -> Result<(Repository, PathBuf)> {
+    let root = fs::canonicalize(store.root())?;
+    let repo = Repository::discover(&root)?;
+    let workdir = fs::canonicalize(
+        repo.workdir()
+            .ok_or_else(|| git2::Error::from_str("the repository of the store is bare"))?,
+    )?;
+    let prefix = root
+        .strip_prefix(&workdir)
+        .map_err(|_| git2::Error::from_str("the store is not part of the repository"))?
+        .to_owned();
+    Ok((repo, prefix))
+}
+
+/// Describe *commit* as [`Revision`]
+fn revision(commit: &Commit) -> Revision {
+    let author = commit.author();
+    let seconds = author.when().seconds();
+    let time = match u64::try_from(seconds) {
+        Ok(seconds) => UNIX_EPOCH + Duration::from_secs(seconds),
+        Err(_) => UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs()),
+    };
+    Revision {
+        commit: commit.id().to_string(),
+        author: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+        email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+        time,
+        summary: commit.summary().unwrap_or_default().to_string(),
+    }
+}
+
+/// Rebase the local commits onto *upstream*, resolving conflicts of entries via *resolve*
+///
+/// The rebase is aborted if any error occurs so that the repository is left in its original state.
+fn rebase(
+    store: &Store,
+    repo: &Repository,
+    upstream: &AnnotatedCommit,
+    resolve: &mut dyn FnMut(&Conflict) -> ConflictResolution,
+    report: &mut SyncReport,
+) -> Result<()> {
+    let signature = repo.signature()?;
+    let mut rebase = repo.rebase(None, Some(upstream), None, Some(&mut RebaseOptions::new()))?;
+
+    let mut apply = || -> Result<()> {
+        while let Some(operation) = rebase.next() {
+            operation?;
+            let mut index = repo.index()?;
+            if index.has_conflicts() {
+                resolve_conflicts(store, repo, &mut index, resolve, report)?;
+            }
+            match rebase.commit(None, &signature, None) {
+                // the commit became empty because the resolution discarded all of its changes
+                Err(e) if e.code() == ErrorCode::Applied => {}
+                result => {
+                    result?;
+                }
+            }
+        }
+        rebase.finish(Some(&signature))?;
+        Ok(())
+    };
+
+    let result = apply();
+    if result.is_err() {
+        if let Err(e) = rebase.abort() {
+            error!("Could not abort rebase: {}", e);
+        }
+    }
+    result
+}
+
+/// Resolve all conflicts that are currently recorded in *index*
+fn resolve_conflicts(
+    store: &Store,
+    repo: &Repository,
+    index: &mut Index,
+    resolve: &mut dyn FnMut(&Conflict) -> ConflictResolution,
+    report: &mut SyncReport,
+) -> Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("the repository of the store is bare"))?
+        .to_owned();
+    let conflicts = index.conflicts()?.collect::<Result<Vec<_>, _>>()?;
+
+    for conflict in conflicts {
+        // while rebasing, "our" side is the upstream and "their" side is the local commit being replayed
+        let (local, remote) = (conflict.their, conflict.our);
+        let relative = local
+            .as_ref()
+            .or(remote.as_ref())
+            .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+            .ok_or_else(|| git2::Error::from_str("conflict without sides"))?;
+        let path = workdir.join(&relative);
+        if !relative.ends_with(".gpg") {
+            return Err(PassError::GitConflict(path));
+        }
+
+        let conflict = Conflict {
+            pass_name: store.relative_name(&path)?,
+            ours: blob_content(repo, local.as_ref())?,
+            theirs: blob_content(repo, remote.as_ref())?,
+        };
+        let resolution = resolve(&conflict);
+        debug!(
+            "Resolving conflict of {} with {:?}",
+            conflict.pass_name, resolution
+        );
+
+        let content = match (resolution, &conflict.ours, &conflict.theirs) {
+            (ConflictResolution::KeepOurs, ours, _) => ours.clone(),
+            (ConflictResolution::KeepTheirs, _, theirs) => theirs.clone(),
+            (ConflictResolution::MergeUnion, Some(ours), Some(theirs)) => Some(merge_union(
+                store,
+                &StoreFileRef {
+                    path: path.clone(),
+                    root: store.root().to_owned(),
+                },
+                ours,
+                theirs,
+            )?),
+            // the union with a deleted version is the other version
+            (ConflictResolution::MergeUnion, ours, theirs) => ours.clone().or(theirs.clone()),
+        };
+
+        match content {
+            Some(content) => {
+                crate::utils::write_atomically(&path, &content, store.config().umask())?;
+                index.add_path(Path::new(&relative))?;
+            }
+            None => {
+                if path.exists() {
+                    fs::remove_file(&path)?;
+                }
+                index.remove_path(Path::new(&relative))?;
+            }
+        }
+
+        report.resolved.push(ResolvedConflict {
+            pass_name: conflict.pass_name,
+            resolution,
+        });
+    }
+
+    index.write()?;
+    Ok(())
+}
+
+/// Decrypt both versions of an entry and encrypt the union of their lines for the keys of *file*
+fn merge_union(store: &Store, file: &StoreFileRef, ours: &[u8], theirs: &[u8]) -> Result<Vec<u8>> {
+    store.acquire_decryption(file)?;
+    let ours = store.decrypt_ciphertext(ours)?;
+    let theirs = store.decrypt_ciphertext(theirs)?;
+    store.record_access(file, AccessOperation::Decrypt)?;
+    let merged = merge::union_lines(&ours, &theirs);
+    store.encrypt_for(file, &merged)
+}
+
+/// The content of the blob referenced by *entry* or `None` if no entry is given
+fn blob_content(repo: &Repository, entry: Option<&IndexEntry>) -> Result<Option<Vec<u8>>> {
+    entry
+        .map(|entry| Ok(repo.find_blob(entry.id)?.content().to_vec()))
+        .transpose()
+}
+
+/// Callbacks which authenticate against remotes with the ssh agent or configured credential helpers
+fn remote_callbacks(config: git2::Config) -> RemoteCallbacks<'static> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        if allowed.contains(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            Cred::credential_helper(&config, url, username)
+        } else {
+            Cred::default()
+        }
+    });
+    callbacks
+}
+
+fn buf_to_string(buf: git2::Buf) -> Result<String> {
+    Ok(buf
+        .as_str()
+        .ok_or_else(|| git2::Error::from_str("reference name is not valid UTF-8"))?
+        .to_string())
+}
@@ -0,0 +1,170 @@
+//! Parsing of `.gpg-id` files which define the keys for which entries of a store are encrypted
+
+use crate::crypto::CryptoOptions;
+use crate::{Result, SignatureStatus};
+use std::convert::Infallible;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The parsed content of a `.gpg-id` file
+///
+/// A `.gpg-id` file lists the recipients for which all entries in its directory (and subdirectories
+/// without their own `.gpg-id` file) are encrypted.
+/// Parsing follows what *pass* itself accepts while being a bit more lenient:
+/// - Everything following a `#` is a comment and ignored
+/// - Blank lines are ignored
+/// - A line usually contains exactly one recipient which may be a key id, fingerprint, email or user id
+///   (which may contain spaces like `John Doe <john@example.com>`)
+/// - Fingerprints may be written in the space separated form that gpg displays
+/// - A line may contain multiple whitespace separated key ids or fingerprints
+///
+/// ## Example
+/// ```
+/// use libpass::GpgIdFile;
+///
+/// let gpg_id: GpgIdFile = "# team keys\n8497251104B6F45F 0x1234567890ABCDEF\nJohn Doe <john@example.com>\n"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(
+///     gpg_id.recipients(),
+///     ["8497251104B6F45F", "0x1234567890ABCDEF", "John Doe <john@example.com>"]
+/// );
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct GpgIdFile {
+    recipients: Vec<String>,
+}
+
+impl GpgIdFile {
+    /// Read and parse the `.gpg-id` file at *path*
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::parse_content(&fs::read_to_string(path)?))
+    }
+
+    /// Parse the given content of a `.gpg-id` file
+    fn parse_content(content: &str) -> Self {
+        Self {
+            recipients: content.lines().flat_map(parse_line).collect(),
+        }
+    }
+
+    /// Verify the detached signature of the `.gpg-id` file at *path*
+    ///
+    /// Like *pass*, the signature is expected in a `.gpg-id.sig` file next to it.
+    /// [`SignatureStatus::Unsigned`] is returned if no such file exists.
+    /// The default gpg configuration is used to verify the signature.
+    pub fn verify_signature(path: impl AsRef<Path>) -> Result<SignatureStatus> {
+        Self::verify_signature_in(&CryptoOptions::default(), path.as_ref())
+    }
+
+    /// Verify the detached signature of the `.gpg-id` file at *path* according to *crypto*
+    pub(crate) fn verify_signature_in(
+        crypto: &CryptoOptions,
+        path: &Path,
+    ) -> Result<SignatureStatus> {
+        let mut sig_path = path.as_os_str().to_owned();
+        sig_path.push(".sig");
+        let signature = match fs::read(&sig_path) {
+            Ok(signature) => signature,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(SignatureStatus::Unsigned),
+            Err(e) => return Err(e.into()),
+        };
+        crypto.verify_detached(&signature, &fs::read(path)?)
+    }
+
+    /// The recipients listed in this file in the order in which they appear
+    pub fn recipients(&self) -> &[String] {
+        &self.recipients
+    }
+}
+
+impl FromStr for GpgIdFile {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::parse_content(s))
+    }
+}
+
+/// Replace every recipient in the `.gpg-id` *content* for which *is_old* returns true with *new_recipient*
+///
+/// Comments and the layout of all other lines are kept.
+/// If *new_recipient* is already listed, the old recipients are only removed.
+/// `None` is returned if no recipient matched.
+pub(crate) fn replace_recipient(
+    content: &str,
+    is_old: impl Fn(&str) -> bool,
+    new_recipient: &str,
+) -> Option<String> {
+    let mut has_new = GpgIdFile::parse_content(content)
+        .recipients
+        .iter()
+        .any(|recipient| recipient == new_recipient);
+    let mut replaced = false;
+    let mut output = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        let recipients = parse_line(line);
+        if !recipients.iter().any(|recipient| is_old(recipient)) {
+            output.push_str(line);
+            continue;
+        }
+        replaced = true;
+        let comment = line
+            .split_once('#')
+            .map(|(_, comment)| format!(" #{}", comment.trim_end()))
+            .unwrap_or_default();
+        let mut kept = Vec::new();
+        for recipient in recipients {
+            if !is_old(&recipient) {
+                kept.push(recipient);
+            } else if !has_new {
+                kept.push(new_recipient.to_string());
+                has_new = true;
+            }
+        }
+        if !kept.is_empty() || !comment.is_empty() {
+            output.push_str(kept.join(" ").trim_start());
+            output.push_str(&comment);
+            output.push('\n');
+        }
+    }
+    replaced.then_some(output)
+}
+
+/// Whether *token* looks like a hex encoded key id or fingerprint
+fn is_key_id(token: &str) -> bool {
+    let hex = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+        .unwrap_or(token);
+    matches!(hex.len(), 8 | 16 | 32 | 40 | 64) && hex.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Extract all recipients from a single line of a `.gpg-id` file
+fn parse_line(line: &str) -> Vec<String> {
+    let line = match line.split_once('#') {
+        Some((content, _comment)) => content,
+        None => line,
+    }
+    .trim();
+
+    if line.is_empty() {
+        return Vec::new();
+    }
+
+    // fingerprints are often copied from gpg output which groups them with spaces
+    let compact = line.split_whitespace().collect::<String>();
+    if line.contains(char::is_whitespace) && compact.len() == 40 && is_key_id(&compact) {
+        return vec![compact];
+    }
+
+    let tokens = line.split_whitespace().collect::<Vec<_>>();
+    if tokens.iter().all(|token| is_key_id(token)) {
+        tokens.into_iter().map(str::to_string).collect()
+    } else {
+        // user ids and emails may contain spaces so keep the line as a whole
+        vec![line.to_string()]
+    }
+}
@@ -0,0 +1,96 @@
+//! Checks which refuse to operate on stores that other users could have tampered with
+//!
+//! These checks are opt-in via [`Store::with_strict_permissions()`](crate::Store::with_strict_permissions)
+//! and only have an effect on unix systems.
+
+use crate::{PassError, Result};
+use std::fs;
+use std::path::Path;
+
+/// Check *root* and everything inside it (except git repositories)
+pub(crate) fn check_tree(root: &Path) -> Result<()> {
+    check_path(root)?;
+    if fs::metadata(root)?.is_dir() {
+        for entry in fs::read_dir(root)? {
+            let path = entry?.path();
+            if path.file_name() != Some(".git".as_ref()) {
+                check_tree(&path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check *root*, every existing directory between *root* and *path*, the `.gpg-id` files inside of them and
+/// *path* itself
+pub(crate) fn check_chain(root: &Path, path: &Path) -> Result<()> {
+    let relative = path.strip_prefix(root).map_err(|_| {
+        PassError::InvalidStoreFormat(
+            path.to_owned(),
+            "Path is not inside password store".to_string(),
+        )
+    })?;
+
+    let mut current = root.to_owned();
+    check_dir(&current)?;
+    for component in relative.components() {
+        current.push(component);
+        match fs::metadata(&current) {
+            Ok(metadata) if metadata.is_dir() => check_dir(&current)?,
+            Ok(_) => check_path(&current)?,
+            // files that are about to be created do not exist yet
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Check the directory at *dir* and the `.gpg-id` file inside of it
+fn check_dir(dir: &Path) -> Result<()> {
+    check_path(dir)?;
+    let gpg_id = dir.join(".gpg-id");
+    if gpg_id.exists() {
+        check_path(&gpg_id)?;
+    }
+    Ok(())
+}
+
+/// Check that *path* is neither group- nor world-writable and owned by the current user or root
+#[cfg(unix)]
+fn check_path(path: &Path) -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let insecure = |reason: String| PassError::InsecurePermissions {
+        path: path.to_owned(),
+        reason,
+    };
+    let metadata = fs::metadata(path)?;
+    if metadata.mode() & 0o022 != 0 {
+        return Err(insecure(format!(
+            "it is writable by other users (mode {:o})",
+            metadata.mode() & 0o777
+        )));
+    }
+    let uid = current_uid();
+    if metadata.uid() != uid && metadata.uid() != 0 {
+        return Err(insecure(format!(
+            "it is owned by uid {} instead of the current user ({})",
+            metadata.uid(),
+            uid
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_path(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+#[allow(unsafe_code)]
+fn current_uid() -> u32 {
+    // SAFETY: geteuid() has no preconditions and always succeeds
+    unsafe { libc::geteuid() }
+}
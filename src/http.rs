@@ -0,0 +1,476 @@
+//! A small HTTP server which provides read-only access to a store
+//!
+//! The [`HttpServer`] serves a REST API so that programs which can not link this library (like dashboards)
+//! can query a store:
+//!
+//! - `GET /v1/entries` lists all entries as [`WireListing`]
+//! - `GET /v1/entries/<name>` describes a single entry as [`WireEntry`]
+//! - `GET /v1/secrets/<name>` decrypts an entry and returns its content
+//! - `GET /metrics` returns the [metrics](crate::metrics) of the store in the Prometheus text format
+//!
+//! Listings, metadata and errors are encoded as JSON in the [`wire`](crate::wire) format.
+//! Every request has to be authenticated with the token of a client (`Authorization: Bearer <token>`) and
+//! every decryption additionally has to be permitted by the [authorizer](HttpServer::with_authorizer).
+//! Without an authorizer, no entry can be decrypted.
+//! The authorizer always sees the normalized name of the entry which is actually decrypted, so requests for
+//! `a//b.gpg` are authorized as `a/b` and aliases have to be permitted together with the entry they lead to.
+//!
+//! The server does not implement TLS and should only listen on the loopback interface or behind a
+//! reverse proxy which terminates TLS.
+//! Connections are handled one after another so that the store is never shared between threads.
+//!
+//! This module is only available with the `http` feature.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::http::HttpServer;
+//! use libpass::Store;
+//!
+//! let store = Store::open_read_only("/home/user/.password-store").unwrap();
+//! let server = HttpServer::bind("127.0.0.1:8300", store)
+//!     .unwrap()
+//!     .with_client("dashboard", "long random token")
+//!     .with_authorizer(|request| request.client == "dashboard" && request.pass_name.starts_with("homelab/"));
+//! server.serve().unwrap();
+//! ```
+
+use crate::secure::{constant_time_eq, SecretString};
+use crate::telemetry::{span, trace};
+use crate::wire::{Versioned, WireEntry, WireError, WireListing};
+use crate::{PassError, ReadOnlyStore, Result};
+use serde::Serialize;
+use std::fmt::{Debug, Formatter};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// Upper bound for the size of a request head so that clients can not exhaust the servers memory
+const MAX_HEAD_LEN: usize = 16 * 1024;
+
+/// Time after which connections of clients which do not send or receive data are closed
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time after which connections are closed even if their clients keep sending or receiving data slowly
+///
+/// Since connections are handled one after another, a single slow client would otherwise block all others.
+const REQUEST_DEADLINE: Duration = Duration::from_secs(30);
+
+/// A decryption which the [authorizer](HttpServer::with_authorizer) has to permit
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct DecryptRequest<'a> {
+    /// The name of the authenticated client
+    pub client: &'a str,
+    /// The normalized name of the entry which should be decrypted
+    pub pass_name: &'a str,
+    /// The address the request was sent from
+    pub peer: SocketAddr,
+}
+
+type Authorizer = Box<dyn Fn(&DecryptRequest<'_>) -> bool>;
+
+/// Server for the REST API described in the [module documentation](self)
+pub struct HttpServer {
+    listener: TcpListener,
+    store: ReadOnlyStore,
+    clients: Vec<(String, SecretString)>,
+    authorizer: Option<Authorizer>,
+    max_age: Option<Duration>,
+}
+
+impl Debug for HttpServer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpServer")
+            .field("listener", &self.listener)
+            .field("store", &self.store)
+            .field(
+                "clients",
+                &self
+                    .clients
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .field("authorizer", &self.authorizer.is_some())
+            .field("max_age", &self.max_age)
+            .finish()
+    }
+}
+
+/// A connection whose reads and writes fail once its deadline has passed
+#[derive(Debug)]
+struct DeadlineStream<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl DeadlineStream<'_> {
+    /// The time which the next read or write may take
+    fn remaining(&self) -> io::Result<Duration> {
+        match self.deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => Ok(remaining.min(CONNECTION_TIMEOUT)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "The request took too long",
+            )),
+        }
+    }
+}
+
+impl Read for DeadlineStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(Some(self.remaining()?))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for DeadlineStream<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(Some(self.remaining()?))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// A response which is about to be sent
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(status: u16, data: impl Serialize) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec(&Versioned::new(data)).unwrap_or_default(),
+        }
+    }
+
+    fn error(error: &PassError) -> Self {
+        let status = match error {
+            PassError::EntryNotFound(_) | PassError::NotAFile(_) => 404,
+            PassError::InvalidPassName(..) | PassError::AmbiguousPassName(_) => 400,
+            PassError::RateLimited { .. } => 429,
+            PassError::Timeout { .. } => 504,
+            _ => 500,
+        };
+        Self::json(status, WireError::from(error))
+    }
+
+    fn status(status: u16, code: &str, message: &str) -> Self {
+        Self::json(
+            status,
+            WireError {
+                code: code.to_string(),
+                message: message.to_string(),
+                suggestion: None,
+            },
+        )
+    }
+}
+
+impl Drop for Response {
+    fn drop(&mut self) {
+        // the body may contain a decrypted entry
+        self.body.fill(0);
+    }
+}
+
+impl HttpServer {
+    /// Listen for connections on *addr* and serve *store*
+    ///
+    /// No client is allowed to access the store until one is added with
+    /// [`with_client()`](HttpServer::with_client).
+    pub fn bind(addr: impl ToSocketAddrs, store: ReadOnlyStore) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            store,
+            clients: Vec::new(),
+            authorizer: None,
+            max_age: None,
+        })
+    }
+
+    /// Allow requests which authenticate with *token* and identify them as the client *name*
+    pub fn with_client(mut self, name: impl Into<String>, token: impl Into<SecretString>) -> Self {
+        self.clients.push((name.into(), token.into()));
+        self
+    }
+
+    /// Decide with *authorizer* whether an authenticated client may decrypt an entry
+    ///
+    /// Requests which are denied are answered with `403 Forbidden`.
+    pub fn with_authorizer(
+        mut self,
+        authorizer: impl Fn(&DecryptRequest<'_>) -> bool + 'static,
+    ) -> Self {
+        self.authorizer = Some(Box::new(authorizer));
+        self
+    }
+
+    /// Count entries which were not modified for longer than *max_age* as expired in the served metrics
+    ///
+    /// See [`Store::metrics()`](crate::Store::metrics).
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// The address this server listens on
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve clients until accepting connections fails
+    pub fn serve(&self) -> Result<()> {
+        loop {
+            self.handle_next()?;
+        }
+    }
+
+    /// Accept the next connection and answer its request
+    ///
+    /// Failures of the connection itself are only logged.
+    pub(crate) fn handle_next(&self) -> Result<()> {
+        let (stream, peer) = self.listener.accept()?;
+        let _span = span!("http_request", peer = %peer);
+        if let Err(e) = self.handle_connection(stream, peer) {
+            trace!("HTTP connection from {} failed: {}", peer, e);
+        }
+        Ok(())
+    }
+
+    fn handle_connection(&self, stream: TcpStream, peer: SocketAddr) -> io::Result<()> {
+        let deadline = Instant::now() + REQUEST_DEADLINE;
+        let mut reader = BufReader::new(DeadlineStream {
+            stream: &stream,
+            deadline,
+        });
+        let response = match read_head(&mut reader)? {
+            Some(head) => self.respond(&head, peer),
+            None => Response::status(400, "bad_request", "The request is malformed"),
+        };
+        write_response(
+            &mut DeadlineStream {
+                stream: &stream,
+                deadline,
+            },
+            &response,
+        )
+    }
+
+    /// Answer the request described by *head*
+    fn respond(&self, head: &RequestHead, peer: SocketAddr) -> Response {
+        let client = match self.authenticate(head) {
+            Some(client) => client,
+            None => {
+                return Response::status(401, "unauthorized", "A valid bearer token is required")
+            }
+        };
+        if head.method != "GET" {
+            return Response::status(405, "method_not_allowed", "Only GET requests are supported");
+        }
+        let path = match percent_decode(&head.path) {
+            Some(path) => path,
+            None => return Response::status(400, "bad_request", "The request path is malformed"),
+        };
+
+        let result = if path == "/metrics" {
+            self.store.metrics(self.max_age).map(|metrics| Response {
+                status: 200,
+                content_type: "text/plain; version=0.0.4",
+                body: metrics.render_prometheus().into_bytes(),
+            })
+        } else if path == "/v1/entries" {
+            WireListing::from_store(self.store.inner()).map(|listing| Response::json(200, listing))
+        } else if let Some(pass_name) = path.strip_prefix("/v1/entries/") {
+            self.store.retrieve(pass_name).and_then(|entry| {
+                WireEntry::from_entry(self.store.inner(), &entry)
+                    .map(|entry| Response::json(200, entry))
+            })
+        } else if let Some(pass_name) = path.strip_prefix("/v1/secrets/") {
+            self.decrypt(client, pass_name, peer)
+        } else {
+            return Response::status(404, "not_found", "There is no such endpoint");
+        };
+        result.unwrap_or_else(|e| Response::error(&e))
+    }
+
+    /// The name of the client whose token was sent with the request
+    fn authenticate(&self, head: &RequestHead) -> Option<&str> {
+        let token = head
+            .header("authorization")?
+            .strip_prefix("Bearer ")?
+            .trim();
+        self.clients
+            .iter()
+            .find(|(_, expected)| constant_time_eq(expected.expose().as_bytes(), token.as_bytes()))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Decrypt the entry *pass_name* for *client* if the authorizer permits it
+    ///
+    /// The authorizer is asked about the normalized name of the entry and, if it is an alias which is
+    /// resolved, about the name of the entry it leads to as well.
+    fn decrypt(&self, client: &str, pass_name: &str, peer: SocketAddr) -> Result<Response> {
+        let store = self.store.inner();
+        let file = store.unresolved_file(pass_name)?;
+        let name = store.relative_name(&file.path)?;
+        if !self.authorize(client, &name, peer) {
+            return Ok(Self::forbidden());
+        }
+        let file = match store.resolves_aliases() {
+            true => {
+                let target = store.resolve_alias(&file)?;
+                let target_name = store.relative_name(&target.path)?;
+                if target_name != name && !self.authorize(client, &target_name, peer) {
+                    return Ok(Self::forbidden());
+                }
+                target
+            }
+            false => file,
+        };
+
+        Ok(Response {
+            status: 200,
+            content_type: "application/octet-stream",
+            body: self.store.plain_io(&file)?.as_ref().to_vec(),
+        })
+    }
+
+    /// Whether the authorizer permits *client* to decrypt the entry *pass_name*
+    fn authorize(&self, client: &str, pass_name: &str, peer: SocketAddr) -> bool {
+        let request = DecryptRequest {
+            client,
+            pass_name,
+            peer,
+        };
+        let permitted = self
+            .authorizer
+            .as_ref()
+            .is_some_and(|authorizer| authorizer(&request));
+        if !permitted {
+            trace!("Denied decryption of {} for client {}", pass_name, client);
+        }
+        permitted
+    }
+
+    fn forbidden() -> Response {
+        Response::status(403, "forbidden", "The client may not decrypt this entry")
+    }
+}
+
+/// The request line and headers of a request
+#[derive(Debug)]
+struct RequestHead {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+}
+
+impl RequestHead {
+    /// The value of the header *name* (which must be lowercase)
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Read the head of a request from *reader*
+///
+/// `None` is returned if the request is malformed.
+fn read_head(reader: &mut impl BufRead) -> io::Result<Option<RequestHead>> {
+    let mut lines = Vec::new();
+    let mut len = 0;
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .by_ref()
+            .take((MAX_HEAD_LEN - len) as u64)
+            .read_line(&mut line)?;
+        len += read;
+        if read == 0 || !line.ends_with('\n') {
+            return Ok(None);
+        }
+        let line = line.trim_end_matches(['\r', '\n']).to_string();
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+
+    let mut lines = lines.into_iter();
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(_version)) => (method, target),
+        _ => return Ok(None),
+    };
+    let mut headers = Vec::new();
+    for line in lines {
+        match line.split_once(':') {
+            Some((key, value)) => {
+                headers.push((key.trim().to_ascii_lowercase(), value.trim().to_string()))
+            }
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(RequestHead {
+        method: method.to_string(),
+        // the query is not used by any endpoint
+        path: target.split('?').next().unwrap_or_default().to_string(),
+        headers,
+    }))
+}
+
+fn write_response(stream: &mut impl Write, response: &Response) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        429 => "Too Many Requests",
+        504 => "Gateway Timeout",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len()
+    )?;
+    if response.status == 401 {
+        stream.write_all(b"WWW-Authenticate: Bearer\r\n")?;
+    }
+    stream.write_all(b"\r\n")?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+/// Decode `%XX` escapes in *path*
+///
+/// `None` is returned if an escape is malformed or the result is not valid UTF-8.
+pub(crate) fn percent_decode(path: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(b);
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
@@ -0,0 +1,388 @@
+//! A persistent index of entry names and non-secret metadata
+//!
+//! Listing a huge store requires reading every directory of it which makes the first lookup of e.g. a GUI
+//! picker slow.
+//! A store which is configured via [`Store::with_index()`](crate::Store::with_index) keeps a [`StoreIndex`] in
+//! a small file (by default below `~/.cache/libpass`, see [`default_path()`]) and only re-reads directories
+//! whose modification time changed since the index was written.
+//!
+//! The index contains entry names, the modification times of entry files and, once they were collected with
+//! [`Store::index_with_tags()`](crate::Store::index_with_tags), the tags of entries.
+//! **Note:** Tags are part of the encrypted content of entries and are stored unencrypted in the index.
+//!
+//! Entry files which are modified in place (e.g. through a [`RwPlainFile`](crate::file_io::RwPlainFile))
+//! do not change the modification time of their directory, so the metadata of such entries may be stale
+//! until their directory changes.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::{index, Store};
+//!
+//! let store = Store::from_env().unwrap();
+//! let store = match index::default_path(store.root()) {
+//!     Some(path) => store.with_index(path),
+//!     None => store,
+//! };
+//! for entry in store.index().unwrap().entries() {
+//!     println!("{}", entry.name());
+//! }
+//! ```
+
+use crate::telemetry::debug;
+use crate::{config, names, snapshot, utils, Result};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// First line of index files which identifies their format
+const HEADER: &str = "libpass-index 1";
+
+/// The path at which the index of the store at *root* is kept by default
+/// (`~/.cache/libpass/index-<hash of root>`)
+///
+/// The hash is the start of the SHA-256 hash of the root path so that it is the same for every build of this
+/// library.
+/// `None` is returned if the platform has no cache directory.
+pub fn default_path(root: &Path) -> Option<PathBuf> {
+    let hash = snapshot::hash_of(root.as_os_str().as_encoded_bytes());
+    directories::BaseDirs::new().map(|dirs| {
+        dirs.cache_dir()
+            .join("libpass")
+            .join(format!("index-{}", &hash[..16]))
+    })
+}
+
+/// A point in time with the precision of filesystem timestamps
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+struct Timestamp(u64, u32);
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        // times before the epoch do not occur in practice and are treated as the epoch itself
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Self(since_epoch.as_secs(), since_epoch.subsec_nanos())
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(Timestamp(secs, nanos): Timestamp) -> Self {
+        UNIX_EPOCH + Duration::new(secs, nanos)
+    }
+}
+
+/// Metadata of an entry which is known without decrypting it (except for its tags)
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct IndexedEntry {
+    name: String,
+    modified: Timestamp,
+    tags: Option<Vec<String>>,
+}
+
+impl IndexedEntry {
+    /// The name of the entry
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The time at which the entry file was last modified
+    pub fn modified(&self) -> SystemTime {
+        self.modified.into()
+    }
+
+    /// The tags of the entry or `None` if they were not collected yet
+    pub fn tags(&self) -> Option<&[String]> {
+        self.tags.as_deref()
+    }
+
+    pub(crate) fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = Some(tags);
+    }
+}
+
+/// A directory of the store and its direct content
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+struct IndexedDir {
+    modified: Timestamp,
+    subdirs: Vec<String>,
+    entries: Vec<IndexedEntry>,
+}
+
+/// Names and metadata of all entries of a store
+///
+/// Get an instance of this by calling [`Store::index()`](crate::Store::index).
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct StoreIndex {
+    /// The time at which the index was built
+    created: Timestamp,
+    /// Directories of the store by their name (the root directory has the empty name)
+    dirs: BTreeMap<String, IndexedDir>,
+}
+
+impl StoreIndex {
+    /// All indexed entries, sorted by their name
+    pub fn entries(&self) -> Vec<&IndexedEntry> {
+        let mut entries = self
+            .dirs
+            .values()
+            .flat_map(|dir| &dir.entries)
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        entries
+    }
+
+    /// The entry named *pass_name*
+    ///
+    /// The name is normalized before looking it up (see [`normalize_name()`](crate::normalize_name)) so that
+    /// all spellings of a name find the same entry.
+    pub fn get(&self, pass_name: &str) -> Option<&IndexedEntry> {
+        let name = names::normalize_name(pass_name).ok()?;
+        let dir = name.rsplit_once('/').map_or("", |(dir, _)| dir);
+        self.dirs
+            .get(dir)?
+            .entries
+            .iter()
+            .find(|entry| entry.name == name)
+    }
+
+    /// All entries whose collected tags contain *tag*, sorted by their name
+    pub fn entries_with_tag(&self, tag: &str) -> Vec<&IndexedEntry> {
+        self.entries()
+            .into_iter()
+            .filter(|entry| {
+                entry
+                    .tags()
+                    .is_some_and(|tags| tags.iter().any(|t| t == tag))
+            })
+            .collect()
+    }
+
+    /// Names which complete *prefix* by one path component, sorted
+    ///
+    /// Only the directory which *prefix* points into is consulted.
+    /// Directories are suffixed with `/` so that completion can continue into them.
+    pub fn completion_candidates(&self, prefix: &str) -> Vec<String> {
+        let dir = prefix.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let mut candidates = match self.dirs.get(dir) {
+            None => return Vec::new(),
+            Some(dir) => dir
+                .subdirs
+                .iter()
+                .map(|subdir| format!("{}/", subdir))
+                .chain(dir.entries.iter().map(|entry| entry.name.clone()))
+                .filter(|candidate| candidate.starts_with(prefix))
+                .collect::<Vec<_>>(),
+        };
+        candidates.sort_unstable();
+        candidates
+    }
+
+    /// The number of indexed entries
+    pub fn len(&self) -> usize {
+        self.dirs.values().map(|dir| dir.entries.len()).sum()
+    }
+
+    /// Whether the store has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn entries_mut(&mut self) -> impl Iterator<Item = &mut IndexedEntry> {
+        self.dirs.values_mut().flat_map(|dir| &mut dir.entries)
+    }
+
+    /// Bring *previous* up to date with the store at *root*
+    ///
+    /// Only directories whose modification time differs from the one recorded in *previous* are read.
+    /// Tags of entries whose modification time did not change are kept.
+    pub(crate) fn refresh(root: &Path, mut previous: StoreIndex) -> Result<StoreIndex> {
+        let mut index = StoreIndex {
+            created: SystemTime::now().into(),
+            dirs: BTreeMap::new(),
+        };
+
+        let mut queue = vec![String::new()];
+        while let Some(name) = queue.pop() {
+            // directories may only exist with a decomposed (NFD) name
+            let path = names::dir_path(root, &name)?;
+            let modified = Timestamp::from(fs::metadata(&path)?.modified()?);
+            let dir = match previous.dirs.remove(&name) {
+                // changes within the same second as the previous build could have been missed on filesystems
+                // with coarse timestamps
+                Some(dir) if dir.modified == modified && modified.0 < previous.created.0 => dir,
+                outdated => read_dir(root, &path, modified, outdated)?,
+            };
+            queue.extend(dir.subdirs.iter().cloned());
+            index.dirs.insert(name, dir);
+        }
+        Ok(index)
+    }
+
+    /// Read the index stored at *path*
+    ///
+    /// A missing or unreadable index is treated as empty so that it is rebuilt.
+    pub(crate) fn load(path: &Path) -> StoreIndex {
+        match fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content).unwrap_or_else(|| {
+                debug!("Ignoring corrupted index {}", path.display());
+                StoreIndex::default()
+            }),
+            Err(_) => StoreIndex::default(),
+        }
+    }
+
+    /// Write this index to *path* so that only the current user can read it
+    pub(crate) fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            utils::create_dir_all(parent, config::DEFAULT_UMASK)?;
+        }
+        utils::write_atomically(path, self.serialize().as_bytes(), config::DEFAULT_UMASK)?;
+        Ok(())
+    }
+
+    fn serialize(&self) -> String {
+        let mut out = format!("{}\n{}\n", HEADER, format_timestamp(self.created));
+        for (name, dir) in &self.dirs {
+            out.push_str(&format!(
+                "d {} {}\n",
+                format_timestamp(dir.modified),
+                escape(name)
+            ));
+            for subdir in &dir.subdirs {
+                out.push_str(&format!("s {}\n", escape(subdir)));
+            }
+            for entry in &dir.entries {
+                let tags = match &entry.tags {
+                    None => "-".to_string(),
+                    Some(tags) => format!("[{}]", tags.join(",")),
+                };
+                out.push_str(&format!(
+                    "e {} {} {}\n",
+                    format_timestamp(entry.modified),
+                    tags,
+                    escape(&entry.name)
+                ));
+            }
+        }
+        out
+    }
+
+    fn parse(content: &str) -> Option<StoreIndex> {
+        let mut lines = content.lines();
+        if lines.next()? != HEADER {
+            return None;
+        }
+        let mut index = StoreIndex {
+            created: parse_timestamp(lines.next()?)?,
+            dirs: BTreeMap::new(),
+        };
+
+        let mut current: Option<&mut IndexedDir> = None;
+        for line in lines {
+            let (kind, rest) = line.split_once(' ')?;
+            match kind {
+                "d" => {
+                    let (modified, name) = rest.split_once(' ').unwrap_or((rest, ""));
+                    let dir = IndexedDir {
+                        modified: parse_timestamp(modified)?,
+                        ..Default::default()
+                    };
+                    current = Some(index.dirs.entry(unescape(name)?).or_insert(dir));
+                }
+                "s" => current.as_mut()?.subdirs.push(unescape(rest)?),
+                "e" => {
+                    let mut parts = rest.splitn(3, ' ');
+                    let modified = parse_timestamp(parts.next()?)?;
+                    let tags = match parts.next()? {
+                        "-" => None,
+                        "[]" => Some(Vec::new()),
+                        tags => Some(
+                            tags.strip_prefix('[')?
+                                .strip_suffix(']')?
+                                .split(',')
+                                .map(str::to_string)
+                                .collect(),
+                        ),
+                    };
+                    current.as_mut()?.entries.push(IndexedEntry {
+                        name: unescape(parts.next()?)?,
+                        modified,
+                        tags,
+                    });
+                }
+                _ => return None,
+            }
+        }
+        Some(index)
+    }
+}
+
+/// Read the directory at *path* whose modification time is *modified*
+///
+/// Tags are taken over from the *outdated* index of the directory for entries which were not modified.
+fn read_dir(
+    root: &Path,
+    path: &Path,
+    modified: Timestamp,
+    outdated: Option<IndexedDir>,
+) -> Result<IndexedDir> {
+    let mut dir = IndexedDir {
+        modified,
+        ..Default::default()
+    };
+    for file in fs::read_dir(path)? {
+        let file = file?;
+        let file_type = file.file_type()?;
+        let file_path = file.path();
+        if file_type.is_dir() {
+            // git repositories (including those of submodules) are not part of the entry hierarchy
+            if file_path.file_name() != Some(OsStr::new(".git")) {
+                dir.subdirs.push(names::path_to_name(root, &file_path)?);
+            }
+        } else if file_type.is_file() && file_path.extension() == Some(OsStr::new("gpg")) {
+            let name = names::path_to_name(root, &file_path)?;
+            let modified = Timestamp::from(file.metadata()?.modified()?);
+            let tags = outdated
+                .as_ref()
+                .and_then(|outdated| outdated.entries.iter().find(|entry| entry.name == name))
+                .filter(|entry| entry.modified == modified)
+                .and_then(|entry| entry.tags.clone());
+            dir.entries.push(IndexedEntry {
+                name,
+                modified,
+                tags,
+            });
+        }
+    }
+    Ok(dir)
+}
+
+fn format_timestamp(Timestamp(secs, nanos): Timestamp) -> String {
+    format!("{}.{:09}", secs, nanos)
+}
+
+fn parse_timestamp(timestamp: &str) -> Option<Timestamp> {
+    let (secs, nanos) = timestamp.split_once('.')?;
+    Some(Timestamp(secs.parse().ok()?, nanos.parse().ok()?))
+}
+
+/// Escape line breaks and `%` in *name* so that it fits on a single line
+pub(crate) fn escape(name: &str) -> String {
+    name.replace('%', "%25")
+        .replace('\n', "%0A")
+        .replace('\r', "%0D")
+}
+
+pub(crate) fn unescape(name: &str) -> Option<String> {
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(i) = rest.find('%') {
+        out.push_str(&rest[..i]);
+        let code = rest.get(i + 1..i + 3)?;
+        out.push(u8::from_str_radix(code, 16).ok()? as char);
+        rest = &rest[i + 3..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
@@ -0,0 +1,183 @@
+//! Sources of the plaintext of new entries which mirror the input modes of `pass insert`
+//!
+//! `pass insert` reads a single line (the password) by default and everything until EOF with `--multiline`.
+//! In single-line mode, the line break which terminated the input is replaced by exactly one newline while
+//! multi-line input is stored unchanged.
+//! [`TrailingNewline`] selects whether the same normalization is applied by [`Store::insert()`](crate::Store::insert).
+
+use crate::secure::SecretString;
+use crate::{PassError, Result};
+use std::fmt::{Debug, Formatter};
+use std::io::Read;
+
+/// The plaintext of a new entry
+///
+/// Slices, vectors and strings convert into [`InsertSource::MultiLine`], a [`SecretString`] into
+/// [`InsertSource::SingleLine`].
+pub enum InsertSource<'a> {
+    /// A password which was entered on a single line (like `pass insert`)
+    ///
+    /// Only the line break which terminates the line is allowed.
+    SingleLine(SecretString),
+    /// Content which may span multiple lines (like `pass insert --multiline`)
+    MultiLine(Vec<u8>),
+    /// Content which is read until EOF, e.g. from stdin
+    Reader(Box<dyn Read + 'a>),
+}
+
+impl<'a> InsertSource<'a> {
+    /// Read the content from *reader* until EOF
+    pub fn reader(reader: impl Read + 'a) -> Self {
+        Self::Reader(Box::new(reader))
+    }
+
+    /// Read the content and normalize its trailing newlines according to *mode*
+    ///
+    /// ## Errors
+    /// [`PassError::MalformedEntry`] is returned if a [`InsertSource::SingleLine`] spans multiple lines.
+    pub(crate) fn into_content(self, mode: TrailingNewline) -> Result<Vec<u8>> {
+        let (mut content, mode) = match self {
+            Self::SingleLine(line) => {
+                let text = line.expose();
+                let text = text.strip_suffix('\n').unwrap_or(text);
+                let text = text.strip_suffix('\r').unwrap_or(text);
+                if text.contains(['\n', '\r']) {
+                    return Err(PassError::MalformedEntry(
+                        "a single-line password must not contain line breaks".to_string(),
+                    ));
+                }
+                (line.expose().as_bytes().to_vec(), mode.single_line())
+            }
+            Self::MultiLine(content) => (content, mode.multi_line()),
+            Self::Reader(mut reader) => {
+                let mut content = Vec::new();
+                reader.read_to_end(&mut content)?;
+                (content, mode.multi_line())
+            }
+        };
+
+        match mode {
+            TrailingNewline::Keep | TrailingNewline::Pass => {}
+            TrailingNewline::Ensure => {
+                if !content.ends_with(b"\n") {
+                    content.push(b'\n');
+                }
+            }
+            TrailingNewline::Strip => strip_newlines(&mut content),
+            TrailingNewline::Single => {
+                strip_newlines(&mut content);
+                content.push(b'\n');
+            }
+        }
+        Ok(content)
+    }
+}
+
+impl Debug for InsertSource<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // the content is secret so only the kind of source is shown
+        f.write_str(match self {
+            Self::SingleLine(_) => "InsertSource::SingleLine(..)",
+            Self::MultiLine(_) => "InsertSource::MultiLine(..)",
+            Self::Reader(_) => "InsertSource::Reader(..)",
+        })
+    }
+}
+
+impl From<SecretString> for InsertSource<'_> {
+    fn from(line: SecretString) -> Self {
+        Self::SingleLine(line)
+    }
+}
+
+impl From<Vec<u8>> for InsertSource<'_> {
+    fn from(content: Vec<u8>) -> Self {
+        Self::MultiLine(content)
+    }
+}
+
+impl From<&Vec<u8>> for InsertSource<'_> {
+    fn from(content: &Vec<u8>) -> Self {
+        Self::MultiLine(content.clone())
+    }
+}
+
+impl From<&[u8]> for InsertSource<'_> {
+    fn from(content: &[u8]) -> Self {
+        Self::MultiLine(content.to_vec())
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for InsertSource<'_> {
+    fn from(content: &[u8; N]) -> Self {
+        Self::MultiLine(content.to_vec())
+    }
+}
+
+impl From<String> for InsertSource<'_> {
+    fn from(content: String) -> Self {
+        Self::MultiLine(content.into_bytes())
+    }
+}
+
+impl From<&str> for InsertSource<'_> {
+    fn from(content: &str) -> Self {
+        Self::MultiLine(content.as_bytes().to_vec())
+    }
+}
+
+/// How trailing newlines of inserted content are normalized
+///
+/// Configure this via [`Store::with_trailing_newline()`](crate::Store::with_trailing_newline).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum TrailingNewline {
+    /// Behave like `pass insert`: single-line input ends with exactly one newline, other input is kept
+    #[default]
+    Pass,
+    /// Store all input exactly as given
+    Keep,
+    /// Append a newline to input which does not end with one
+    Ensure,
+    /// Remove all trailing line breaks
+    Strip,
+    /// Replace all trailing line breaks by exactly one newline
+    Single,
+}
+
+impl TrailingNewline {
+    fn single_line(self) -> Self {
+        match self {
+            Self::Pass => Self::Single,
+            mode => mode,
+        }
+    }
+
+    fn multi_line(self) -> Self {
+        match self {
+            Self::Pass => Self::Keep,
+            mode => mode,
+        }
+    }
+}
+
+/// How an existing entry is handled when a new entry with the same name is written
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum OverwritePolicy {
+    /// Fail with [`PassError::EntryAlreadyExists`](crate::PassError::EntryAlreadyExists) (like *pass* without
+    /// `--force`)
+    #[default]
+    Error,
+    /// Replace the existing entry (like `pass insert --force`)
+    Overwrite,
+    /// Keep the existing entry and write the new one under the first free name with a numeric suffix like
+    /// `secret (2)`
+    KeepBoth,
+}
+
+/// Remove all trailing `\n` and `\r\n` line breaks from *content*
+fn strip_newlines(content: &mut Vec<u8>) {
+    while let Some(stripped) = content.strip_suffix(b"\n") {
+        let len = stripped.strip_suffix(b"\r").unwrap_or(stripped).len();
+        content.truncate(len);
+    }
+}
@@ -0,0 +1,92 @@
+//! Export of store directories as Kubernetes secrets
+//!
+//! GitOps pipelines which hydrate secrets from pass can turn a directory into a `Secret` manifest with
+//! [`StoreDirectoryRef::to_k8s_secret()`](crate::StoreDirectoryRef::to_k8s_secret).
+//! Every entry which is directly contained in the directory becomes one key of the secret whose value is
+//! the complete (base64 encoded) content of the entry.
+//!
+//! This module is only available with the `k8s` feature.
+
+use crate::{PassError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+
+/// A Kubernetes `Secret` manifest of type `Opaque`
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Secret {
+    /// Always `v1`
+    pub api_version: String,
+    /// Always `Secret`
+    pub kind: String,
+    /// Name and namespace of the secret
+    pub metadata: Metadata,
+    /// The type of the secret (`Opaque`)
+    #[serde(rename = "type")]
+    pub secret_type: String,
+    /// The base64 encoded values of the secret by their keys
+    pub data: BTreeMap<String, String>,
+}
+
+/// The metadata of a [`Secret`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Metadata {
+    /// The name of the secret
+    pub name: String,
+    /// The namespace of the secret
+    pub namespace: String,
+}
+
+impl Secret {
+    /// Create an empty secret named *name* in *namespace*
+    pub fn new(name: impl Into<String>, namespace: impl Into<String>) -> Self {
+        Self {
+            api_version: "v1".to_string(),
+            kind: "Secret".to_string(),
+            metadata: Metadata {
+                name: name.into(),
+                namespace: namespace.into(),
+            },
+            secret_type: "Opaque".to_string(),
+            data: BTreeMap::new(),
+        }
+    }
+
+    /// Add *value* under *key* (which must consist of alphanumeric characters, `-`, `_` or `.`)
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidField`] is returned if *key* is not a valid key or already exists.
+    pub fn insert(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        let invalid = |reason: &str| PassError::InvalidField {
+            key: key.to_string(),
+            reason: reason.to_string(),
+        };
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        {
+            return Err(invalid(
+                "Keys of secrets can only consist of alphanumeric characters, '-', '_' or '.'",
+            ));
+        }
+        if self.data.contains_key(key) {
+            return Err(invalid("The secret already contains the key"));
+        }
+        self.data
+            .insert(key.to_string(), crate::utils::base64_encode(value));
+        Ok(())
+    }
+
+    /// Serialize the manifest as JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self).map_err(io::Error::from)?)
+    }
+
+    /// Serialize the manifest as YAML
+    pub fn to_yaml(&self) -> Result<String> {
+        serde_yaml::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+    }
+}
@@ -0,0 +1,306 @@
+//! Discovery of gpg keys that are available in the local keyring
+//!
+//! These helpers are intended for onboarding flows where an application needs to find out which keys a
+//! user has and whether they are able to use a given store at all.
+//! The free functions use the default gpg configuration while functions which take a [`Store`] follow its
+//! options, e.g. [`Store::with_offline()`].
+
+use crate::crypto::CryptoOptions;
+use crate::telemetry::debug;
+use crate::{gpg_id, utils, PassError, Result, Store, StoreEntry};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whether *key* can currently be used to decrypt content because its secret part is available locally
+fn is_usable_for_decryption(key: &gpgme::Key) -> bool {
+    key.has_secret()
+        && !key.is_revoked()
+        && !key.is_disabled()
+        && !key.is_invalid()
+        && key
+            .subkeys()
+            .any(|subkey| subkey.can_encrypt() && subkey.is_secret() && !subkey.is_revoked())
+}
+
+/// List all keys from the local keyring whose secret part is available and can be used for decryption
+pub fn secret_keys() -> Result<Vec<gpgme::Key>> {
+    secret_keys_in(&CryptoOptions::default())
+}
+
+/// List all keys whose secret part is available and can be used for decryption using a context configured
+/// according to *crypto*
+fn secret_keys_in(crypto: &CryptoOptions) -> Result<Vec<gpgme::Key>> {
+    let mut gpg_ctx = crypto.new_context()?;
+    let keys = gpg_ctx.secret_keys()?.collect::<Result<Vec<_>, _>>()?;
+    Ok(keys.into_iter().filter(is_usable_for_decryption).collect())
+}
+
+/// Check whether gpg-agent currently caches the passphrase of any secret subkey of *key*
+///
+/// If it does, decrypting content for *key* does not trigger a pinentry prompt.
+/// Keys whose secret part is stored on a smartcard are never reported as cached because the PIN is managed
+/// by the card daemon instead.
+pub fn is_passphrase_cached(key: &gpgme::Key) -> Result<bool> {
+    let mut agent_ctx = gpgme::Context::from_protocol(gpgme::Protocol::Assuan)?;
+    for subkey in key.subkeys().filter(|subkey| subkey.is_secret()) {
+        let keygrip = match subkey.keygrip() {
+            Ok(keygrip) if !keygrip.is_empty() => keygrip,
+            _ => continue,
+        };
+
+        // the status line is "KEYINFO <keygrip> <type> <serialno> <idstr> <cached> ..."
+        let mut cached = false;
+        agent_ctx.assuan_transact_with_callbacks(
+            format!("KEYINFO {}", keygrip),
+            |_| Ok(()),
+            |_, _, _| Ok(()),
+            |keyword, args| {
+                if keyword == "KEYINFO" {
+                    cached |= args.and_then(|args| args.split(' ').nth(4)) == Some("1");
+                }
+                Ok(())
+            },
+        )?;
+        if cached {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Look up all keys in the local keyring which have a user id with the given email address
+///
+/// The email address is compared case-insensitively.
+pub fn find_by_email(email: &str) -> Result<Vec<gpgme::Key>> {
+    let mut gpg_ctx = CryptoOptions::default().new_context()?;
+    let keys = gpg_ctx.find_keys([email])?.collect::<Result<Vec<_>, _>>()?;
+    Ok(keys
+        .into_iter()
+        .filter(|key| {
+            key.user_ids().any(|uid| {
+                uid.email()
+                    .map(|uid_email| uid_email.eq_ignore_ascii_case(email))
+                    .unwrap_or(false)
+            })
+        })
+        .collect())
+}
+
+/// Look up the key with the given fingerprint (or key id) in the local keyring
+///
+/// ## Errors
+/// [`PassError::GpgKeyNotFoundError`] is returned if no such key exists.
+pub fn find_by_fingerprint(fingerprint: &str) -> Result<gpgme::Key> {
+    find_by_fingerprint_in(&CryptoOptions::default(), fingerprint)
+}
+
+/// Look up the key with the given fingerprint using a context configured according to *crypto*
+fn find_by_fingerprint_in(crypto: &CryptoOptions, fingerprint: &str) -> Result<gpgme::Key> {
+    let mut gpg_ctx = crypto.new_context()?;
+    gpg_ctx
+        .get_key(fingerprint)
+        .map_err(|_| PassError::GpgKeyNotFoundError(fingerprint.to_string()))
+}
+
+/// Check whether every entry of *store* is encrypted for at least one locally available secret key
+///
+/// Only the unencrypted packet headers of entries are inspected so no decryption (and no pinentry prompt)
+/// takes place.
+/// Entries with hidden recipients are assumed to be decryptable.
+pub fn is_store_decryptable(store: &Store) -> Result<bool> {
+    let local_key_ids = secret_keys_in(store.crypto())?
+        .iter()
+        .flat_map(|key| {
+            key.subkeys()
+                .filter_map(|subkey| subkey.id().ok().map(str::to_string))
+                .collect::<Vec<_>>()
+        })
+        .collect::<HashSet<_>>();
+
+    for entry in store.list()? {
+        if let StoreEntry::File(file) = entry {
+            let recipients = file.recipient_key_ids()?;
+            let is_hidden = recipients.iter().any(|id| id.bytes().all(|b| b == b'0'));
+            if !is_hidden && !recipients.iter().any(|id| local_key_ids.contains(id)) {
+                debug!(
+                    "{} is not encrypted for any local secret key",
+                    file.path.display()
+                );
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Options for [`Store::rotate_recipient()`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct RotationOptions {
+    signing_key: Option<String>,
+}
+
+impl RotationOptions {
+    /// Re-sign every changed `.gpg-id` file with the secret key *signing_key* and write the detached
+    /// signature to the `.gpg-id.sig` file next to it
+    pub fn with_signing_key(mut self, signing_key: impl Into<String>) -> Self {
+        self.signing_key = Some(signing_key.into());
+        self
+    }
+
+    /// The key with which changed `.gpg-id` files are signed
+    pub fn signing_key(&self) -> Option<&str> {
+        self.signing_key.as_deref()
+    }
+}
+
+/// Summary of a [`Store::rotate_recipient()`] run
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct RotationReport {
+    /// The `.gpg-id` files in which the old key was replaced
+    pub gpg_id_files: Vec<PathBuf>,
+    /// The names of all entries that were re-encrypted
+    pub reencrypted: Vec<String>,
+    /// The `.gpg-id.sig` files that were (re-)created
+    pub signed: Vec<PathBuf>,
+    /// The `.gpg-id.sig` files that no longer match their changed `.gpg-id` file because no signing key
+    /// was given
+    pub stale_signatures: Vec<PathBuf>,
+}
+
+/// Normalize a key id or fingerprint for comparison
+fn normalize_key_id(key_id: &str) -> String {
+    key_id
+        .trim_start_matches("0x")
+        .replace(' ', "")
+        .to_uppercase()
+}
+
+/// Collect all `.gpg-id` files inside *dir* sorted by their path
+fn gpg_id_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let gpg_id = dir.join(".gpg-id");
+    if gpg_id.is_file() {
+        files.push(gpg_id);
+    }
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() && path.file_name() != Some(".git".as_ref()) {
+            dirs.push(path);
+        }
+    }
+    dirs.sort_unstable();
+    for dir in dirs {
+        gpg_id_files(&dir, files)?;
+    }
+    Ok(())
+}
+
+/// The directory of the `.gpg-id` file that governs the entry at *path* inside the store at *root*
+fn governing_dir(root: &Path, path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(root))
+        .find(|dir| dir.join(".gpg-id").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Replace *old_key* with *new_key* in all `.gpg-id` files of *store* and re-encrypt the affected entries
+pub(crate) fn rotate_recipient(
+    store: &Store,
+    old_key: &str,
+    new_key: &str,
+    options: &RotationOptions,
+) -> Result<RotationReport> {
+    let crypto = store.crypto();
+    utils::check_key_usable(&find_by_fingerprint_in(crypto, new_key)?)?;
+
+    // the old key may already be gone from the keyring so recipients are also compared textually
+    let old_id = normalize_key_id(old_key);
+    let old_fingerprint = find_by_fingerprint_in(crypto, old_key)
+        .ok()
+        .and_then(|key| key.fingerprint().ok().map(normalize_key_id));
+    // entries are selected by the keys they are actually encrypted for so that entries which were skipped by
+    // an interrupted rotation are still found once the .gpg-id files are already updated
+    let mut old_key_ids = find_by_fingerprint_in(crypto, old_key)
+        .map(|key| {
+            key.subkeys()
+                .filter_map(|subkey| subkey.id().ok().map(normalize_key_id))
+                .collect::<HashSet<_>>()
+        })
+        .unwrap_or_default();
+    if old_id.len() >= 16 {
+        old_key_ids.insert(old_id[old_id.len() - 16..].to_string());
+    }
+    let mut gpg_ctx = crypto.new_context()?;
+    let mut is_old = |recipient: &str| {
+        let id = normalize_key_id(recipient);
+        if id == old_id
+            || old_fingerprint
+                .as_ref()
+                .is_some_and(|fpr| fpr.ends_with(&id))
+        {
+            return true;
+        }
+        match (&old_fingerprint, gpg_ctx.get_key(recipient)) {
+            (Some(old_fingerprint), Ok(key)) => key
+                .fingerprint()
+                .is_ok_and(|fpr| normalize_key_id(fpr) == *old_fingerprint),
+            _ => false,
+        }
+    };
+
+    let mut report = RotationReport::default();
+    let mut all_gpg_ids = Vec::new();
+    gpg_id_files(store.root(), &mut all_gpg_ids)?;
+    for path in all_gpg_ids {
+        let content = fs::read_to_string(&path)?;
+        let recipients = content.parse::<gpg_id::GpgIdFile>().unwrap_or_default();
+        let old_recipients = recipients
+            .recipients()
+            .iter()
+            .filter(|recipient| is_old(recipient))
+            .collect::<HashSet<_>>();
+        let updated = match gpg_id::replace_recipient(
+            &content,
+            |recipient| old_recipients.contains(&recipient.to_string()),
+            new_key,
+        ) {
+            Some(updated) => updated,
+            None => continue,
+        };
+
+        debug!("Replacing {} in {}", old_key, path.display());
+        utils::write_atomically(&path, updated.as_bytes(), store.config().umask())?;
+        store.track_in_manifest(&path)?;
+        let mut sig_path = path.clone().into_os_string();
+        sig_path.push(".sig");
+        let sig_path = PathBuf::from(sig_path);
+        match options.signing_key() {
+            Some(signing_key) => {
+                let signature = crypto.sign_detached(signing_key, updated.as_bytes())?;
+                utils::write_atomically(&sig_path, &signature, store.config().umask())?;
+                report.signed.push(sig_path);
+            }
+            None if sig_path.exists() => report.stale_signatures.push(sig_path),
+            None => {}
+        }
+        report.gpg_id_files.push(path);
+    }
+
+    for file in store.files()? {
+        let is_affected = governing_dir(store.root(), &file.path)
+            .is_some_and(|dir| report.gpg_id_files.contains(&dir.join(".gpg-id")))
+            || file
+                .recipient_key_ids()?
+                .iter()
+                .any(|id| old_key_ids.contains(&normalize_key_id(id)));
+        if is_affected {
+            store.reencrypt(&file)?;
+            report.reencrypted.push(store.relative_name(&file.path)?);
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,246 @@
+//! Validation of the structure of a store against naming conventions
+//!
+//! A [`LayoutSpec`] lists patterns like `sites/<domain>/<username>` which the names of all entries have to
+//! follow and [`Store::validate_layout()`](crate::Store::validate_layout) reports every entry which does not.
+//!
+//! Patterns consist of segments separated by `/`:
+//! - `<placeholder>` matches exactly one segment.
+//!   A regular expression which the whole segment has to match can be attached to the placeholder with
+//!   [`LayoutSpec::with_placeholder()`]; otherwise any segment is accepted.
+//! - `**` matches any number of segments, including none.
+//! - Every other segment has to appear literally.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::layout::LayoutSpec;
+//! use libpass::Store;
+//!
+//! let spec = LayoutSpec::new()
+//!     .with_pattern("sites/<domain>/<username>")
+//!     .unwrap()
+//!     .with_pattern("misc/**")
+//!     .unwrap()
+//!     .with_placeholder("domain", r"[a-z0-9-]+(\.[a-z0-9-]+)+")
+//!     .unwrap();
+//! for violation in Store::from_env().unwrap().validate_layout(&spec).unwrap() {
+//!     println!("{:?}", violation);
+//! }
+//! ```
+
+use crate::{PassError, Result};
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// An entry whose name does not follow a [`LayoutSpec`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum LayoutViolation {
+    /// The entry does not have the structure of any pattern, e.g. because it is nested too deep or lies in an
+    /// unexpected directory
+    Unmatched(String),
+    /// The entry has the structure of a pattern but one of its segments is not accepted by the placeholder
+    InvalidSegment {
+        /// The name of the entry
+        pass_name: String,
+        /// The pattern whose structure the entry has
+        pattern: String,
+        /// The placeholder which does not accept the segment
+        placeholder: String,
+        /// The offending segment
+        segment: String,
+    },
+}
+
+impl LayoutViolation {
+    /// The name of the offending entry
+    pub fn pass_name(&self) -> &str {
+        match self {
+            Self::Unmatched(pass_name) | Self::InvalidSegment { pass_name, .. } => pass_name,
+        }
+    }
+}
+
+/// The expected structure of the entry names of a store
+///
+/// A spec without patterns accepts every entry.
+#[derive(Debug, Clone, Default)]
+pub struct LayoutSpec {
+    patterns: Vec<Pattern>,
+    placeholders: BTreeMap<String, Regex>,
+}
+
+impl LayoutSpec {
+    /// Create a spec without any patterns
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow entries whose names match *pattern*
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidLayoutRule`] is returned if *pattern* is empty or contains a placeholder which does
+    /// not span a whole segment.
+    pub fn with_pattern(mut self, pattern: &str) -> Result<Self> {
+        self.patterns.push(Pattern::parse(pattern)?);
+        Ok(self)
+    }
+
+    /// The patterns of which entry names have to match at least one
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().map(|pattern| pattern.source.as_str())
+    }
+
+    /// Only accept segments in place of `<name>` which fully match the regular expression *regex*
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidLayoutRule`] is returned if *regex* is not a valid regular expression.
+    pub fn with_placeholder(mut self, name: impl Into<String>, regex: &str) -> Result<Self> {
+        let anchored =
+            Regex::new(&format!("^(?:{})$", regex)).map_err(|e| PassError::InvalidLayoutRule {
+                rule: regex.to_string(),
+                reason: e.to_string(),
+            })?;
+        self.placeholders.insert(name.into(), anchored);
+        Ok(self)
+    }
+
+    /// Check the entry name *pass_name* against the spec
+    ///
+    /// If the name has the structure of a pattern whose placeholders do not accept it, this is reported
+    /// instead of [`LayoutViolation::Unmatched`] since it is the more specific hint.
+    pub fn check(&self, pass_name: &str) -> Option<LayoutViolation> {
+        if self.patterns.is_empty() {
+            return None;
+        }
+        let segments = pass_name
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+        let mut violation = LayoutViolation::Unmatched(pass_name.to_string());
+        for pattern in &self.patterns {
+            match self.match_segments(&pattern.segments, &segments) {
+                Match::Full => return None,
+                Match::Invalid {
+                    placeholder,
+                    segment,
+                } if matches!(violation, LayoutViolation::Unmatched(_)) => {
+                    violation = LayoutViolation::InvalidSegment {
+                        pass_name: pass_name.to_string(),
+                        pattern: pattern.source.clone(),
+                        placeholder: placeholder.to_string(),
+                        segment: segment.to_string(),
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(violation)
+    }
+
+    /// Match the segments of a name against the segments of a pattern
+    fn match_segments<'a>(&'a self, pattern: &'a [Segment], name: &[&'a str]) -> Match<'a> {
+        match (pattern.split_first(), name.split_first()) {
+            (None, None) => Match::Full,
+            (None, Some(_)) => Match::None,
+            (Some((Segment::Any, rest)), _) => (0..=name.len())
+                .map(|skipped| self.match_segments(rest, &name[skipped..]))
+                .fold(Match::None, Match::or),
+            (Some(_), None) => Match::None,
+            (Some((Segment::Literal(literal), rest)), Some((segment, name))) => {
+                match literal == segment {
+                    true => self.match_segments(rest, name),
+                    false => Match::None,
+                }
+            }
+            (Some((Segment::Placeholder(placeholder), rest)), Some((segment, name))) => {
+                match self.match_segments(rest, name) {
+                    Match::None => Match::None,
+                    _ if self
+                        .placeholders
+                        .get(placeholder)
+                        .is_some_and(|regex| !regex.is_match(segment)) =>
+                    {
+                        Match::Invalid {
+                            placeholder,
+                            segment,
+                        }
+                    }
+                    rest => rest,
+                }
+            }
+        }
+    }
+}
+
+/// A pattern of a [`LayoutSpec`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+struct Pattern {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Result<Self> {
+        let invalid = |reason: &str| PassError::InvalidLayoutRule {
+            rule: pattern.to_string(),
+            reason: reason.to_string(),
+        };
+        let segments = pattern
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                if segment == "**" {
+                    return Ok(Segment::Any);
+                }
+                match segment
+                    .strip_prefix('<')
+                    .and_then(|inner| inner.strip_suffix('>'))
+                {
+                    Some(name) if !name.is_empty() && !name.contains(['<', '>']) => {
+                        Ok(Segment::Placeholder(name.to_string()))
+                    }
+                    _ if segment.contains(['<', '>']) => {
+                        Err(invalid("Placeholders must span a whole segment"))
+                    }
+                    _ => Ok(Segment::Literal(segment.to_string())),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if segments.is_empty() {
+            return Err(invalid("The pattern has no segments"));
+        }
+        Ok(Self {
+            source: pattern.to_string(),
+            segments,
+        })
+    }
+}
+
+/// A segment of a [`Pattern`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+enum Segment {
+    Literal(String),
+    Placeholder(String),
+    Any,
+}
+
+/// The result of matching a name against a pattern
+#[derive(Debug, Copy, Clone)]
+enum Match<'a> {
+    Full,
+    Invalid {
+        placeholder: &'a str,
+        segment: &'a str,
+    },
+    None,
+}
+
+impl<'a> Match<'a> {
+    /// The better one of two results where a full match beats a match with an invalid segment
+    fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Full, _) | (_, Self::Full) => Self::Full,
+            (Self::Invalid { .. }, _) => self,
+            (Self::None, _) => other,
+        }
+    }
+}
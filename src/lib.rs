@@ -27,6 +27,30 @@
 //!     }
 //!   ```
 //!
+//! # Features
+//!
+//! - `git`: Cloning and synchronization of stores that are managed in a git repository (see `Store::clone_from()`
+//!   and `Store::git_sync()`)
+//! - `agent`: A unix socket server which caches decrypted entries for short-lived processes (see the `agent`
+//!   module and `Store::with_agent()`)
+//! - `gopass`: Populating a `registry::StoreRegistry` with the mounts of a gopass configuration file (see
+//!   `StoreRegistry::from_gopass()`) and access to the YAML documents of gopass entries (see
+//!   `ParsedEntry::yaml()`)
+//! - `diceware`: The large wordlist of the EFF for generating passphrases (see `generate::Wordlist::eff_large()`)
+//! - `http`: A server for read-only access to stores over a REST API (see the `http` module)
+//! - `k8s`: Export of directories as Kubernetes secrets (see the `k8s` module)
+//! - `layout`: Validation of entry names against naming conventions (see the `layout` module and
+//!   `Store::validate_layout()`)
+//! - `mmap`: Large entry files can be memory-mapped instead of read into memory before decrypting them (see
+//!   `Store::with_mmap_threshold()`)
+//! - `otp-qr`: Reading `otpauth://` URIs from QR codes in images (see `otp::import_from_image()`)
+//! - `secure-mem`: Decrypted content is locked into RAM so that it is not written to swap and overwritten with
+//!   zeroes when it is dropped
+//! - `tracing`: Messages are emitted as `tracing` events and expensive operations are wrapped in spans which
+//!   record their duration
+//! - `serde`: A versioned serialization format for entries, listings and errors (see the `wire` module) and
+//!   serialization of `listing::Listing`
+//! - `testing`: Fixtures for building temporary stores in integration tests (see the `testing` module)
 
 #![deny(unsafe_code)]
 #![warn(
@@ -43,19 +67,96 @@
 
 extern crate core;
 
-pub use crate::errors::PassError;
+pub use crate::config::Config;
+pub use crate::crypto::{
+    Compression, EncryptionPolicy, GpgOperation, SignatureStatus, DEFAULT_STREAMING_THRESHOLD,
+};
+pub use crate::diff::diff_stores;
+pub use crate::errors::{PassError, Suggestion};
+pub use crate::gpg_id::GpgIdFile;
+pub use crate::insert_source::{InsertSource, OverwritePolicy, TrailingNewline};
+pub use crate::names::{normalize_name, PassName, Prefer};
+pub use crate::parsed_entry::{Login, ParseMode, ParsedEntry, UrlMatch};
+pub use crate::store::{ReadOnlyStore, RecipientMismatch, Store};
 pub use crate::store_entry::{StoreDirectoryIter, StoreDirectoryRef, StoreEntry, StoreFileRef};
+pub use crate::text::LineEnding;
 use std::collections::HashSet;
-use std::ffi::{OsStr, OsString};
-use std::path::{Path, PathBuf};
-use std::{env, fs};
+use std::env;
+use std::path::PathBuf;
 
+pub mod access;
+#[cfg(all(feature = "agent", unix))]
+pub mod agent;
+mod alias;
+pub mod audit;
+pub mod bundle;
+pub mod compat;
+pub mod completion;
+pub mod config;
+mod context_pool;
+pub mod credentials;
+mod crypto;
+pub mod diff;
 mod errors;
+pub mod export;
 pub mod file_io;
+pub mod filter;
+pub mod fsck;
+pub mod generate;
+#[cfg(feature = "git")]
+pub mod git;
+mod gpg_id;
+mod hardening;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod index;
+mod insert_source;
+#[cfg(feature = "k8s")]
+pub mod k8s;
+pub mod keys;
+#[cfg(feature = "layout")]
+pub mod layout;
+pub mod listing;
+mod locks;
+pub mod manifest;
+pub mod merge;
+pub mod metrics;
+pub mod names;
+pub mod obfuscation;
+pub mod otp;
+mod packets;
+mod parsed_entry;
+pub mod passkey;
+pub mod picker;
+pub mod policy;
+pub mod protection;
+pub mod rate_limit;
+mod recipients;
+pub mod reference;
+pub mod registry;
+pub mod scrub;
+pub mod secure;
+mod secure_mem;
+pub mod share;
+pub mod snapshot;
+pub mod split;
+mod store;
 mod store_entry;
+pub mod systemd;
+pub mod tags;
+mod telemetry;
+pub mod templates;
+#[cfg(feature = "testing")]
+pub mod testing;
 #[cfg(test)]
 mod tests;
+mod text;
+pub mod transform;
 mod utils;
+pub mod view;
+mod walk;
+#[cfg(feature = "serde")]
+pub mod wire;
 
 /// Custom Result that is equivalent to `Result<T, PassError>`.
 pub type Result<T, E = PassError> = core::result::Result<T, E>;
@@ -73,11 +174,11 @@ pub const PASSWORD_STORE_DIR_ENV: &str = "PASSWORD_STORE_DIR";
 /// This means that paths which begin with `~` are resolved to the current users home directory which can
 /// produce io errors.
 pub fn password_store_dir() -> Result<PathBuf> {
-    let path = match env::var(PASSWORD_STORE_DIR_ENV) {
-        Ok(env_var) => Path::new(&env_var).to_path_buf(),
-        Err(_) => Path::new("~/.password-store").to_path_buf(),
+    let config = match env::var(PASSWORD_STORE_DIR_ENV) {
+        Ok(env_var) => Config::default().with_store_dir(env_var),
+        Err(_) => Config::default(),
     };
-    Ok(utils::canonicalize_path(&path)?)
+    config.store_dir()
 }
 
 /// List all passwords in the password store in a flat data structure
@@ -85,79 +186,12 @@ pub fn password_store_dir() -> Result<PathBuf> {
 /// For detailed information that preserves the tree structure of the store use [`retrieve("/")`](retrieve)
 /// instead.
 pub fn list() -> Result<HashSet<StoreEntry>> {
-    match retrieve("/")? {
-        StoreEntry::File(file) => Err(PassError::InvalidStoreFormat(
-            file.path,
-            "Store root is not a directory but a file".to_string(),
-        )),
-        StoreEntry::Directory(dir) => Ok(HashSet::from_iter(dir.iter().cloned())),
-    }
-}
-
-/// Inspect the folder at *path* and recursively map it and its content to a [`StoreEntry`]
-fn inspect_folder(path: impl AsRef<Path>) -> Result<HashSet<StoreEntry>> {
-    fs::read_dir(path)?
-        // retrieve additional information about each file from filesystem
-        .map(|file| match file {
-            Err(e) => Err(e),
-            Ok(file) => Ok((
-                file.path(),
-                file.path().extension().unwrap_or_else(|| OsStr::new("")).to_os_string(),
-                file.file_type()?,
-            )),
-        })
-        // rule out that any errors occurred during information retrieval
-        .collect::<Result<Vec<_>, _>>()?
-        .iter()
-        // filter out files without .gpg extension
-        .filter(|(_, file_extension, file_type)| (file_type.is_file() && file_extension == &OsString::from("gpg") || !file_type.is_file()))
-        // map to correct StoreEntry representation and recurse into subdirectories
-        .map(|(path, _, file_type)|
-            if file_type.is_file() {
-                Ok(StoreEntry::File(StoreFileRef {
-                    path: path.clone()
-                }))
-            } else if file_type.is_dir() {
-                Ok(StoreEntry::Directory(StoreDirectoryRef{
-                    content: inspect_folder(path)?,
-                    path: path.clone(),
-                }))
-            } else {
-                Err(PassError::InvalidStoreFormat(
-                    path.clone(),
-                    "File is neither a string nor directory but pass stores can only contain those types of files".to_string())
-                )
-            })
-        .collect()
+    Store::from_env()?.list()
 }
 
 /// Retrieve the stored entry identified by *pass_name*
 ///
 /// `pass_name` is a path to a password file or directory relative to the store root
 pub fn retrieve(pass_name: &str) -> Result<StoreEntry> {
-    // strip leading / characters if there is one
-    let pass_name = match pass_name.strip_prefix('/') {
-        Some(result) => result,
-        None => pass_name,
-    };
-
-    // resolve paths that could possibly be meant by pass_name
-    let dir_path = password_store_dir()?.join(pass_name);
-    let file_path = password_store_dir()?.join(pass_name.to_string() + ".gpg");
-
-    // check if there is a file or directory with that name and return the correct result after
-    // additional verification
-    match (dir_path.exists(), file_path.exists()) {
-        (true, true) => Err(PassError::AmbiguousPassName(pass_name.to_string())),
-        (false, false) => Err(PassError::EntryNotFound(pass_name.to_string())),
-        (true, false) => Ok(StoreEntry::Directory(StoreDirectoryRef {
-            content: inspect_folder(&dir_path)?,
-            path: dir_path,
-        })),
-        (false, true) => Ok(StoreEntry::File(StoreFileRef { path: file_path })),
-    }
-    .and_then(|store_entry| {
-        store_entry.verify()?;
-        Ok(store_entry)
-    })
+    Store::from_env()?.retrieve(pass_name)
 }
@@ -0,0 +1,163 @@
+//! Snapshots of the entries of a store for detecting changes
+//!
+//! A [`Listing`] records the name, modification time and size of every entry file of a store without
+//! decrypting anything.
+//! Clients which want to react to changes (e.g. to refresh a GUI) can keep the listing of their last poll and
+//! pass it to [`Store::changes_since()`](crate::Store::changes_since) which reports added, removed and
+//! modified entries together with the new listing for the next poll.
+//! This is cheap enough to be called every few seconds and works where filesystem watchers are unavailable.
+//!
+//! Listings are also returned by other APIs like [`Store::list_filtered()`](crate::Store::list_filtered).
+//! With the `serde` feature, they can be serialized to persist or exchange the state of a store.
+//! Wrap them into a [`wire::Versioned`](crate::wire::Versioned) envelope so that readers can detect listings
+//! produced by incompatible versions of this library.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::Store;
+//!
+//! let store = Store::from_env().unwrap();
+//! let mut listing = store.listing().unwrap();
+//! loop {
+//!     std::thread::sleep(std::time::Duration::from_secs(5));
+//!     let changes = store.changes_since(&listing).unwrap();
+//!     for name in &changes.modified {
+//!         println!("{} was modified", name);
+//!     }
+//!     listing = changes.listing;
+//! }
+//! ```
+
+use crate::{names, Result, Store, StoreFileRef};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The recorded state of a single entry file
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ListingEntry {
+    /// The time at which the file was last modified
+    pub modified: SystemTime,
+    /// The size of the encrypted file in bytes
+    pub size: u64,
+}
+
+/// The entries of a store at a point in time
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Listing {
+    root: PathBuf,
+    generated_at: SystemTime,
+    entries: BTreeMap<String, ListingEntry>,
+}
+
+impl Listing {
+    /// Record the entries of *store*
+    pub(crate) fn generate(store: &Store) -> Result<Self> {
+        Self::of(store, store.entry_files()?)
+    }
+
+    /// Record the entries of *store* which are stored in *files*
+    pub(crate) fn of(store: &Store, files: impl IntoIterator<Item = StoreFileRef>) -> Result<Self> {
+        let generated_at = SystemTime::now();
+        let mut entries = BTreeMap::new();
+        for file in files {
+            let metadata = fs::metadata(&file.path)?;
+            entries.insert(
+                store.relative_name(&file.path)?,
+                ListingEntry {
+                    modified: metadata.modified()?,
+                    size: metadata.len(),
+                },
+            );
+        }
+        Ok(Self {
+            root: store.root().to_owned(),
+            generated_at,
+            entries,
+        })
+    }
+
+    /// The root directory of the listed store
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The time at which the store was listed
+    pub fn generated_at(&self) -> SystemTime {
+        self.generated_at
+    }
+
+    /// All entries by their name
+    pub fn entries(&self) -> &BTreeMap<String, ListingEntry> {
+        &self.entries
+    }
+
+    /// The recorded state of the entry *pass_name*
+    ///
+    /// Like with [`Store::retrieve()`], the name is normalized before looking it up.
+    pub fn get(&self, pass_name: &str) -> Option<&ListingEntry> {
+        self.entries.get(&names::normalize_name(pass_name).ok()?)
+    }
+
+    /// The number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the store had no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Compare this listing against the older listing *previous*
+    ///
+    /// Entries whose modification time or size differ are reported as modified.
+    pub fn changes_since(self, previous: &Listing) -> ListingChanges {
+        let mut changes = ListingChanges {
+            added: Vec::new(),
+            removed: Vec::new(),
+            modified: Vec::new(),
+            listing: self,
+        };
+        for (name, entry) in &changes.listing.entries {
+            match previous.entries.get(name) {
+                None => changes.added.push(name.clone()),
+                Some(previous) if previous != entry => changes.modified.push(name.clone()),
+                Some(_) => {}
+            }
+        }
+        changes.removed = previous
+            .entries
+            .keys()
+            .filter(|name| !changes.listing.entries.contains_key(*name))
+            .cloned()
+            .collect();
+        changes
+    }
+}
+
+/// The differences between two listings of a store
+///
+/// All names are sorted.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ListingChanges {
+    /// Names of entries which did not exist before
+    pub added: Vec<String>,
+    /// Names of entries which no longer exist
+    pub removed: Vec<String>,
+    /// Names of entries whose files were changed
+    pub modified: Vec<String>,
+    /// The newer listing which can be used for detecting further changes
+    pub listing: Listing,
+}
+
+impl ListingChanges {
+    /// Whether no entry was added, removed or modified
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
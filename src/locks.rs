@@ -0,0 +1,59 @@
+//! Coordination of concurrent operations on a store
+//!
+//! A [`Store`](crate::Store) can be shared between threads (e.g. by a server handling requests in parallel).
+//! Operations on single entries may run concurrently since every file is replaced atomically, but they must
+//! not observe the intermediate states of operations which change the whole tree like
+//! [`Store::git_sync()`](crate::Store::git_sync) or [`Store::prune_empty_dirs()`](crate::Store::prune_empty_dirs).
+//! Files which are updated by reading, modifying and writing them again (like the manifest) additionally
+//! need to be updated by one operation at a time so that no update is lost.
+
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// The locks of a store which are shared between all of its clones
+///
+/// Comparison and hashing ignore the locks.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StoreLocks {
+    tree: Arc<RwLock<()>>,
+    metadata: Arc<Mutex<()>>,
+}
+
+impl StoreLocks {
+    /// Hold the tree of the store for an operation on single entries
+    ///
+    /// Guards must not be nested since a waiting [`exclusive()`](StoreLocks::exclusive) lock may block the
+    /// second one.
+    pub(crate) fn shared(&self) -> RwLockReadGuard<'_, ()> {
+        // the lock does not protect any data so a panic while holding it is harmless
+        self.tree
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Hold the tree of the store for an operation which changes many files at once
+    pub(crate) fn exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        self.tree
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Hold the metadata files of the store (like the manifest) while updating them
+    pub(crate) fn metadata(&self) -> MutexGuard<'_, ()> {
+        self.metadata
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl PartialEq for StoreLocks {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for StoreLocks {}
+
+impl Hash for StoreLocks {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
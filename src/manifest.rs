@@ -0,0 +1,254 @@
+//! Integrity manifests for stores which are not managed with git
+//!
+//! Stores which are synchronized by file sync services or network shares have no history which would reveal
+//! files that were silently corrupted or replaced on the way.
+//! A [`Manifest`] records the SHA-256 hash of every entry file and `.gpg-id` file of a store in the file
+//! `.libpass-manifest` at the store root.
+//! With [`Store::with_manifest_maintenance()`](crate::Store::with_manifest_maintenance), the manifest is
+//! updated whenever the store writes, moves or removes an entry or `.gpg-id` file (including changes pulled by
+//! [`Store::git_sync()`](crate::Store::git_sync)) and, if a signing key is configured, signed into
+//! `.libpass-manifest.sig`.
+//! [`Store::verify_manifest()`](crate::Store::verify_manifest) compares the store against its manifest and
+//! verifies that the manifest is signed by the configured signing key.
+//!
+//! Files which are changed without going through [`Store`](crate::Store) (e.g. by *pass* or through
+//! [`CipherFile`](crate::file_io::CipherFile) handles) are reported as modified until
+//! [`Store::update_manifest()`](crate::Store::update_manifest) is called.
+//!
+//! ## Format
+//! The manifest starts with the line `libpass-manifest 1` followed by one line `<sha256> <path>` per file,
+//! sorted by path.
+//! Paths are relative to the store root, use `/` as separator and `%`, CR and LF in them are
+//! percent-encoded.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::Store;
+//!
+//! let store = Store::from_env()
+//!     .unwrap()
+//!     .with_manifest_maintenance(true)
+//!     .with_manifest_signing_key("4837F49E22F425EA91725C428497251104B6F45F");
+//! store.update_manifest().unwrap();
+//!
+//! // later, e.g. after syncing the store to another machine
+//! let report = store.verify_manifest().unwrap();
+//! for issue in &report.issues {
+//!     eprintln!("{:?}", issue);
+//! }
+//! ```
+
+use crate::snapshot::hash_of;
+use crate::{index, walk, PassError, Result, SignatureStatus};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest file at the store root
+pub const MANIFEST_FILE: &str = ".libpass-manifest";
+
+/// Name of the detached signature of the manifest file at the store root
+pub const SIGNATURE_FILE: &str = ".libpass-manifest.sig";
+
+const HEADER: &str = "libpass-manifest 1";
+
+/// The hashes of all entry files and `.gpg-id` files of a store
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct Manifest {
+    files: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Hash all entry files (with one of *entry_extensions*) and `.gpg-id` files below *root*
+    pub(crate) fn generate(root: &Path, entry_extensions: &[&str]) -> Result<Self> {
+        let mut paths = walk::entry_files_with(root, entry_extensions)?;
+        collect_gpg_ids(root, &mut paths)?;
+        let mut manifest = Self::default();
+        for path in paths {
+            manifest
+                .files
+                .insert(relative_path(root, &path)?, hash_of(&fs::read(&path)?));
+        }
+        Ok(manifest)
+    }
+
+    /// Read the manifest of the store at *root* or return `None` if it has none
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidStoreFormat`] is returned if the manifest file is malformed.
+    pub(crate) fn load(root: &Path) -> Result<Option<Self>> {
+        let path = root.join(MANIFEST_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let invalid =
+            || PassError::InvalidStoreFormat(path.clone(), "The manifest is malformed".to_string());
+        let mut lines = content.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(invalid());
+        }
+        let mut files = BTreeMap::new();
+        for line in lines {
+            let (hash, file) = line.split_once(' ').ok_or_else(invalid)?;
+            if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            files.insert(index::unescape(file).ok_or_else(invalid)?, hash.to_string());
+        }
+        Ok(Some(Self { files }))
+    }
+
+    /// The content of the manifest file
+    pub(crate) fn serialize(&self) -> String {
+        let mut content = format!("{}\n", HEADER);
+        for (file, hash) in &self.files {
+            content.push_str(&format!("{} {}\n", hash, index::escape(file)));
+        }
+        content
+    }
+
+    /// Whether the file at *path* is recorded in manifests, i.e. it is a `.gpg-id` file or an entry file with
+    /// one of *entry_extensions*
+    pub(crate) fn covers(path: &Path, entry_extensions: &[&str]) -> bool {
+        path.file_name() == Some(".gpg-id".as_ref()) || walk::has_extension(path, entry_extensions)
+    }
+
+    /// Record the current content of the file at *path* or forget it if it no longer exists
+    pub(crate) fn update(&mut self, root: &Path, path: &Path) -> Result<()> {
+        let file = relative_path(root, path)?;
+        match fs::read(path) {
+            Ok(content) => {
+                self.files.insert(file, hash_of(&content));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.files.remove(&file);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    /// The hashes of all recorded files by their path relative to the store root
+    pub fn files(&self) -> &BTreeMap<String, String> {
+        &self.files
+    }
+
+    /// The recorded hash of the file at *path* relative to the store root (e.g. `folder/entry.gpg`)
+    pub fn hash(&self, path: &str) -> Option<&str> {
+        self.files.get(path).map(String::as_str)
+    }
+
+    /// Compare the recorded files against the files of *current*, sorted by path
+    pub(crate) fn issues(&self, current: &Manifest) -> Vec<ManifestIssue> {
+        let mut issues = Vec::new();
+        for (file, hash) in &self.files {
+            match current.files.get(file) {
+                None => issues.push(ManifestIssue::Missing { file: file.clone() }),
+                Some(current) if current != hash => {
+                    issues.push(ManifestIssue::Modified { file: file.clone() })
+                }
+                Some(_) => {}
+            }
+        }
+        issues.extend(
+            current
+                .files
+                .keys()
+                .filter(|file| !self.files.contains_key(*file))
+                .map(|file| ManifestIssue::Unlisted { file: file.clone() }),
+        );
+        issues.sort_unstable_by(|a, b| a.file().cmp(b.file()));
+        issues
+    }
+}
+
+/// A difference between a store and its manifest
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ManifestIssue {
+    /// The content of a file does not match its recorded hash
+    Modified {
+        /// The path of the file relative to the store root
+        file: String,
+    },
+    /// A recorded file does not exist anymore
+    Missing {
+        /// The path of the file relative to the store root
+        file: String,
+    },
+    /// A file exists which is not recorded in the manifest
+    Unlisted {
+        /// The path of the file relative to the store root
+        file: String,
+    },
+}
+
+impl ManifestIssue {
+    /// The path of the affected file relative to the store root
+    pub fn file(&self) -> &str {
+        match self {
+            Self::Modified { file } | Self::Missing { file } | Self::Unlisted { file } => file,
+        }
+    }
+}
+
+/// The result of [`Store::verify_manifest()`](crate::Store::verify_manifest)
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ManifestReport {
+    /// The status of the signature of the manifest
+    pub signature: SignatureStatus,
+    /// Whether the manifest carries a valid signature by the
+    /// [manifest signing key](crate::Store::with_manifest_signing_key) of the store
+    pub trusted_signature: bool,
+    /// All differences between the store and its manifest, sorted by path
+    pub issues: Vec<ManifestIssue>,
+}
+
+impl ManifestReport {
+    /// Whether the store matches its manifest and the manifest is validly signed by the manifest signing key
+    /// of the store
+    ///
+    /// Unsigned manifests and manifests of stores without a signing key are never intact because anyone who can
+    /// change the store could also have rewritten them.
+    pub fn is_intact(&self) -> bool {
+        self.issues.is_empty() && self.trusted_signature
+    }
+}
+
+/// Collect the paths of all `.gpg-id` files below *dir*, skipping git repositories
+fn collect_gpg_ids(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if entry.file_name() != ".git" {
+                collect_gpg_ids(&entry.path(), paths)?;
+            }
+        } else if file_type.is_file() && entry.file_name() == ".gpg-id" {
+            paths.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// The path of the file at *path* relative to *root* with `/` as separator
+fn relative_path(root: &Path, path: &Path) -> Result<String> {
+    let relative = path.strip_prefix(root).map_err(|_| {
+        PassError::InvalidStoreFormat(
+            path.to_owned(),
+            "Path is not inside password store".to_string(),
+        )
+    })?;
+    Ok(relative
+        .components()
+        .map(|component| {
+            component
+                .as_os_str()
+                .to_str()
+                .ok_or_else(|| PassError::PathDecodingError(path.to_owned()))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .join("/"))
+}
@@ -0,0 +1,242 @@
+//! Merging of two diverged copies of a store
+//!
+//! Stores which are synchronized by other means than git (e.g. Syncthing or a shared drive) can diverge when
+//! entries are changed on two machines.
+//! [`Store::merge_from()`](crate::Store::merge_from) brings the entries of another copy into a store and
+//! resolves entries which were changed differently according to a [`MergeStrategy`].
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::merge::{MergeConflict, MergeResolution};
+//! use libpass::Store;
+//!
+//! let store = Store::from_env().unwrap();
+//! let other = Store::open("/mnt/laptop/.password-store").unwrap();
+//!
+//! // resolve all conflicts in the same way
+//! store.merge_from(&other, MergeResolution::KeepBoth).unwrap();
+//!
+//! // or decide for every conflict
+//! store
+//!     .merge_from(&other, |conflict: &MergeConflict| match conflict.pass_name.starts_with("work/") {
+//!         true => MergeResolution::KeepTheirs,
+//!         false => MergeResolution::KeepNewer,
+//!     })
+//!     .unwrap();
+//! ```
+
+use crate::secure_mem::{self, LockedRegion};
+use crate::telemetry::debug;
+use crate::{OverwritePolicy, Result, Store, StoreFileRef};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// How an entry which differs between both stores is resolved
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum MergeResolution {
+    /// Keep the version of this store
+    KeepOurs,
+    /// Replace the version of this store with the one of the other store
+    KeepTheirs,
+    /// Keep whichever version was modified more recently
+    KeepNewer,
+    /// Keep the version of this store and add the version of the other store next to it (named like
+    /// `name (2)`)
+    KeepBoth,
+    /// Combine all lines of both versions and encrypt the result again
+    ///
+    /// The lines of this store come first (so its password is kept) followed by all lines of the other
+    /// version which this version does not contain.
+    MergeUnion,
+}
+
+/// An entry whose content differs between both stores
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MergeConflict {
+    /// Name of the conflicting entry
+    pub pass_name: String,
+    /// The time at which the version of this store was last modified
+    pub ours_modified: SystemTime,
+    /// The time at which the version of the other store was last modified
+    pub theirs_modified: SystemTime,
+}
+
+/// Decides how conflicts are resolved by [`Store::merge_from()`](crate::Store::merge_from)
+///
+/// A [`MergeResolution`] resolves all conflicts in the same way and any closure taking a [`MergeConflict`]
+/// can be used to decide for every conflict.
+pub trait MergeStrategy {
+    /// Decide how *conflict* is resolved
+    fn resolve(&mut self, conflict: &MergeConflict) -> MergeResolution;
+}
+
+impl MergeStrategy for MergeResolution {
+    fn resolve(&mut self, _conflict: &MergeConflict) -> MergeResolution {
+        *self
+    }
+}
+
+impl<F> MergeStrategy for F
+where
+    F: FnMut(&MergeConflict) -> MergeResolution,
+{
+    fn resolve(&mut self, conflict: &MergeConflict) -> MergeResolution {
+        self(conflict)
+    }
+}
+
+/// Outcome of a merge
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct MergeReport {
+    /// Entries which only existed in the other store and were copied, sorted by name
+    pub copied: Vec<String>,
+    /// All conflicts sorted by name and how they were resolved
+    ///
+    /// [`KeepNewer`](MergeResolution::KeepNewer) is reported as the version which was actually kept.
+    pub resolved: Vec<(String, MergeResolution)>,
+}
+
+pub(crate) fn merge(
+    store: &Store,
+    other: &Store,
+    strategy: &mut impl MergeStrategy,
+) -> Result<MergeReport> {
+    let named_files = |store: &Store| -> Result<BTreeMap<String, StoreFileRef>> {
+        store
+            .files()?
+            .into_iter()
+            .map(|file| Ok((store.relative_name(&file.path)?, file)))
+            .collect()
+    };
+    let mut ours = named_files(store)?;
+
+    let mut report = MergeReport::default();
+    for (pass_name, theirs) in named_files(other)? {
+        let their_ciphertext = fs::read(&theirs.path)?;
+        let ours = match ours.remove(&pass_name) {
+            Some(ours) => ours,
+            None => {
+                debug!("Copying {} which only exists in the other store", pass_name);
+                write_theirs(
+                    store,
+                    other,
+                    &theirs,
+                    store.target_path(&pass_name, OverwritePolicy::Error)?,
+                    &their_ciphertext,
+                )?;
+                report.copied.push(pass_name);
+                continue;
+            }
+        };
+        if fs::read(&ours.path)? == their_ciphertext {
+            continue;
+        }
+        let mut our_plaintext = store.read_plaintext(&ours)?;
+        let mut their_plaintext = match other.read_plaintext(&theirs) {
+            Ok(plaintext) => plaintext,
+            Err(e) => {
+                secure_mem::release(&mut our_plaintext, &mut LockedRegion::default());
+                return Err(e);
+            }
+        };
+
+        let resolve = || -> Result<Option<(String, MergeResolution)>> {
+            if our_plaintext == their_plaintext {
+                return Ok(None);
+            }
+            let conflict = MergeConflict {
+                pass_name,
+                ours_modified: fs::metadata(&ours.path)?.modified()?,
+                theirs_modified: fs::metadata(&theirs.path)?.modified()?,
+            };
+            let resolution = match strategy.resolve(&conflict) {
+                MergeResolution::KeepNewer if conflict.theirs_modified > conflict.ours_modified => {
+                    MergeResolution::KeepTheirs
+                }
+                MergeResolution::KeepNewer => MergeResolution::KeepOurs,
+                resolution => resolution,
+            };
+            debug!("Resolving {} with {:?}", conflict.pass_name, resolution);
+            match resolution {
+                MergeResolution::KeepOurs | MergeResolution::KeepNewer => {}
+                MergeResolution::KeepTheirs => {
+                    write_theirs(store, other, &theirs, ours.path, &their_ciphertext)?;
+                }
+                MergeResolution::KeepBoth => {
+                    write_theirs(
+                        store,
+                        other,
+                        &theirs,
+                        store.target_path(&conflict.pass_name, OverwritePolicy::KeepBoth)?,
+                        &their_ciphertext,
+                    )?;
+                }
+                MergeResolution::MergeUnion => {
+                    store.write_new(ours.path, union_lines(&our_plaintext, &their_plaintext))?;
+                }
+            }
+            Ok(Some((conflict.pass_name, resolution)))
+        };
+        let resolved = resolve();
+        // both versions are decrypted for every entry whose files differ
+        for plaintext in [&mut our_plaintext, &mut their_plaintext] {
+            secure_mem::release(plaintext, &mut LockedRegion::default());
+        }
+        report.resolved.extend(resolved?);
+    }
+    Ok(report)
+}
+
+/// Write the entry *theirs* of *other* to *path* in *store*
+///
+/// The ciphertext is copied as it is if *path* has the same recipients as *theirs* and encrypted again for
+/// the recipients of *path* otherwise.
+fn write_theirs(
+    store: &Store,
+    other: &Store,
+    theirs: &StoreFileRef,
+    path: PathBuf,
+    ciphertext: &[u8],
+) -> Result<StoreFileRef> {
+    let mut our_recipients = store.recipients_of(&path)?;
+    let mut their_recipients = other.recipients_of(&theirs.path)?;
+    our_recipients.sort_unstable();
+    their_recipients.sort_unstable();
+    if our_recipients == their_recipients {
+        return store.write_ciphertext(path, ciphertext);
+    }
+
+    debug!(
+        "Encrypting {} again because the recipients differ",
+        path.display()
+    );
+    store.write_new(path, other.read_plaintext(theirs)?)
+}
+
+/// Combine *ours* with all lines of *theirs* which *ours* does not contain
+///
+/// The result is allocated once with its final capacity so that no copy of the plaintext is left behind by
+/// growing it.
+pub(crate) fn union_lines(ours: &[u8], theirs: &[u8]) -> Vec<u8> {
+    fn trim(line: &[u8]) -> &[u8] {
+        line.strip_suffix(b"\n").unwrap_or(line)
+    }
+    let our_lines = ours
+        .split_inclusive(|b| *b == b'\n')
+        .map(trim)
+        .collect::<Vec<_>>();
+
+    let mut merged = Vec::with_capacity(ours.len() + theirs.len() + 1);
+    merged.extend_from_slice(ours);
+    for line in theirs.split_inclusive(|b| *b == b'\n') {
+        if !our_lines.contains(&trim(line)) {
+            if !merged.is_empty() && !merged.ends_with(b"\n") {
+                merged.push(b'\n');
+            }
+            merged.extend_from_slice(line);
+        }
+    }
+    merged
+}
@@ -0,0 +1,106 @@
+//! Health metrics of a store for monitoring
+//!
+//! [`Store::metrics()`](crate::Store::metrics) collects [`StoreMetrics`] without decrypting any entry so that
+//! it can be polled frequently, e.g. for shared team stores.
+//! The metrics can be used directly or rendered in the Prometheus text exposition format with
+//! [`StoreMetrics::render_prometheus()`].
+//! With the `http` feature, [`HttpServer`](crate::http::HttpServer) also serves them at `/metrics`.
+//!
+//! ## Example
+//! ```
+//! use libpass::Store;
+//! use std::time::Duration;
+//!
+//! let store = Store::open("tests/simple").unwrap();
+//! let metrics = store.metrics(Some(Duration::from_secs(365 * 24 * 60 * 60))).unwrap();
+//! assert!(metrics.render_prometheus().contains("pass_entries_total 7\n"));
+//! ```
+
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A snapshot of the health of a store
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct StoreMetrics {
+    /// The number of entries in the store
+    pub entries_total: u64,
+    /// The number of entries which were not modified for longer than the maximal age passed to
+    /// [`Store::metrics()`](crate::Store::metrics)
+    pub expired_entries: u64,
+    /// The number of decryptions through this store (and its clones) which failed
+    pub decryption_errors: u64,
+    /// The time since the store was last fetched from its git remote
+    ///
+    /// `None` if the store is not a git repository or was never fetched.
+    pub last_sync_age: Option<Duration>,
+}
+
+impl StoreMetrics {
+    /// Render the metrics in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let mut output = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            output.push_str(&format!(
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+            ));
+        };
+        metric(
+            "pass_entries_total",
+            "gauge",
+            "Number of entries in the store",
+            self.entries_total.to_string(),
+        );
+        metric(
+            "pass_expired_entries",
+            "gauge",
+            "Number of entries which exceed the maximal age",
+            self.expired_entries.to_string(),
+        );
+        metric(
+            "pass_decryption_errors_total",
+            "counter",
+            "Number of failed decryptions",
+            self.decryption_errors.to_string(),
+        );
+        if let Some(age) = self.last_sync_age {
+            metric(
+                "pass_last_sync_age_seconds",
+                "gauge",
+                "Time since the store was last fetched from its git remote",
+                age.as_secs_f64().to_string(),
+            );
+        }
+        output
+    }
+}
+
+/// A counter of failed decryptions
+///
+/// Clones share their count so that failures of all clones of a store are counted together.
+/// The count is runtime state rather than configuration and therefore does not affect equality.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ErrorCounter(Arc<AtomicU64>);
+
+impl ErrorCounter {
+    pub(crate) fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl PartialEq for ErrorCounter {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for ErrorCounter {}
+
+impl Hash for ErrorCounter {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
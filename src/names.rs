@@ -0,0 +1,436 @@
+//! Mapping between entry names and paths inside of a store
+//!
+//! Entry names (*pass names*) are `/`-separated paths relative to the store root without the `.gpg` suffix
+//! of files.
+//! All operations of this library map names to paths via these functions so that applications can reason
+//! about names in the same way, e.g. to detect two spellings of the same entry before using them.
+//!
+//! Names are Unicode NFC normalized so that stores which are shared between macOS (which decomposes file
+//! names into NFD) and other platforms reference the same entries by the same names.
+//!
+//! [`PassName`] holds a name which is known to be normalized so that invalid names are rejected when they are
+//! constructed instead of when they are used.
+//! Stores accept it wherever they retrieve, insert or remove entries by name (e.g. [`Store::retrieve()`] and
+//! [`Store::insert()`]) and it dereferences to `str` so that it can be passed to all other functions which
+//! take names as well.
+//!
+//! ## Example
+//! ```
+//! use libpass::names::{self, PassName};
+//!
+//! assert_eq!(names::normalize_name("/web//./github.com/").unwrap(), "web/github.com");
+//! assert!(names::normalize_name("web/../../etc/passwd").is_err());
+//!
+//! let web = PassName::new("web").unwrap();
+//! let github = web.join("github.com").unwrap();
+//! assert_eq!(github, "web/github.com");
+//! assert!(github.starts_with(&web));
+//! assert_eq!(github.parent(), Some(web));
+//! ```
+//!
+//! [`Store::retrieve()`]: crate::Store::retrieve
+//! [`Store::insert()`]: crate::Store::insert
+
+use crate::{utils, walk, PassError, Result};
+use std::borrow::Borrow;
+use std::fmt;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// Which entry a name refers to if both a file and a directory with that name exist
+///
+/// Configure this via [`Store::with_ambiguity()`](crate::Store::with_ambiguity).
+/// Names ending with `/` always refer to directories.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum Prefer {
+    /// Refer to the file like `pass show` does
+    File,
+    /// Refer to the directory
+    Directory,
+    /// Fail with [`PassError::AmbiguousPassName`]
+    #[default]
+    Error,
+}
+
+/// Bring *pass_name* into its canonical form
+///
+/// The name is brought into Unicode normalization form C (NFC) and leading, trailing and repeated `/` as well
+/// as `.` segments are removed.
+/// The empty name refers to the store root.
+///
+/// ## Errors
+/// [`PassError::InvalidPassName`] is returned for names which could reference something outside of the
+/// store, i.e. names with `..` segments or segments which the platform interprets specially (like drive
+/// prefixes on windows), for names inside of git repositories (i.e. with a `.git` segment) and for names
+/// containing control characters (including NUL bytes).
+pub fn normalize_name(pass_name: &str) -> Result<String> {
+    let invalid =
+        |reason: &str| PassError::InvalidPassName(pass_name.to_string(), reason.to_string());
+    if pass_name.chars().any(char::is_control) {
+        return Err(invalid("Name must not contain control characters"));
+    }
+
+    let composed = pass_name.nfc().collect::<String>();
+    let mut segments = Vec::new();
+    for segment in composed.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                return Err(invalid(
+                    "Name must not contain relative components like '..'",
+                ))
+            }
+            // compared case-insensitively because of case-insensitive filesystems
+            segment if segment.eq_ignore_ascii_case(".git") => {
+                return Err(invalid(
+                    "Name must not reference the internals of a git repository",
+                ))
+            }
+            segment => {
+                let mut components = Path::new(segment).components();
+                match (components.next(), components.next()) {
+                    (Some(Component::Normal(_)), None) => segments.push(segment),
+                    _ => {
+                        return Err(invalid(
+                            "Name contains a segment that is not a plain file name",
+                        ))
+                    }
+                }
+            }
+        }
+    }
+    Ok(segments.join("/"))
+}
+
+/// The path at which a directory named *pass_name* is located inside the store at *root*
+///
+/// See [`normalize_name()`] for which names are accepted.
+/// If the directory only exists with a decomposed (NFD) name, e.g. because it was created on macOS, the path
+/// of that directory is returned.
+pub fn dir_path(root: &Path, pass_name: &str) -> Result<PathBuf> {
+    let pass_name = normalize_name(pass_name)?;
+    if pass_name.is_empty() {
+        Ok(root.to_owned())
+    } else {
+        Ok(existing_spelling(root, &pass_name))
+    }
+}
+
+/// The path at which the file storing the entry *pass_name* is located inside the store at *root*
+///
+/// Like with [`dir_path()`], the path of a file with a decomposed (NFD) name is returned if only that exists.
+///
+/// ## Errors
+/// In addition to the errors of [`normalize_name()`], [`PassError::InvalidPassName`] is returned if
+/// *pass_name* refers to the store root or ends with `/` which marks it as a directory.
+pub fn file_path(root: &Path, pass_name: &str) -> Result<PathBuf> {
+    let normalized = normalize_name(pass_name)?;
+    if normalized.is_empty() || pass_name.ends_with('/') {
+        return Err(PassError::InvalidPassName(
+            pass_name.to_string(),
+            "Name does not reference a file".to_string(),
+        ));
+    }
+    Ok(existing_spelling(root, &(normalized + ".gpg")))
+}
+
+/// Join the NFC normalized *relative* path to *root*, preferring its NFD spelling if only that exists
+fn existing_spelling(root: &Path, relative: &str) -> PathBuf {
+    let composed = root.join(relative);
+    if composed.exists() {
+        return composed;
+    }
+    let decomposed = root.join(relative.nfd().collect::<String>());
+    if decomposed != composed && decomposed.exists() {
+        decomposed
+    } else {
+        composed
+    }
+}
+
+/// The name of the entry or directory stored at *path* inside the store at *root*
+///
+/// This is the inverse of [`file_path()`] and [`dir_path()`] for normalized names and the returned name is
+/// NFC normalized as well.
+///
+/// ## Errors
+/// [`PassError::InvalidStoreFormat`] is returned if *path* is not inside of *root*.
+pub fn path_to_name(root: &Path, path: &Path) -> Result<String> {
+    let relative = path.strip_prefix(root).map_err(|_| {
+        PassError::InvalidStoreFormat(
+            path.to_owned(),
+            "Path is not inside password store".to_string(),
+        )
+    })?;
+    let relative = utils::path2str(relative)?;
+    Ok(relative
+        .strip_suffix(".gpg")
+        .unwrap_or(relative)
+        .nfc()
+        .collect())
+}
+
+/// A normalized name of an entry or directory
+///
+/// The empty name refers to the store root.
+/// Prefixes are matched segment-wise so that `web` is a prefix of `web/github.com` but not of `webmail`.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct PassName(String);
+
+impl PassName {
+    /// Normalize *pass_name* into a name
+    ///
+    /// ## Errors
+    /// See [`normalize_name()`].
+    pub fn new(pass_name: &str) -> Result<Self> {
+        normalize_name(pass_name).map(Self)
+    }
+
+    /// The name of the store root
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// Whether this is the name of the store root
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The name as string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The segments of the name, i.e. the names of the directories leading to it followed by its last
+    /// segment
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.0.split('/').filter(|segment| !segment.is_empty())
+    }
+
+    /// The last segment of the name or `None` for the store root
+    pub fn file_name(&self) -> Option<&str> {
+        self.segments().last()
+    }
+
+    /// The name of the directory containing this name or `None` for the store root
+    pub fn parent(&self) -> Option<Self> {
+        if self.is_root() {
+            return None;
+        }
+        Some(match self.0.rsplit_once('/') {
+            Some((parent, _)) => Self(parent.to_string()),
+            None => Self::root(),
+        })
+    }
+
+    /// Append the relative name *relative* (which may consist of multiple segments) to this name
+    ///
+    /// ## Errors
+    /// See [`normalize_name()`]; `..` segments are rejected so that the joined name is always below this
+    /// one.
+    pub fn join(&self, relative: &str) -> Result<Self> {
+        let relative = normalize_name(relative)?;
+        Ok(match (self.is_root(), relative.is_empty()) {
+            (_, true) => self.clone(),
+            (true, false) => Self(relative),
+            (false, false) => Self(format!("{}/{}", self.0, relative)),
+        })
+    }
+
+    /// Whether *prefix* is this name or one of the directories leading to it
+    ///
+    /// The store root is a prefix of every name.
+    pub fn starts_with(&self, prefix: &PassName) -> bool {
+        self.strip_prefix(prefix).is_some()
+    }
+
+    /// This name relative to *prefix* or `None` if *prefix* is not a [prefix](PassName::starts_with) of it
+    pub fn strip_prefix(&self, prefix: &PassName) -> Option<Self> {
+        if prefix.is_root() {
+            return Some(self.clone());
+        }
+        let rest = self.0.strip_prefix(prefix.as_str())?;
+        match rest.strip_prefix('/') {
+            Some(rest) => Some(Self(rest.to_string())),
+            None if rest.is_empty() => Some(Self::root()),
+            None => None,
+        }
+    }
+
+    /// Convert the name into a `String`
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for PassName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for PassName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Allows looking up names in maps and sets by plain (already normalized) strings
+impl Borrow<str> for PassName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for PassName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for PassName {
+    type Err = PassError;
+
+    fn from_str(pass_name: &str) -> Result<Self> {
+        Self::new(pass_name)
+    }
+}
+
+impl TryFrom<&str> for PassName {
+    type Error = PassError;
+
+    fn try_from(pass_name: &str) -> Result<Self> {
+        Self::new(pass_name)
+    }
+}
+
+impl TryFrom<String> for PassName {
+    type Error = PassError;
+
+    fn try_from(pass_name: String) -> Result<Self> {
+        Self::new(&pass_name)
+    }
+}
+
+impl From<PassName> for String {
+    fn from(name: PassName) -> Self {
+        name.0
+    }
+}
+
+impl PartialEq<str> for PassName {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for PassName {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+/// A lazy iterator over the names of all entries of a store
+///
+/// Get an instance of this by calling [`Store::names()`](crate::Store::names).
+/// Directories are only read once the iterator reaches them and the only allocation per entry is the
+/// returned name itself.
+/// Names are yielded in unspecified order; errors (e.g. unreadable directories) are yielded in place of the
+/// entries which could not be read.
+#[derive(Debug)]
+pub struct Names {
+    /// The directories which are currently being read together with their name prefix (ending with `/`)
+    stack: Vec<(String, fs::ReadDir)>,
+    /// The file extensions of entry files
+    extensions: &'static [&'static str],
+}
+
+impl Names {
+    /// Iterate over the names of all entries below *root* which are stored in files with one of the given
+    /// *extensions*
+    pub(crate) fn new(root: &Path, extensions: &'static [&'static str]) -> Result<Self> {
+        Ok(Self {
+            stack: vec![(String::new(), fs::read_dir(root)?)],
+            extensions,
+        })
+    }
+
+    /// The name of the entry *file_name* inside the directory with the name *prefix*
+    fn name(prefix: &str, file_name: &str) -> String {
+        let mut name = String::with_capacity(prefix.len() + file_name.len());
+        name.push_str(prefix);
+        name.push_str(file_name);
+        if is_nfc(&name) {
+            name
+        } else {
+            name.nfc().collect()
+        }
+    }
+
+    /// Handle *entry* of the directory with the name *prefix* and return its name if it is an entry file
+    fn visit(&mut self, prefix: &str, entry: fs::DirEntry) -> Result<Option<String>> {
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        let file_name = entry.file_name();
+        let file_name = file_name
+            .to_str()
+            .ok_or_else(|| PassError::PathDecodingError(path.clone()))?;
+        if file_type.is_dir() {
+            // git repositories (including those of submodules) are not part of the entry hierarchy
+            if file_name != ".git" {
+                let mut prefix = Self::name(prefix, file_name);
+                prefix.push('/');
+                self.stack.push((prefix, fs::read_dir(&path)?));
+            }
+            Ok(None)
+        } else if file_type.is_file() {
+            Ok(file_name
+                .rsplit_once('.')
+                .filter(|_| walk::has_extension(&path, self.extensions))
+                .map(|(stem, _)| Self::name(prefix, stem)))
+        } else {
+            Err(PassError::InvalidStoreFormat(
+                path,
+                "File is neither a string nor directory but pass stores can only contain those types of files"
+                    .to_string(),
+            ))
+        }
+    }
+}
+
+impl Iterator for Names {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let depth = self.stack.len().checked_sub(1)?;
+            let entry = match self.stack[depth].1.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(e)) => return Some(Err(e.into())),
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            // the prefix is moved out while visiting so that it does not have to be copied
+            let prefix = std::mem::take(&mut self.stack[depth].0);
+            let visited = self.visit(&prefix, entry);
+            self.stack[depth].0 = prefix;
+            match visited {
+                Ok(Some(name)) => return Some(Ok(name)),
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
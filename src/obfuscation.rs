@@ -0,0 +1,441 @@
+//! Stores whose entry names are not visible on disk
+//!
+//! The names of entries leak which services a user has accounts at to everyone who can see the files of a
+//! store, e.g. the provider of a file sync service.
+//! An [`ObfuscatedStore`] keeps every entry in a file with a random name like
+//! `3f1c0a9e5b7d42e8a6c1f0b2d4e6a8c0.gpg` at the store root and maps these to the logical names in the
+//! encrypted file `.libpass-names`.
+//! Apart from that, it offers the same operations as [`Store`] so that applications only have to change how
+//! they open the store.
+//!
+//! The name map is encrypted for the recipients of the `.gpg-id` file at the store root and so are all
+//! entries since they are stored there as well.
+//! Tools which are unaware of the obfuscation (including *pass*) only see the random names.
+//!
+//! ## Format
+//! The decrypted name map starts with the line `libpass-names 1` followed by one line `<id> <name>` per entry
+//! where `%`, CR and LF in the name are percent-encoded.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::Store;
+//!
+//! let mut store = Store::from_env().unwrap().obfuscated().unwrap();
+//! store.insert("web/github.com", "hunter2").unwrap();
+//! let file = store.retrieve_file("web/github.com").unwrap();
+//! assert_eq!(file.plain_io_ro().unwrap().as_ref(), b"hunter2\n");
+//! ```
+
+use crate::file_io::RwPlainFile;
+use crate::generate::PasswordGenerator;
+use crate::secure::SecretString;
+use crate::telemetry::warning;
+use crate::{index, names, InsertSource, OverwritePolicy, PassError, Result, Store, StoreFileRef};
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Name of the encrypted file at the store root which maps random file names to entry names
+pub const NAME_MAP_FILE: &str = ".libpass-names";
+
+const HEADER: &str = "libpass-names 1";
+
+/// A store whose entries are kept in files with random names
+///
+/// Get an instance of this by calling [`Store::obfuscated()`].
+/// Operations which add, rename or remove entries update the encrypted name map immediately and therefore
+/// need mutable access.
+/// Clones share their name map so that they never disagree about the names of entries.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ObfuscatedStore {
+    store: Store,
+    ids: NameMap,
+}
+
+/// The random ids of all entries by their name
+///
+/// Stores are compared by the content of their name maps.
+#[derive(Debug, Clone, Default)]
+struct NameMap(Arc<Mutex<BTreeMap<String, String>>>);
+
+impl NameMap {
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, String>> {
+        // the map is only changed together with saving it so a panic while holding the lock is harmless
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl PartialEq for NameMap {
+    fn eq(&self, other: &Self) -> bool {
+        // never hold both locks at once so that concurrent comparisons can not deadlock
+        Arc::ptr_eq(&self.0, &other.0) || {
+            let ids = self.lock().clone();
+            ids == *other.lock()
+        }
+    }
+}
+
+impl Eq for NameMap {}
+
+impl Hash for NameMap {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.lock().hash(state);
+    }
+}
+
+impl ObfuscatedStore {
+    /// Open the name map of *store* or start with an empty one if the store has none yet
+    pub(crate) fn open(store: Store) -> Result<Self> {
+        let file = StoreFileRef {
+            path: store.root().join(NAME_MAP_FILE),
+            root: store.root().to_owned(),
+        };
+        if !file.path.exists() {
+            return Ok(Self {
+                store,
+                ids: NameMap::default(),
+            });
+        }
+        let plaintext = store.read_plaintext(&file)?;
+        let invalid =
+            || PassError::InvalidStoreFormat(file.path.clone(), "The name map is malformed".into());
+        let content = String::from_utf8(plaintext).map_err(|_| invalid())?;
+        let mut lines = content.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(invalid());
+        }
+        let mut ids = BTreeMap::new();
+        for line in lines {
+            let (id, name) = line.split_once(' ').ok_or_else(invalid)?;
+            if !is_id(id) {
+                return Err(invalid());
+            }
+            ids.insert(index::unescape(name).ok_or_else(invalid)?, id.to_string());
+        }
+        Ok(Self {
+            store,
+            ids: NameMap(Arc::new(Mutex::new(ids))),
+        })
+    }
+
+    /// The underlying store which only knows the random names of the entries
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// The root directory of the underlying store
+    pub fn root(&self) -> &Path {
+        self.store.root()
+    }
+
+    /// The names of all entries, sorted
+    pub fn names(&self) -> Vec<String> {
+        self.ids.lock().keys().cloned().collect()
+    }
+
+    /// Whether an entry named *pass_name* exists
+    pub fn contains(&self, pass_name: &str) -> bool {
+        names::normalize_name(pass_name).is_ok_and(|name| self.ids.lock().contains_key(&name))
+    }
+
+    /// The logical name of the entry stored in *file* or `None` if it is not part of the name map
+    pub fn name_of(&self, file: &StoreFileRef) -> Option<String> {
+        let id = file.path.file_stem()?.to_str()?;
+        self.ids
+            .lock()
+            .iter()
+            .find(|(_, candidate)| *candidate == id)
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Retrieve the file of the entry named *pass_name*
+    ///
+    /// Since there are no directories, names only ever refer to entries.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if there is no entry named *pass_name*.
+    pub fn retrieve_file(&self, pass_name: &str) -> Result<StoreFileRef> {
+        let id = id_of(&self.ids.lock(), &names::normalize_name(pass_name)?)?;
+        Ok(StoreFileRef {
+            path: self.id_path(&id),
+            root: self.root().to_owned(),
+        })
+    }
+
+    /// Get a read-write IO handle to the plaintext content of *file*
+    ///
+    /// See [`Store::plain_io_rw()`].
+    pub fn plain_io_rw(&self, file: &StoreFileRef) -> Result<RwPlainFile> {
+        self.store.plain_io_rw(file)
+    }
+
+    /// Insert a new entry named *pass_name*
+    ///
+    /// See [`Store::insert()`].
+    pub fn insert<'a>(
+        &mut self,
+        pass_name: &str,
+        content: impl Into<InsertSource<'a>>,
+    ) -> Result<StoreFileRef> {
+        self.insert_with(pass_name, content, OverwritePolicy::Error)
+    }
+
+    /// Insert a new entry named *pass_name* and handle an existing entry according to *policy*
+    ///
+    /// See [`Store::insert_with()`].
+    /// If the name map can not be saved, a newly created entry file is removed again.
+    pub fn insert_with<'a>(
+        &mut self,
+        pass_name: &str,
+        content: impl Into<InsertSource<'a>>,
+        policy: OverwritePolicy,
+    ) -> Result<StoreFileRef> {
+        let mut ids = self.ids.lock();
+        let name = target_name(&ids, pass_name, policy)?;
+        let (id, is_new) = match id_of(&ids, &name) {
+            Ok(id) => (id, false),
+            Err(_) => (self.new_id(&ids)?, true),
+        };
+        let file = self
+            .store
+            .insert_with(&id, content, OverwritePolicy::Overwrite)?;
+        self.add_name(&mut ids, name, id, is_new)?;
+        Ok(file)
+    }
+
+    /// Generate a password and store it in a new entry named *pass_name*
+    ///
+    /// See [`Store::generate()`].
+    pub fn generate(
+        &mut self,
+        pass_name: &str,
+        generator: &PasswordGenerator,
+        policy: OverwritePolicy,
+    ) -> Result<(StoreFileRef, SecretString)> {
+        let password = generator.generate()?;
+        let file = self.insert_with(
+            pass_name,
+            InsertSource::SingleLine(SecretString::new(password.expose())),
+            policy,
+        )?;
+        Ok((file, password))
+    }
+
+    /// Copy the entry *from* to a new entry named *to*
+    ///
+    /// See [`Store::copy()`].
+    pub fn copy(&mut self, from: &str, to: &str, policy: OverwritePolicy) -> Result<StoreFileRef> {
+        let mut ids = self.ids.lock();
+        let source = id_of(&ids, &names::normalize_name(from)?)?;
+        let name = target_name(&ids, to, policy)?;
+        let (id, is_new) = match id_of(&ids, &name) {
+            Ok(id) => (id, false),
+            Err(_) => (self.new_id(&ids)?, true),
+        };
+        let file = self.store.copy(&source, &id, OverwritePolicy::Overwrite)?;
+        self.add_name(&mut ids, name, id, is_new)?;
+        Ok(file)
+    }
+
+    /// Rename the entry *from* to *to* without touching its encrypted file
+    ///
+    /// An existing entry named *to* is handled according to *policy*.
+    pub fn rename(&mut self, from: &str, to: &str, policy: OverwritePolicy) -> Result<()> {
+        let mut ids = self.ids.lock();
+        let from = names::normalize_name(from)?;
+        let id = id_of(&ids, &from)?;
+        let to = target_name(&ids, to, policy)?;
+        if to == from {
+            return Ok(());
+        }
+        let previous = ids.clone();
+        let replaced = ids.insert(to, id);
+        ids.remove(&from);
+        if let Err(e) = self.save(&ids) {
+            *ids = previous;
+            return Err(e);
+        }
+        if let Some(replaced) = replaced {
+            self.store.remove(&replaced)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the entry named *pass_name*
+    ///
+    /// See [`Store::remove()`].
+    pub fn remove(&mut self, pass_name: &str) -> Result<()> {
+        let mut ids = self.ids.lock();
+        let name = names::normalize_name(pass_name)?;
+        let id = ids
+            .remove(&name)
+            .ok_or_else(|| PassError::EntryNotFound(name.clone()))?;
+        if let Err(e) = self.save(&ids) {
+            ids.insert(name, id);
+            return Err(e);
+        }
+        self.store.remove(&id)
+    }
+
+    /// Move all entries of the underlying store which are not part of the name map yet into files with random
+    /// names and return their names
+    ///
+    /// Entries are moved without decrypting them so they stay encrypted for the recipients they were
+    /// encrypted for, even if these differ from the recipients at the store root.
+    /// The name map is saved before any entry is moved so that no name is lost if moving is interrupted;
+    /// calling this again afterwards moves the remaining entries.
+    /// Directories which are empty afterwards are removed.
+    pub fn obfuscate_existing(&mut self) -> Result<Vec<String>> {
+        let mut ids = self.ids.lock();
+        let previous = ids.clone();
+        let mut pending = Vec::new();
+        for file in self.store.files()? {
+            let name = self.store.relative_name(&file.path)?;
+            if ids.values().any(|id| *id == name) {
+                continue;
+            }
+            let id = match ids.get(&name) {
+                // a previous call was interrupted before moving this entry
+                Some(id) if !self.id_path(id).exists() => id.clone(),
+                Some(_) => continue,
+                None => {
+                    let id = self.new_id(&ids)?;
+                    ids.insert(name.clone(), id.clone());
+                    id
+                }
+            };
+            pending.push((file, name, id));
+        }
+        if pending.is_empty() {
+            return Ok(Vec::new());
+        }
+        if *ids != previous {
+            if let Err(e) = self.save(&ids) {
+                *ids = previous;
+                return Err(e);
+            }
+        }
+
+        let mut moved = Vec::with_capacity(pending.len());
+        for (file, name, id) in pending {
+            fs::rename(&file.path, self.id_path(&id))?;
+            self.store
+                .track_all_in_manifest([&file.path, &self.id_path(&id)])?;
+            moved.push(name);
+            // remove directories which only contained the moved entry
+            let mut dir = file.path.parent();
+            while let Some(parent) = dir.filter(|dir| *dir != self.root()) {
+                if fs::remove_dir(parent).is_err() {
+                    break;
+                }
+                dir = parent.parent();
+            }
+        }
+        Ok(moved)
+    }
+
+    /// Map *name* to the entry file *id* in *ids* and save the name map
+    ///
+    /// If saving fails, the map is left unchanged and a *new* entry file is removed again so that no file
+    /// without a name remains.
+    fn add_name(
+        &self,
+        ids: &mut BTreeMap<String, String>,
+        name: String,
+        id: String,
+        is_new: bool,
+    ) -> Result<()> {
+        let previous = ids.insert(name.clone(), id.clone());
+        if let Err(e) = self.save(ids) {
+            match previous {
+                Some(previous) => ids.insert(name, previous),
+                None => ids.remove(&name),
+            };
+            if is_new {
+                if let Err(e) = self.store.remove(&id) {
+                    warning!("Could not remove entry {} without a name: {}", id, e);
+                }
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Generate a random id which is neither used by a file of the store nor by an entry in *ids*
+    fn new_id(&self, ids: &BTreeMap<String, String>) -> Result<String> {
+        loop {
+            let mut bytes = [0u8; 16];
+            getrandom::getrandom(&mut bytes).map_err(std::io::Error::from)?;
+            let id = bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>();
+            if !self.id_path(&id).exists() && !ids.values().any(|other| *other == id) {
+                return Ok(id);
+            }
+        }
+    }
+
+    /// The path of the file with the random name *id*
+    fn id_path(&self, id: &str) -> PathBuf {
+        self.store.root().join(format!("{}.gpg", id))
+    }
+
+    /// Encrypt the name map *ids* and write it into the store
+    fn save(&self, ids: &BTreeMap<String, String>) -> Result<()> {
+        let mut content = format!("{}\n", HEADER);
+        for (name, id) in ids {
+            content.push_str(&format!("{} {}\n", id, index::escape(name)));
+        }
+        let path = self.store.root().join(NAME_MAP_FILE);
+        let file = StoreFileRef {
+            path: path.clone(),
+            root: self.root().to_owned(),
+        };
+        let ciphertext = self.store.encrypt_for(&file, content.as_bytes())?;
+        self.store.write_ciphertext(path, &ciphertext)?;
+        Ok(())
+    }
+}
+
+/// The random id of the entry with the normalized *name* in *ids*
+fn id_of(ids: &BTreeMap<String, String>, name: &str) -> Result<String> {
+    ids.get(name)
+        .cloned()
+        .ok_or_else(|| PassError::EntryNotFound(name.to_string()))
+}
+
+/// The name of a new entry for *pass_name* according to *policy* given the existing entries *ids*
+fn target_name(
+    ids: &BTreeMap<String, String>,
+    pass_name: &str,
+    policy: OverwritePolicy,
+) -> Result<String> {
+    let name = names::normalize_name(pass_name)?;
+    if name.is_empty() {
+        return Err(PassError::InvalidPassName(
+            pass_name.to_string(),
+            "The store root can not be an entry".to_string(),
+        ));
+    }
+    if !ids.contains_key(&name) {
+        return Ok(name);
+    }
+    match policy {
+        OverwritePolicy::Error => Err(PassError::EntryAlreadyExists(name)),
+        OverwritePolicy::Overwrite => Ok(name),
+        OverwritePolicy::KeepBoth => Ok((2..)
+            .map(|suffix| format!("{} ({})", name, suffix))
+            .find(|candidate| !ids.contains_key(candidate))
+            .expect("there is always a free suffix")),
+    }
+}
+
+/// Whether *id* is a random file name as generated by [`ObfuscatedStore`]
+fn is_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
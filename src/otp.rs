@@ -0,0 +1,168 @@
+//! Provisioning of one-time password secrets like the *pass-otp* extension does
+//!
+//! *pass-otp* keeps the secret of an entry as `otpauth://` URI on a line of its own.
+//! [`insert_uri()`] adds such a URI to an entry like `pass otp insert` and `pass otp append` do.
+//!
+//! Services usually only present the URI as QR code.
+//! With the `otp-qr` feature, [`import_from_image()`] reads the code from a screenshot or photo instead of
+//! requiring it to be scanned with a separate program.
+//!
+//! ## Example
+//! ```no_run
+//! # #[cfg(feature = "otp-qr")]
+//! # {
+//! use libpass::{otp, Store};
+//! use std::path::Path;
+//!
+//! let store = Store::from_env().unwrap();
+//! let uri = otp::import_from_image(&store, "github.com", Path::new("screenshot.png")).unwrap();
+//! println!("Imported {}", uri.expose());
+//! # }
+//! ```
+
+#[cfg(feature = "otp-qr")]
+use crate::secure::SecretString;
+use crate::templates::OTP_FIELD;
+use crate::{ParsedEntry, PassError, Result, Store, StoreFileRef};
+#[cfg(feature = "otp-qr")]
+use std::path::{Path, PathBuf};
+
+/// The scheme with which the URIs of one-time password secrets start
+pub const OTPAUTH_PREFIX: &str = "otpauth://";
+
+/// Add the `otpauth://` URI *uri* to the entry *pass_name*
+///
+/// The URI is appended to the entry on a line of its own (like `pass otp append`) or, if the entry does not
+/// exist, it becomes the only content of a new entry (like `pass otp insert`).
+///
+/// ## Errors
+/// [`PassError::InvalidField`] is returned if *uri* is not an `otpauth://` URI or if the entry already
+/// contains one.
+pub fn insert_uri(store: &Store, pass_name: &str, uri: &str) -> Result<StoreFileRef> {
+    let error = |reason: &str| PassError::InvalidField {
+        key: OTP_FIELD.to_string(),
+        reason: reason.to_string(),
+    };
+    let uri = uri.trim();
+    if !uri.starts_with(OTPAUTH_PREFIX) || uri.contains(['\n', '\r']) {
+        return Err(error("the value is not a single otpauth:// URI"));
+    }
+
+    let file = match store.file(pass_name) {
+        Ok(file) => file,
+        Err(PassError::EntryNotFound(_)) => return store.insert(pass_name, format!("{}\n", uri)),
+        Err(e) => return Err(e),
+    };
+    let entry = store.parse_entry(&file)?;
+    if entry
+        .content()
+        .lines()
+        .any(|line| line.trim_start().starts_with(OTPAUTH_PREFIX))
+    {
+        return Err(error("the entry already contains an otpauth:// URI"));
+    }
+    let newline = entry.line_ending().as_str();
+    let mut content = entry.content().to_string();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push_str(newline);
+    }
+    content.push_str(uri);
+    content.push_str(newline);
+    store.write_entry(&file, &ParsedEntry::from(content).with_mode(entry.mode()))?;
+    Ok(file)
+}
+
+/// An image which contains a QR code
+#[cfg(feature = "otp-qr")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ImageSource<'a> {
+    /// An image file in a format like PNG or JPEG
+    Path(&'a Path),
+    /// The encoded content of an image file
+    Bytes(&'a [u8]),
+}
+
+#[cfg(feature = "otp-qr")]
+impl<'a> From<&'a Path> for ImageSource<'a> {
+    fn from(value: &'a Path) -> Self {
+        Self::Path(value)
+    }
+}
+
+#[cfg(feature = "otp-qr")]
+impl<'a> From<&'a PathBuf> for ImageSource<'a> {
+    fn from(value: &'a PathBuf) -> Self {
+        Self::Path(value)
+    }
+}
+
+#[cfg(feature = "otp-qr")]
+impl<'a> From<&'a [u8]> for ImageSource<'a> {
+    fn from(value: &'a [u8]) -> Self {
+        Self::Bytes(value)
+    }
+}
+
+#[cfg(feature = "otp-qr")]
+impl<'a> From<&'a Vec<u8>> for ImageSource<'a> {
+    fn from(value: &'a Vec<u8>) -> Self {
+        Self::Bytes(value)
+    }
+}
+
+/// Read the `otpauth://` URI from a QR code in *image*
+///
+/// If the image contains several QR codes, the first one which holds an `otpauth://` URI is used.
+///
+/// ## Errors
+/// [`PassError::IOError`] is returned if the image file cannot be read and [`PassError::NoOtpQrCode`] if
+/// the image cannot be decoded or contains no such QR code.
+#[cfg(feature = "otp-qr")]
+pub fn uri_from_image<'a>(image: impl Into<ImageSource<'a>>) -> Result<SecretString> {
+    let decoded = match image.into() {
+        ImageSource::Path(path) => image::open(path),
+        ImageSource::Bytes(bytes) => image::load_from_memory(bytes),
+    }
+    .map_err(|e| match e {
+        image::ImageError::IoError(e) => PassError::from(e),
+        e => PassError::NoOtpQrCode(e.to_string()),
+    })?
+    .to_luma8();
+
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        decoded.width() as usize,
+        decoded.height() as usize,
+        |x, y| decoded.get_pixel(x as u32, y as u32).0[0],
+    );
+    let grids = prepared.detect_grids();
+    let mut reason = "it contains no QR code".to_string();
+    for grid in grids {
+        match grid.decode() {
+            Ok((_, content)) => {
+                let content = SecretString::from(content);
+                let uri = content.expose().trim();
+                if uri.starts_with(OTPAUTH_PREFIX) {
+                    return Ok(SecretString::new(uri));
+                }
+                reason = "the QR code holds no otpauth:// URI".to_string();
+            }
+            Err(e) => reason = format!("the QR code cannot be decoded: {}", e),
+        }
+    }
+    Err(PassError::NoOtpQrCode(reason))
+}
+
+/// Read the `otpauth://` URI from a QR code in *image* and add it to the entry *pass_name*
+///
+/// The imported URI is returned.
+/// See [`uri_from_image()`] for how the image is read and [`insert_uri()`] for how the URI is added.
+#[cfg(feature = "otp-qr")]
+pub fn import_from_image<'a>(
+    store: &Store,
+    pass_name: &str,
+    image: impl Into<ImageSource<'a>>,
+) -> Result<SecretString> {
+    let uri = uri_from_image(image)?;
+    insert_uri(store, pass_name, uri.expose())?;
+    Ok(uri)
+}
@@ -0,0 +1,177 @@
+//! Minimal inspection of the OpenPGP packet structure of ciphertext without decrypting it
+
+use crate::utils;
+use std::borrow::Cow;
+
+/// The first line of an ASCII armored OpenPGP message
+const ARMOR_HEADER: &[u8] = b"-----BEGIN PGP MESSAGE-----";
+
+/// Packet tag of a *Public-Key Encrypted Session Key* packet
+const TAG_PKESK: u8 = 1;
+/// Packet tag of a *Symmetrically Encrypted Data* packet
+const TAG_SED: u8 = 9;
+/// Packet tag of a *Symmetrically Encrypted Integrity Protected Data* packet
+const TAG_SEIPD: u8 = 18;
+/// Packet tag of an *AEAD Encrypted Data* packet
+const TAG_AEAD: u8 = 20;
+
+/// Header information of a single OpenPGP packet
+struct PacketHeader {
+    tag: u8,
+    header_len: usize,
+    /// Length of the packet body or `None` if the body has an indeterminate or partial length
+    body_len: Option<usize>,
+}
+
+/// Parse the packet header at the beginning of *data*
+fn parse_header(data: &[u8]) -> Result<PacketHeader, &'static str> {
+    let first = *data.first().ok_or("unexpected end of data")?;
+    if first & 0x80 == 0 {
+        return Err("data is not an OpenPGP packet");
+    }
+
+    let byte = |i: usize| {
+        data.get(i)
+            .map(|b| *b as usize)
+            .ok_or("unexpected end of data in packet header")
+    };
+
+    if first & 0x40 == 0 {
+        // old packet format
+        let tag = (first >> 2) & 0x0f;
+        match first & 0x03 {
+            0 => Ok(PacketHeader {
+                tag,
+                header_len: 2,
+                body_len: Some(byte(1)?),
+            }),
+            1 => Ok(PacketHeader {
+                tag,
+                header_len: 3,
+                body_len: Some((byte(1)? << 8) | byte(2)?),
+            }),
+            2 => Ok(PacketHeader {
+                tag,
+                header_len: 5,
+                body_len: Some((byte(1)? << 24) | (byte(2)? << 16) | (byte(3)? << 8) | byte(4)?),
+            }),
+            _ => Ok(PacketHeader {
+                tag,
+                header_len: 1,
+                body_len: None,
+            }),
+        }
+    } else {
+        // new packet format
+        let tag = first & 0x3f;
+        match byte(1)? {
+            len @ 0..=191 => Ok(PacketHeader {
+                tag,
+                header_len: 2,
+                body_len: Some(len),
+            }),
+            len @ 192..=223 => Ok(PacketHeader {
+                tag,
+                header_len: 3,
+                body_len: Some(((len - 192) << 8) + byte(2)? + 192),
+            }),
+            255 => Ok(PacketHeader {
+                tag,
+                header_len: 6,
+                body_len: Some((byte(2)? << 24) | (byte(3)? << 16) | (byte(4)? << 8) | byte(5)?),
+            }),
+            // partial body length
+            _ => Ok(PacketHeader {
+                tag,
+                header_len: 2,
+                body_len: None,
+            }),
+        }
+    }
+}
+
+/// Decode the ASCII armored OpenPGP message in *data* into its binary form
+///
+/// Binary data is returned unchanged.
+/// The CRC24 checksum of the armor is not verified since corruption is detected when parsing the packets.
+fn dearmor(data: &[u8]) -> Result<Cow<'_, [u8]>, &'static str> {
+    if !data.starts_with(ARMOR_HEADER) {
+        return Ok(Cow::Borrowed(data));
+    }
+    let text = std::str::from_utf8(data).map_err(|_| "armored message is not valid text")?;
+    let mut lines = text.lines().skip(1).map(str::trim_end);
+    // armor headers like `Version: ...` are terminated by an empty line
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+    }
+    let mut encoded = String::new();
+    for line in lines {
+        // the checksum line starts with `=` which never starts a line of base64 data
+        if line.starts_with('=') || line.starts_with("-----END") {
+            break;
+        }
+        encoded.push_str(line);
+    }
+    utils::base64_decode(&encoded)
+        .map(Cow::Owned)
+        .ok_or("armored message contains invalid base64")
+}
+
+/// Extract the ids of all keys for which the OpenPGP message in *data* is encrypted
+///
+/// The message may be binary or ASCII armored.
+/// Key ids are returned as uppercase hex strings like gpg displays them.
+/// Recipients that have been hidden (`gpg --throw-keyids`) are reported with a key id consisting only of
+/// zeroes.
+pub(crate) fn recipient_key_ids(data: &[u8]) -> Result<Vec<String>, &'static str> {
+    let data = dearmor(data)?;
+    if data.is_empty() {
+        return Err("ciphertext is empty");
+    }
+
+    let mut key_ids = Vec::new();
+    let mut remaining = &*data;
+    loop {
+        if remaining.is_empty() {
+            return Err("message does not contain any encrypted data");
+        }
+
+        let header = parse_header(remaining)?;
+        if matches!(header.tag, TAG_SED | TAG_SEIPD | TAG_AEAD) {
+            // session key packets always precede the encrypted data so we are done
+            return Ok(key_ids);
+        }
+
+        let body_len = header
+            .body_len
+            .ok_or("non-data packet has an indeterminate length")?;
+        let body = remaining
+            .get(header.header_len..header.header_len + body_len)
+            .ok_or("unexpected end of data in packet body")?;
+
+        if header.tag == TAG_PKESK {
+            // only version 3 packets carry a plain key id
+            match body.first() {
+                Some(3) => {
+                    let key_id = body.get(1..9).ok_or("truncated session key packet")?;
+                    key_ids.push(key_id.iter().map(|b| format!("{:02X}", b)).collect());
+                }
+                Some(_) => return Err("unsupported session key packet version"),
+                None => return Err("empty session key packet"),
+            }
+        }
+
+        remaining = &remaining[header.header_len + body_len..];
+    }
+}
+
+/// Check that *data* is an encrypted OpenPGP message in binary or ASCII armored form
+pub(crate) fn validate_message(data: &[u8]) -> Result<(), &'static str> {
+    if data.starts_with(ARMOR_HEADER) {
+        Ok(())
+    } else {
+        recipient_key_ids(data).map(|_| ())
+    }
+}
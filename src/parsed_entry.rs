@@ -0,0 +1,652 @@
+//! Interpretation of decrypted entry content according to common pass conventions
+
+use crate::passkey::{self, Passkey};
+use crate::secure::SecretString;
+use crate::secure_mem::{self, push_str};
+use crate::{utils, LineEnding, PassError, Result};
+use std::convert::Infallible;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Field names which are interpreted as username
+const USERNAME_FIELDS: &[&str] = &["user", "username", "login"];
+
+/// Field names which are interpreted as URL
+const URL_FIELDS: &[&str] = &["url", "website", "site"];
+
+/// Name of the field which lists the tags of an entry
+const TAGS_FIELD: &str = "tags";
+
+/// The line which starts the YAML document of gopass entries
+const YAML_SEPARATOR: &str = "---";
+
+/// The decrypted content of an entry interpreted according to common pass conventions
+///
+/// By convention, the first line of an entry contains the password and following lines may contain
+/// additional `key: value` fields like `user: john` or `url: https://example.com`.
+/// The original content is kept unchanged so that it can be written back without losing anything.
+/// Like plaintext buffers, the content is overwritten with zeroes when it is replaced or dropped (with the
+/// `secure-mem` feature).
+///
+/// Get an instance of this by calling [`StoreFileRef::parse()`](crate::StoreFileRef::parse) or by parsing
+/// a string.
+///
+/// Entries written by gopass may contain a YAML document after a `---` line which should not be interpreted
+/// line by line.
+/// Use [`ParsedEntry::with_mode()`] with [`ParseMode::Gopass`] for them.
+///
+/// ## Example
+/// ```
+/// use libpass::ParsedEntry;
+///
+/// let entry: ParsedEntry = "hunter2\nuser: john\nurl: https://example.com\n".parse().unwrap();
+/// assert_eq!(entry.password(), "hunter2");
+/// assert_eq!(entry.field("user"), Some("john"));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct ParsedEntry {
+    content: String,
+    mode: ParseMode,
+}
+
+/// How the lines following the password of an entry are interpreted
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum ParseMode {
+    /// Every line following the password may be a `key: value` field
+    #[default]
+    Pass,
+    /// The format of gopass where `key: value` fields follow the password and an optional line `---` starts
+    /// a YAML document
+    ///
+    /// Fields are only read from and written to the lines before the YAML document which is kept unchanged
+    /// by [`ParsedEntry::set_field()`] and [`ParsedEntry::remove_field()`].
+    /// Entries without a `---` line are interpreted exactly like in [`ParseMode::Pass`] so this mode is safe
+    /// to use for stores that mix entries written by *pass* and gopass.
+    Gopass,
+}
+
+/// Best-effort login information extracted from an entry
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct Login {
+    /// The username or `None` if none could be found
+    pub username: Option<String>,
+    /// The URL of the service or `None` if none could be found
+    pub url: Option<String>,
+}
+
+impl ParsedEntry {
+    /// Interpret the content according to *mode*
+    pub fn with_mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// How the content is interpreted
+    pub fn mode(&self) -> ParseMode {
+        self.mode
+    }
+
+    /// The unchanged content of the entry
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// The style of the line breaks of the entry, taken from its first line break
+    ///
+    /// Lines which are added by methods like [`ParsedEntry::set_field()`] use this style so that modifying an
+    /// entry does not mix styles.
+    /// Entries without any line break use [`LineEnding::Lf`].
+    pub fn line_ending(&self) -> LineEnding {
+        LineEnding::detect(&self.content).unwrap_or_default()
+    }
+
+    /// The password which is stored on the first line
+    pub fn password(&self) -> &str {
+        self.content.lines().next().unwrap_or("")
+    }
+
+    /// All lines following the password
+    pub fn body_lines(&self) -> impl Iterator<Item = &str> {
+        self.content.lines().skip(1)
+    }
+
+    /// All `key: value` fields in the order in which they appear
+    ///
+    /// In [`ParseMode::Gopass`], the YAML document is not included (see `ParsedEntry::yaml()` of the `gopass`
+    /// feature).
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.field_lines().filter_map(parse_field)
+    }
+
+    /// The lines following the password which are interpreted line by line
+    fn field_lines(&self) -> impl Iterator<Item = &str> {
+        self.sections().0.lines().skip(1)
+    }
+
+    /// The byte offset of the line which starts the YAML document or `None` if there is none
+    fn yaml_start(&self) -> Option<usize> {
+        let mut offset = 0;
+        for (i, line) in self.content.split_inclusive('\n').enumerate() {
+            if i > 0 && split_line_ending(line).0 == YAML_SEPARATOR {
+                return Some(offset);
+            }
+            offset += line.len();
+        }
+        None
+    }
+
+    /// Split the content into the part which is interpreted line by line and the YAML document (including the
+    /// line which starts it) according to the parse mode
+    fn sections(&self) -> (&str, &str) {
+        match (self.mode, self.yaml_start()) {
+            (ParseMode::Gopass, Some(start)) => self.content.split_at(start),
+            _ => (&self.content, ""),
+        }
+    }
+
+    /// The value of the first field whose key equals *key* case-insensitively
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields()
+            .find(|(field_key, _)| field_key.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    /// Extract the username and URL of the entry in a best-effort manner
+    ///
+    /// The username is taken from a `user:`, `username:` or `login:` field or, if none exists, from the
+    /// second line if it is neither a field nor a URL.
+    /// The URL is taken from a `url:`, `website:` or `site:` field or, if none exists, from the first line
+    /// that looks like a http(s) URL.
+    pub fn login(&self) -> Login {
+        let username = USERNAME_FIELDS
+            .iter()
+            .find_map(|key| self.field(key))
+            .or_else(|| {
+                self.field_lines().next().filter(|line| {
+                    !line.trim().is_empty() && parse_field(line).is_none() && !is_url(line)
+                })
+            })
+            .map(|username| username.trim().to_string());
+
+        let url = URL_FIELDS
+            .iter()
+            .find_map(|key| self.field(key))
+            .or_else(|| self.field_lines().find(|line| is_url(line)))
+            .map(|url| url.trim().to_string());
+
+        Login { username, url }
+    }
+
+    /// The tags of the entry which are listed in its `tags:` field
+    ///
+    /// Tags are separated by commas or whitespace, e.g. `tags: work, email`.
+    pub fn tags(&self) -> Vec<&str> {
+        self.field(TAGS_FIELD)
+            .map(|tags| {
+                tags.split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|tag| !tag.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether the entry is tagged with *tag*
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags().contains(&tag)
+    }
+
+    /// Add *tag* to the `tags:` field of the entry, creating the field if necessary
+    ///
+    /// Returns whether the tag was added, i.e. `false` if the entry already had it.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidField`] is returned if *tag* is empty or contains commas or whitespace.
+    pub fn add_tag(&mut self, tag: &str) -> Result<bool> {
+        if tag.is_empty() || tag.contains(|c: char| c == ',' || c.is_whitespace()) {
+            return Err(PassError::InvalidField {
+                key: TAGS_FIELD.to_string(),
+                reason: format!("tag {:?} is empty or contains commas or whitespace", tag),
+            });
+        }
+        if self.has_tag(tag) {
+            return Ok(false);
+        }
+
+        let mut tags = self.tags();
+        tags.push(tag);
+        let tags = tags.join(", ");
+        self.set_field(TAGS_FIELD, &tags)?;
+        Ok(true)
+    }
+
+    /// Remove *tag* from the `tags:` field of the entry, removing the field if no tags remain
+    ///
+    /// Returns whether the entry had the tag.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        if !self.has_tag(tag) {
+            return false;
+        }
+
+        let tags = self
+            .tags()
+            .into_iter()
+            .filter(|other| *other != tag)
+            .collect::<Vec<_>>()
+            .join(", ");
+        if tags.is_empty() {
+            self.remove_field(TAGS_FIELD);
+        } else {
+            self.set_field(TAGS_FIELD, &tags)
+                .expect("tags field and joined tags are always valid");
+        }
+        true
+    }
+
+    /// Set the value of the field *key*
+    ///
+    /// If the entry already contains the field (compared case-insensitively), the value of its first
+    /// occurrence is replaced in place.
+    /// Otherwise a new `key: value` line is appended at the end of the entry (or before the YAML document in
+    /// [`ParseMode::Gopass`]) using the [line ending](ParsedEntry::line_ending) of the entry.
+    /// All other lines including their order, comments and line endings are preserved byte-for-byte.
+    ///
+    /// Use [`Store::write_entry()`](crate::Store::write_entry) to save the modified entry.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidField`] is returned if *key* is empty, contains whitespace or a colon or if *value*
+    /// spans multiple lines.
+    pub fn set_field(&mut self, key: &str, value: &str) -> Result<()> {
+        let error = |reason: &str| PassError::InvalidField {
+            key: key.to_string(),
+            reason: reason.to_string(),
+        };
+        if key.is_empty() || key.contains(char::is_whitespace) || key.contains(':') {
+            return Err(error(
+                "keys must not be empty or contain whitespace or colons",
+            ));
+        }
+        if value.contains(['\n', '\r']) {
+            return Err(error("values must not span multiple lines"));
+        }
+
+        let newline = self.line_ending().as_str();
+        let (fields, yaml) = self.sections();
+        let mut content = String::with_capacity(self.content.len() + key.len() + value.len() + 3);
+        let mut replaced = false;
+        for (i, line) in fields.split_inclusive('\n').enumerate() {
+            let (text, ending) = split_line_ending(line);
+            match parse_field_span(text) {
+                Some(field) if i > 0 && !replaced && field.key.eq_ignore_ascii_case(key) => {
+                    // keep the key as it was written including any indentation
+                    push_str(&mut content, &text[..=field.colon]);
+                    if !value.is_empty() {
+                        push_str(&mut content, " ");
+                        push_str(&mut content, value);
+                    }
+                    push_str(&mut content, ending);
+                    replaced = true;
+                }
+                _ => push_str(&mut content, line),
+            }
+        }
+
+        if !replaced {
+            if content.is_empty() {
+                // keep the first line reserved for the password
+                push_str(&mut content, newline);
+            } else if !content.ends_with('\n') {
+                push_str(&mut content, newline);
+            }
+            push_field(&mut content, key, value, newline);
+        }
+        push_str(&mut content, yaml);
+
+        self.replace_content(content);
+        Ok(())
+    }
+
+    /// Remove all occurrences of the field *key* (compared case-insensitively)
+    ///
+    /// All other lines are preserved byte-for-byte.
+    /// The value of the first removed occurrence is returned or `None` if the entry did not contain the field.
+    ///
+    /// Use [`Store::write_entry()`](crate::Store::write_entry) to save the modified entry.
+    pub fn remove_field(&mut self, key: &str) -> Option<SecretString> {
+        let (fields, yaml) = self.sections();
+        let mut removed = None;
+        let mut content = String::with_capacity(self.content.len());
+        for (i, line) in fields.split_inclusive('\n').enumerate() {
+            match parse_field(split_line_ending(line).0) {
+                Some((field_key, value)) if i > 0 && field_key.eq_ignore_ascii_case(key) => {
+                    removed.get_or_insert_with(|| SecretString::from(value));
+                }
+                _ => push_str(&mut content, line),
+            }
+        }
+
+        if removed.is_some() {
+            push_str(&mut content, yaml);
+            self.replace_content(content);
+        } else {
+            secure_mem::release_string(content);
+        }
+        removed
+    }
+
+    /// The metadata of the passkeys which are stored in the entry
+    ///
+    /// See the [`passkey`](crate::passkey) module for the format.
+    ///
+    /// ## Errors
+    /// [`PassError::MalformedEntry`] is returned if the fields of a passkey are incomplete or malformed.
+    pub fn passkeys(&self) -> Result<Vec<Passkey>> {
+        passkey::parse(self.field_lines().map(parse_field))
+    }
+
+    /// Append the metadata of *passkey* to the entry (before the YAML document in [`ParseMode::Gopass`])
+    ///
+    /// Returns whether the passkey was added, i.e. `false` if the entry already contains a passkey with the
+    /// same credential id.
+    /// Use [`Store::write_entry()`](crate::Store::write_entry) to save the modified entry.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidField`] is returned if the credential id or relying party of *passkey* is empty or
+    /// if its user name spans multiple lines.
+    /// [`PassError::MalformedEntry`] is returned if the passkeys which are already stored are malformed.
+    pub fn add_passkey(&mut self, passkey: &Passkey) -> Result<bool> {
+        let fields = passkey.to_fields()?;
+        if self
+            .passkeys()?
+            .iter()
+            .any(|existing| existing.credential_id == passkey.credential_id)
+        {
+            return Ok(false);
+        }
+
+        let newline = self.line_ending().as_str();
+        let (lines, yaml) = self.sections();
+        let mut content = String::with_capacity(self.content.len());
+        push_str(&mut content, lines);
+        if content.is_empty() || !content.ends_with('\n') {
+            push_str(&mut content, newline);
+        }
+        for (key, value) in fields {
+            push_field(&mut content, key, &value, newline);
+        }
+        push_str(&mut content, yaml);
+        self.replace_content(content);
+        Ok(true)
+    }
+
+    /// Remove the metadata of the passkey with the credential id *credential_id* from the entry
+    ///
+    /// Returns whether the entry contained the passkey.
+    /// Use [`Store::write_entry()`](crate::Store::write_entry) to save the modified entry.
+    pub fn remove_passkey(&mut self, credential_id: &[u8]) -> bool {
+        let (lines, yaml) = self.sections();
+        let mut content = String::with_capacity(self.content.len());
+        let mut removed = false;
+        let mut in_removed_group = false;
+        for (i, line) in lines.split_inclusive('\n').enumerate() {
+            match parse_field(split_line_ending(line).0).filter(|_| i > 0) {
+                Some((key, value)) if key.eq_ignore_ascii_case(passkey::PASSKEY_FIELD) => {
+                    in_removed_group =
+                        utils::base64url_decode(value).as_deref() == Some(credential_id);
+                    removed |= in_removed_group;
+                }
+                Some((key, _)) if in_removed_group && passkey::is_passkey_field(key) => {}
+                _ => in_removed_group = false,
+            }
+            if !in_removed_group {
+                push_str(&mut content, line);
+            }
+        }
+
+        if removed {
+            push_str(&mut content, yaml);
+            self.replace_content(content);
+        } else {
+            secure_mem::release_string(content);
+        }
+        removed
+    }
+
+    /// The YAML document which follows a `---` line in entries written by gopass or `None` if there is none
+    ///
+    /// The document is read regardless of the parse mode.
+    /// An empty document is returned as empty mapping.
+    ///
+    /// ## Errors
+    /// [`PassError::MalformedEntry`] is returned if the document is no valid YAML mapping.
+    #[cfg(feature = "gopass")]
+    pub fn yaml(&self) -> Result<Option<serde_yaml::Mapping>> {
+        let start = match self.yaml_start() {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+        let document = self.content[start..]
+            .split_once('\n')
+            .map(|(_, document)| document)
+            .unwrap_or("");
+        match serde_yaml::from_str(document) {
+            Ok(serde_yaml::Value::Null) => Ok(Some(serde_yaml::Mapping::new())),
+            Ok(serde_yaml::Value::Mapping(mapping)) => Ok(Some(mapping)),
+            Ok(_) => Err(PassError::MalformedEntry(
+                "the YAML document is no mapping".to_string(),
+            )),
+            Err(e) => Err(PassError::MalformedEntry(e.to_string())),
+        }
+    }
+
+    /// The value of *key* in the YAML document of the entry
+    ///
+    /// See [`ParsedEntry::yaml()`].
+    #[cfg(feature = "gopass")]
+    pub fn yaml_value(&self, key: &str) -> Result<Option<serde_yaml::Value>> {
+        Ok(self.yaml()?.and_then(|mut mapping| mapping.remove(key)))
+    }
+
+    /// Set *key* in the YAML document of the entry to *value*, creating the document if necessary
+    ///
+    /// Everything before the YAML document is preserved byte-for-byte while the document itself is
+    /// serialized again so that comments and formatting in it are lost.
+    ///
+    /// ## Errors
+    /// [`PassError::MalformedEntry`] is returned if the existing document is no valid YAML mapping.
+    #[cfg(feature = "gopass")]
+    pub fn set_yaml_value(&mut self, key: &str, value: impl Into<serde_yaml::Value>) -> Result<()> {
+        let mut mapping = self.yaml()?.unwrap_or_default();
+        mapping.insert(key.into(), value.into());
+        self.replace_yaml(&mapping)
+    }
+
+    /// Remove *key* from the YAML document of the entry and return its value
+    ///
+    /// The entry is only modified if the document contained *key*.
+    ///
+    /// ## Errors
+    /// [`PassError::MalformedEntry`] is returned if the existing document is no valid YAML mapping.
+    #[cfg(feature = "gopass")]
+    pub fn remove_yaml_value(&mut self, key: &str) -> Result<Option<serde_yaml::Value>> {
+        let mut mapping = match self.yaml()? {
+            Some(mapping) => mapping,
+            None => return Ok(None),
+        };
+        let removed = mapping.remove(key);
+        if removed.is_some() {
+            self.replace_yaml(&mapping)?;
+        }
+        Ok(removed)
+    }
+
+    /// Replace the YAML document of the entry with *mapping*
+    #[cfg(feature = "gopass")]
+    fn replace_yaml(&mut self, mapping: &serde_yaml::Mapping) -> Result<()> {
+        let document =
+            serde_yaml::to_string(mapping).map_err(|e| PassError::MalformedEntry(e.to_string()))?;
+        let document = SecretString::from(document);
+        let ending = self.line_ending();
+        let mut content = String::with_capacity(self.content.len() + document.expose().len());
+        match self.yaml_start() {
+            // keep the separator line including its line ending
+            Some(start) => match self.content[start..].find('\n') {
+                Some(end) => push_str(&mut content, &self.content[..=start + end]),
+                None => {
+                    push_str(&mut content, &self.content);
+                    push_str(&mut content, ending.as_str());
+                }
+            },
+            None => {
+                push_str(&mut content, &self.content);
+                if !content.ends_with('\n') {
+                    push_str(&mut content, ending.as_str());
+                }
+                push_str(&mut content, YAML_SEPARATOR);
+                push_str(&mut content, ending.as_str());
+            }
+        }
+        push_str(
+            &mut content,
+            SecretString::from(ending.apply(document.expose())).expose(),
+        );
+        self.replace_content(content);
+        Ok(())
+    }
+
+    /// Replace the content with *content*, overwriting the previous content with zeroes
+    fn replace_content(&mut self, content: String) {
+        secure_mem::release_string(std::mem::replace(&mut self.content, content));
+    }
+}
+
+impl Drop for ParsedEntry {
+    fn drop(&mut self) {
+        secure_mem::release_string(std::mem::take(&mut self.content));
+    }
+}
+
+impl From<String> for ParsedEntry {
+    fn from(content: String) -> Self {
+        Self {
+            content,
+            mode: ParseMode::default(),
+        }
+    }
+}
+
+impl FromStr for ParsedEntry {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self::from(s.to_string()))
+    }
+}
+
+/// Interpret *line* as `key: value` field
+///
+/// Keys must not contain whitespace and the colon must be followed by whitespace or the end of the line so
+/// that URLs like `https://example.com` are not mistaken for fields.
+pub(crate) fn parse_field(line: &str) -> Option<(&str, &str)> {
+    parse_field_span(line).map(|field| (field.key, &line[field.value]))
+}
+
+/// The parts of a `key: value` field inside of its line
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct FieldSpan<'a> {
+    /// The key without surrounding whitespace
+    pub key: &'a str,
+    /// Byte offset of the colon which follows the key
+    pub colon: usize,
+    /// Byte range of the value without surrounding whitespace
+    pub value: Range<usize>,
+}
+
+/// Interpret *line* as `key: value` field like [`parse_field()`] but return the positions of its parts
+pub(crate) fn parse_field_span(line: &str) -> Option<FieldSpan<'_>> {
+    let colon = line.find(':')?;
+    let key = line[..colon].trim();
+    let value = &line[colon + 1..];
+    if key.is_empty()
+        || key.contains(char::is_whitespace)
+        || !(value.is_empty() || value.starts_with(char::is_whitespace))
+    {
+        return None;
+    }
+    let start = colon + 1 + (value.len() - value.trim_start().len());
+    let end = (colon + 1 + value.trim_end().len()).max(start);
+    Some(FieldSpan {
+        key,
+        colon,
+        value: start..end,
+    })
+}
+
+/// Append the field line `key: value` to *content*
+fn push_field(content: &mut String, key: &str, value: &str, newline: &str) {
+    for part in [key, ": ", value, newline] {
+        push_str(content, part);
+    }
+}
+
+/// Split *line* into its text and its line ending (`\n`, `\r\n` or nothing)
+pub(crate) fn split_line_ending(line: &str) -> (&str, &str) {
+    let text = line
+        .strip_suffix('\n')
+        .map(|text| text.strip_suffix('\r').unwrap_or(text))
+        .unwrap_or(line);
+    (text, &line[text.len()..])
+}
+
+/// Whether *line* looks like a http(s) URL
+fn is_url(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("http://") || line.starts_with("https://")
+}
+
+/// Extract the lowercase host of *url* without a `www.` prefix
+///
+/// URLs without scheme are interpreted as if they started with their host.
+pub(crate) fn url_host(url: &str) -> Option<String> {
+    let without_scheme = match url.trim().split_once("://") {
+        Some((_, rest)) => rest,
+        None => url.trim(),
+    };
+    let authority = without_scheme.split(['/', '?', '#']).next()?;
+    let host_and_port = authority.rsplit('@').next()?;
+    let host = host_and_port.split(':').next()?.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host).to_string();
+
+    if host.is_empty() {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+/// How [`Store::entries_for_url()`](crate::Store::entries_for_url) compares domains
+///
+/// Configure this via [`Store::with_url_matching()`](crate::Store::with_url_matching).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum UrlMatch {
+    /// Only match the host of the URL itself (ignoring a `www.` prefix)
+    #[default]
+    Exact,
+    /// Also match parent domains of the host, e.g. an entry for `github.com` when visiting `gist.github.com`
+    ///
+    /// Only use this if no entry is named after a domain below which anyone can register subdomains (like
+    /// `github.io` or `co.uk`), because such an entry would match every site below that domain.
+    Subdomains,
+}
+
+/// Whether *candidate* (e.g. a part of an entry name) names the domain *host* or, if *matching* allows it, one
+/// of its parent domains
+pub(crate) fn domain_matches(host: &str, candidate: &str, matching: UrlMatch) -> bool {
+    let candidate = candidate.to_lowercase();
+    let candidate = candidate.strip_prefix("www.").unwrap_or(&candidate);
+    candidate.contains('.')
+        && (host == candidate
+            || matching == UrlMatch::Subdomains
+                && host
+                    .strip_suffix(candidate)
+                    .is_some_and(|prefix| prefix.ends_with('.')))
+}
@@ -0,0 +1,196 @@
+//! A convention for storing the metadata of passkeys (WebAuthn/FIDO2 credentials) in entries
+//!
+//! The private key of a passkey never leaves its authenticator, but frontends still need to know which
+//! passkeys exist for an account, e.g. to show them next to the password or to remind users to revoke them.
+//! [`ParsedEntry::passkeys()`](crate::ParsedEntry::passkeys),
+//! [`ParsedEntry::add_passkey()`](crate::ParsedEntry::add_passkey) and
+//! [`ParsedEntry::remove_passkey()`](crate::ParsedEntry::remove_passkey) read and write the metadata of the
+//! passkeys of an entry and [`Store::find_passkeys()`](crate::Store::find_passkeys) finds all passkeys for a
+//! relying party.
+//!
+//! ## Format
+//! Every passkey is a group of consecutive fields which starts with a `passkey:` field:
+//! ```text
+//! hunter2
+//! user: john
+//! passkey: 8J-sZCx4mRnCEcMp9Ejh2w
+//! passkey-rp: example.com
+//! passkey-public-key: pQECAyYgASFYIGmb...
+//! passkey-user-handle: MTIzNDU2
+//! passkey-user: john@example.com
+//! ```
+//! - `passkey` holds the credential id.
+//! - `passkey-rp` holds the id of the relying party, usually its domain.
+//! - `passkey-public-key` holds the COSE encoded public key.
+//! - `passkey-user-handle` and `passkey-user` optionally hold the user handle and the user name which the
+//!   relying party assigned.
+//!
+//! Binary values are encoded as base64url without padding like in WebAuthn.
+//! A group ends at the first line which is not a `passkey-` field so an entry can hold several passkeys.
+
+use crate::{utils, PassError, Result};
+
+/// Name of the field which starts a passkey and holds its credential id
+pub const PASSKEY_FIELD: &str = "passkey";
+
+/// Name of the field which holds the id of the relying party of a passkey
+pub const RP_ID_FIELD: &str = "passkey-rp";
+
+/// Name of the field which holds the public key of a passkey
+pub const PUBLIC_KEY_FIELD: &str = "passkey-public-key";
+
+/// Name of the field which holds the user handle of a passkey
+pub const USER_HANDLE_FIELD: &str = "passkey-user-handle";
+
+/// Name of the field which holds the user name of a passkey
+pub const USER_NAME_FIELD: &str = "passkey-user";
+
+/// The metadata of a passkey
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Passkey {
+    /// The id of the credential which the authenticator generated
+    pub credential_id: Vec<u8>,
+    /// The id of the relying party, usually its domain like `example.com`
+    pub rp_id: String,
+    /// The COSE encoded public key of the credential
+    pub public_key: Vec<u8>,
+    /// The user handle which the relying party assigned to the account
+    pub user_handle: Option<Vec<u8>>,
+    /// The human-readable name of the account at the relying party
+    pub user_name: Option<String>,
+}
+
+impl Passkey {
+    /// Describe the passkey *credential_id* of the relying party *rp_id* with the public key *public_key*
+    pub fn new(
+        credential_id: impl Into<Vec<u8>>,
+        rp_id: impl Into<String>,
+        public_key: impl Into<Vec<u8>>,
+    ) -> Self {
+        Self {
+            credential_id: credential_id.into(),
+            rp_id: rp_id.into(),
+            public_key: public_key.into(),
+            user_handle: None,
+            user_name: None,
+        }
+    }
+
+    /// Set the user handle which the relying party assigned to the account
+    pub fn with_user_handle(mut self, user_handle: impl Into<Vec<u8>>) -> Self {
+        self.user_handle = Some(user_handle.into());
+        self
+    }
+
+    /// Set the human-readable name of the account
+    pub fn with_user_name(mut self, user_name: impl Into<String>) -> Self {
+        self.user_name = Some(user_name.into());
+        self
+    }
+
+    /// The `key: value` fields which describe this passkey in an entry
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidField`] is returned if the credential id or relying party is empty or if a text
+    /// value spans multiple lines.
+    pub(crate) fn to_fields(&self) -> Result<Vec<(&'static str, String)>> {
+        let error = |key: &str, reason: &str| PassError::InvalidField {
+            key: key.to_string(),
+            reason: reason.to_string(),
+        };
+        if self.credential_id.is_empty() {
+            return Err(error(PASSKEY_FIELD, "the credential id must not be empty"));
+        }
+        if self.rp_id.is_empty() || self.rp_id.contains(char::is_whitespace) {
+            return Err(error(
+                RP_ID_FIELD,
+                "the relying party must not be empty or contain whitespace",
+            ));
+        }
+        let mut fields = vec![
+            (PASSKEY_FIELD, utils::base64url_encode(&self.credential_id)),
+            (RP_ID_FIELD, self.rp_id.clone()),
+            (PUBLIC_KEY_FIELD, utils::base64url_encode(&self.public_key)),
+        ];
+        if let Some(user_handle) = &self.user_handle {
+            fields.push((USER_HANDLE_FIELD, utils::base64url_encode(user_handle)));
+        }
+        if let Some(user_name) = &self.user_name {
+            if user_name.contains(['\n', '\r']) {
+                return Err(error(
+                    USER_NAME_FIELD,
+                    "values must not span multiple lines",
+                ));
+            }
+            fields.push((USER_NAME_FIELD, user_name.clone()));
+        }
+        Ok(fields)
+    }
+}
+
+/// Collect the passkeys from the *fields* of an entry where `None` stands for a line which is not a field
+///
+/// ## Errors
+/// [`PassError::MalformedEntry`] is returned if a passkey lacks its relying party or public key or if a
+/// binary value is not valid base64url.
+pub(crate) fn parse<'a>(
+    fields: impl Iterator<Item = Option<(&'a str, &'a str)>>,
+) -> Result<Vec<Passkey>> {
+    let decode = |key: &str, value: &str| {
+        utils::base64url_decode(value).ok_or_else(|| {
+            PassError::MalformedEntry(format!("The {} field is not valid base64url", key))
+        })
+    };
+
+    let mut groups: Vec<Vec<(&str, &str)>> = Vec::new();
+    let mut in_group = false;
+    for field in fields {
+        match field {
+            Some((key, value)) if key.eq_ignore_ascii_case(PASSKEY_FIELD) => {
+                groups.push(vec![(PASSKEY_FIELD, value)]);
+                in_group = true;
+            }
+            Some((key, value)) if in_group && is_passkey_field(key) => {
+                if let Some(group) = groups.last_mut() {
+                    group.push((key, value));
+                }
+            }
+            _ => in_group = false,
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|group| {
+            let get = |name: &str| {
+                group
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| *value)
+            };
+            let required = |name: &str| {
+                get(name).ok_or_else(|| {
+                    PassError::MalformedEntry(format!("A passkey lacks the {} field", name))
+                })
+            };
+            Ok(Passkey {
+                credential_id: decode(PASSKEY_FIELD, required(PASSKEY_FIELD)?)?,
+                rp_id: required(RP_ID_FIELD)?.to_string(),
+                public_key: decode(PUBLIC_KEY_FIELD, required(PUBLIC_KEY_FIELD)?)?,
+                user_handle: get(USER_HANDLE_FIELD)
+                    .map(|value| decode(USER_HANDLE_FIELD, value))
+                    .transpose()?,
+                user_name: get(USER_NAME_FIELD).map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// Whether *key* belongs to the group of fields of a passkey (without starting a new one)
+pub(crate) fn is_passkey_field(key: &str) -> bool {
+    let prefix = "passkey-";
+    key.len() > prefix.len()
+        && key
+            .get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
@@ -0,0 +1,176 @@
+//! Fuzzy matching of entry names for interactive pickers
+//!
+//! A [`Picker`] ranks entry names against a query like rofi or fzf do: the characters of the query have to
+//! appear in a name in the same order but not necessarily next to each other.
+//! Matches at the start of words and consecutive matches are scored higher and the positions of the matched
+//! characters are returned so that frontends can highlight them.
+//!
+//! Matching is case-insensitive unless the query contains an uppercase character (*smart case*).
+//! With the `serde` feature, [`PickerMatch`] can be serialized for frontends which talk to this library over
+//! IPC.
+//!
+//! ## Example
+//! ```
+//! use libpass::picker::Picker;
+//!
+//! let picker = Picker::new(["web/github.com", "web/gitlab.com", "mail/gmail"]);
+//! let matches = picker.query("gh");
+//! assert_eq!(matches[0].name, "web/github.com");
+//! assert_eq!(matches[0].positions, [4, 7]);
+//! ```
+
+use crate::index::StoreIndex;
+
+/// Score of every matched character
+const SCORE_MATCH: i64 = 16;
+/// Bonus for a character which directly follows the previously matched one
+const BONUS_CONSECUTIVE: i64 = 8;
+/// Bonus for a character at the start of a word (e.g. after `/` or `-`)
+const BONUS_BOUNDARY: i64 = 10;
+/// Additional bonus for matching the first character of the last path component
+const BONUS_BASENAME: i64 = 6;
+/// Penalty for every unmatched character between the first and last match
+const PENALTY_GAP: i64 = 1;
+
+/// A name which matches a query
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PickerMatch {
+    /// The matching name
+    pub name: String,
+    /// How well the name matches (higher is better)
+    pub score: i64,
+    /// The indices (counted in characters, not bytes) of the characters of *name* which matched the query
+    pub positions: Vec<usize>,
+}
+
+/// Ranks entry names against queries
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct Picker {
+    names: Vec<String>,
+    limit: Option<usize>,
+}
+
+impl Picker {
+    /// Pick from *names*
+    pub fn new(names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            names: names.into_iter().map(Into::into).collect(),
+            limit: None,
+        }
+    }
+
+    /// Pick from the entries of *index*
+    ///
+    /// See [`Store::picker()`](crate::Store::picker).
+    pub fn from_index(index: &StoreIndex) -> Self {
+        Self::new(index.entries().into_iter().map(|entry| entry.name()))
+    }
+
+    /// Return at most *limit* matches from [`query()`](Picker::query)
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// The maximal number of returned matches
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// The names which are picked from
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// All names which match *query*, best matches first
+    ///
+    /// Matches with the same score are ordered by length and then alphabetically.
+    /// An empty query matches all names.
+    pub fn query(&self, query: &str) -> Vec<PickerMatch> {
+        let case_sensitive = query.chars().any(char::is_uppercase);
+        let query = query
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<Vec<_>>();
+        let mut matches = self
+            .names
+            .iter()
+            .filter_map(|name| fuzzy_match(name, &query, case_sensitive))
+            .collect::<Vec<_>>();
+        matches.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.name.len().cmp(&b.name.len()))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        if let Some(limit) = self.limit {
+            matches.truncate(limit);
+        }
+        matches
+    }
+}
+
+/// Match *query* against *name* and score the match
+///
+/// The first occurrence of the query is searched and then narrowed from the back so that matched characters
+/// are as close to each other as possible.
+fn fuzzy_match(name: &str, query: &[char], case_sensitive: bool) -> Option<PickerMatch> {
+    let chars = name.chars().collect::<Vec<_>>();
+    let eq = |a: char, b: char| match case_sensitive {
+        true => a == b,
+        false => a.to_lowercase().eq(b.to_lowercase()),
+    };
+
+    // find the end of the first occurrence
+    let mut end = 0;
+    let mut matched = 0;
+    while matched < query.len() {
+        if end == chars.len() {
+            return None;
+        }
+        if eq(chars[end], query[matched]) {
+            matched += 1;
+        }
+        end += 1;
+    }
+
+    // narrow the occurrence down from its end
+    let mut start = end;
+    for q in query.iter().rev() {
+        start -= 1;
+        while !eq(chars[start], *q) {
+            start -= 1;
+        }
+    }
+
+    let basename_start = chars.iter().rposition(|c| *c == '/').map_or(0, |i| i + 1);
+    let mut positions = Vec::with_capacity(query.len());
+    let mut score = 0;
+    let mut i = start;
+    for q in query {
+        while !eq(chars[i], *q) {
+            i += 1;
+        }
+        score += SCORE_MATCH;
+        match positions.last() {
+            Some(last) if *last + 1 == i => score += BONUS_CONSECUTIVE,
+            Some(last) => score -= (i - last - 1) as i64 * PENALTY_GAP,
+            None => {}
+        }
+        if i == 0 || matches!(chars[i - 1], '/' | '-' | '_' | '.' | ' ' | '@') {
+            score += BONUS_BOUNDARY;
+        }
+        if i == basename_start {
+            score += BONUS_BASENAME;
+        }
+        positions.push(i);
+        i += 1;
+    }
+
+    Some(PickerMatch {
+        name: name.to_string(),
+        score,
+        positions,
+    })
+}
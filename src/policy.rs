@@ -0,0 +1,181 @@
+//! Validation of passwords against organizational rules
+//!
+//! A [`PasswordPolicy`] describes which passwords are acceptable.
+//! Single passwords are checked with [`PasswordPolicy::check()`] and all entries of a store with
+//! [`Store::audit_against_policy()`](crate::Store::audit_against_policy).
+//!
+//! ## Example
+//! ```
+//! use libpass::policy::{CharacterClass, PasswordPolicy, PolicyViolation};
+//!
+//! let policy = PasswordPolicy::default()
+//!     .with_min_length(12)
+//!     .with_required_class(CharacterClass::Digit)
+//!     .with_forbidden_substring("password");
+//! assert_eq!(
+//!     policy.check("Password123"),
+//!     [
+//!         PolicyViolation::TooShort { min_length: 12, length: 11 },
+//!         PolicyViolation::ForbiddenSubstring("password".to_string()),
+//!     ]
+//! );
+//! ```
+
+use crate::StoreFileRef;
+use std::time::Duration;
+
+/// A kind of character which passwords can be required to contain
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum CharacterClass {
+    /// A lowercase letter
+    Lowercase,
+    /// An uppercase letter
+    Uppercase,
+    /// A decimal digit
+    Digit,
+    /// Any character which is neither a letter, a digit nor whitespace
+    Symbol,
+}
+
+impl CharacterClass {
+    /// Whether *c* belongs to this class
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            Self::Lowercase => c.is_lowercase(),
+            Self::Uppercase => c.is_uppercase(),
+            Self::Digit => c.is_ascii_digit(),
+            Self::Symbol => !c.is_alphanumeric() && !c.is_whitespace(),
+        }
+    }
+}
+
+/// A rule of a [`PasswordPolicy`] which a password does not satisfy
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum PolicyViolation {
+    /// The password has fewer characters than required
+    TooShort {
+        /// The minimal number of characters
+        min_length: usize,
+        /// The number of characters of the password
+        length: usize,
+    },
+    /// The password does not contain a character of a required class
+    MissingClass(CharacterClass),
+    /// The password contains a forbidden substring (compared case-insensitively)
+    ForbiddenSubstring(String),
+    /// The entry was not changed for longer than allowed
+    TooOld {
+        /// The maximal age of entries
+        max_age: Duration,
+        /// The time since the entry was last modified
+        age: Duration,
+    },
+}
+
+/// Rules which passwords have to satisfy
+///
+/// The default policy accepts every password.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct PasswordPolicy {
+    min_length: usize,
+    required_classes: Vec<CharacterClass>,
+    forbidden_substrings: Vec<String>,
+    max_age: Option<Duration>,
+}
+
+impl PasswordPolicy {
+    /// Require passwords to have at least *min_length* characters
+    pub fn with_min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// The minimal number of characters of passwords
+    pub fn min_length(&self) -> usize {
+        self.min_length
+    }
+
+    /// Require passwords to contain at least one character of *class*
+    pub fn with_required_class(mut self, class: CharacterClass) -> Self {
+        if !self.required_classes.contains(&class) {
+            self.required_classes.push(class);
+        }
+        self
+    }
+
+    /// The classes of which passwords must contain at least one character
+    pub fn required_classes(&self) -> &[CharacterClass] {
+        &self.required_classes
+    }
+
+    /// Forbid passwords which contain *substring* (compared case-insensitively), e.g. the company name
+    pub fn with_forbidden_substring(mut self, substring: impl Into<String>) -> Self {
+        self.forbidden_substrings.push(substring.into());
+        self
+    }
+
+    /// Substrings which passwords must not contain
+    pub fn forbidden_substrings(&self) -> &[String] {
+        &self.forbidden_substrings
+    }
+
+    /// Require entries to be changed at least every *max_age*
+    ///
+    /// The age of an entry is the time since its file was last modified, so this rule is only checked by
+    /// [`Store::audit_against_policy()`](crate::Store::audit_against_policy).
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// The maximal age of entries
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    /// Check *password* against all rules except the maximal age
+    ///
+    /// An empty list is returned if the password satisfies the policy.
+    pub fn check(&self, password: &str) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+        if length < self.min_length {
+            violations.push(PolicyViolation::TooShort {
+                min_length: self.min_length,
+                length,
+            });
+        }
+        for class in &self.required_classes {
+            if !password.chars().any(|c| class.contains(c)) {
+                violations.push(PolicyViolation::MissingClass(*class));
+            }
+        }
+        let lowercase = password.to_lowercase();
+        for substring in &self.forbidden_substrings {
+            if lowercase.contains(&substring.to_lowercase()) {
+                violations.push(PolicyViolation::ForbiddenSubstring(substring.clone()));
+            }
+        }
+        violations
+    }
+
+    /// Check an entry whose file was last modified *age* ago against the maximal age
+    pub(crate) fn check_age(&self, age: Duration) -> Option<PolicyViolation> {
+        self.max_age
+            .filter(|max_age| age > *max_age)
+            .map(|max_age| PolicyViolation::TooOld { max_age, age })
+    }
+}
+
+/// An entry which violates a [`PasswordPolicy`] or whose password could not be checked
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PolicyReport {
+    /// The violating entry
+    pub file: StoreFileRef,
+    /// All rules which the entry does not satisfy
+    pub violations: Vec<PolicyViolation>,
+    /// Whether the password was not checked because the entry is not valid UTF-8 text (e.g. a binary file)
+    ///
+    /// Only the age of skipped entries is checked.
+    pub skipped: bool,
+}
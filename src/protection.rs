@@ -0,0 +1,70 @@
+//! Protection of important entries against accidental removal
+//!
+//! Entries which are marked with [`Store::protect()`](crate::Store::protect) can not be removed or replaced
+//! through the store until they are unprotected again, unless the store is explicitly configured with
+//! [`Store::with_override_protection()`](crate::Store::with_override_protection).
+//! Changing the content of a protected entry (e.g. through
+//! [`Store::plain_io_rw()`](crate::Store::plain_io_rw)) is still allowed.
+//!
+//! The names of protected entries are not secret and are stored unencrypted in the file `.libpass-protected`
+//! at the store root so that they can be committed and shared along with the store.
+//! The protection is only honoured by this library; *pass* and other tools ignore it.
+//!
+//! ## Format
+//! The file contains one name per line, sorted, where `%`, CR and LF in names are percent-encoded.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::{PassError, Store};
+//!
+//! let store = Store::from_env().unwrap();
+//! store.protect("infrastructure/root-ca").unwrap();
+//! assert!(matches!(
+//!     store.remove("infrastructure/root-ca"),
+//!     Err(PassError::EntryProtected(_))
+//! ));
+//! ```
+
+use crate::{index, utils, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Name of the file at the store root which lists the protected entries
+pub const PROTECTION_FILE: &str = ".libpass-protected";
+
+/// Read the names of the protected entries of the store at *root*
+pub(crate) fn load(root: &Path) -> Result<BTreeSet<String>> {
+    let content = match fs::read_to_string(root.join(PROTECTION_FILE)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeSet::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(content
+        .lines()
+        .filter(|line| !line.is_empty())
+        // names which cannot be decoded can not match any entry
+        .filter_map(index::unescape)
+        .collect())
+}
+
+/// Replace the list of protected entries of the store at *root* with *names*
+///
+/// The file is removed once no entry is protected anymore.
+pub(crate) fn save(root: &Path, names: &BTreeSet<String>, umask: u32) -> Result<()> {
+    let path = root.join(PROTECTION_FILE);
+    if names.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+    let mut content = String::new();
+    for name in names {
+        content.push_str(&index::escape(name));
+        content.push('\n');
+    }
+    utils::write_atomically(&path, content.as_bytes(), umask)?;
+    Ok(())
+}
@@ -0,0 +1,170 @@
+//! Throttling of decryptions for long-running consumers
+//!
+//! Daemons which decrypt entries on behalf of other processes (e.g. a Secret Service frontend) can limit how
+//! many decryptions a misbehaving client is able to trigger by configuring a [`RateLimit`] via
+//! [`Store::with_rate_limit()`](crate::Store::with_rate_limit).
+//!
+//! ## Example
+//! ```
+//! use libpass::rate_limit::RateLimit;
+//! use libpass::Store;
+//! use std::time::Duration;
+//!
+//! let store = Store::open("tests/simple")
+//!     .unwrap()
+//!     .with_rate_limit(RateLimit::global(100, Duration::from_secs(60)))
+//!     .with_rate_limit(RateLimit::per_entry(5, Duration::from_secs(60)));
+//! ```
+
+use crate::telemetry::debug;
+use crate::{PassError, Result};
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The maximum number of decryptions that are allowed during a sliding time window
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RateLimit {
+    max_decryptions: usize,
+    period: Duration,
+    per_entry: bool,
+}
+
+impl RateLimit {
+    /// Allow at most *max_decryptions* decryptions of all entries together during any *period*
+    pub fn global(max_decryptions: usize, period: Duration) -> Self {
+        Self {
+            max_decryptions,
+            period,
+            per_entry: false,
+        }
+    }
+
+    /// Allow at most *max_decryptions* decryptions of each individual entry during any *period*
+    pub fn per_entry(max_decryptions: usize, period: Duration) -> Self {
+        Self {
+            max_decryptions,
+            period,
+            per_entry: true,
+        }
+    }
+
+    /// The maximum number of decryptions during [`period()`](RateLimit::period)
+    pub fn max_decryptions(&self) -> usize {
+        self.max_decryptions
+    }
+
+    /// The length of the sliding time window
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Whether decryptions are counted per entry instead of globally
+    pub fn is_per_entry(&self) -> bool {
+        self.per_entry
+    }
+
+    /// The key under which decryptions of *pass_name* are counted for this limit
+    fn key<'a>(&self, pass_name: &'a str) -> &'a str {
+        if self.per_entry {
+            pass_name
+        } else {
+            ""
+        }
+    }
+}
+
+/// Decryption timestamps of all entries within the last period of a limit
+type History = HashMap<String, VecDeque<Instant>>;
+
+/// The configured limits of a store together with the recent decryptions they are checked against
+///
+/// Clones share their history so that all clones of a store are limited together.
+/// Comparison and hashing only consider the configured limits.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RateLimiter {
+    limits: Vec<RateLimit>,
+    history: Arc<Mutex<Vec<History>>>,
+}
+
+impl RateLimiter {
+    /// Add *limit*, starting with a fresh history that is no longer shared with previous clones
+    pub(crate) fn add(&mut self, limit: RateLimit) {
+        self.limits.push(limit);
+        self.history = Default::default();
+    }
+
+    pub(crate) fn limits(&self) -> &[RateLimit] {
+        &self.limits
+    }
+
+    /// Count a decryption of *pass_name* if it is allowed by all limits
+    ///
+    /// ## Errors
+    /// [`PassError::RateLimited`] is returned if any limit would be exceeded; the decryption is not counted
+    /// then.
+    pub(crate) fn acquire(&self, pass_name: &str) -> Result<()> {
+        if self.limits.is_empty() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        // the history stays consistent even if another thread panicked while holding the lock
+        let mut history = self
+            .history
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        history.resize_with(self.limits.len(), HashMap::new);
+
+        let mut exceeded = None;
+        for (limit, history) in self.limits.iter().zip(history.iter_mut()) {
+            history.retain(|_, timestamps| {
+                while timestamps
+                    .front()
+                    .is_some_and(|timestamp| now.duration_since(*timestamp) >= limit.period)
+                {
+                    timestamps.pop_front();
+                }
+                !timestamps.is_empty()
+            });
+
+            let timestamps = history.get(limit.key(pass_name));
+            if timestamps.map_or(0, VecDeque::len) >= limit.max_decryptions {
+                let oldest = timestamps.and_then(VecDeque::front).copied().unwrap_or(now);
+                let wait = limit.period - now.duration_since(oldest);
+                exceeded = Some(exceeded.map_or(wait, |other: Duration| other.max(wait)));
+            }
+        }
+
+        if let Some(retry_after) = exceeded {
+            debug!("Rate limit for decrypting {} is exceeded", pass_name);
+            return Err(PassError::RateLimited {
+                pass_name: pass_name.to_string(),
+                retry_after,
+            });
+        }
+
+        for (limit, history) in self.limits.iter().zip(history.iter_mut()) {
+            history
+                .entry(limit.key(pass_name).to_string())
+                .or_default()
+                .push_back(now);
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for RateLimiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.limits == other.limits
+    }
+}
+
+impl Eq for RateLimiter {}
+
+impl Hash for RateLimiter {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.limits.hash(state);
+    }
+}
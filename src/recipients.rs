@@ -0,0 +1,150 @@
+//! Resolution of the `.gpg-id` file that governs a location inside of a store
+//!
+//! Looking up the recipients of an entry requires walking up the directory hierarchy until a `.gpg-id` file
+//! is found and parsing it.
+//! Since most entries of a store share few `.gpg-id` files, the results are cached per directory.
+
+use crate::telemetry::trace;
+use crate::{GpgIdFile, PassError, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// The recipients of a `.gpg-id` file
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct GpgIdRecipients {
+    /// The path of the `.gpg-id` file
+    pub(crate) gpg_id: PathBuf,
+    /// The recipients listed in the file
+    pub(crate) recipients: Vec<String>,
+    /// The modification time of the file when it was read
+    modified: Option<SystemTime>,
+}
+
+impl GpgIdRecipients {
+    fn read(gpg_id: PathBuf) -> Result<Self> {
+        let modified = fs::metadata(&gpg_id)?.modified().ok();
+        let recipients = GpgIdFile::read(&gpg_id)?.recipients().to_vec();
+        Ok(Self {
+            gpg_id,
+            recipients,
+            modified,
+        })
+    }
+
+    /// Whether the file was not modified since it was read
+    fn is_current(&self) -> bool {
+        self.gpg_id.is_file()
+            && fs::metadata(&self.gpg_id)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                == self.modified
+    }
+}
+
+/// A cached result of resolving the `.gpg-id` file which governs a directory
+#[derive(Debug)]
+struct Resolution {
+    recipients: Arc<GpgIdRecipients>,
+    /// The `.gpg-id` files which did not exist in the directories between the resolved one and the governing one
+    absent: Vec<PathBuf>,
+}
+
+impl Resolution {
+    /// Whether the governing file was not modified and no `.gpg-id` file was created in between since the
+    /// result was cached
+    fn is_current(&self) -> bool {
+        self.recipients.is_current() && self.absent.iter().all(|gpg_id| !gpg_id.exists())
+    }
+}
+
+/// Resolves the governing `.gpg-id` file of directories and caches the results
+///
+/// Clones share their cache.
+/// A cached result is reused as long as the `.gpg-id` file was not modified and no `.gpg-id` file was
+/// created in one of the directories between it and the resolved directory.
+/// Comparison and hashing ignore the cache.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RecipientResolver {
+    cache: Arc<Mutex<HashMap<PathBuf, Resolution>>>,
+}
+
+impl RecipientResolver {
+    fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, Resolution>> {
+        // entries are only inserted once they are complete so a panic while holding the lock is harmless
+        self.cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The `.gpg-id` file governing *dir* which is the nearest one in *dir* or its parents up to *root*
+    ///
+    /// ## Errors
+    /// [`PassError::StoreNotInitialized`] is returned if there is no such file.
+    pub(crate) fn resolve(&self, root: &Path, dir: &Path) -> Result<Arc<GpgIdRecipients>> {
+        let mut cache = self.lock();
+        if let Some(cached) = cache.get(dir).filter(|cached| cached.is_current()) {
+            return Ok(cached.recipients.clone());
+        }
+
+        let mut visited = Vec::new();
+        let mut absent = Vec::new();
+        for ancestor in dir
+            .ancestors()
+            .take_while(|ancestor| ancestor.starts_with(root))
+        {
+            let gpg_id = ancestor.join(".gpg-id");
+            if gpg_id.is_dir() {
+                return Err(PassError::InvalidStoreFormat(
+                    gpg_id,
+                    "Path is a directory but should be a file containing encryption key ids"
+                        .to_string(),
+                ));
+            }
+            visited.push(ancestor.to_path_buf());
+            if !gpg_id.is_file() {
+                absent.push(gpg_id);
+                continue;
+            }
+
+            let found = Arc::new(GpgIdRecipients::read(gpg_id)?);
+            trace!(
+                "{} is governed by {}",
+                dir.display(),
+                found.gpg_id.display()
+            );
+            for (i, visited) in visited.into_iter().enumerate() {
+                cache.insert(
+                    visited,
+                    Resolution {
+                        recipients: found.clone(),
+                        absent: absent[i..].to_vec(),
+                    },
+                );
+            }
+            return Ok(found);
+        }
+
+        Err(PassError::StoreNotInitialized(root.to_owned()))
+    }
+
+    /// Forget all cached results
+    pub(crate) fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+impl PartialEq for RecipientResolver {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for RecipientResolver {}
+
+impl Hash for RecipientResolver {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
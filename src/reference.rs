@@ -0,0 +1,174 @@
+//! References from one entry to the password of another
+//!
+//! Credentials which are shared by several accounts can live in one entry and be referenced from all
+//! others so that they only need to be changed in one place:
+//! ```text
+//! !ref shared/database
+//! user: john
+//! backup-password: !ref shared/backup
+//! ```
+//! A password line or field value of the form `!ref <pass-name>` is replaced by the password of the
+//! referenced entry when a [`ReferenceResolver`] is registered with
+//! [`Store::with_transformer()`](crate::Store::with_transformer) and the entry is retrieved with
+//! [`Store::retrieve_entry()`](crate::Store::retrieve_entry).
+//! The stored content keeps the reference and the spacing around the value of a field is preserved.
+//! Only [`Store::retrieve_entry()`](crate::Store::retrieve_entry) resolves references, so entries which are
+//! read with [`StoreFileRef::parse()`](crate::StoreFileRef::parse) or through the plaintext handles still
+//! contain them.
+//!
+//! Referenced entries are retrieved through all transformers of the store so references can be nested.
+//! References which lead back to an entry which is already being resolved fail with
+//! [`PassError::ReferenceLoop`] and nesting is limited to [`ReferenceResolver::max_depth()`] levels.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::reference::ReferenceResolver;
+//! use libpass::Store;
+//!
+//! let store = Store::from_env()
+//!     .unwrap()
+//!     .with_transformer(ReferenceResolver::new());
+//! let entry = store.retrieve_entry("servers/db").unwrap();
+//! println!("{}", entry.password());
+//! ```
+
+use crate::parsed_entry::{parse_field_span, split_line_ending};
+use crate::secure::SecretString;
+use crate::secure_mem::{self, push_str};
+use crate::transform::{EntryTransformer, TransformContext};
+use crate::{ParsedEntry, PassError, Result};
+
+/// The marker with which references start
+pub const REFERENCE_PREFIX: &str = "!ref";
+
+/// The number of nested references which [`ReferenceResolver::new()`] resolves at most
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+
+/// A [transformer](crate::transform) which replaces `!ref <pass-name>` references by the password of the
+/// referenced entry
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ReferenceResolver {
+    max_depth: usize,
+}
+
+impl ReferenceResolver {
+    /// Create a resolver which follows up to [`DEFAULT_MAX_DEPTH`] nested references
+    pub fn new() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Follow up to *max_depth* nested references
+    ///
+    /// A depth of 1 resolves the references of the retrieved entry but not those of the referenced entries.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// The maximum number of nested references which are followed
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// The password of the entry *pass_name* which is referenced by the entry described by *context*
+    fn resolve(&self, context: &TransformContext<'_>, pass_name: &str) -> Result<SecretString> {
+        if context.depth() >= self.max_depth {
+            let mut chain = context.chain().to_vec();
+            chain.push(pass_name.to_string());
+            return Err(PassError::ReferenceTooDeep {
+                chain,
+                max_depth: self.max_depth,
+            });
+        }
+        Ok(SecretString::from(
+            context.retrieve_entry(pass_name)?.password(),
+        ))
+    }
+}
+
+impl Default for ReferenceResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EntryTransformer for ReferenceResolver {
+    fn transform(&self, context: &TransformContext<'_>, entry: ParsedEntry) -> Result<ParsedEntry> {
+        let mut content = String::with_capacity(entry.content().len());
+        match self.resolve_lines(context, &entry, &mut content) {
+            Ok(true) => Ok(ParsedEntry::from(content).with_mode(entry.mode())),
+            Ok(false) => {
+                secure_mem::release_string(content);
+                Ok(entry)
+            }
+            Err(e) => {
+                // passwords which were already resolved must not be left behind
+                secure_mem::release_string(content);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl ReferenceResolver {
+    /// Write the lines of *entry* with all references resolved into *content*
+    ///
+    /// Returns whether any reference was resolved.
+    fn resolve_lines(
+        &self,
+        context: &TransformContext<'_>,
+        entry: &ParsedEntry,
+        content: &mut String,
+    ) -> Result<bool> {
+        let mut resolved = false;
+        for (i, line) in entry.content().split_inclusive('\n').enumerate() {
+            let (text, newline) = split_line_ending(line);
+
+            // the value which may be a reference and the text around it which is kept, i.e. the key and the
+            // spacing of fields
+            let (value, prefix, suffix) = match (i, parse_field_span(text)) {
+                (0, _) => (text, "", ""),
+                (_, Some(field)) => (
+                    &text[field.value.clone()],
+                    &text[..field.value.start],
+                    &text[field.value.end..],
+                ),
+                (_, None) => {
+                    push_str(content, line);
+                    continue;
+                }
+            };
+            match parse_reference(value)? {
+                Some(target) => {
+                    let replacement = self.resolve(context, target)?;
+                    for part in [prefix, replacement.expose(), suffix, newline] {
+                        push_str(content, part);
+                    }
+                    resolved = true;
+                }
+                None => push_str(content, line),
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+/// The name of the entry which *value* references or `None` if it is no reference
+///
+/// ## Errors
+/// [`PassError::MalformedEntry`] is returned if the reference does not name an entry.
+fn parse_reference(value: &str) -> Result<Option<&str>> {
+    let value = value.trim();
+    let target = match value.strip_prefix(REFERENCE_PREFIX) {
+        Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => rest.trim(),
+        _ => return Ok(None),
+    };
+    match target.is_empty() {
+        true => Err(PassError::MalformedEntry(
+            "A reference does not name an entry".to_string(),
+        )),
+        false => Ok(Some(target)),
+    }
+}
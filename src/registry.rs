@@ -0,0 +1,217 @@
+//! Multiple stores combined into a single namespace
+//!
+//! A [`StoreRegistry`] mounts stores at prefixes of a common namespace like `gopass` does so that e.g. a team
+//! store at `work/` and a personal store at the root can be used through the same names.
+//! Names are resolved to the store of the longest matching mount point.
+//!
+//! With the `gopass` feature, a registry can be populated from the configuration file of
+//! [gopass](https://www.gopass.pw/) with [`StoreRegistry::from_gopass()`].
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::registry::StoreRegistry;
+//! use libpass::Store;
+//!
+//! let registry = StoreRegistry::new()
+//!     .with_root(Store::from_env().unwrap())
+//!     .with_mount("work", Store::open("~/.password-store-work").unwrap())
+//!     .unwrap();
+//! let entry = registry.retrieve("work/servers/database").unwrap();
+//! ```
+
+use crate::{PassError, PassName, Result, Store, StoreEntry};
+use std::collections::BTreeMap;
+#[cfg(feature = "gopass")]
+use std::path::{Path, PathBuf};
+
+/// Stores mounted at prefixes of a common namespace
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct StoreRegistry {
+    root: Option<Store>,
+    mounts: BTreeMap<PassName, Store>,
+}
+
+impl StoreRegistry {
+    /// Create a registry without any stores
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use *store* for all names which are not below a mount point
+    pub fn with_root(mut self, store: Store) -> Self {
+        self.root = Some(store);
+        self
+    }
+
+    /// The store which is used for names outside of all mount points
+    pub fn root(&self) -> Option<&Store> {
+        self.root.as_ref()
+    }
+
+    /// Mount *store* at *prefix* so that the entry `<prefix>/<name>` refers to `<name>` inside of *store*
+    ///
+    /// A store which is already mounted at the same prefix is replaced.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidPassName`] is returned if *prefix* is not a valid name or the empty name.
+    pub fn with_mount(mut self, prefix: &str, store: Store) -> Result<Self> {
+        let normalized = PassName::new(prefix)?;
+        if normalized.is_root() {
+            return Err(PassError::InvalidPassName(
+                prefix.to_string(),
+                "the store root cannot be used as mount point".to_string(),
+            ));
+        }
+        self.mounts.insert(normalized, store);
+        Ok(self)
+    }
+
+    /// All mounted stores by their prefix
+    pub fn mounts(&self) -> &BTreeMap<PassName, Store> {
+        &self.mounts
+    }
+
+    /// Find the store which contains *pass_name* and the name of the entry inside of it
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if *pass_name* is not below a mount point and the registry has
+    /// no root store.
+    pub fn resolve(&self, pass_name: &str) -> Result<(&Store, PassName)> {
+        let normalized = PassName::new(pass_name)?;
+        let mount = self
+            .mounts
+            .iter()
+            .filter_map(|(prefix, store)| {
+                let rest = normalized.strip_prefix(prefix)?;
+                Some((prefix.len(), store, rest))
+            })
+            .max_by_key(|(len, _, _)| *len);
+        match (mount, &self.root) {
+            (Some((_, store, rest)), _) => Ok((store, rest)),
+            (None, Some(root)) => Ok((root, normalized)),
+            (None, None) => Err(PassError::EntryNotFound(normalized.into_string())),
+        }
+    }
+
+    /// Retrieve the entry identified by *pass_name* from the store it is mounted in
+    ///
+    /// See [`Store::retrieve()`].
+    pub fn retrieve(&self, pass_name: &str) -> Result<StoreEntry> {
+        let (store, name) = self.resolve(pass_name)?;
+        store.retrieve(&name)
+    }
+
+    /// The names of all entries of all stores in the namespace of this registry, sorted
+    ///
+    /// Entries of the root store which are hidden by a mount point are omitted.
+    pub fn names(&self) -> Result<Vec<String>> {
+        let mut all = Vec::new();
+        if let Some(root) = &self.root {
+            for name in root.names()? {
+                let name = name?;
+                if self.resolve(&name)?.0.root() == root.root() {
+                    all.push(name);
+                }
+            }
+        }
+        for (prefix, store) in &self.mounts {
+            for name in store.names()? {
+                let name = prefix.join(&name?)?.into_string();
+                // nested mount points hide entries of the store they are mounted in
+                if self.resolve(&name)?.0.root() == store.root() {
+                    all.push(name);
+                }
+            }
+        }
+        all.sort_unstable();
+        Ok(all)
+    }
+
+    /// Create a registry from the gopass configuration file at the default location
+    ///
+    /// The location is taken from `GOPASS_CONFIG` or defaults to `config.yml` in the gopass directory of the
+    /// user's configuration directory (e.g. `~/.config/gopass/config.yml`).
+    /// See [`StoreRegistry::from_gopass_config()`].
+    #[cfg(feature = "gopass")]
+    pub fn from_gopass() -> Result<Self> {
+        let path = match std::env::var_os(GOPASS_CONFIG_ENV) {
+            Some(path) => PathBuf::from(path),
+            None => directories::BaseDirs::new()
+                .map(|dirs| dirs.config_dir().join("gopass").join("config.yml"))
+                .ok_or_else(|| PassError::InvalidConfig {
+                    variable: GOPASS_CONFIG_ENV.to_string(),
+                    value: String::new(),
+                    reason: "the platform has no configuration directory".to_string(),
+                })?,
+        };
+        Self::from_gopass_config(path)
+    }
+
+    /// Create a registry from the YAML configuration file of gopass at *path*
+    ///
+    /// The root store is opened at `root.path` (or the legacy top-level `path`) and every store of `mounts` is
+    /// mounted under its name.
+    /// Store paths may use the `<backends>+file://` URLs that gopass writes as well as plain paths.
+    /// All stores use the default [`Config`](crate::Config).
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidConfig`] is returned if the file is no valid configuration and
+    /// [`PassError::PasswordStoreNotFound`] if a configured store does not exist.
+    #[cfg(feature = "gopass")]
+    pub fn from_gopass_config(path: impl AsRef<Path>) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let invalid = |variable: &str, reason: String| PassError::InvalidConfig {
+            variable: variable.to_string(),
+            value: String::new(),
+            reason,
+        };
+        let config: serde_yaml::Value =
+            serde_yaml::from_str(&content).map_err(|e| invalid("config.yml", e.to_string()))?;
+
+        let mut registry = Self::new();
+        let root_path = config
+            .get("root")
+            .and_then(|root| root.get("path"))
+            .or_else(|| config.get("path"));
+        if let Some(value) = root_path {
+            registry.root = Some(Store::open(gopass_store_path("root.path", value)?)?);
+        }
+        match config.get("mounts") {
+            None | Some(serde_yaml::Value::Null) => {}
+            Some(serde_yaml::Value::Mapping(mounts)) => {
+                for (name, mount) in mounts {
+                    let name = name.as_str().ok_or_else(|| {
+                        invalid("mounts", "a mount name is no string".to_string())
+                    })?;
+                    let variable = format!("mounts.{}.path", name);
+                    // gopass < 1.10 stored the path directly instead of a mapping
+                    let value = mount.get("path").unwrap_or(mount);
+                    let store = Store::open(gopass_store_path(&variable, value)?)?;
+                    registry = registry.with_mount(name, store)?;
+                }
+            }
+            Some(_) => return Err(invalid("mounts", "expected a mapping".to_string())),
+        }
+        Ok(registry)
+    }
+}
+
+/// The environment variable which overrides the location of the gopass configuration file
+#[cfg(feature = "gopass")]
+pub const GOPASS_CONFIG_ENV: &str = "GOPASS_CONFIG";
+
+/// Extract the directory of a store from the path *value* of the gopass configuration *variable*
+#[cfg(feature = "gopass")]
+fn gopass_store_path(variable: &str, value: &serde_yaml::Value) -> Result<PathBuf> {
+    let value = value.as_str().ok_or_else(|| PassError::InvalidConfig {
+        variable: variable.to_string(),
+        value: format!("{:?}", value),
+        reason: "expected a path".to_string(),
+    })?;
+    // e.g. gpgcli-gitcli-fs+file:///home/user/.password-store
+    let path = match value.split_once("file://") {
+        Some((backends, path)) if backends.is_empty() || backends.ends_with('+') => path,
+        _ => value,
+    };
+    Ok(PathBuf::from(path))
+}
@@ -0,0 +1,231 @@
+//! Scrubbing of removed entries for policies that require retired credentials to be erased
+//!
+//! Unlinking a file leaves its content on disk until the space is reused and a store which is managed with
+//! git keeps every removed entry in its history.
+//! With [`Store::with_secure_delete()`](crate::Store::with_secure_delete),
+//! [`Store::remove()`](crate::Store::remove) overwrites the encrypted file before unlinking it.
+//! This is best-effort only: copy-on-write and journaling filesystems, SSDs and backups may still hold old
+//! copies.
+//!
+//! To erase entries from other places like the git history, register a [`ScrubHook`] with
+//! [`Store::with_scrub_hook()`](crate::Store::with_scrub_hook).
+//! Hooks are called after the entry was removed and [`CommandHook`] runs an external program for this, e.g.
+//! `git filter-repo`.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::scrub::CommandHook;
+//! use libpass::Store;
+//!
+//! let store = Store::from_env()
+//!     .unwrap()
+//!     .with_secure_delete(true)
+//!     .with_scrub_hook(CommandHook::new(
+//!         "git",
+//!         ["filter-repo", "--force", "--invert-paths", "--path"],
+//!     ));
+//! store.remove("retired/database").unwrap();
+//! ```
+
+use crate::telemetry::{debug, warning};
+use crate::Result;
+use std::fmt::{Debug, Formatter};
+use std::fs::{File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// An entry which was removed from a store
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RemovedEntry {
+    /// Name of the removed entry relative to the store root (without `.gpg` suffix)
+    pub pass_name: String,
+    /// The path of the removed file relative to the store root (e.g. `folder/entry.gpg`)
+    pub path: PathBuf,
+}
+
+/// An action which erases traces of a removed entry
+///
+/// Hooks are called synchronously by [`Store::remove()`](crate::Store::remove) with the root of the store
+/// after the entry was removed.
+/// Any closure taking the store root and a [`RemovedEntry`] can be used as hook.
+pub trait ScrubHook: Send + Sync {
+    /// Erase traces of *entry* from the store at *root*
+    fn scrub(&self, root: &Path, entry: &RemovedEntry) -> Result<()>;
+}
+
+impl<F> ScrubHook for F
+where
+    F: Fn(&Path, &RemovedEntry) -> Result<()> + Send + Sync,
+{
+    fn scrub(&self, root: &Path, entry: &RemovedEntry) -> Result<()> {
+        self(root, entry)
+    }
+}
+
+/// A hook which runs a program inside the store root with the path of the removed file as last argument
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct CommandHook {
+    program: PathBuf,
+    args: Vec<String>,
+}
+
+impl CommandHook {
+    /// Run *program* with *args* followed by the path of the removed file relative to the store root
+    pub fn new(
+        program: impl Into<PathBuf>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// The program which is run
+    pub fn program(&self) -> &Path {
+        &self.program
+    }
+
+    /// The arguments which precede the path of the removed file
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+}
+
+impl ScrubHook for CommandHook {
+    /// ## Errors
+    /// An io error is returned if the program cannot be started or exits unsuccessfully.
+    fn scrub(&self, root: &Path, entry: &RemovedEntry) -> Result<()> {
+        debug!(
+            "Running {} {} {}",
+            self.program.display(),
+            self.args.join(" "),
+            entry.path.display()
+        );
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .arg(&entry.path)
+            .current_dir(root)
+            .output()?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "{} failed with {}: {}",
+                    self.program.display(),
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            )
+            .into())
+        }
+    }
+}
+
+/// The scrubbing settings of a store
+///
+/// Only `overwrite` is compared and hashed because hooks are closures.
+#[derive(Clone, Default)]
+pub(crate) struct Scrubber {
+    overwrite: bool,
+    hooks: Vec<Arc<dyn ScrubHook>>,
+}
+
+impl Scrubber {
+    pub(crate) fn set_overwrite(&mut self, enabled: bool) {
+        self.overwrite = enabled;
+    }
+
+    pub(crate) fn overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    pub(crate) fn add_hook(&mut self, hook: Arc<dyn ScrubHook>) {
+        self.hooks.push(hook);
+    }
+
+    /// Overwrite the content of the file at *path* if enabled so that it does not linger on disk after
+    /// unlinking it
+    ///
+    /// Files with multiple hard links (like entries which are part of a snapshot) are left untouched since
+    /// overwriting them would destroy the other links as well.
+    pub(crate) fn prepare_removal(&self, path: &Path) -> io::Result<()> {
+        if !self.overwrite {
+            return Ok(());
+        }
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        if link_count(&file)? > 1 {
+            warning!(
+                "Not overwriting {} because it is hard-linked",
+                path.display()
+            );
+            return Ok(());
+        }
+        let mut remaining = file.metadata()?.len();
+        let zeroes = [0u8; 8192];
+        while remaining > 0 {
+            let chunk = remaining.min(zeroes.len() as u64) as usize;
+            file.write_all(&zeroes[..chunk])?;
+            remaining -= chunk as u64;
+        }
+        file.sync_all()
+    }
+
+    /// Call all hooks for *entry* which was removed from the store at *root*
+    ///
+    /// All hooks are called even if one of them fails; the first error is returned.
+    pub(crate) fn run_hooks(&self, root: &Path, entry: &RemovedEntry) -> Result<()> {
+        let mut result = Ok(());
+        for hook in &self.hooks {
+            if let Err(e) = hook.scrub(root, entry) {
+                warning!("Scrubbing {} failed: {}", entry.pass_name, e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Debug for Scrubber {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scrubber")
+            .field("overwrite", &self.overwrite)
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+impl PartialEq for Scrubber {
+    fn eq(&self, other: &Self) -> bool {
+        self.overwrite == other.overwrite
+    }
+}
+
+impl Eq for Scrubber {}
+
+impl Hash for Scrubber {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.overwrite.hash(state);
+    }
+}
+
+/// The number of hard links to *file*
+fn link_count(file: &File) -> io::Result<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Ok(file.metadata()?.nlink())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = file;
+        Ok(1)
+    }
+}
@@ -0,0 +1,90 @@
+//! Helpers for workflows which verify secrets instead of revealing them
+//!
+//! Comparing secrets with `==` returns as soon as the first differing byte is found which leaks through
+//! timing how much of a guess was correct.
+//! The functions in this module take the same time regardless of where the inputs differ.
+//!
+//! ## Example
+//! ```
+//! use libpass::secure::constant_time_eq;
+//!
+//! assert!(constant_time_eq(b"hunter2", b"hunter2"));
+//! assert!(!constant_time_eq(b"hunter2", b"hunter3"));
+//! ```
+
+use crate::secure_mem::{self, LockedRegion};
+use crate::{Result, StoreFileRef};
+use std::fmt::{Debug, Formatter};
+use std::hint::black_box;
+
+/// Compare *a* and *b* in time that only depends on their lengths
+///
+/// Only the lengths are compared directly, so whether both inputs have the same length may be observable.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let difference = a
+        .iter()
+        .zip(b)
+        .fold(0u8, |difference, (x, y)| difference | (x ^ y));
+    black_box(difference) == 0
+}
+
+/// Decrypt *entry* and check whether its password (the first line) equals *candidate*
+///
+/// The comparison is done with [`constant_time_eq()`] and the decrypted content is never copied outside of
+/// the plaintext buffer of the entry.
+pub fn verify_password(entry: &StoreFileRef, candidate: impl AsRef<[u8]>) -> Result<bool> {
+    let plain = entry.plain_io_ro()?;
+    let content = plain.as_ref().as_slice();
+    let password = content.split(|b| *b == b'\n').next().unwrap_or_default();
+    let password = password.strip_suffix(b"\r").unwrap_or(password);
+    Ok(constant_time_eq(password, candidate.as_ref()))
+}
+
+/// A string holding a secret like a password
+///
+/// The secret is not shown by the [`Debug`] implementation and, with the `secure-mem` feature, overwritten
+/// with zeroes when it is dropped.
+/// It can neither be cloned nor compared so that no copies or timing leaks are created by accident; use
+/// [`constant_time_eq()`] to compare secrets.
+#[derive(Default)]
+pub struct SecretString(String);
+
+impl SecretString {
+    /// Wrap *secret*
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// Reveal the secret
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for SecretString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretString(..)")
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        let mut buffer = std::mem::take(&mut self.0).into_bytes();
+        secure_mem::release(&mut buffer, &mut LockedRegion::default());
+    }
+}
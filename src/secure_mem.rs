@@ -0,0 +1,248 @@
+//! Protection of plaintext buffers against being written to swap
+//!
+//! With the `secure-mem` feature enabled, decrypted buffers are locked into RAM (via `mlock` on unix and
+//! `VirtualLock` on windows) and overwritten with zeroes before they are freed.
+//! Without the feature, all operations are no-ops.
+//!
+//! Operating systems lock whole pages and do not count how often a page was locked, so the pages of all
+//! locked buffers are counted here and a page is only unlocked once no locked buffer uses it anymore.
+
+/// The allocation of a plaintext buffer which is locked into RAM
+#[derive(Debug, Default)]
+pub(crate) struct LockedRegion {
+    /// Address and length of the locked allocation
+    #[cfg(feature = "secure-mem")]
+    region: Option<(usize, usize)>,
+}
+
+impl LockedRegion {
+    /// Lock the allocation of *buffer* into RAM, unlocking the previously locked allocation if it differs
+    ///
+    /// Failing to lock is not an error because the operating system may restrict how much memory can be
+    /// locked (e.g. via `RLIMIT_MEMLOCK`).
+    /// Instead, a warning is logged once and the buffer stays unlocked.
+    pub(crate) fn lock(&mut self, buffer: &Vec<u8>) {
+        #[cfg(feature = "secure-mem")]
+        {
+            let region = (buffer.as_ptr() as usize, buffer.capacity());
+            if self.region == Some(region) {
+                return;
+            }
+            self.unlock();
+            if region.1 == 0 {
+                return;
+            }
+
+            match lock_pages(region) {
+                Ok(()) => self.region = Some(region),
+                Err(e) => warn_once(&e),
+            }
+        }
+        #[cfg(not(feature = "secure-mem"))]
+        let _ = buffer;
+    }
+
+    /// Unlock the currently locked allocation
+    fn unlock(&mut self) {
+        #[cfg(feature = "secure-mem")]
+        if let Some(region) = self.region.take() {
+            if let Err(e) = unlock_pages(region) {
+                crate::telemetry::debug!("Could not unlock plaintext buffer: {}", e);
+            }
+        }
+    }
+}
+
+/// Overwrite *buffer* with zeroes and unlock its allocation
+///
+/// This must be called before *buffer* is freed.
+pub(crate) fn release(buffer: &mut Vec<u8>, region: &mut LockedRegion) {
+    #[cfg(feature = "secure-mem")]
+    zeroize::Zeroize::zeroize(buffer);
+    #[cfg(not(feature = "secure-mem"))]
+    let _ = buffer;
+    region.unlock();
+}
+
+/// Make sure that *buffer* can hold *additional* more bytes without being reallocated
+///
+/// If the allocation is too small, the content is moved into a larger one and the old allocation is released
+/// with [`release()`] so that no copy of the content is left behind in freed memory.
+pub(crate) fn reserve(buffer: &mut Vec<u8>, region: &mut LockedRegion, additional: usize) {
+    if buffer.capacity() - buffer.len() >= additional {
+        return;
+    }
+    let capacity = (buffer.len() + additional).max(buffer.capacity() * 2);
+    let mut grown = Vec::with_capacity(capacity);
+    grown.extend_from_slice(buffer);
+    let mut old = std::mem::replace(buffer, grown);
+    release(&mut old, region);
+    region.lock(buffer);
+}
+
+/// Append *s* to *buffer* like [`String::push_str()`] but release the old allocation if it has to grow
+///
+/// See [`reserve()`].
+pub(crate) fn push_str(buffer: &mut String, s: &str) {
+    if buffer.capacity() - buffer.len() < s.len() {
+        let mut grown = String::with_capacity((buffer.len() + s.len()).max(buffer.capacity() * 2));
+        grown.push_str(buffer);
+        release_string(std::mem::replace(buffer, grown));
+    }
+    buffer.push_str(s);
+}
+
+/// Overwrite *buffer* with zeroes before freeing it
+pub(crate) fn release_string(buffer: String) {
+    release(&mut buffer.into_bytes(), &mut LockedRegion::default());
+}
+
+/// Number of locked regions which overlap a page, keyed by the address of the page
+#[cfg(feature = "secure-mem")]
+static LOCKED_PAGES: std::sync::Mutex<std::collections::BTreeMap<usize, usize>> =
+    std::sync::Mutex::new(std::collections::BTreeMap::new());
+
+/// The addresses of all pages which overlap *region*
+#[cfg(feature = "secure-mem")]
+fn pages((address, len): (usize, usize)) -> impl Iterator<Item = usize> {
+    let page_size = sys::page_size();
+    let start = address & !(page_size - 1);
+    let end = (address + len + page_size - 1) & !(page_size - 1);
+    (start..end).step_by(page_size)
+}
+
+/// Lock *region* into RAM and count its pages as used
+#[cfg(feature = "secure-mem")]
+fn lock_pages(region: (usize, usize)) -> std::io::Result<()> {
+    let mut locked = LOCKED_PAGES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    // locking pages again is harmless because the operating system does not count locks
+    sys::lock(region)?;
+    for page in pages(region) {
+        *locked.entry(page).or_default() += 1;
+    }
+    Ok(())
+}
+
+/// Stop counting the pages of *region* as used and unlock those which are not used by another region
+#[cfg(feature = "secure-mem")]
+fn unlock_pages(region: (usize, usize)) -> std::io::Result<()> {
+    let mut locked = LOCKED_PAGES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let page_size = sys::page_size();
+    let mut result = Ok(());
+    let mut unused: Option<(usize, usize)> = None;
+    for page in pages(region) {
+        let count = locked.entry(page).or_insert(1);
+        *count -= 1;
+        if *count == 0 {
+            locked.remove(&page);
+            let (start, len) = unused.get_or_insert((page, 0));
+            debug_assert_eq!(*start + *len, page);
+            *len += page_size;
+        } else if let Some(run) = unused.take() {
+            result = result.and(sys::unlock(run));
+        }
+    }
+    if let Some(run) = unused {
+        result = result.and(sys::unlock(run));
+    }
+    result
+}
+
+#[cfg(feature = "secure-mem")]
+fn warn_once(error: &std::io::Error) {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    static WARNED: AtomicBool = AtomicBool::new(false);
+
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        crate::telemetry::warning!(
+            "Could not lock plaintext buffer into memory so that it may be swapped to disk \
+            (is the limit of locked memory too low?): {}",
+            error
+        );
+    }
+}
+
+#[cfg(all(feature = "secure-mem", unix))]
+#[allow(unsafe_code)]
+mod sys {
+    use std::io;
+
+    pub(super) fn lock((address, len): (usize, usize)) -> io::Result<()> {
+        // SAFETY: the region is the live allocation of a Vec; mlock does not access its content
+        match unsafe { libc::mlock(address as *const libc::c_void, len) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    pub(super) fn unlock((address, len): (usize, usize)) -> io::Result<()> {
+        // SAFETY: see lock()
+        match unsafe { libc::munlock(address as *const libc::c_void, len) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    pub(super) fn page_size() -> usize {
+        // SAFETY: sysconf only reads system configuration
+        match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+            size if size > 0 => size as usize,
+            _ => 4096,
+        }
+    }
+}
+
+#[cfg(all(feature = "secure-mem", windows))]
+#[allow(unsafe_code)]
+mod sys {
+    use std::io;
+    use windows_sys::Win32::System::Memory::{VirtualLock, VirtualUnlock};
+    use windows_sys::Win32::System::SystemInformation::{GetSystemInfo, SYSTEM_INFO};
+
+    pub(super) fn lock((address, len): (usize, usize)) -> io::Result<()> {
+        // SAFETY: the region is the live allocation of a Vec; VirtualLock does not access its content
+        match unsafe { VirtualLock(address as *const std::ffi::c_void, len) } {
+            0 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn unlock((address, len): (usize, usize)) -> io::Result<()> {
+        // SAFETY: see lock()
+        match unsafe { VirtualUnlock(address as *const std::ffi::c_void, len) } {
+            0 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn page_size() -> usize {
+        // SAFETY: GetSystemInfo only writes into the given struct which is valid when zeroed
+        let mut info: SYSTEM_INFO = unsafe { std::mem::zeroed() };
+        unsafe { GetSystemInfo(&mut info) };
+        info.dwPageSize as usize
+    }
+}
+
+#[cfg(all(feature = "secure-mem", not(any(unix, windows))))]
+mod sys {
+    use std::io;
+
+    pub(super) fn lock(_: (usize, usize)) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "memory locking is not supported on this platform",
+        ))
+    }
+
+    pub(super) fn unlock(_: (usize, usize)) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub(super) fn page_size() -> usize {
+        4096
+    }
+}
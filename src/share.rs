@@ -0,0 +1,57 @@
+//! Ad-hoc sharing of single entries with people who have no access to the store
+//!
+//! Sending a credential to a colleague should not involve pasting it into a chat.
+//! [`Store::share()`](crate::Store::share) decrypts an entry and encrypts its content for a single key from
+//! the local keyring which does not need to be listed in any `.gpg-id` file.
+//! The result is an ASCII armored message which can be sent over any channel and decrypted by the recipient
+//! with `gpg --decrypt`, together with metadata describing what was shared with whom.
+//! Nothing is written into the store.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::share::ShareOptions;
+//! use libpass::Store;
+//!
+//! let store = Store::from_env().unwrap();
+//! let shared = store
+//!     .share("servers/database", "colleague@example.com", &ShareOptions::default().with_password_only(true))
+//!     .unwrap();
+//! println!("Encrypted for {:?}:\n{}", shared.recipient.user_ids, shared.armored);
+//! ```
+
+use crate::access::AccessKey;
+use std::time::SystemTime;
+
+/// Options for [`Store::share()`](crate::Store::share)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct ShareOptions {
+    password_only: bool,
+}
+
+impl ShareOptions {
+    /// Only share the password (the first line) of the entry instead of its whole content
+    pub fn with_password_only(mut self, password_only: bool) -> Self {
+        self.password_only = password_only;
+        self
+    }
+
+    /// Whether only the password of the entry is shared
+    pub fn password_only(&self) -> bool {
+        self.password_only
+    }
+}
+
+/// An entry which was encrypted for a single recipient outside of the store
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SharedEntry {
+    /// The name of the shared entry
+    pub pass_name: String,
+    /// The key for which the content was encrypted
+    pub recipient: AccessKey,
+    /// Whether only the password of the entry was shared
+    pub password_only: bool,
+    /// The time at which the entry was shared
+    pub created_at: SystemTime,
+    /// The ASCII armored OpenPGP message containing the shared content
+    pub armored: String,
+}
@@ -0,0 +1,484 @@
+//! Point-in-time copies of stores for users who do not manage their store with git
+//!
+//! [`Store::snapshot()`](crate::Store::snapshot) copies the encrypted files of a store into a new directory
+//! named after the current time (like `20261016T120000Z`) below a destination directory.
+//! On unix systems, files are hard-linked into the snapshot if the destination is on the same filesystem so
+//! that snapshots only take up space for entries which changed afterwards.
+//! This library always replaces hard-linked files instead of modifying them, so snapshots are never changed
+//! by later writes through it.
+//! **Note:** Writing directly into a [`CipherFile`](crate::file_io::CipherFile) handle modifies the file in
+//! place and thereby also the snapshots which share it.
+//!
+//! Entries are rolled back with [`Store::restore()`](crate::Store::restore).
+//! The `.git` directory of a store is not part of its snapshots.
+//! The destination of snapshots and backups must be outside of the store because the store would otherwise
+//! copy itself into itself.
+//!
+//! ## Deduplicated backups
+//! Where hard links are not available (e.g. on external drives or network shares),
+//! [`Store::backup()`](crate::Store::backup) writes a content-addressed [`Backup`] instead:
+//! every encrypted file is stored once as a blob named by the SHA-256 hash of its content in `blobs/` and
+//! every backup only adds a small manifest mapping file names to blobs in `manifests/`.
+//! Repeated backups of a mostly unchanged store therefore only copy the changed files.
+//! The integrity of a backup can be checked with [`Backup::verify()`] and entries are rolled back with
+//! [`Store::restore_backup()`](crate::Store::restore_backup).
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::snapshot;
+//! use libpass::Store;
+//!
+//! let store = Store::from_env().unwrap();
+//! store.snapshot("/var/backups/pass").unwrap();
+//!
+//! // later
+//! let latest = snapshot::list("/var/backups/pass").unwrap().pop().unwrap();
+//! store.restore(&latest, &["web/github.com"]).unwrap();
+//! ```
+
+use crate::telemetry::debug;
+use crate::{config, index, names, utils, PassError, Result, Store};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A snapshot of a store
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Snapshot {
+    path: PathBuf,
+    created: SystemTime,
+}
+
+impl Snapshot {
+    /// Open the snapshot at *path*
+    ///
+    /// ## Errors
+    /// [`PassError::PasswordStoreNotFound`] is returned if *path* is not a directory whose name is the
+    /// timestamp of a snapshot.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = utils::canonicalize_path(&path)?;
+        let created = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_timestamp)
+            .filter(|_| path.is_dir())
+            .ok_or_else(|| PassError::PasswordStoreNotFound(path.clone()))?;
+        Ok(Self { path, created })
+    }
+
+    /// The directory which contains the snapshot
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The time at which the snapshot was created (with a precision of seconds)
+    pub fn created(&self) -> SystemTime {
+        self.created
+    }
+
+    /// Open the snapshot as store to inspect its entries
+    pub fn store(&self) -> Result<crate::ReadOnlyStore> {
+        Store::open_read_only(&self.path)
+    }
+}
+
+/// All snapshots in *dir*, oldest first
+///
+/// Other files and directories in *dir* are ignored.
+pub fn list(dir: impl AsRef<Path>) -> Result<Vec<Snapshot>> {
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        if let Ok(snapshot) = Snapshot::open(entry?.path()) {
+            snapshots.push(snapshot);
+        }
+    }
+    snapshots.sort_unstable_by(|a, b| a.created.cmp(&b.created).then(a.path.cmp(&b.path)));
+    Ok(snapshots)
+}
+
+/// First line of backup manifests which identifies their format
+const MANIFEST_HEADER: &str = "libpass-backup 1";
+
+/// A deduplicated backup of a store
+///
+/// See the [module documentation](self) for the format.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Backup {
+    dir: PathBuf,
+    manifest: PathBuf,
+    created: SystemTime,
+    /// Paths of the backed up files relative to the store root and the hashes of their content
+    files: Vec<(String, String)>,
+}
+
+/// A problem which was found while verifying a [`Backup`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum BackupIssue {
+    /// The blob of a file does not exist
+    MissingBlob {
+        /// Path of the file relative to the store root
+        file: String,
+        /// The hash of the missing blob
+        hash: String,
+    },
+    /// The content of a blob does not match its hash
+    CorruptedBlob {
+        /// Path of the file relative to the store root
+        file: String,
+        /// The hash of the corrupted blob
+        hash: String,
+    },
+}
+
+impl Backup {
+    /// Open the backup whose manifest is at *manifest*
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidStoreFormat`] is returned if the manifest is malformed.
+    pub fn open(manifest: impl AsRef<Path>) -> Result<Self> {
+        let manifest = utils::canonicalize_path(&manifest)?;
+        let invalid = || {
+            PassError::InvalidStoreFormat(manifest.clone(), "Malformed backup manifest".to_string())
+        };
+        let created = manifest
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_timestamp)
+            .ok_or_else(invalid)?;
+        let dir = manifest
+            .parent()
+            .and_then(Path::parent)
+            .ok_or_else(invalid)?
+            .to_owned();
+
+        let content = fs::read_to_string(&manifest)?;
+        let mut lines = content.lines();
+        if lines.next() != Some(MANIFEST_HEADER) {
+            return Err(invalid());
+        }
+        let files = lines
+            .map(|line| {
+                let (hash, file) = line.split_once(' ').ok_or_else(invalid)?;
+                if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(invalid());
+                }
+                Ok((index::unescape(file).ok_or_else(invalid)?, hash.to_string()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            dir,
+            manifest,
+            created,
+            files,
+        })
+    }
+
+    /// The directory which contains the blobs and manifests of this and other backups
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The manifest of this backup
+    pub fn manifest(&self) -> &Path {
+        &self.manifest
+    }
+
+    /// The time at which the backup was created (with a precision of seconds)
+    pub fn created(&self) -> SystemTime {
+        self.created
+    }
+
+    /// The names of all entries in this backup, sorted
+    pub fn entries(&self) -> Vec<String> {
+        let mut entries = self
+            .files
+            .iter()
+            .filter_map(|(file, _)| file.strip_suffix(".gpg"))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        entries.sort_unstable();
+        entries
+    }
+
+    /// Check that the blobs of all files of this backup exist and are unchanged
+    ///
+    /// An empty list is returned if the backup is intact.
+    pub fn verify(&self) -> Result<Vec<BackupIssue>> {
+        let mut issues = Vec::new();
+        for (file, hash) in &self.files {
+            match fs::read(self.blob_path(hash)) {
+                Ok(content) if hash_of(&content) == *hash => {}
+                Ok(_) => issues.push(BackupIssue::CorruptedBlob {
+                    file: file.clone(),
+                    hash: hash.clone(),
+                }),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    issues.push(BackupIssue::MissingBlob {
+                        file: file.clone(),
+                        hash: hash.clone(),
+                    })
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(issues)
+    }
+
+    /// The backed up files which belong to the entry or directory *pass_name* and their verified content
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if *pass_name* is not part of this backup and
+    /// [`PassError::InvalidStoreFormat`] if the blob of a file is missing or corrupted.
+    pub(crate) fn read_entries(&self, pass_name: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let pass_name = names::normalize_name(pass_name)?;
+        let file_name = format!("{}.gpg", pass_name);
+        let dir_prefix = format!("{}/", pass_name);
+        let mut entries = Vec::new();
+        for (file, hash) in &self.files {
+            let matches = match pass_name.as_str() {
+                "" => file.ends_with(".gpg"),
+                _ => {
+                    *file == file_name || (file.starts_with(&dir_prefix) && file.ends_with(".gpg"))
+                }
+            };
+            if !matches {
+                continue;
+            }
+            let path = self.blob_path(hash);
+            let content = fs::read(&path)?;
+            if hash_of(&content) != *hash {
+                return Err(PassError::InvalidStoreFormat(
+                    path,
+                    "Backup blob does not match its hash".to_string(),
+                ));
+            }
+            entries.push((
+                file.strip_suffix(".gpg").unwrap_or(file).to_string(),
+                content,
+            ));
+        }
+        if entries.is_empty() {
+            return Err(PassError::EntryNotFound(pass_name));
+        }
+        Ok(entries)
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        blob_path(&self.dir, hash)
+    }
+}
+
+/// All backups in *dir*, oldest first
+pub fn list_backups(dir: impl AsRef<Path>) -> Result<Vec<Backup>> {
+    let manifests = dir.as_ref().join("manifests");
+    if !manifests.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(manifests)? {
+        if let Ok(backup) = Backup::open(entry?.path()) {
+            backups.push(backup);
+        }
+    }
+    backups.sort_unstable_by(|a, b| a.created.cmp(&b.created).then(a.manifest.cmp(&b.manifest)));
+    Ok(backups)
+}
+
+/// Write a deduplicated backup of the store at *root* into *dir*
+pub(crate) fn create_backup(root: &Path, dir: &Path) -> Result<Backup> {
+    verify_outside_store(root, dir)?;
+    let mut files = Vec::new();
+    collect_files(root, &mut files)?;
+    files.sort_unstable();
+
+    let mut manifest = format!("{}\n", MANIFEST_HEADER);
+    for file in files {
+        let content = fs::read(&file)?;
+        let hash = hash_of(&content);
+        let blob = blob_path(dir, &hash);
+        if !blob.exists() {
+            if let Some(parent) = blob.parent() {
+                utils::create_dir_all(parent, config::DEFAULT_UMASK)?;
+            }
+            utils::write_atomically(&blob, &content, config::DEFAULT_UMASK)?;
+        }
+        let relative = file.strip_prefix(root).unwrap_or(&file);
+        let name = utils::path2str(relative)?.replace(std::path::MAIN_SEPARATOR, "/");
+        manifest.push_str(&format!("{} {}\n", hash, index::escape(&name)));
+    }
+
+    let manifests = dir.join("manifests");
+    utils::create_dir_all(&manifests, config::DEFAULT_UMASK)?;
+    let name = format_timestamp(SystemTime::now());
+    let mut path = manifests.join(&name);
+    let mut counter = 2;
+    while path.exists() {
+        path = manifests.join(format!("{}-{}", name, counter));
+        counter += 1;
+    }
+    debug!("Writing backup manifest {}", path.display());
+    utils::write_atomically(&path, manifest.as_bytes(), config::DEFAULT_UMASK)?;
+    Backup::open(path)
+}
+
+/// Collect all files below *dir*, skipping `.git` directories
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if entry.file_name() != ".git" {
+                collect_files(&entry.path(), files)?;
+            }
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// The path of the blob with *hash* in the backup directory *dir*
+fn blob_path(dir: &Path, hash: &str) -> PathBuf {
+    dir.join("blobs").join(&hash[..2]).join(&hash[2..])
+}
+
+/// The SHA-256 hash of *content* in hexadecimal notation
+pub(crate) fn hash_of(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Copy the store at *root* into a new snapshot below *dest*
+pub(crate) fn create(root: &Path, dest: &Path) -> Result<Snapshot> {
+    verify_outside_store(root, dest)?;
+    utils::create_dir_all(dest, config::DEFAULT_UMASK)?;
+    let created = SystemTime::now();
+    let name = format_timestamp(created);
+    // snapshots which are created within the same second get a counter
+    let mut path = dest.join(&name);
+    let mut counter = 2;
+    while path.exists() {
+        path = dest.join(format!("{}-{}", name, counter));
+        counter += 1;
+    }
+
+    debug!(
+        "Creating snapshot of {} at {}",
+        root.display(),
+        path.display()
+    );
+    copy_tree(root, &path)?;
+    Snapshot::open(path)
+}
+
+/// Make sure that *dest* is not inside the store at *root*
+///
+/// Symbolic links in the existing parts of both paths are resolved before they are compared.
+fn verify_outside_store(root: &Path, dest: &Path) -> io::Result<()> {
+    if resolve_existing(dest)?.starts_with(root.canonicalize()?) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} is inside of the password store at {}",
+                dest.display(),
+                root.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Canonicalize the longest existing ancestor of *path* and append the components which do not exist yet
+fn resolve_existing(path: &Path) -> io::Result<PathBuf> {
+    let mut existing = std::env::current_dir()?.join(path);
+    let mut missing = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(resolved) => {
+                return Ok(missing
+                    .into_iter()
+                    .rev()
+                    .fold(resolved, |path, component| path.join(component)))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => match existing.file_name() {
+                Some(name) => {
+                    missing.push(name.to_owned());
+                    existing.pop();
+                }
+                None => return Err(e),
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Recursively link or copy all files of *from* into *to*, skipping `.git` directories
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    utils::create_dir_all(to, config::DEFAULT_UMASK)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+        if file_type.is_dir() {
+            if entry.file_name() != ".git" {
+                copy_tree(&entry.path(), &target)?;
+            }
+        } else if file_type.is_file() {
+            link_or_copy(&entry.path(), &target)?;
+        } else {
+            debug!("Not including {} in snapshot", entry.path().display());
+        }
+    }
+    Ok(())
+}
+
+fn link_or_copy(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    if fs::hard_link(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to).map(|_| ())
+}
+
+/// Format *time* like `20261016T120000Z`
+fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (year, month, day) = utils::civil_from_days(seconds.div_euclid(86400));
+    let seconds = seconds.rem_euclid(86400);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Parse the timestamp at the start of a snapshot name like `20261016T120000Z` or `20261016T120000Z-2`
+fn parse_timestamp(name: &str) -> Option<SystemTime> {
+    let (timestamp, counter) = match name.split_once('-') {
+        Some((timestamp, counter)) => (timestamp, Some(counter)),
+        None => (name, None),
+    };
+    if counter.is_some_and(|counter| counter.parse::<u32>().is_err())
+        || timestamp.len() != 16
+        || !timestamp.is_ascii()
+        || &timestamp[8..9] != "T"
+        || !timestamp.ends_with('Z')
+    {
+        return None;
+    }
+    let field = |range: std::ops::Range<usize>| timestamp[range].parse::<i64>().ok();
+    let days = utils::days_from_civil(field(0..4)?, field(4..6)?, field(6..8)?);
+    let seconds = days * 86400 + field(9..11)? * 3600 + field(11..13)? * 60 + field(13..15)?;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?))
+}
@@ -0,0 +1,241 @@
+//! Splitting entries which hold several accounts and merging entries into one
+//!
+//! Inherited stores often contain entries which hold the credentials of several accounts like
+//! ```text
+//! hunter2
+//! user: john
+//! admin-user: root
+//! admin-password: correct horse
+//! ```
+//! A [`SplitMapping`] describes which fields belong to which new entry and
+//! [`Store::split_entry()`](crate::Store::split_entry) moves them there.
+//! [`Store::merge_entries()`](crate::Store::merge_entries) does the opposite and combines several entries
+//! into one without losing any of their fields.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::split::SplitMapping;
+//! use libpass::Store;
+//!
+//! let mapping = SplitMapping::new()
+//!     .with_entry("servers/db-admin", "admin-password")
+//!     .with_renamed_field("servers/db-admin", "admin-user", "user");
+//! Store::from_env()
+//!     .unwrap()
+//!     .split_entry("servers/db", &mapping)
+//!     .unwrap();
+//! ```
+
+use crate::parsed_entry::parse_field;
+use crate::secure::SecretString;
+use crate::secure_mem::push_str;
+use crate::{names, ParsedEntry, PassError, Result};
+use std::borrow::Cow;
+
+/// Describes which fields of an entry are moved into which new entries
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct SplitMapping {
+    targets: Vec<SplitTarget>,
+}
+
+/// A new entry which is created by splitting an entry
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SplitTarget {
+    /// Name of the new entry (normalized unless it is invalid)
+    pub pass_name: String,
+    /// The field whose value becomes the password of the new entry or `None` to leave the password empty
+    pub password_field: Option<String>,
+    /// The fields which are moved into the new entry as pairs of their key in the split entry and their key in
+    /// the new entry
+    pub fields: Vec<(String, String)>,
+}
+
+impl SplitMapping {
+    /// Create a mapping which does not move any fields
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the entry *pass_name* whose password is the value of the field *password_field*
+    pub fn with_entry(mut self, pass_name: &str, password_field: impl Into<String>) -> Self {
+        self.target(pass_name).password_field = Some(password_field.into());
+        self
+    }
+
+    /// Move the field *key* into the entry *pass_name*
+    pub fn with_field(self, pass_name: &str, key: impl Into<String>) -> Self {
+        let key = key.into();
+        self.with_renamed_field(pass_name, key.clone(), key)
+    }
+
+    /// Move the field *key* into the entry *pass_name* where it is named *new_key*
+    pub fn with_renamed_field(
+        mut self,
+        pass_name: &str,
+        key: impl Into<String>,
+        new_key: impl Into<String>,
+    ) -> Self {
+        self.target(pass_name)
+            .fields
+            .push((key.into(), new_key.into()));
+        self
+    }
+
+    /// The entries which are created, in the order in which they were first mentioned
+    pub fn targets(&self) -> &[SplitTarget] {
+        &self.targets
+    }
+
+    /// The target named *pass_name*, creating it if necessary
+    ///
+    /// Names are normalized first so that all spellings of a name refer to the same target.
+    /// Invalid names are kept as they are and rejected when the mapping is applied.
+    fn target(&mut self, pass_name: &str) -> &mut SplitTarget {
+        let pass_name = names::normalize_name(pass_name).unwrap_or_else(|_| pass_name.to_string());
+        let index = match self
+            .targets
+            .iter()
+            .position(|target| target.pass_name == pass_name)
+        {
+            Some(index) => index,
+            None => {
+                self.targets.push(SplitTarget {
+                    pass_name,
+                    password_field: None,
+                    fields: Vec::new(),
+                });
+                self.targets.len() - 1
+            }
+        };
+        &mut self.targets[index]
+    }
+}
+
+/// Move the fields of *entry* into new entries as described by *mapping*
+///
+/// The moved fields are removed from *entry* while all other lines are kept.
+/// New entries use the parse mode and line ending of *entry*.
+/// The new entries are returned along with their names in the order of [`SplitMapping::targets()`].
+///
+/// ## Errors
+/// [`PassError::InvalidField`] is returned if *entry* does not contain a field of the mapping or if a new key
+/// is not a valid field key.
+pub fn split(
+    entry: &mut ParsedEntry,
+    mapping: &SplitMapping,
+) -> Result<Vec<(String, ParsedEntry)>> {
+    let newline = entry.line_ending().as_str();
+    let mut remaining = entry.clone();
+    let mut split = Vec::with_capacity(mapping.targets.len());
+    for target in &mapping.targets {
+        let password = match &target.password_field {
+            Some(key) => take_field(&mut remaining, key)?,
+            None => SecretString::default(),
+        };
+        let mut content = String::new();
+        push_str(&mut content, password.expose());
+        push_str(&mut content, newline);
+        let mut new_entry = ParsedEntry::from(content).with_mode(entry.mode());
+        for (key, new_key) in &target.fields {
+            let value = take_field(&mut remaining, key)?;
+            new_entry.set_field(new_key, value.expose())?;
+        }
+        split.push((target.pass_name.clone(), new_entry));
+    }
+    *entry = remaining;
+    Ok(split)
+}
+
+/// Combine *entries* given along with their names into one entry
+///
+/// The merged entry starts with the complete first entry.
+/// The lines of the following entries are appended unless the merged entry already contains the same field
+/// with the same value.
+/// Fields whose key is already taken by another value as well as passwords which differ from the first one
+/// (as `password` field) are kept under a key which is suffixed with the last segment of the name of their
+/// entry, e.g. `user-work` for the `user` field of `mail/work`.
+pub fn merge<'a>(entries: impl IntoIterator<Item = (&'a str, &'a ParsedEntry)>) -> ParsedEntry {
+    let mut entries = entries.into_iter();
+    let first = match entries.next() {
+        Some((_, first)) => first,
+        None => return ParsedEntry::default(),
+    };
+    let newline = first.line_ending().as_str();
+    let mut content = String::new();
+    push_str(&mut content, first.content());
+    if !content.is_empty() && !content.ends_with('\n') {
+        push_str(&mut content, newline);
+    }
+    // values are borrowed from the merged entries so that no further copies of them are made
+    let mut fields = first
+        .fields()
+        .map(|(key, value)| (Cow::Borrowed(key), value))
+        .collect::<Vec<_>>();
+
+    for (pass_name, entry) in entries {
+        let suffix = key_suffix(pass_name);
+        let mut push_field = |key: &'a str, value: &'a str, content: &mut String| {
+            if fields.iter().any(|(other, other_value)| {
+                other.eq_ignore_ascii_case(key) && *other_value == value
+            }) {
+                return;
+            }
+            let mut unique_key = key.to_string();
+            let mut attempt = 1;
+            while fields
+                .iter()
+                .any(|(other, _)| other.eq_ignore_ascii_case(&unique_key))
+            {
+                unique_key = match attempt {
+                    1 => format!("{}-{}", key, suffix),
+                    _ => format!("{}-{}-{}", key, suffix, attempt),
+                };
+                attempt += 1;
+            }
+            for part in [unique_key.as_str(), ": ", value, newline] {
+                push_str(content, part);
+            }
+            fields.push((Cow::Owned(unique_key), value));
+        };
+
+        if !entry.password().is_empty() && entry.password() != first.password() {
+            push_field("password", entry.password(), &mut content);
+        }
+        for line in entry.body_lines() {
+            match parse_field(line) {
+                Some((key, value)) => push_field(key, value, &mut content),
+                _ if line.trim().is_empty() => {}
+                _ => {
+                    push_str(&mut content, line);
+                    push_str(&mut content, newline);
+                }
+            }
+        }
+    }
+    ParsedEntry::from(content).with_mode(first.mode())
+}
+
+/// Remove the field *key* from *entry* and return its value
+fn take_field(entry: &mut ParsedEntry, key: &str) -> Result<SecretString> {
+    entry
+        .remove_field(key)
+        .ok_or_else(|| PassError::InvalidField {
+            key: key.to_string(),
+            reason: "the entry does not contain this field".to_string(),
+        })
+}
+
+/// The last segment of *pass_name* in a form which can be used inside field keys
+fn key_suffix(pass_name: &str) -> String {
+    pass_name
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| match c.is_whitespace() || c == ':' {
+            true => '-',
+            false => c,
+        })
+        .collect()
+}
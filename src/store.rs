@@ -0,0 +1,3313 @@
+//! Handle to a password store located at a specific directory
+
+use crate::access::{self, AccessKey, AccessMap};
+#[cfg(all(feature = "agent", unix))]
+use crate::agent::AgentClient;
+use crate::alias;
+use crate::audit::{AccessLogger, AccessOperation, AccessRecorder};
+use crate::bundle::{self, BundleFile};
+use crate::context_pool::{self, ContextPool};
+use crate::crypto::{self, CryptoOptions};
+use crate::export;
+use crate::file_io::{CipherFile, Ciphertext, RoPlainFile, RwPlainFile};
+use crate::filter::Filter;
+use crate::fsck::{self, FsckReport};
+use crate::generate::PasswordGenerator;
+#[cfg(feature = "git")]
+use crate::git;
+use crate::hardening;
+use crate::index::StoreIndex;
+use crate::keys::{self, RotationOptions, RotationReport};
+#[cfg(feature = "layout")]
+use crate::layout::{LayoutSpec, LayoutViolation};
+use crate::listing::{Listing, ListingChanges};
+use crate::locks::StoreLocks;
+use crate::manifest::{self, Manifest, ManifestReport};
+use crate::merge::{self, MergeReport, MergeStrategy};
+use crate::metrics::{ErrorCounter, StoreMetrics};
+use crate::names;
+use crate::obfuscation::ObfuscatedStore;
+use crate::parsed_entry;
+use crate::passkey::Passkey;
+use crate::picker::Picker;
+use crate::policy::{PasswordPolicy, PolicyReport};
+use crate::protection;
+use crate::rate_limit::{RateLimit, RateLimiter};
+use crate::recipients::RecipientResolver;
+use crate::scrub::{RemovedEntry, ScrubHook, Scrubber};
+use crate::secure::SecretString;
+use crate::secure_mem::{self, LockedRegion};
+use crate::share::{ShareOptions, SharedEntry};
+use crate::snapshot::{self, Backup, Snapshot};
+use crate::split::{self, SplitMapping};
+use crate::store_entry;
+use crate::tags::TagIndex;
+use crate::telemetry::{debug, span, trace, warning};
+use crate::templates::Template;
+use crate::text::TextOptions;
+use crate::transform::{EntryTransformer, TransformContext, Transformers};
+use crate::view::StoreView;
+use crate::walk;
+use crate::{
+    utils, Compression, Config, EncryptionPolicy, GpgOperation, InsertSource, LineEnding,
+    OverwritePolicy, ParsedEntry, PassError, PassName, Prefer, Result, SignatureStatus,
+    StoreDirectoryRef, StoreEntry, StoreFileRef, TrailingNewline, UrlMatch,
+};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// A password store located at a specific directory
+///
+/// The free functions [`list()`](crate::list) and [`retrieve()`](crate::retrieve) operate on the store
+/// that is located at [`password_store_dir()`](crate::password_store_dir).
+/// Use this type instead if you want to interact with a store at another location.
+///
+/// ## Concurrency
+/// `Store` is `Send + Sync` so that one instance (or clones of it, which share their caches, gpg contexts and
+/// locks) can serve multiple threads, e.g. the request handlers of a server.
+/// Operations on single entries like [`Store::retrieve()`] or [`Store::insert()`] run concurrently while
+/// [`Store::git_sync()`] waits for them to finish and holds them off until the tree is consistent again, so
+/// readers never observe a half-applied rebase.
+/// Concurrent writes to the *same* entry are not ordered; the last one wins.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Store {
+    root: PathBuf,
+    crypto: CryptoOptions,
+    config: Config,
+    strict_permissions: bool,
+    resolve_aliases: bool,
+    ambiguity: Prefer,
+    url_matching: UrlMatch,
+    asc_entries: bool,
+    shallow_directories: bool,
+    trailing_newline: TrailingNewline,
+    text: TextOptions,
+    index_path: Option<PathBuf>,
+    manifest_maintenance: bool,
+    manifest_signing_key: Option<String>,
+    override_protection: bool,
+    auto_prune: bool,
+    streaming_threshold: u64,
+    access: AccessRecorder,
+    rate_limiter: RateLimiter,
+    decryption_errors: ErrorCounter,
+    recipients: RecipientResolver,
+    gpg_contexts: ContextPool,
+    locks: StoreLocks,
+    scrubber: Scrubber,
+    transformers: Transformers,
+    #[cfg(all(feature = "agent", unix))]
+    agent: Option<AgentClient>,
+    #[cfg(feature = "mmap")]
+    mmap_threshold: Option<u64>,
+}
+
+/// An entry whose actual recipients differ from the keys configured in its governing `.gpg-id` file
+///
+/// This usually means that the `.gpg-id` file was changed (e.g. during key rotation) without re-encrypting
+/// the entry afterwards.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RecipientMismatch {
+    /// The affected entry
+    pub entry: StoreFileRef,
+    /// Ids of keys that are configured in `.gpg-id` but for which the entry is not encrypted
+    pub missing: Vec<String>,
+    /// Ids of keys for which the entry is encrypted but which are not configured in `.gpg-id`
+    ///
+    /// Hidden recipients can not be attributed to a key and are always reported here with a key id
+    /// consisting only of zeroes.
+    pub unexpected: Vec<String>,
+}
+
+impl Store {
+    /// Open the password store located at *path*
+    ///
+    /// The store uses the default [`Config`] regardless of environment variables.
+    /// Use [`Store::from_env()`] or [`Store::with_config()`] to apply settings.
+    ///
+    /// ## Errors
+    /// [`PassError::PasswordStoreNotFound`] is returned if *path* is not a directory.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let root = utils::canonicalize_path(&path)?;
+        if root.is_dir() {
+            Ok(Self {
+                root,
+                crypto: CryptoOptions::default(),
+                config: Config::default(),
+                strict_permissions: false,
+                resolve_aliases: false,
+                ambiguity: Prefer::default(),
+                url_matching: UrlMatch::default(),
+                asc_entries: false,
+                shallow_directories: false,
+                trailing_newline: TrailingNewline::default(),
+                text: TextOptions::default(),
+                index_path: None,
+                manifest_maintenance: false,
+                manifest_signing_key: None,
+                override_protection: false,
+                auto_prune: false,
+                streaming_threshold: crypto::DEFAULT_STREAMING_THRESHOLD,
+                access: AccessRecorder::default(),
+                rate_limiter: RateLimiter::default(),
+                decryption_errors: ErrorCounter::default(),
+                recipients: RecipientResolver::default(),
+                gpg_contexts: ContextPool::new(context_pool::DEFAULT_POOL_SIZE),
+                locks: StoreLocks::default(),
+                scrubber: Scrubber::default(),
+                transformers: Transformers::default(),
+                #[cfg(all(feature = "agent", unix))]
+                agent: None,
+                #[cfg(feature = "mmap")]
+                mmap_threshold: None,
+            })
+        } else {
+            Err(PassError::PasswordStoreNotFound(root))
+        }
+    }
+
+    /// Open the password store located at *path* in read-only mode
+    ///
+    /// The returned [`ReadOnlyStore`] does not provide any mutating operations and only opens files for
+    /// reading which makes it usable on read-only filesystems like mounted backups.
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<ReadOnlyStore> {
+        Ok(ReadOnlyStore {
+            store: Self::open(path)?,
+        })
+    }
+
+    /// Restrict this store to the entries below *subpaths*
+    ///
+    /// All operations of the returned [`StoreView`] fail with [`PassError::AccessDenied`] for entries outside
+    /// of the subpaths.
+    /// The empty name (or `/`) is the store root and grants access to all entries.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidPassName`] is returned if one of the subpaths is not a valid name.
+    pub fn view<S: AsRef<str>>(self, subpaths: impl IntoIterator<Item = S>) -> Result<StoreView> {
+        StoreView::new(self, subpaths)
+    }
+
+    /// Keep the entries of this store in files with random names so that their names are not visible on disk
+    ///
+    /// See the [`obfuscation`](crate::obfuscation) module for details.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidStoreFormat`] is returned if the name map of the store cannot be interpreted.
+    pub fn obfuscated(self) -> Result<ObfuscatedStore> {
+        ObfuscatedStore::open(self)
+    }
+
+    /// Open the default password store as returned by [`password_store_dir()`](crate::password_store_dir)
+    ///
+    /// All other settings are read from the environment as well (see [`Config::from_env()`]).
+    pub fn from_env() -> Result<Self> {
+        Self::from_config(Config::from_env())
+    }
+
+    /// Open the password store at the location configured in *config* and apply all other settings of it
+    pub fn from_config(config: Config) -> Result<Self> {
+        Ok(Self::open(config.store_dir()?)?.with_config(config))
+    }
+
+    /// Apply the settings of *config* to operations of this store
+    ///
+    /// The store directory of *config* is ignored since the store is already opened.
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// The settings that are applied to operations of this store
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The root directory of this store
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Use the given policy to decide which recipient keys are acceptable when encrypting entries
+    ///
+    /// Defaults to [`EncryptionPolicy::AlwaysTrust`].
+    pub fn with_encryption_policy(mut self, policy: EncryptionPolicy) -> Self {
+        self.crypto.policy = policy;
+        self
+    }
+
+    /// The policy that is used to decide which recipient keys are acceptable when encrypting entries
+    pub fn encryption_policy(&self) -> EncryptionPolicy {
+        self.crypto.policy
+    }
+
+    /// Sign entries with the default gpg secret key while encrypting them
+    ///
+    /// Signatures can be checked with [`StoreFileRef::verify_signature()`] which allows detecting tampering
+    /// of shared stores.
+    pub fn with_encrypt_and_sign(mut self, enabled: bool) -> Self {
+        self.crypto.sign = enabled;
+        self
+    }
+
+    /// Whether entries are signed while encrypting them
+    pub fn encrypt_and_sign(&self) -> bool {
+        self.crypto.sign
+    }
+
+    /// Compress payloads before encrypting them according to *compression*
+    ///
+    /// Defaults to [`Compression::Default`] which leaves the decision to gpg like *pass* does.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.crypto.compression = compression;
+        self
+    }
+
+    /// Whether payloads are compressed before encrypting them
+    pub fn compression(&self) -> Compression {
+        self.crypto.compression
+    }
+
+    /// Write entries in ASCII armored form instead of binary
+    ///
+    /// Armored entries are still stored in `.gpg` files which *pass* and gpg decrypt just like binary ones
+    /// but they produce readable diffs in version control.
+    /// Reading entries detects the form automatically regardless of this setting.
+    ///
+    /// Disabled by default.
+    pub fn with_armor(mut self, enabled: bool) -> Self {
+        self.crypto.armor = enabled;
+        self
+    }
+
+    /// Whether entries are written in ASCII armored form
+    pub fn armor(&self) -> bool {
+        self.crypto.armor
+    }
+
+    /// Prevent gpg from accessing the network during operations of this store
+    ///
+    /// gpg then neither retrieves missing keys from key servers or Web Key Directories (e.g. to verify a
+    /// signature with `auto-key-retrieve`) nor refreshes keys while resolving recipients, so that operations
+    /// behave the same on air-gapped machines.
+    /// Keys which are not in the local keyring are reported as missing instead.
+    ///
+    /// Disabled by default.
+    pub fn with_offline(mut self, enabled: bool) -> Self {
+        self.crypto.offline = enabled;
+        self
+    }
+
+    /// Whether gpg is prevented from accessing the network
+    pub fn offline(&self) -> bool {
+        self.crypto.offline
+    }
+
+    /// Use the keyring in the directory *gpg_home* for all gpg operations of this store
+    ///
+    /// This is like setting `GNUPGHOME` for *pass* but only affects this store, e.g. so that tests do not
+    /// touch the keyring of the current user.
+    /// By default, gpg uses `GNUPGHOME` or `~/.gnupg`.
+    pub fn with_gpg_home(mut self, gpg_home: impl Into<PathBuf>) -> Self {
+        self.crypto.gpg_home = Some(gpg_home.into().into());
+        self
+    }
+
+    /// The directory containing the keyring of this store if it is not the default one
+    pub fn gpg_home(&self) -> Option<&Path> {
+        self.crypto.gpg_home.as_deref()
+    }
+
+    /// Abort gpg operations of the kind *operation* which take longer than *timeout*
+    ///
+    /// A hung gpg-agent or an unanswered pinentry prompt otherwise blocks the calling thread forever.
+    /// Operations with a timeout run on a worker thread; once the timeout elapses, they fail with
+    /// [`PassError::Timeout`] and the worker is cancelled so that its result is discarded whenever gpg
+    /// returns.
+    /// Each timed operation copies its input for the worker, so very large entries are copied once more.
+    ///
+    /// By default, operations may take arbitrarily long.
+    pub fn with_gpg_timeout(mut self, operation: GpgOperation, timeout: Duration) -> Self {
+        self.crypto.set_timeout(operation, Some(timeout));
+        self
+    }
+
+    /// The time after which gpg operations of the kind *operation* are aborted
+    pub fn gpg_timeout(&self, operation: GpgOperation) -> Option<Duration> {
+        self.crypto.timeout(operation)
+    }
+
+    /// Treat `*.asc` files as entries in addition to `*.gpg` files
+    ///
+    /// Some tools store ASCII armored entries with the `.asc` extension.
+    /// If enabled, these files are included when listing the store and an entry named *name* refers to
+    /// `name.asc` if there is no `name.gpg`.
+    /// New entries are still created as `.gpg` files.
+    ///
+    /// Disabled by default because *pass* ignores `*.asc` files.
+    pub fn with_asc_entries(mut self, enabled: bool) -> Self {
+        self.asc_entries = enabled;
+        self
+    }
+
+    /// Whether `*.asc` files are treated as entries
+    pub fn asc_entries(&self) -> bool {
+        self.asc_entries
+    }
+
+    /// The file extensions of entry files in this store
+    pub(crate) fn entry_extensions(&self) -> &'static [&'static str] {
+        if self.asc_entries {
+            walk::GPG_AND_ASC
+        } else {
+            walk::GPG
+        }
+    }
+
+    /// Refuse to operate on files and directories which other users could have tampered with
+    ///
+    /// If enabled, every operation first checks that the store root, all directories leading to the
+    /// affected entries, the `.gpg-id` files inside of them and the entries themselves are neither group-
+    /// nor world-writable and owned by the current user (or root).
+    /// Operations on the whole store like [`Store::list()`] check every file of the store.
+    /// These checks are only performed on unix systems.
+    ///
+    /// Disabled by default.
+    pub fn with_strict_permissions(mut self, enabled: bool) -> Self {
+        self.strict_permissions = enabled;
+        self
+    }
+
+    /// Whether permissions are checked before operating on the store
+    pub fn strict_permissions(&self) -> bool {
+        self.strict_permissions
+    }
+
+    /// Transparently resolve aliases (see [`Store::create_alias()`]) when retrieving entries
+    ///
+    /// If enabled, [`Store::retrieve()`] decrypts every entry it returns to check whether it is an alias and
+    /// returns the entry which the alias (possibly via further aliases) redirects to instead.
+    ///
+    /// Disabled by default because it requires a decryption for every retrieved entry.
+    pub fn with_alias_resolution(mut self, enabled: bool) -> Self {
+        self.resolve_aliases = enabled;
+        self
+    }
+
+    /// Whether aliases are resolved when retrieving entries
+    pub fn resolves_aliases(&self) -> bool {
+        self.resolve_aliases
+    }
+
+    /// Resolve names which refer to both a file and a directory according to *prefer*
+    ///
+    /// By default, [`Store::retrieve()`] fails with [`PassError::AmbiguousPassName`] for such names while
+    /// *pass* itself prefers the file ([`Prefer::File`]).
+    /// Use [`Store::retrieve_all()`] to get both entries.
+    pub fn with_ambiguity(mut self, prefer: Prefer) -> Self {
+        self.ambiguity = prefer;
+        self
+    }
+
+    /// How names which refer to both a file and a directory are resolved
+    pub fn ambiguity(&self) -> Prefer {
+        self.ambiguity
+    }
+
+    /// Compare the domains of URLs and entries according to *matching* in [`Store::entries_for_url()`]
+    ///
+    /// By default, only entries for the exact host of a URL are found ([`UrlMatch::Exact`]).
+    pub fn with_url_matching(mut self, matching: UrlMatch) -> Self {
+        self.url_matching = matching;
+        self
+    }
+
+    /// How the domains of URLs and entries are compared
+    pub fn url_matching(&self) -> UrlMatch {
+        self.url_matching
+    }
+
+    /// Only read the direct content of directories which are returned by [`Store::retrieve()`]
+    ///
+    /// Retrieving a directory normally reads (and with strict permissions checks) its whole subtree which
+    /// takes a while for giant directories.
+    /// If enabled, subdirectories are returned without content and are only read once they are retrieved
+    /// themselves.
+    /// Use [`StoreDirectoryRef::verify_recursive()`] to check a complete subtree in parallel.
+    ///
+    /// Disabled by default.
+    pub fn with_shallow_directories(mut self, enabled: bool) -> Self {
+        self.shallow_directories = enabled;
+        self
+    }
+
+    /// Whether retrieved directories only contain their direct content
+    pub fn shallow_directories(&self) -> bool {
+        self.shallow_directories
+    }
+
+    /// Normalize trailing newlines of content which is inserted via [`Store::insert()`] according to *mode*
+    ///
+    /// By default, content is normalized like `pass insert` does it (see [`TrailingNewline::Pass`]).
+    pub fn with_trailing_newline(mut self, mode: TrailingNewline) -> Self {
+        self.trailing_newline = mode;
+        self
+    }
+
+    /// How trailing newlines of inserted content are normalized
+    pub fn trailing_newline(&self) -> TrailingNewline {
+        self.trailing_newline
+    }
+
+    /// Convert all line breaks of text entries to *ending* whenever their plaintext is read through
+    /// [`Store::plain_io_rw()`] or written by this store
+    ///
+    /// Entries are only rewritten if they are changed, so reading an entry with other line breaks does not
+    /// modify it.
+    /// Content which is not valid UTF-8 is never converted.
+    /// By default, or with `None`, line breaks are kept as they are.
+    pub fn with_line_ending(mut self, ending: Option<LineEnding>) -> Self {
+        self.text.line_ending = ending;
+        self
+    }
+
+    /// The style to which line breaks of entries are converted or `None` if they are kept
+    pub fn line_ending(&self) -> Option<LineEnding> {
+        self.text.line_ending
+    }
+
+    /// Reject plaintext which is not valid UTF-8 when reading it through [`Store::plain_io_rw()`] or writing
+    /// it with this store
+    ///
+    /// Violations fail with [`PassError::MalformedEntry`].
+    /// Enable this for stores which must only contain text so that binary content (e.g. from a wrong
+    /// encoding) is noticed early.
+    ///
+    /// Disabled by default.
+    pub fn with_utf8_validation(mut self, enabled: bool) -> Self {
+        self.text.require_utf8 = enabled;
+        self
+    }
+
+    /// Whether plaintext which is not valid UTF-8 is rejected
+    pub fn utf8_validation(&self) -> bool {
+        self.text.require_utf8
+    }
+
+    /// Persist the [`StoreIndex`] of this store at *path* so that it only needs to be updated for changed
+    /// directories
+    ///
+    /// See [`index::default_path()`](crate::index::default_path) for the default location.
+    /// By default, [`Store::index()`] builds the index from scratch.
+    pub fn with_index(mut self, path: impl Into<PathBuf>) -> Self {
+        self.index_path = Some(path.into());
+        self
+    }
+
+    /// The path at which the index of this store is persisted
+    pub fn index_path(&self) -> Option<&Path> {
+        self.index_path.as_deref()
+    }
+
+    /// Update the [manifest](crate::manifest) of this store whenever an entry is written or removed through it
+    ///
+    /// If the store has no manifest yet, it is created by the first write.
+    pub fn with_manifest_maintenance(mut self, maintain: bool) -> Self {
+        self.manifest_maintenance = maintain;
+        self
+    }
+
+    /// Whether the manifest of this store is updated whenever an entry is written or removed through it
+    pub fn manifest_maintenance(&self) -> bool {
+        self.manifest_maintenance
+    }
+
+    /// Sign the manifest of this store with the secret key *signing_key* whenever it is written
+    ///
+    /// [`Store::verify_manifest()`] only trusts signatures by this key, so it must be given as full
+    /// fingerprint for manifests to be verifiable.
+    /// Without a signing key, manifests are written without signature and a previous signature is removed.
+    pub fn with_manifest_signing_key(mut self, signing_key: impl Into<String>) -> Self {
+        self.manifest_signing_key = Some(signing_key.into());
+        self
+    }
+
+    /// The secret key with which the manifest of this store is signed
+    pub fn manifest_signing_key(&self) -> Option<&str> {
+        self.manifest_signing_key.as_deref()
+    }
+
+    /// Record the hashes of all entry files and `.gpg-id` files of this store in its manifest
+    ///
+    /// This replaces the previous manifest and should be called after files were changed without going
+    /// through this store.
+    /// The manifest is signed if a signing key is configured (see [`Store::with_manifest_signing_key()`]).
+    pub fn update_manifest(&self) -> Result<Manifest> {
+        let _metadata = self.locks.metadata();
+        let manifest = Manifest::generate(&self.root, self.entry_extensions())?;
+        self.save_manifest(&manifest)?;
+        Ok(manifest)
+    }
+
+    /// Compare the entry files and `.gpg-id` files of this store against its manifest and verify the
+    /// signature of the manifest
+    ///
+    /// The signature is only trusted if it was made by the key that is configured with
+    /// [`Store::with_manifest_signing_key()`] (see [`ManifestReport::is_intact()`]).
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidStoreFormat`] is returned if the store has no manifest or it is malformed.
+    pub fn verify_manifest(&self) -> Result<ManifestReport> {
+        let manifest = Manifest::load(&self.root)?.ok_or_else(|| {
+            PassError::InvalidStoreFormat(
+                self.root.join(manifest::MANIFEST_FILE),
+                "The store has no manifest".to_string(),
+            )
+        })?;
+        let signature = match fs::read(self.root.join(manifest::SIGNATURE_FILE)) {
+            Ok(signature) => self.crypto.verify_detached(
+                &signature,
+                &fs::read(self.root.join(manifest::MANIFEST_FILE))?,
+            )?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => SignatureStatus::Unsigned,
+            Err(e) => return Err(e.into()),
+        };
+        let trusted_signature = match &self.manifest_signing_key {
+            Some(signing_key) => signature.is_valid_from(std::slice::from_ref(signing_key)),
+            None => false,
+        };
+        let current = Manifest::generate(&self.root, self.entry_extensions())?;
+        Ok(ManifestReport {
+            signature,
+            trusted_signature,
+            issues: manifest.issues(&current),
+        })
+    }
+
+    /// Record the current state of the file at *path* in the manifest if manifest maintenance is enabled
+    pub(crate) fn track_in_manifest(&self, path: &Path) -> Result<()> {
+        self.track_all_in_manifest([path])
+    }
+
+    /// Record the current state of all files at *paths* which manifests cover in the manifest if manifest
+    /// maintenance is enabled
+    pub(crate) fn track_all_in_manifest(
+        &self,
+        paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    ) -> Result<()> {
+        if !self.manifest_maintenance {
+            return Ok(());
+        }
+        let paths = paths
+            .into_iter()
+            .filter(|path| Manifest::covers(path.as_ref(), self.entry_extensions()))
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return Ok(());
+        }
+        let _metadata = self.locks.metadata();
+        let manifest = match Manifest::load(&self.root)? {
+            Some(mut manifest) => {
+                for path in paths {
+                    manifest.update(&self.root, path.as_ref())?;
+                }
+                manifest
+            }
+            None => Manifest::generate(&self.root, self.entry_extensions())?,
+        };
+        self.save_manifest(&manifest)
+    }
+
+    /// Write *manifest* into the store and sign it if a signing key is configured
+    fn save_manifest(&self, manifest: &Manifest) -> Result<()> {
+        let content = manifest.serialize();
+        let signature_path = self.root.join(manifest::SIGNATURE_FILE);
+        let signature = match &self.manifest_signing_key {
+            Some(signing_key) => Some(self.crypto.sign_detached(signing_key, content.as_bytes())?),
+            None => None,
+        };
+        utils::write_atomically(
+            &self.root.join(manifest::MANIFEST_FILE),
+            content.as_bytes(),
+            self.config.umask(),
+        )?;
+        match signature {
+            Some(signature) => {
+                utils::write_atomically(&signature_path, &signature, self.config.umask())?
+            }
+            None => match fs::remove_file(&signature_path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+        Ok(())
+    }
+
+    /// Get the names and non-secret metadata of all entries without decrypting them
+    ///
+    /// If an index path is configured (see [`Store::with_index()`]), the persisted index is updated by only
+    /// reading directories which changed since it was written and then saved again.
+    /// Tags are only available for entries for which they were collected before (see
+    /// [`Store::index_with_tags()`]) and which were not modified since.
+    pub fn index(&self) -> Result<StoreIndex> {
+        if self.strict_permissions {
+            hardening::check_tree(&self.root)?;
+        }
+        let previous = match &self.index_path {
+            Some(path) => StoreIndex::load(path),
+            None => StoreIndex::default(),
+        };
+        let index = StoreIndex::refresh(&self.root, previous)?;
+        self.save_index(&index)?;
+        Ok(index)
+    }
+
+    /// Get a [`Picker`] for fuzzy matching the names of all entries
+    ///
+    /// The names are taken from the [index](Store::index) of this store so that no entry is decrypted.
+    pub fn picker(&self) -> Result<Picker> {
+        Ok(Picker::from_index(&self.index()?))
+    }
+
+    /// List the names which complete *prefix* by one path component for interactive completion
+    ///
+    /// Candidates are taken from the [index](Store::index) of this store so that no entry is decrypted and,
+    /// if the index is persisted, only changed directories are read.
+    /// Directories are suffixed with `/`.
+    /// Use [`completion::render()`](crate::completion::render) to format the candidates for a shell.
+    pub fn completion_candidates(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self.index()?.completion_candidates(prefix))
+    }
+
+    /// Get the index of this store like [`Store::index()`] and collect the tags of all entries for which
+    /// they are not known yet
+    ///
+    /// This decrypts all entries which were added or modified since their tags were last collected.
+    /// Entries which are not text have no tags.
+    pub fn index_with_tags(&self) -> Result<StoreIndex> {
+        let mut index = self.index()?;
+        let mut changed = false;
+        for entry in index.entries_mut().filter(|entry| entry.tags().is_none()) {
+            let file = StoreFileRef {
+                path: self.file_path(entry.name())?,
+                root: self.root.clone(),
+            };
+            let tags = match self.parse_entry(&file) {
+                Ok(parsed) => parsed.tags().into_iter().map(str::to_string).collect(),
+                Err(PassError::InvalidStoreFormat(..)) => Vec::new(),
+                Err(e) => return Err(e),
+            };
+            entry.set_tags(tags);
+            changed = true;
+        }
+        if changed {
+            self.save_index(&index)?;
+        }
+        Ok(index)
+    }
+
+    fn save_index(&self, index: &StoreIndex) -> Result<()> {
+        match &self.index_path {
+            Some(path) => index.save(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Check the permissions of the whole store as described in [`Store::with_strict_permissions()`]
+    ///
+    /// This check is performed regardless of whether strict permissions are enabled.
+    ///
+    /// ## Errors
+    /// [`PassError::InsecurePermissions`] is returned for the first file or directory which does not pass
+    /// the check.
+    pub fn check_permissions(&self) -> Result<()> {
+        hardening::check_tree(&self.root)
+    }
+
+    /// Overwrite the encrypted file of entries before unlinking them in [`Store::remove()`]
+    ///
+    /// This is best-effort only since filesystems and storage devices may keep old copies; see the
+    /// [`scrub`](crate::scrub) module for details.
+    /// Files which are hard-linked (e.g. because they are part of a [snapshot](crate::snapshot)) are not
+    /// overwritten.
+    ///
+    /// Disabled by default.
+    pub fn with_secure_delete(mut self, enabled: bool) -> Self {
+        self.scrubber.set_overwrite(enabled);
+        self
+    }
+
+    /// Whether entry files are overwritten before unlinking them
+    pub fn secure_delete(&self) -> bool {
+        self.scrubber.overwrite()
+    }
+
+    /// Call *hook* whenever an entry is removed through this store, e.g. to erase it from the git history
+    ///
+    /// Hooks are called in the order in which they were added.
+    pub fn with_scrub_hook(mut self, hook: impl ScrubHook + 'static) -> Self {
+        self.scrubber.add_hook(Arc::new(hook));
+        self
+    }
+
+    /// Run *transformer* on every entry which is retrieved with [`Store::retrieve_entry()`]
+    ///
+    /// Transformers run in the order in which they were added.
+    /// See the [`transform`](crate::transform) module for details.
+    pub fn with_transformer(mut self, transformer: impl EntryTransformer + 'static) -> Self {
+        self.transformers.add(Arc::new(transformer));
+        self
+    }
+
+    /// Notify *logger* whenever an entry is decrypted, written or deleted through this store
+    ///
+    /// Events are emitted by operations which handle plaintext or remove entries, e.g.
+    /// [`Store::plain_io_rw()`] (and syncing the returned handle), [`Store::insert()`],
+    /// [`Store::entries_for_url()`] and [`Store::remove()`].
+    /// Entries which are decrypted directly via [`StoreFileRef`] are not observed because they are not
+    /// associated with a store.
+    /// Clones of this store share the logger.
+    pub fn set_access_logger(&mut self, logger: impl AccessLogger + 'static) {
+        self.access.set_logger(Arc::new(logger));
+    }
+
+    /// Attach *context* (e.g. the user or client on whose behalf the store is used) to all access events
+    pub fn with_access_context(mut self, context: impl Into<String>) -> Self {
+        self.access.set_context(Some(context.into()));
+        self
+    }
+
+    /// The context which is attached to access events
+    pub fn access_context(&self) -> Option<&str> {
+        self.access.context()
+    }
+
+    /// Notify the access logger that *operation* was done with *file*
+    pub(crate) fn record_access(
+        &self,
+        file: &StoreFileRef,
+        operation: AccessOperation,
+    ) -> Result<()> {
+        self.access
+            .record(&self.relative_name(&file.path)?, operation);
+        Ok(())
+    }
+
+    /// Refuse decryptions which would exceed *limit*
+    ///
+    /// Decryptions are counted by all operations which notify the [access logger](Store::set_access_logger)
+    /// about decryptions.
+    /// Once a limit is exceeded, these operations fail with [`PassError::RateLimited`] until enough time has
+    /// passed.
+    /// Multiple limits can be combined by calling this method repeatedly.
+    /// Clones of this store count their decryptions together.
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limiter.add(limit);
+        self
+    }
+
+    /// The limits which decryptions through this store are subject to
+    pub fn rate_limits(&self) -> &[RateLimit] {
+        self.rate_limiter.limits()
+    }
+
+    /// Keep up to *size* idle gpg contexts around for reuse by later operations of this store
+    ///
+    /// Creating a gpg context is comparatively expensive so reusing them speeds up operations which touch
+    /// many entries.
+    /// The pool is shared between clones of this store and a size of 0 disables pooling.
+    /// Defaults to 4.
+    pub fn with_gpg_context_pool_size(mut self, size: usize) -> Self {
+        self.gpg_contexts = ContextPool::new(size);
+        self
+    }
+
+    /// The maximum number of idle gpg contexts which this store keeps for reuse
+    pub fn gpg_context_pool_size(&self) -> usize {
+        self.gpg_contexts.size()
+    }
+
+    /// The locks which coordinate concurrent operations on this store and its clones
+    #[cfg(test)]
+    pub(crate) fn locks(&self) -> &StoreLocks {
+        &self.locks
+    }
+
+    /// The options with which this store encrypts and decrypts entries
+    pub(crate) fn crypto(&self) -> &CryptoOptions {
+        &self.crypto
+    }
+
+    /// Consult the [agent](crate::agent::Agent) listening on *socket_path* before decrypting entries
+    ///
+    /// Entries which are decrypted by this store are handed to the agent so that other processes can
+    /// retrieve them without decrypting them again.
+    /// If the agent is not reachable, entries are decrypted as usual.
+    #[cfg(all(feature = "agent", unix))]
+    pub fn with_agent(mut self, socket_path: impl Into<PathBuf>) -> Self {
+        self.agent = Some(AgentClient::new(socket_path));
+        self
+    }
+
+    /// The agent which is consulted before decrypting entries
+    #[cfg(all(feature = "agent", unix))]
+    pub fn agent(&self) -> Option<&AgentClient> {
+        self.agent.as_ref()
+    }
+
+    /// Memory-map entry files of at least *min_size* bytes instead of reading them into memory before
+    /// decrypting them
+    ///
+    /// This avoids holding a second copy of very large entries (like encrypted attachments) in memory.
+    /// Note that truncating a file while it is mapped terminates the process, so this should only be used if
+    /// no other process rewrites entries of the store concurrently.
+    /// By default, all files are read into memory.
+    #[cfg(feature = "mmap")]
+    pub fn with_mmap_threshold(mut self, min_size: u64) -> Self {
+        self.mmap_threshold = Some(min_size);
+        self
+    }
+
+    /// The size from which entry files are memory-mapped instead of read into memory
+    #[cfg(feature = "mmap")]
+    pub fn mmap_threshold(&self) -> Option<u64> {
+        self.mmap_threshold
+    }
+
+    /// Stream entries of at least *min_size* bytes from decryption to encryption when they are re-encrypted
+    /// (e.g. by [`Store::rotate_recipient()`]) instead of decrypting them into memory first
+    ///
+    /// This keeps memory use bounded when re-encrypting large entries like file attachments.
+    /// The plaintext of streamed entries is not normalized and the agent is not consulted for them.
+    /// Entries of at least [`DEFAULT_STREAMING_THRESHOLD`](crate::DEFAULT_STREAMING_THRESHOLD) bytes are
+    /// streamed by default; use `u64::MAX` to never stream.
+    pub fn with_streaming_threshold(mut self, min_size: u64) -> Self {
+        self.streaming_threshold = min_size;
+        self
+    }
+
+    /// The size from which entries are streamed when they are re-encrypted
+    pub fn streaming_threshold(&self) -> u64 {
+        self.streaming_threshold
+    }
+
+    /// Decrypt the content of *file*, preferring the plaintext cached by the agent if one is configured
+    ///
+    /// Failures are counted in the [metrics](Store::metrics) of this store.
+    /// This is the single point through which the plaintext of entries is read so it is normalized here as
+    /// configured via [`Store::with_line_ending()`] and [`Store::with_utf8_validation()`].
+    pub(crate) fn decrypt(&self, file: &StoreFileRef) -> Result<Vec<u8>> {
+        let result = self.decrypt_uncounted(file);
+        if result.is_err() {
+            self.decryption_errors.increment();
+        }
+        self.text.apply(result?)
+    }
+
+    /// Decrypt *ciphertext* which is not read from a file of this store (e.g. a past version of an entry)
+    /// and normalize it like [`Store::decrypt()`]
+    #[cfg(feature = "git")]
+    pub(crate) fn decrypt_ciphertext(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        self.text
+            .apply(self.crypto.decrypt(&self.gpg_contexts, ciphertext)?)
+    }
+
+    fn decrypt_uncounted(&self, file: &StoreFileRef) -> Result<Vec<u8>> {
+        #[cfg(feature = "mmap")]
+        let mmap_threshold = self.mmap_threshold;
+        #[cfg(not(feature = "mmap"))]
+        let mmap_threshold = None;
+        let ciphertext = {
+            let _span = span!("read_ciphertext", path = %file.path.display());
+            let _shared = self.locks.shared();
+            Ciphertext::load(&file.path, mmap_threshold)?
+        };
+        #[cfg(all(feature = "agent", unix))]
+        if let Some(agent) = &self.agent {
+            return agent.decrypt(&self.crypto, &self.gpg_contexts, &file.path, &ciphertext);
+        }
+        self.crypto.decrypt(&self.gpg_contexts, &ciphertext)
+    }
+
+    /// Count a decryption of *file* against the configured rate limits
+    pub(crate) fn acquire_decryption(&self, file: &StoreFileRef) -> Result<()> {
+        if self.rate_limiter.limits().is_empty() {
+            return Ok(());
+        }
+        self.rate_limiter.acquire(&self.relative_name(&file.path)?)
+    }
+
+    /// Check the permissions leading to *path* if strict permissions are enabled
+    fn check_permissions_of(&self, path: &Path) -> Result<()> {
+        if self.strict_permissions {
+            hardening::check_chain(&self.root, path)?;
+        }
+        Ok(())
+    }
+
+    /// Get a read-write IO handle to the plaintext content of *file* which applies the encryption options
+    /// of this store
+    pub fn plain_io_rw(&self, file: &StoreFileRef) -> Result<RwPlainFile> {
+        self.check_permissions_of(&file.path)?;
+        self.acquire_decryption(file)?;
+        let mut plain = RwPlainFile::with_plaintext(
+            &file.path,
+            self.decrypt(file)?,
+            self.encryption_keys(file)?,
+            self.crypto.clone(),
+            self.gpg_contexts.clone(),
+        )?;
+        self.record_access(file, AccessOperation::Decrypt)?;
+        plain.set_access_recorder(self.access.clone(), self.relative_name(&file.path)?);
+        plain.set_text_options(self.text);
+        Ok(plain)
+    }
+
+    /// Replace the password (the first line) of *file* with *password* and keep all following lines
+    ///
+    /// See [`StoreFileRef::replace_password()`].
+    /// This applies the encryption options of this store.
+    pub fn replace_password(&self, file: &StoreFileRef, password: &str) -> Result<()> {
+        let mut plain = self.plain_io_rw(file)?;
+        plain.reserve(password.len() + 1);
+        store_entry::replace_first_line(plain.as_mut(), password)?;
+        plain.close()
+    }
+
+    /// The keys for which *file* is encrypted
+    ///
+    /// These are the keys configured via [`Config::with_keys()`] (`PASSWORD_STORE_KEY`) if any and the keys
+    /// of the governing `.gpg-id` file otherwise.
+    pub fn encryption_keys(&self, file: &StoreFileRef) -> Result<Vec<gpgme::Key>> {
+        let recipients = self.recipients_of(&file.path)?;
+        let keys = self.crypto.lookup_keys(&self.gpg_contexts, &recipients)?;
+        keys.iter().try_for_each(utils::check_key_usable)?;
+        Ok(keys)
+    }
+
+    /// Decrypt *file* and verify the signatures that were made while encrypting it
+    ///
+    /// See [`StoreFileRef::verify_signature()`].
+    pub fn verify_signature(&self, file: &StoreFileRef) -> Result<SignatureStatus> {
+        self.check_permissions_of(&file.path)?;
+        self.crypto.verify_signature(&fs::read(&file.path)?)
+    }
+
+    /// The recipients for which an entry named *pass_name* is encrypted
+    ///
+    /// These are the keys configured via [`Config::with_keys()`] (`PASSWORD_STORE_KEY`) if any and the
+    /// recipients listed in the `.gpg-id` file governing the location of the entry otherwise.
+    /// The entry does not need to exist.
+    /// Governing `.gpg-id` files are cached per directory and read again once they are modified.
+    pub fn effective_recipients(&self, pass_name: &str) -> Result<Vec<String>> {
+        self.recipients_of(&self.file_path(pass_name)?)
+    }
+
+    /// The recipients for which the entry at *path* is encrypted
+    pub(crate) fn recipients_of(&self, path: &Path) -> Result<Vec<String>> {
+        if !self.config.keys().is_empty() {
+            return Ok(self.config.keys().to_vec());
+        }
+        let dir = path.parent().unwrap_or(&self.root);
+        Ok(self.recipients.resolve(&self.root, dir)?.recipients.clone())
+    }
+
+    /// List all passwords in the store in a flat data structure
+    ///
+    /// For detailed information that preserves the tree structure of the store use
+    /// [`retrieve("/")`](Store::retrieve) instead.
+    pub fn list(&self) -> Result<HashSet<StoreEntry>> {
+        Ok(self
+            .entry_files()?
+            .into_iter()
+            .map(StoreEntry::File)
+            .collect())
+    }
+
+    /// List all entries of the store which match *filter*
+    ///
+    /// Only the directories selected by *filter* are traversed and entries are filtered while traversing the
+    /// store.
+    /// See the [`filter`](crate::filter) module for details.
+    pub fn list_filtered(&self, filter: &Filter) -> Result<Listing> {
+        let _span = span!("list_filtered", root = %self.root.display());
+        if self.strict_permissions {
+            hardening::check_tree(&self.root)?;
+        }
+        let files = filter.entry_files(&self.root, self.entry_extensions())?;
+        Listing::of(
+            self,
+            files.into_iter().map(|path| StoreFileRef {
+                path,
+                root: self.root.clone(),
+            }),
+        )
+    }
+
+    /// Record the names, modification times and sizes of all entries without decrypting them
+    ///
+    /// See the [`listing`](crate::listing) module for how to use this for detecting changes.
+    pub fn listing(&self) -> Result<Listing> {
+        if self.strict_permissions {
+            hardening::check_tree(&self.root)?;
+        }
+        Listing::generate(self)
+    }
+
+    /// Compare the current entries of the store against the older listing *previous*
+    ///
+    /// Entries are compared by their modification time and size so nothing is decrypted.
+    /// The returned changes contain the current listing which should be passed to the next call.
+    pub fn changes_since(&self, previous: &Listing) -> Result<ListingChanges> {
+        Ok(self.listing()?.changes_since(previous))
+    }
+
+    /// Iterate over the names of all entries in the store
+    ///
+    /// Unlike [`Store::list()`], no collection of all entries is built up front: directories are read lazily
+    /// while iterating, which makes this the fastest way for frontends that only need entry names.
+    /// See [`names::Names`] for details.
+    pub fn names(&self) -> Result<names::Names> {
+        if self.strict_permissions {
+            hardening::check_tree(&self.root)?;
+        }
+        names::Names::new(&self.root, self.entry_extensions())
+    }
+
+    /// List all password files in the store sorted by their path
+    pub(crate) fn files(&self) -> Result<Vec<StoreFileRef>> {
+        let mut files = self.entry_files()?;
+        files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+
+    /// List all password files in the store in unspecified order
+    pub(crate) fn entry_files(&self) -> Result<Vec<StoreFileRef>> {
+        let _span = span!("list", root = %self.root.display());
+        if self.strict_permissions {
+            hardening::check_tree(&self.root)?;
+        }
+        Ok(walk::entry_files_with(&self.root, self.entry_extensions())?
+            .into_iter()
+            .map(|path| StoreFileRef {
+                path,
+                root: self.root.clone(),
+            })
+            .collect())
+    }
+
+    /// Retrieve the stored entry identified by *pass_name*
+    ///
+    /// `pass_name` is a path to a password file or directory relative to the store root which is given as string
+    /// or as [`PassName`] that was validated before.
+    /// It is normalized as described in [`names::normalize_name()`] and names ending with `/` only
+    /// reference directories.
+    /// Like *pass*, names of files may include the `.gpg` extension (e.g. `secret-a.gpg`) unless an entry
+    /// with that full name exists.
+    /// Use [`Store::retrieve_file()`] and [`Store::retrieve_dir()`] if only one kind of entry is expected.
+    /// If alias resolution is enabled (see [`Store::with_alias_resolution()`]), aliases are resolved to the
+    /// entries they redirect to.
+    pub fn retrieve(&self, pass_name: impl AsRef<str>) -> Result<StoreEntry> {
+        let pass_name = pass_name.as_ref();
+        self.resolve_entry(self.retrieve_unresolved(pass_name)?)
+    }
+
+    /// Retrieve the entry file identified by *pass_name*
+    ///
+    /// Directories with the same name are ignored so that the name is never ambiguous.
+    /// Like [`Store::retrieve()`], the `.gpg` extension may be included in the name and aliases are resolved
+    /// if enabled.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if there is no file named *pass_name*, even if a directory
+    /// with that name exists.
+    pub fn retrieve_file(&self, pass_name: impl AsRef<str>) -> Result<StoreFileRef> {
+        let pass_name = pass_name.as_ref();
+        match self.resolve_entry(self.retrieve_preferring(pass_name, Prefer::File)?)? {
+            StoreEntry::File(file) => Ok(file),
+            StoreEntry::Directory(_) => {
+                Err(PassError::EntryNotFound(names::normalize_name(pass_name)?))
+            }
+        }
+    }
+
+    /// Retrieve the directory identified by *pass_name*
+    ///
+    /// This is equivalent to retrieving *pass_name* with a trailing `/` and never returns a file.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if there is no directory named *pass_name*, even if a file
+    /// with that name exists.
+    pub fn retrieve_dir(&self, pass_name: impl AsRef<str>) -> Result<StoreDirectoryRef> {
+        let pass_name = pass_name.as_ref();
+        match self.retrieve_preferring(&format!("{}/", pass_name), Prefer::Directory)? {
+            StoreEntry::Directory(dir) => Ok(dir),
+            StoreEntry::File(_) => Err(PassError::EntryNotFound(names::normalize_name(pass_name)?)),
+        }
+    }
+
+    /// Retrieve the entry file identified by *pass_name* and fail if it is a directory
+    ///
+    /// This is like [`Store::retrieve_file()`] but distinguishes missing entries from directories.
+    ///
+    /// ## Errors
+    /// [`PassError::NotAFile`] is returned if *pass_name* only references a directory and
+    /// [`PassError::EntryNotFound`] if it references nothing at all.
+    pub fn file(&self, pass_name: impl AsRef<str>) -> Result<StoreFileRef> {
+        let pass_name = pass_name.as_ref();
+        match self.retrieve_file(pass_name) {
+            Err(PassError::EntryNotFound(name)) if names::dir_path(&self.root, &name)?.is_dir() => {
+                Err(PassError::NotAFile(name))
+            }
+            result => result,
+        }
+    }
+
+    /// Retrieve the entry file identified by *pass_name* like [`Store::file()`] but without resolving aliases
+    #[cfg(feature = "http")]
+    pub(crate) fn unresolved_file(&self, pass_name: &str) -> Result<StoreFileRef> {
+        match self.retrieve_preferring(pass_name, Prefer::File)? {
+            StoreEntry::File(file) => Ok(file),
+            StoreEntry::Directory(_) => Err(PassError::NotAFile(names::normalize_name(pass_name)?)),
+        }
+    }
+
+    /// Retrieve the directory identified by *pass_name* and fail if it is a file
+    ///
+    /// This is like [`Store::retrieve_dir()`] but distinguishes missing entries from files.
+    ///
+    /// ## Errors
+    /// [`PassError::NotADirectory`] is returned if *pass_name* only references a file and
+    /// [`PassError::EntryNotFound`] if it references nothing at all.
+    pub fn dir(&self, pass_name: impl AsRef<str>) -> Result<StoreDirectoryRef> {
+        let pass_name = pass_name.as_ref();
+        match self.retrieve_dir(pass_name) {
+            Err(PassError::EntryNotFound(name)) if self.retrieve_file(&name).is_ok() => {
+                Err(PassError::NotADirectory(name))
+            }
+            result => result,
+        }
+    }
+
+    /// The name of the entry stored in *file*
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidStoreFormat`] is returned if *file* is not located inside this store.
+    pub fn name_of(&self, file: &StoreFileRef) -> Result<PassName> {
+        PassName::new(&self.relative_name(&file.path)?)
+    }
+
+    /// Retrieve both the file and the directory identified by *pass_name*
+    ///
+    /// Unlike [`Store::retrieve()`], this does not depend on the configured [ambiguity](Store::with_ambiguity)
+    /// and returns the file first if both exist.
+    /// Otherwise, only the one existing entry is returned.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if neither a file nor a directory named *pass_name* exists.
+    pub fn retrieve_all(&self, pass_name: &str) -> Result<Vec<StoreEntry>> {
+        let mut entries = vec![self.retrieve_preferring(pass_name, Prefer::File)?];
+        if let StoreEntry::File(_) = entries[0] {
+            if let dir @ StoreEntry::Directory(_) =
+                self.retrieve_preferring(pass_name, Prefer::Directory)?
+            {
+                entries.push(dir);
+            }
+        }
+        entries
+            .into_iter()
+            .map(|entry| self.resolve_entry(entry))
+            .collect()
+    }
+
+    /// Resolve *entry* if it is an alias and alias resolution is enabled
+    fn resolve_entry(&self, entry: StoreEntry) -> Result<StoreEntry> {
+        match entry {
+            StoreEntry::File(file) if self.resolve_aliases => {
+                Ok(StoreEntry::File(self.resolve_alias(&file)?))
+            }
+            entry => Ok(entry),
+        }
+    }
+
+    /// Retrieve the stored entry identified by *pass_name* without resolving aliases
+    fn retrieve_unresolved(&self, pass_name: &str) -> Result<StoreEntry> {
+        self.retrieve_preferring(pass_name, self.ambiguity)
+    }
+
+    /// Retrieve the stored entry identified by *pass_name* without resolving aliases and resolve ambiguous
+    /// names according to *prefer*
+    fn retrieve_preferring(&self, pass_name: &str, prefer: Prefer) -> Result<StoreEntry> {
+        let _shared = self.locks.shared();
+        self.lookup(pass_name, prefer)
+    }
+
+    /// Look up the entry identified by *pass_name* like [`Store::retrieve_preferring()`] while the tree is
+    /// held
+    fn lookup(&self, pass_name: &str, prefer: Prefer) -> Result<StoreEntry> {
+        // resolve paths that could possibly be meant by pass_name
+        let normalized = names::normalize_name(pass_name)?;
+        let dir_path = names::dir_path(&self.root, &normalized)?;
+        let file_path = self.file_path(pass_name).ok();
+
+        // check if there is a file or directory with that name and return the correct result after
+        // additional verification
+        let dir_entry = || {
+            Ok(StoreEntry::Directory(StoreDirectoryRef {
+                content: inspect_folder(
+                    &self.root,
+                    &dir_path,
+                    self.entry_extensions(),
+                    if self.shallow_directories {
+                        Descend::Never
+                    } else {
+                        Descend::InParallel
+                    },
+                )?,
+                path: dir_path.clone(),
+                root: self.root.clone(),
+            }))
+        };
+        match (dir_path.is_dir(), file_path.filter(|path| path.exists())) {
+            (true, Some(file_path)) => match prefer {
+                Prefer::File => Ok(StoreEntry::File(StoreFileRef {
+                    path: file_path,
+                    root: self.root.clone(),
+                })),
+                Prefer::Directory => dir_entry(),
+                Prefer::Error => Err(PassError::AmbiguousPassName(normalized)),
+            },
+            // like pass, tolerate names which include the file extension
+            (false, None) if normalized.ends_with(".gpg") && !pass_name.ends_with('/') => {
+                let stripped = &normalized[..normalized.len() - ".gpg".len()];
+                match self.lookup(stripped, Prefer::File) {
+                    Ok(StoreEntry::File(file)) => return Ok(StoreEntry::File(file)),
+                    _ => Err(PassError::EntryNotFound(normalized)),
+                }
+            }
+            (false, None) => Err(PassError::EntryNotFound(normalized)),
+            (true, None) => dir_entry(),
+            (false, Some(file_path)) => Ok(StoreEntry::File(StoreFileRef {
+                path: file_path,
+                root: self.root.clone(),
+            })),
+        }
+        .and_then(|store_entry| {
+            store_entry.verify()?;
+            match &store_entry {
+                StoreEntry::File(file) => self.check_permissions_of(&file.path)?,
+                StoreEntry::Directory(dir) => {
+                    self.check_permissions_of(&dir.path)?;
+                    if self.strict_permissions && !self.shallow_directories {
+                        hardening::check_tree(&dir.path)?;
+                    }
+                }
+            }
+            Ok(store_entry)
+        })
+    }
+
+    /// Check whether entries of this store can currently be decrypted
+    ///
+    /// This is a health check which decrypts one entry of the store and reports problems with the local gpg
+    /// setup in an actionable way, e.g. [`PassError::CardMissing`] if the smartcard holding the secret key
+    /// is not inserted, [`PassError::PinRequired`] or [`PassError::AgentUnavailable`].
+    /// Note that this may trigger a pinentry prompt if the secret key is not yet unlocked.
+    ///
+    /// A store without entries is trivially decryptable.
+    pub fn probe_decryption_capability(&self) -> Result<()> {
+        match self.files()?.into_iter().next() {
+            None => Ok(()),
+            Some(file) => {
+                debug!("Probing decryption capability with {}", file.path.display());
+                self.acquire_decryption(&file)?;
+                file.plain_io_ro()?;
+                self.record_access(&file, AccessOperation::Decrypt)
+            }
+        }
+    }
+
+    /// Decrypt one entry so that subsequent bulk operations do not stall on the first pinentry prompt
+    ///
+    /// After gpg-agent cached the passphrase (see [`Store::passphrase_cached()`]), many entries can be
+    /// decrypted in a row without interaction.
+    /// The decrypted entry is the first one of the store and the decryption is observed like any other
+    /// (see [`Store::set_access_logger()`]).
+    /// Nothing is decrypted if the passphrase is already cached.
+    pub fn warm_up(&self) -> Result<()> {
+        if self.passphrase_cached()? {
+            debug!("Passphrase is already cached by gpg-agent");
+            return Ok(());
+        }
+        self.probe_decryption_capability()
+    }
+
+    /// Check whether gpg-agent caches the passphrase of a secret key which can decrypt the entries of this
+    /// store
+    ///
+    /// The recipients of the first entry of the store are checked with
+    /// [`keys::is_passphrase_cached()`](crate::keys::is_passphrase_cached) which does not decrypt anything.
+    /// A store without entries needs no passphrase and is always reported as cached.
+    pub fn passphrase_cached(&self) -> Result<bool> {
+        let file = match self.files()?.into_iter().next() {
+            None => return Ok(true),
+            Some(file) => file,
+        };
+
+        let mut gpg_ctx = self.crypto.context(&self.gpg_contexts)?;
+        for key_id in file.recipient_key_ids()? {
+            if let Ok(key) = gpg_ctx.get_secret_key(key_id.as_str()) {
+                if keys::is_passphrase_cached(&key)? {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Perform a comprehensive consistency check of the store
+    ///
+    /// The check looks for problems like corrupted or unreadable entries, files that are not part of the
+    /// store format, empty directories, broken symlinks, entries which are not covered by a `.gpg-id` file
+    /// and entries whose recipients differ from their `.gpg-id` file.
+    /// All findings are collected into an [`FsckReport`]; errors are only returned if the check itself
+    /// could not be performed.
+    pub fn fsck(&self) -> Result<FsckReport> {
+        fsck::check_store(&self.root, &self.crypto)
+    }
+
+    /// Synchronize the store with the upstream of its current git branch
+    ///
+    /// Remote changes are fetched and local commits are rebased on top of them.
+    /// Whenever an entry was changed on both sides, *resolve* is called to decide how the conflict is
+    /// resolved (see [`ConflictResolution`](crate::git::ConflictResolution)).
+    /// The store stays locked exclusively while the rebase is in progress, so *resolve* must not use this
+    /// store or one of its clones because that would dead-lock; the [`Conflict`](crate::git::Conflict)
+    /// contains both versions of the entry instead.
+    /// Afterwards, local commits are pushed and submodules are updated to the commits recorded in the store.
+    /// Submodules are not synchronized with their own remotes.
+    /// If anything fails while rebasing, the rebase is aborted and the repository is left unchanged.
+    ///
+    /// Authentication uses the ssh agent or the credential helpers configured in git.
+    ///
+    /// ## Errors
+    /// [`PassError::GitConflict`] is returned if a file which is not an entry (e.g. `.gpg-id`) is conflicting.
+    #[cfg(feature = "git")]
+    pub fn git_sync(
+        &self,
+        mut resolve: impl FnMut(&git::Conflict) -> git::ConflictResolution,
+    ) -> Result<git::SyncReport> {
+        let _exclusive = self.locks.exclusive();
+        git::sync(self, &mut resolve)
+    }
+
+    /// List the entries of this store as they were recorded in the git commit *rev* without checking it out
+    ///
+    /// *rev* is any revision which git understands, e.g. a commit id, `HEAD~3` or a tag.
+    /// Use [`Store::plain_io_at()`] to decrypt the listed entries.
+    /// Entries inside of submodules are not listed.
+    #[cfg(feature = "git")]
+    pub fn list_at(&self, rev: &str) -> Result<git::PastListing> {
+        let _span = span!("list_at", root = %self.root.display(), rev = rev);
+        git::list_at(self, rev)
+    }
+
+    /// Get a read-only IO handle to the plaintext of *entry* as it was recorded in a past commit
+    ///
+    /// Rate limits and the access log apply as if the current entry of the same name was decrypted.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidPassName`] is returned if the name of *entry* does not denote a file inside of the
+    /// store.
+    #[cfg(feature = "git")]
+    pub fn plain_io_at(&self, entry: &git::PastEntry) -> Result<RoPlainFile> {
+        let file = StoreFileRef {
+            path: names::file_path(&self.root, &entry.pass_name)?,
+            root: self.root.clone(),
+        };
+        self.acquire_decryption(&file)?;
+        let ciphertext = git::past_ciphertext(self, entry)?;
+        let plaintext = self.decrypt_ciphertext(&ciphertext)?;
+        self.record_access(&file, AccessOperation::Decrypt)?;
+        Ok(RoPlainFile::from_plaintext(plaintext))
+    }
+
+    /// Copy the encrypted files of this store into a new timestamped snapshot below *dest*
+    ///
+    /// See the [`snapshot`](crate::snapshot) module for details.
+    pub fn snapshot(&self, dest: impl AsRef<Path>) -> Result<Snapshot> {
+        snapshot::create(&self.root, dest.as_ref())
+    }
+
+    /// Roll the entries or directories *pass_names* back to their state in *snapshot*
+    ///
+    /// The encrypted files are copied back as they are; other entries of the store are not touched and
+    /// entries which were added to a restored directory after the snapshot was created are kept.
+    /// The restored entries are returned.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if one of *pass_names* does not exist in *snapshot*.
+    pub fn restore(&self, snapshot: &Snapshot, pass_names: &[&str]) -> Result<Vec<StoreFileRef>> {
+        let mut sources = Vec::new();
+        for pass_name in pass_names {
+            let file = names::file_path(snapshot.path(), pass_name)?;
+            let dir = names::dir_path(snapshot.path(), pass_name)?;
+            if file.is_file() {
+                sources.push(file);
+            } else if dir.is_dir() {
+                sources.extend(walk::entry_files(&dir)?);
+            } else {
+                return Err(PassError::EntryNotFound(pass_name.to_string()));
+            }
+        }
+
+        let mut restored = Vec::new();
+        for source in sources {
+            let pass_name = names::path_to_name(snapshot.path(), &source)?;
+            debug!("Restoring {} from {}", pass_name, snapshot.path().display());
+            restored.push(self.write_ciphertext(self.file_path(&pass_name)?, &fs::read(&source)?)?);
+        }
+        Ok(restored)
+    }
+
+    /// Write the encrypted files of the entries *pass_names* into a single bundle
+    ///
+    /// Directories include all entries below them.
+    /// The `.gpg-id` files governing the entries and their signatures are included as well so that the
+    /// recipients of the entries are known when unpacking them.
+    /// See the [`bundle`](crate::bundle) module for details.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if one of *pass_names* does not exist.
+    pub fn pack(&self, pass_names: &[&str], writer: impl Write) -> Result<()> {
+        let mut sources = Vec::new();
+        for pass_name in pass_names {
+            let dir = names::dir_path(&self.root, pass_name)?;
+            match self.retrieve_file(pass_name) {
+                Ok(file) => sources.push(file.path),
+                Err(PassError::EntryNotFound(_)) if dir.is_dir() => {
+                    sources.extend(walk::entry_files(&dir)?)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        sources.sort_unstable();
+        sources.dedup();
+
+        let mut gpg_ids = Vec::new();
+        for source in &sources {
+            let dir = source.parent().unwrap_or(&self.root);
+            let gpg_id = self.recipients.resolve(&self.root, dir)?.gpg_id.clone();
+            let mut signature = gpg_id.clone().into_os_string();
+            signature.push(".sig");
+            for path in [gpg_id, PathBuf::from(signature)] {
+                if path.is_file() && !gpg_ids.contains(&path) {
+                    gpg_ids.push(path);
+                }
+            }
+        }
+        gpg_ids.sort_unstable();
+
+        let mut files = Vec::new();
+        for path in gpg_ids.into_iter().chain(sources) {
+            let relative = path.strip_prefix(&self.root).unwrap_or(&path);
+            files.push(BundleFile {
+                path: utils::path2str(relative)?.replace(std::path::MAIN_SEPARATOR, "/"),
+                content: fs::read(&path)?,
+            });
+        }
+        debug!("Packing {} files of {}", files.len(), self.root.display());
+        bundle::write(&files, writer)
+    }
+
+    /// Write the entries of a bundle created by [`Store::pack()`] into this store
+    ///
+    /// `.gpg-id` files and signatures of the bundle are only written where the store has none yet so that
+    /// the recipients of an existing store are never changed.
+    /// Use [`Store::check_recipients()`] afterwards to find unpacked entries which are encrypted for other
+    /// keys than their new location requires.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidBundle`] is returned if the bundle is malformed or corrupted or if it contains a
+    /// `.gpg-id` file which would change the recipients of existing entries and
+    /// [`PassError::EntryAlreadyExists`] if one of its entries already exists.
+    /// In all cases, nothing is written.
+    pub fn unpack(&self, reader: impl Read) -> Result<Vec<StoreFileRef>> {
+        let files = bundle::read(reader)?;
+        for file in &files {
+            match file.pass_name() {
+                Some(pass_name) if self.file_path(pass_name)?.exists() => {
+                    return Err(PassError::EntryAlreadyExists(pass_name.to_string()));
+                }
+                Some(_) => {}
+                None if file.path.ends_with(".gpg-id") => self.check_new_gpg_id(&file.path)?,
+                None => {}
+            }
+        }
+
+        let mut unpacked = Vec::new();
+        for file in files {
+            match file.pass_name() {
+                Some(pass_name) => {
+                    debug!("Unpacking {}", pass_name);
+                    unpacked
+                        .push(self.write_ciphertext(self.file_path(pass_name)?, &file.content)?);
+                }
+                None => {
+                    let path = self.root.join(&file.path);
+                    if !path.exists() {
+                        self.check_permissions_of(&path)?;
+                        if let Some(parent) = path.parent() {
+                            utils::create_dir_all(parent, self.config.umask())?;
+                        }
+                        utils::write_atomically(&path, &file.content, self.config.umask())?;
+                        self.track_in_manifest(&path)?;
+                        self.recipients.clear();
+                    }
+                }
+            }
+        }
+        Ok(unpacked)
+    }
+
+    /// Make sure that the `.gpg-id` file at *path* of a bundle does not govern existing entries if it is
+    /// written into this store
+    fn check_new_gpg_id(&self, path: &str) -> Result<()> {
+        let gpg_id = self.root.join(path);
+        let dir = gpg_id.parent().unwrap_or(&self.root);
+        if gpg_id.exists() || !dir.is_dir() {
+            return Ok(());
+        }
+        for entry in walk::entry_files(dir)? {
+            let governed_outside = match self
+                .recipients
+                .resolve(&self.root, entry.parent().unwrap_or(&self.root))
+            {
+                Ok(recipients) => !recipients.gpg_id.starts_with(dir),
+                Err(PassError::StoreNotInitialized(_)) => true,
+                Err(e) => return Err(e),
+            };
+            if governed_outside {
+                return Err(PassError::InvalidBundle(format!(
+                    "{} would change the recipients of the existing entry {}",
+                    path,
+                    self.relative_name(&entry)?
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Encrypt the content of the entry *pass_name* for the single key *recipient* which does not need to be
+    /// a recipient of the store
+    ///
+    /// *recipient* may be anything that identifies a key of the local keyring, e.g. a fingerprint or an email
+    /// address.
+    /// The encryption options of this store apply and nothing is written into the store.
+    /// See the [`share`](crate::share) module for details.
+    ///
+    /// ## Errors
+    /// [`PassError::GpgKeyNotFoundError`] or [`PassError::UnusableRecipient`] is returned if *recipient*
+    /// cannot be used for encryption.
+    pub fn share(
+        &self,
+        pass_name: &str,
+        recipient: &str,
+        options: &ShareOptions,
+    ) -> Result<SharedEntry> {
+        let file = self.file(pass_name)?;
+        let key = self
+            .crypto
+            .lookup_keys(&self.gpg_contexts, &[recipient.to_string()])?
+            .remove(0);
+        utils::check_key_usable(&key)?;
+
+        let plaintext = self.read_plaintext(&file)?;
+        let content = if options.password_only() {
+            plaintext
+                .split(|&byte| byte == b'\n')
+                .next()
+                .unwrap_or_default()
+        } else {
+            &plaintext
+        };
+        debug!(
+            "Sharing {} with {}",
+            pass_name,
+            key.fingerprint().unwrap_or_default()
+        );
+        Ok(SharedEntry {
+            pass_name: self.relative_name(&file.path)?,
+            armored: self.crypto.encrypt_armored(
+                &self.gpg_contexts,
+                std::slice::from_ref(&key),
+                content,
+            )?,
+            recipient: AccessKey::of(&key),
+            password_only: options.password_only(),
+            created_at: SystemTime::now(),
+        })
+    }
+
+    /// Write a deduplicated backup of the encrypted files of this store into *dir*
+    ///
+    /// Only files which are not yet part of another backup in *dir* are copied.
+    /// See the [`snapshot`](crate::snapshot#deduplicated-backups) module for details.
+    pub fn backup(&self, dir: impl AsRef<Path>) -> Result<Backup> {
+        snapshot::create_backup(&self.root, dir.as_ref())
+    }
+
+    /// Roll the entries or directories *pass_names* back to their state in *backup*
+    ///
+    /// This works like [`Store::restore()`] but nothing is restored if the content of one of the entries
+    /// does not match the hash recorded in the backup.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if one of *pass_names* does not exist in *backup* and
+    /// [`PassError::InvalidStoreFormat`] if the backup is damaged.
+    pub fn restore_backup(
+        &self,
+        backup: &Backup,
+        pass_names: &[&str],
+    ) -> Result<Vec<StoreFileRef>> {
+        let mut entries = Vec::new();
+        for pass_name in pass_names {
+            entries.extend(backup.read_entries(pass_name)?);
+        }
+
+        let mut restored = Vec::new();
+        for (pass_name, ciphertext) in entries {
+            debug!(
+                "Restoring {} from {}",
+                pass_name,
+                backup.manifest().display()
+            );
+            restored.push(self.write_ciphertext(self.file_path(&pass_name)?, &ciphertext)?);
+        }
+        Ok(restored)
+    }
+
+    /// Merge the entries of *other* into this store, e.g. after two copies of a store diverged while being
+    /// synchronized with Syncthing
+    ///
+    /// Entries which only exist in *other* are copied as they are (without re-encrypting them) unless the
+    /// recipients of their destination differ, in which case they are encrypted again for those recipients.
+    /// Entries which exist in both stores are decrypted if their files differ and whenever their content
+    /// differs, *strategy* decides how the conflict is resolved (see [`MergeStrategy`](crate::merge::MergeStrategy)).
+    /// Entries which only exist in this store are kept.
+    pub fn merge_from(
+        &self,
+        other: &Store,
+        mut strategy: impl MergeStrategy,
+    ) -> Result<MergeReport> {
+        merge::merge(self, other, &mut strategy)
+    }
+
+    /// Clone the store from the git remote at *url* into *path* and open it
+    ///
+    /// Submodules are cloned as well.
+    /// After cloning, every `.gpg-id` file (including those in submodules) must carry a valid signature by one
+    /// of the configured [signing keys](git::CloneOptions::with_signing_keys) so that a compromised remote can
+    /// not silently add its own recipients.
+    /// If verification fails, the clone is removed again, i.e. *path* is removed if it did not exist beforehand
+    /// and emptied again if it was an empty directory.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidConfig`] is returned before cloning if verification is enabled but no signing keys
+    /// are configured or one of them is no full fingerprint.
+    /// [`PassError::InvalidGpgIdSignature`] is returned if a `.gpg-id` file fails verification.
+    #[cfg(feature = "git")]
+    pub fn clone_from(
+        url: &str,
+        path: impl AsRef<Path>,
+        options: &git::CloneOptions,
+    ) -> Result<Self> {
+        git::clone(url, path.as_ref(), options)
+    }
+
+    /// Initialize and update all git submodules of the store recursively
+    ///
+    /// Submodules are checked out at the commits which are recorded in their parent repository.
+    /// This is done automatically by [`Store::clone_from()`] and [`Store::git_sync()`] but may be called
+    /// whenever submodules need to be brought up to date, e.g. after new ones were added manually.
+    ///
+    /// The working directories of all updated submodules are returned.
+    #[cfg(feature = "git")]
+    pub fn update_submodules(&self) -> Result<Vec<PathBuf>> {
+        git::update_submodules(&git2::Repository::open(&self.root)?)
+    }
+
+    /// The working directory of the git repository that *file* belongs to
+    ///
+    /// For entries inside of a submodule, this is the working directory of the submodule so that history
+    /// operations can be attributed to the repository which actually records them.
+    #[cfg(feature = "git")]
+    pub fn repository_of(&self, file: &StoreFileRef) -> Result<PathBuf> {
+        git::repository_of(&file.path)
+    }
+
+    /// Resolve the path of the file that stores the entry *pass_name*
+    fn file_path(&self, pass_name: &str) -> Result<PathBuf> {
+        let path = names::file_path(&self.root, pass_name)?;
+        if self.asc_entries && !path.exists() {
+            let asc_path = path.with_extension("asc");
+            if asc_path.is_file() {
+                return Ok(asc_path);
+            }
+        }
+        Ok(path)
+    }
+
+    /// Insert a new entry named *pass_name* with the given plaintext *content* into the store
+    ///
+    /// *content* can be given as byte slice or string (which is stored like `pass insert --multiline` does)
+    /// or as an [`InsertSource`] which selects the input mode of `pass insert`.
+    /// Trailing newlines are normalized as configured via [`Store::with_trailing_newline()`].
+    ///
+    /// The content is encrypted for the keys of the `.gpg-id` file governing the entries location and
+    /// missing parent directories are created.
+    /// New files and directories get permissions derived from the configured
+    /// [umask](Config::umask) (`PASSWORD_STORE_UMASK`).
+    ///
+    /// ## Errors
+    /// [`PassError::EntryAlreadyExists`] is returned if an entry with the same name already exists and
+    /// [`PassError::StoreNotInitialized`] if no `.gpg-id` file governs its location (see
+    /// [`Store::insert_with_keys()`]).
+    pub fn insert<'a>(
+        &self,
+        pass_name: impl AsRef<str>,
+        content: impl Into<InsertSource<'a>>,
+    ) -> Result<StoreFileRef> {
+        self.insert_with(pass_name, content, OverwritePolicy::Error)
+    }
+
+    /// Insert a new entry named *pass_name* like [`Store::insert()`] but handle an existing entry with the
+    /// same name according to *policy*
+    ///
+    /// The returned reference points to the written entry which has another name than *pass_name* if
+    /// [`OverwritePolicy::KeepBoth`] had to choose a free name.
+    pub fn insert_with<'a>(
+        &self,
+        pass_name: impl AsRef<str>,
+        content: impl Into<InsertSource<'a>>,
+        policy: OverwritePolicy,
+    ) -> Result<StoreFileRef> {
+        let path = self.target_path(pass_name.as_ref(), policy)?;
+        let content = content.into().into_content(self.trailing_newline)?;
+        self.write_new(path, content)
+    }
+
+    /// Insert a new entry named *pass_name* like [`Store::insert()`] and initialize the store for *keys* if
+    /// necessary
+    ///
+    /// If no `.gpg-id` file governs the location of the entry, a `.gpg-id` file listing *keys* is created at
+    /// the store root first (like `pass init` does).
+    /// Otherwise, the entry is encrypted for the governing recipients as usual and *keys* are not used.
+    ///
+    /// ## Errors
+    /// [`PassError::GpgKeyNotFoundError`] or [`PassError::UnusableRecipient`] is returned before anything is
+    /// written if one of *keys* cannot be used for encryption and [`PassError::StoreNotInitialized`] if the
+    /// store would have to be initialized but *keys* is empty.
+    pub fn insert_with_keys<'a>(
+        &self,
+        pass_name: impl AsRef<str>,
+        content: impl Into<InsertSource<'a>>,
+        keys: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<StoreFileRef> {
+        let path = self.target_path(pass_name.as_ref(), OverwritePolicy::Error)?;
+        match self.recipients_of(&path) {
+            Err(PassError::StoreNotInitialized(_)) => {
+                let keys = keys.into_iter().map(Into::into).collect::<Vec<String>>();
+                if keys.is_empty() {
+                    return Err(PassError::StoreNotInitialized(self.root.clone()));
+                }
+                self.crypto
+                    .lookup_keys(&self.gpg_contexts, &keys)?
+                    .iter()
+                    .try_for_each(utils::check_key_usable)?;
+
+                trace!("Initializing store at {}", self.root.display());
+                let gpg_id = self.root.join(".gpg-id");
+                self.check_permissions_of(&gpg_id)?;
+                let mut content = keys.join("\n");
+                content.push('\n');
+                utils::write_atomically(&gpg_id, content.as_bytes(), self.config.umask())?;
+                self.track_in_manifest(&gpg_id)?;
+            }
+            result => {
+                result?;
+            }
+        }
+        let content = content.into().into_content(self.trailing_newline)?;
+        self.write_new(path, content)
+    }
+
+    /// Generate a password with *generator* and store it as the only line of a new entry named *pass_name*
+    /// (like `pass generate`)
+    ///
+    /// An existing entry with the same name is handled according to *policy*.
+    /// The written entry is returned together with the generated password.
+    /// Use [`PasswordGenerator::from_config()`] to generate passwords like *pass* does.
+    pub fn generate(
+        &self,
+        pass_name: &str,
+        generator: &PasswordGenerator,
+        policy: OverwritePolicy,
+    ) -> Result<(StoreFileRef, SecretString)> {
+        let password = generator.generate()?;
+        let file = self.insert_with(
+            pass_name,
+            InsertSource::SingleLine(SecretString::new(password.expose())),
+            policy,
+        )?;
+        Ok((file, password))
+    }
+
+    /// Copy the entry *from* to a new entry named *to* (like `pass cp`)
+    ///
+    /// The content is decrypted and encrypted again for the keys which govern the location of the copy.
+    /// If *to* ends with `/` or names an existing directory, the copy is placed inside of it and keeps its
+    /// name.
+    /// An existing entry at the destination is handled according to *policy*.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidPassName`] is returned if *from* is a directory.
+    pub fn copy(&self, from: &str, to: &str, policy: OverwritePolicy) -> Result<StoreFileRef> {
+        let source = match self.retrieve_unresolved(from)? {
+            StoreEntry::File(file) => file,
+            StoreEntry::Directory(_) => {
+                return Err(PassError::InvalidPassName(
+                    from.to_string(),
+                    "Only entries can be copied".to_string(),
+                ))
+            }
+        };
+        let into_dir = to.ends_with('/')
+            || matches!(self.retrieve_unresolved(to), Ok(StoreEntry::Directory(_)));
+        let to = match into_dir {
+            true => {
+                let source_name = PassName::new(&self.relative_name(&source.path)?)?;
+                PassName::new(to)?
+                    .join(source_name.file_name().unwrap_or_default())?
+                    .into_string()
+            }
+            false => to.to_string(),
+        };
+
+        let path = self.target_path(&to, policy)?;
+        self.acquire_decryption(&source)?;
+        let plaintext = self.decrypt(&source)?;
+        self.record_access(&source, AccessOperation::Decrypt)?;
+        self.write_new(path, plaintext)
+    }
+
+    /// Create one entry below the directory *dir* for every variable of the `.env` file *content*
+    ///
+    /// The entries are named like the lowercased variables and contain their values as password, so that
+    /// [`StoreDirectoryRef::render_dotenv()`] produces the same variables again.
+    /// Existing entries are handled according to *policy*.
+    /// See [`export::parse_dotenv()`](crate::export::parse_dotenv) for the supported syntax.
+    pub fn import_dotenv(
+        &self,
+        dir: &str,
+        content: &str,
+        policy: OverwritePolicy,
+    ) -> Result<Vec<StoreFileRef>> {
+        let dir = dir.trim_end_matches('/');
+        export::parse_dotenv(content)?
+            .into_iter()
+            .map(|(variable, value)| {
+                let name = match dir {
+                    "" => variable.to_lowercase(),
+                    dir => format!("{}/{}", dir, variable.to_lowercase()),
+                };
+                self.insert_with(&name, InsertSource::SingleLine(value), policy)
+            })
+            .collect()
+    }
+
+    /// The path at which a new entry named *pass_name* is written according to *policy*
+    ///
+    /// [`PassError::EntryProtected`] is returned if a protected entry would be overwritten.
+    pub(crate) fn target_path(&self, pass_name: &str, policy: OverwritePolicy) -> Result<PathBuf> {
+        let path = self.file_path(pass_name)?;
+        if !path.exists() {
+            return Ok(path);
+        }
+        match policy {
+            OverwritePolicy::Error => Err(PassError::EntryAlreadyExists(pass_name.to_string())),
+            OverwritePolicy::Overwrite => {
+                self.check_unprotected(&path)?;
+                Ok(path)
+            }
+            OverwritePolicy::KeepBoth => {
+                let pass_name = pass_name.trim_end_matches('/');
+                let mut suffix = 2;
+                loop {
+                    let path = self.file_path(&format!("{} ({})", pass_name, suffix))?;
+                    if !path.exists() {
+                        return Ok(path);
+                    }
+                    suffix += 1;
+                }
+            }
+        }
+    }
+
+    /// Encrypt *content* and write it to *path*, replacing a file which possibly exists there
+    ///
+    /// *content* is overwritten with zeroes afterwards.
+    pub(crate) fn write_new(&self, path: PathBuf, mut content: Vec<u8>) -> Result<StoreFileRef> {
+        self.check_permissions_of(&path)?;
+
+        trace!("Inserting new entry at {}", path.display());
+        let file = StoreFileRef {
+            path,
+            root: self.root.clone(),
+        };
+        let ciphertext = self.encrypt_for(&file, &content);
+        secure_mem::release(&mut content, &mut LockedRegion::default());
+        let ciphertext = ciphertext?;
+        {
+            let _shared = self.locks.shared();
+            if let Some(parent) = file.path.parent() {
+                utils::create_dir_all(parent, self.config.umask())?;
+            }
+            utils::write_atomically(&file.path, &ciphertext, self.config.umask())?;
+        }
+        self.track_in_manifest(&file.path)?;
+        self.record_access(&file, AccessOperation::Write)?;
+        Ok(file)
+    }
+
+    /// Write already encrypted *ciphertext* to *path*, replacing a file which possibly exists there
+    ///
+    /// [`PassError::EntryProtected`] is returned if the replaced file is a protected entry.
+    pub(crate) fn write_ciphertext(
+        &self,
+        path: PathBuf,
+        ciphertext: &[u8],
+    ) -> Result<StoreFileRef> {
+        self.check_permissions_of(&path)?;
+        if path.exists() {
+            self.check_unprotected(&path)?;
+        }
+        let file = StoreFileRef {
+            path,
+            root: self.root.clone(),
+        };
+        {
+            let _shared = self.locks.shared();
+            if let Some(parent) = file.path.parent() {
+                utils::create_dir_all(parent, self.config.umask())?;
+            }
+            utils::write_atomically(&file.path, ciphertext, self.config.umask())?;
+        }
+        self.track_in_manifest(&file.path)?;
+        self.record_access(&file, AccessOperation::Write)?;
+        Ok(file)
+    }
+
+    /// Create an entry named *pass_name* which redirects to the existing entry *target*
+    ///
+    /// The alias is a regular entry containing the line `@alias: <target>` so that it is encrypted like
+    /// every other entry and shows where it leads when viewed with *pass*.
+    /// This is useful to keep old names of renamed services working.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if *target* does not exist and
+    /// [`PassError::InvalidPassName`] if it is a directory.
+    pub fn create_alias(&self, pass_name: &str, target: &str) -> Result<StoreFileRef> {
+        if let StoreEntry::Directory(_) = self.retrieve_unresolved(target)? {
+            return Err(PassError::InvalidPassName(
+                target.to_string(),
+                "Aliases can only redirect to entries".to_string(),
+            ));
+        }
+        let content = alias::content(&names::normalize_name(target)?);
+        self.insert(pass_name, content)
+    }
+
+    /// Follow the alias *file* (and all aliases it leads to) to the entry which is not an alias
+    ///
+    /// Entries which are no aliases resolve to themselves.
+    /// Every visited entry is decrypted.
+    ///
+    /// ## Errors
+    /// [`PassError::AliasLoop`] is returned if an alias leads back to an already visited alias.
+    pub fn resolve_alias(&self, file: &StoreFileRef) -> Result<StoreFileRef> {
+        let mut chain = vec![self.relative_name(&file.path)?];
+        let mut current = file.clone();
+        loop {
+            self.acquire_decryption(&current)?;
+            let plaintext = self.decrypt(&current)?;
+            self.record_access(&current, AccessOperation::Decrypt)?;
+            let target = match alias::target(&plaintext) {
+                None => return Ok(current),
+                Some(target) => names::normalize_name(&target)?,
+            };
+
+            let visited = chain.contains(&target);
+            chain.push(target);
+            if visited {
+                return Err(PassError::AliasLoop(chain));
+            }
+            current = match self.retrieve_unresolved(&chain[chain.len() - 1])? {
+                StoreEntry::File(file) => file,
+                StoreEntry::Directory(dir) => {
+                    return Err(PassError::InvalidStoreFormat(
+                        dir.path,
+                        "Alias redirects to a directory instead of an entry".to_string(),
+                    ))
+                }
+            };
+        }
+    }
+
+    /// Remove the entry named *pass_name* from the store
+    ///
+    /// Only single entries can be removed; directories are left untouched unless
+    /// [auto-pruning](Store::with_auto_prune) is enabled.
+    /// The file is overwritten first if [secure delete](Store::with_secure_delete) is enabled and the
+    /// [scrub hooks](Store::with_scrub_hook) are called afterwards.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if there is no entry named *pass_name* and
+    /// [`PassError::EntryProtected`] if the entry is [protected](Store::protect).
+    /// If a scrub hook fails, its error is returned although the entry was removed.
+    pub fn remove(&self, pass_name: impl AsRef<str>) -> Result<()> {
+        let pass_name = pass_name.as_ref();
+        let path = self.file_path(pass_name)?;
+        if !path.is_file() {
+            return Err(PassError::EntryNotFound(pass_name.to_string()));
+        }
+        self.check_permissions_of(&path)?;
+        self.check_unprotected(&path)?;
+
+        trace!("Removing entry at {}", path.display());
+        {
+            let _shared = self.locks.shared();
+            self.scrubber.prepare_removal(&path)?;
+            fs::remove_file(&path)?;
+        }
+        if self.auto_prune {
+            let _exclusive = self.locks.exclusive();
+            self.prune_parents(&path)?;
+        }
+        self.track_in_manifest(&path)?;
+        let removed = RemovedEntry {
+            pass_name: self.relative_name(&path)?,
+            path: path.strip_prefix(&self.root).unwrap_or(&path).to_owned(),
+        };
+        let file = StoreFileRef {
+            path,
+            root: self.root.clone(),
+        };
+        self.record_access(&file, AccessOperation::Delete)?;
+        self.scrubber.run_hooks(&self.root, &removed)
+    }
+
+    /// Remove all directories of the store which do not contain any entries
+    ///
+    /// Directories are removed bottom-up so that directories which only contained empty directories are removed
+    /// as well.
+    /// Directories which contain anything except entries and subdirectories (like hidden files or
+    /// directories) are kept.
+    /// A directory whose only remaining content is a `.gpg-id` file (and its signature) is only removed if
+    /// *confirm* returns `true` for its name since it may have been prepared for new entries.
+    /// The store root is never removed.
+    ///
+    /// The names of the removed directories are returned in the order in which they were removed.
+    pub fn prune_empty_dirs(&self, mut confirm: impl FnMut(&str) -> bool) -> Result<Vec<String>> {
+        let _span = span!("prune_empty_dirs", root = %self.root.display());
+        // entries which are written concurrently would otherwise lose their directory
+        let _exclusive = self.locks.exclusive();
+        let mut pruned = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() && !is_hidden(&entry.file_name()) {
+                self.prune_dir(&entry.path(), &mut confirm, &mut pruned)?;
+            }
+        }
+        if !pruned.is_empty() {
+            self.recipients.clear();
+        }
+        Ok(pruned)
+    }
+
+    /// Remove *dir* if it does not contain any entries after pruning its subdirectories and return whether it
+    /// was removed
+    fn prune_dir(
+        &self,
+        dir: &Path,
+        confirm: &mut dyn FnMut(&str) -> bool,
+        pruned: &mut Vec<String>,
+    ) -> Result<bool> {
+        let mut keep = false;
+        let mut gpg_id_files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            if entry.file_type()?.is_dir() && !is_hidden(&file_name) {
+                keep |= !self.prune_dir(&entry.path(), confirm, pruned)?;
+            } else if file_name == ".gpg-id" || file_name == ".gpg-id.sig" {
+                gpg_id_files.push(entry.path());
+            } else {
+                keep = true;
+            }
+        }
+        if keep {
+            return Ok(false);
+        }
+        let name = names::path_to_name(&self.root, dir)?;
+        if !gpg_id_files.is_empty() && !confirm(&name) {
+            return Ok(false);
+        }
+        trace!("Pruning empty directory {}", dir.display());
+        let mut removed = Vec::new();
+        for file in &gpg_id_files {
+            removed.push((file, fs::read(file)?));
+            fs::remove_file(file)?;
+        }
+        if !remove_empty_dir(dir)? {
+            // something was added to the directory in between so it keeps its recipients
+            if dir.is_dir() {
+                for (file, content) in removed {
+                    utils::write_atomically(file, &content, self.config.umask())?;
+                }
+            }
+            return Ok(false);
+        }
+        self.track_all_in_manifest(&gpg_id_files)?;
+        pruned.push(name);
+        Ok(true)
+    }
+
+    /// Remove the directories leading to the removed entry at *path* as long as they are empty
+    fn prune_parents(&self, path: &Path) -> Result<()> {
+        let mut dir = path.parent();
+        while let Some(parent) = dir.filter(|dir| *dir != self.root) {
+            if !remove_empty_dir(parent)? {
+                break;
+            }
+            trace!("Pruned empty directory {}", parent.display());
+            dir = parent.parent();
+        }
+        Ok(())
+    }
+
+    /// Remove directories which became empty when removing entries
+    ///
+    /// Directories holding a `.gpg-id` file are never removed this way; use [`Store::prune_empty_dirs()`] for
+    /// these.
+    ///
+    /// Disabled by default.
+    pub fn with_auto_prune(mut self, enabled: bool) -> Self {
+        self.auto_prune = enabled;
+        self
+    }
+
+    /// Whether directories which became empty when removing entries are removed
+    pub fn auto_prune(&self) -> bool {
+        self.auto_prune
+    }
+
+    /// Protect the entry named *pass_name* against being removed or replaced through this library
+    ///
+    /// See the [`protection`](crate::protection) module for details.
+    /// Protecting an entry twice has no effect.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryNotFound`] is returned if there is no entry named *pass_name*.
+    pub fn protect(&self, pass_name: &str) -> Result<()> {
+        let file = self.file(pass_name)?;
+        let name = self.relative_name(&file.path)?;
+        let _metadata = self.locks.metadata();
+        let mut protected = protection::load(&self.root)?;
+        if protected.insert(name) {
+            protection::save(&self.root, &protected, self.config.umask())?;
+        }
+        Ok(())
+    }
+
+    /// Remove the protection of the entry named *pass_name* and return whether it was protected
+    ///
+    /// The entry does not need to exist so that protections of entries which were removed by other tools can
+    /// be cleaned up.
+    pub fn unprotect(&self, pass_name: &str) -> Result<bool> {
+        let name = names::normalize_name(pass_name)?;
+        let _metadata = self.locks.metadata();
+        let mut protected = protection::load(&self.root)?;
+        let removed = protected.remove(&name);
+        if removed {
+            protection::save(&self.root, &protected, self.config.umask())?;
+        }
+        Ok(removed)
+    }
+
+    /// Whether the entry named *pass_name* is protected
+    pub fn is_protected(&self, pass_name: &str) -> Result<bool> {
+        let name = names::normalize_name(pass_name)?;
+        Ok(protection::load(&self.root)?.contains(&name))
+    }
+
+    /// The names of all protected entries, sorted
+    pub fn protected_entries(&self) -> Result<Vec<String>> {
+        Ok(protection::load(&self.root)?.into_iter().collect())
+    }
+
+    /// Remove or replace protected entries anyway
+    ///
+    /// This is meant for deliberate maintenance, e.g. through a clone of the store which is only used for
+    /// retiring a protected credential.
+    ///
+    /// Disabled by default.
+    pub fn with_override_protection(mut self, enabled: bool) -> Self {
+        self.override_protection = enabled;
+        self
+    }
+
+    /// Whether protected entries may be removed or replaced
+    pub fn override_protection(&self) -> bool {
+        self.override_protection
+    }
+
+    /// Fail with [`PassError::EntryProtected`] if the entry at *path* is protected and protection is not
+    /// overridden
+    fn check_unprotected(&self, path: &Path) -> Result<()> {
+        if self.override_protection {
+            return Ok(());
+        }
+        let name = self.relative_name(path)?;
+        if protection::load(&self.root)?.contains(&name) {
+            return Err(PassError::EntryProtected(name));
+        }
+        Ok(())
+    }
+
+    /// Insert a new entry named *pass_name* whose content is rendered from *template* and *values*
+    ///
+    /// See [`Template::render()`] for how the content is rendered and validated and [`Store::insert()`] for
+    /// how it is stored.
+    pub fn insert_from_template(
+        &self,
+        pass_name: &str,
+        template: &Template,
+        values: &HashMap<String, String>,
+    ) -> Result<StoreFileRef> {
+        let content = template.render(values)?;
+        self.insert(pass_name, content)
+    }
+
+    /// Encrypt *plaintext* for the keys of *file* according to the options of this store
+    ///
+    /// This is the single point through which the plaintext of entries is written (except for
+    /// [`RwPlainFile`] which normalizes on its own) so it is normalized here like in [`Store::decrypt()`].
+    pub(crate) fn encrypt_for(&self, file: &StoreFileRef, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let keys = self.encryption_keys(file)?;
+        let mut normalized = self.text.normalize(plaintext)?;
+        let ciphertext = self.crypto.encrypt(
+            &self.gpg_contexts,
+            &keys,
+            normalized.as_deref().unwrap_or(plaintext),
+        );
+        if let Some(normalized) = &mut normalized {
+            secure_mem::release(normalized, &mut LockedRegion::default());
+        }
+        ciphertext
+    }
+
+    /// The name of the entry stored at *path* relative to the root of this store
+    pub(crate) fn relative_name(&self, path: &Path) -> Result<String> {
+        let name = names::path_to_name(&self.root, path)?;
+        match name.strip_suffix(".asc") {
+            Some(stem) if self.asc_entries && walk::has_extension(path, &["asc"]) => {
+                Ok(stem.to_string())
+            }
+            _ => Ok(name),
+        }
+    }
+
+    /// Decrypt the entry *pass_name*, interpret it according to common pass conventions and run all
+    /// [transformers](Store::with_transformer) on it
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidStoreFormat`] is returned if the content is not valid UTF-8 and every error of a
+    /// transformer is passed on.
+    pub fn retrieve_entry(&self, pass_name: impl AsRef<str>) -> Result<ParsedEntry> {
+        let pass_name = pass_name.as_ref();
+        self.retrieve_entry_in(pass_name, &[])
+    }
+
+    /// Retrieve the entry *pass_name* on behalf of the entries in *chain*
+    ///
+    /// ## Errors
+    /// [`PassError::ReferenceLoop`] is returned if the entry is already part of *chain*.
+    pub(crate) fn retrieve_entry_in(
+        &self,
+        pass_name: &str,
+        chain: &[String],
+    ) -> Result<ParsedEntry> {
+        let file = self.file(pass_name)?;
+        let pass_name = self.relative_name(&file.path)?;
+        let visited = chain.contains(&pass_name);
+        let mut chain = chain.to_vec();
+        chain.push(pass_name);
+        if visited {
+            return Err(PassError::ReferenceLoop(chain));
+        }
+        let entry = self.parse_entry(&file)?;
+        self.transformers
+            .run(&TransformContext::new(self, &chain), entry)
+    }
+
+    /// Encrypt *entry* and write it into *file*, replacing its previous content
+    ///
+    /// This goes through the same encryption path as [`plain_io_rw()`](Store::plain_io_rw) so the options
+    /// of this store apply.
+    pub fn write_entry(&self, file: &StoreFileRef, entry: &ParsedEntry) -> Result<()> {
+        let content = entry.content().as_bytes();
+        let mut plain = self.plain_io_rw(file)?;
+        plain.reserve(content.len().saturating_sub(plain.as_ref().len()));
+        // the previous content is overwritten in place instead of freeing its buffer
+        let buffer = plain.as_mut();
+        buffer.fill(0);
+        buffer.clear();
+        buffer.extend_from_slice(content);
+        plain.close()
+    }
+
+    /// Move fields of the entry *pass_name* into new entries as described by *mapping*
+    ///
+    /// The moved fields are removed from the entry while all its other lines are kept.
+    /// See the [`split`](crate::split) module for details.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryAlreadyExists`] is returned if one of the new entries already exists and
+    /// [`PassError::InvalidField`] if the entry does not contain a field of the mapping.
+    /// Nothing is written in both cases.
+    pub fn split_entry(
+        &self,
+        pass_name: &str,
+        mapping: &SplitMapping,
+    ) -> Result<Vec<StoreFileRef>> {
+        let file = self.file(pass_name)?;
+        let mut entry = self.parse_entry(&file)?;
+        let targets = split::split(&mut entry, mapping)?;
+        for (target, _) in &targets {
+            self.target_path(target, OverwritePolicy::Error)?;
+        }
+
+        let mut created = Vec::with_capacity(targets.len());
+        for (target, new_entry) in targets {
+            created.push(self.insert(&target, new_entry.content())?);
+        }
+        self.write_entry(&file, &entry)?;
+        Ok(created)
+    }
+
+    /// Combine the entries *pass_names* into the entry *target* and remove them afterwards
+    ///
+    /// *target* may be one of the merged entries; otherwise it must not exist yet.
+    /// See [`split::merge()`](crate::split::merge) for how the entries are combined.
+    ///
+    /// ## Errors
+    /// [`PassError::EntryAlreadyExists`] is returned if *target* exists but is not merged and
+    /// [`PassError::EntryProtected`] if one of the entries which would be removed is protected.
+    /// Nothing is written in both cases.
+    pub fn merge_entries<S: AsRef<str>>(
+        &self,
+        pass_names: &[S],
+        target: &str,
+    ) -> Result<StoreFileRef> {
+        let target_path = self.file_path(target)?;
+        let mut sources = Vec::with_capacity(pass_names.len());
+        for pass_name in pass_names {
+            let file = self.file(pass_name.as_ref())?;
+            if sources.iter().any(|(_, other, _)| *other == file) {
+                continue;
+            }
+            let entry = self.parse_entry(&file)?;
+            sources.push((self.relative_name(&file.path)?, file, entry));
+        }
+        if !sources.iter().any(|(_, file, _)| file.path == target_path) {
+            self.target_path(target, OverwritePolicy::Error)?;
+        }
+        for (_, file, _) in &sources {
+            if file.path != target_path {
+                self.check_unprotected(&file.path)?;
+            }
+        }
+
+        let merged = split::merge(
+            sources
+                .iter()
+                .map(|(pass_name, _, entry)| (pass_name.as_str(), entry)),
+        );
+        let merged_file = match sources.iter().find(|(_, file, _)| file.path == target_path) {
+            Some((_, file, _)) => {
+                self.write_entry(file, &merged)?;
+                file.clone()
+            }
+            None => self.insert(target, merged.content())?,
+        };
+        for (pass_name, file, _) in &sources {
+            if file.path != target_path {
+                self.remove(pass_name)?;
+            }
+        }
+        Ok(merged_file)
+    }
+
+    /// Find all entries which belong to the domain of *url*
+    ///
+    /// An entry matches if a part of its name (like `github.com` in `web/github.com/john`) names the domain
+    /// of the URL.
+    /// Entries whose names do not match are decrypted and match if the URL of their
+    /// [`login()`](ParsedEntry::login) information does.
+    /// Parent domains of the URL only match if [configured](Store::with_url_matching).
+    /// This is the lookup which browser integrations usually need.
+    ///
+    /// Matching entries are returned sorted by their path.
+    pub fn entries_for_url(&self, url: &str) -> Result<Vec<StoreFileRef>> {
+        let host = match parsed_entry::url_host(url) {
+            Some(host) => host,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut matches = Vec::new();
+        for file in self.files()? {
+            let name = self.relative_name(&file.path)?;
+            let name_matches = name.split('/').any(|component| {
+                let domain = component.rsplit('@').next().unwrap_or(component);
+                parsed_entry::domain_matches(&host, domain, self.url_matching)
+            });
+
+            let url_matches = || -> Result<bool> {
+                let entry_url = match self.parse_entry(&file) {
+                    Ok(entry) => entry.login().url,
+                    // entries that are not text can not contain a url
+                    Err(PassError::InvalidStoreFormat(..)) => None,
+                    Err(e) => return Err(e),
+                };
+                Ok(entry_url
+                    .and_then(|entry_url| parsed_entry::url_host(&entry_url))
+                    .is_some_and(|entry_host| {
+                        parsed_entry::domain_matches(&host, &entry_host, self.url_matching)
+                    }))
+            };
+
+            if name_matches || url_matches()? {
+                matches.push(file);
+            }
+        }
+
+        Ok(matches)
+    }
+
+    /// Decrypt every entry of the store and collect the passkeys of the relying party *rp_id*
+    ///
+    /// Relying parties are compared case-insensitively.
+    /// Entries which are not text or whose passkeys are malformed are skipped.
+    /// See the [`passkey`](crate::passkey) module for how passkeys are stored.
+    pub fn find_passkeys(&self, rp_id: &str) -> Result<Vec<(StoreFileRef, Passkey)>> {
+        let mut found = Vec::new();
+        for file in self.files()? {
+            let passkeys = match self.parse_entry(&file).map(|entry| entry.passkeys()) {
+                Ok(Ok(passkeys)) => passkeys,
+                Ok(Err(e)) => {
+                    warning!("Skipping passkeys of {}: {}", file.path.display(), e);
+                    continue;
+                }
+                Err(PassError::InvalidStoreFormat(..)) => continue,
+                Err(e) => return Err(e),
+            };
+            found.extend(
+                passkeys
+                    .into_iter()
+                    .filter(|passkey| passkey.rp_id.eq_ignore_ascii_case(rp_id))
+                    .map(|passkey| (file.clone(), passkey)),
+            );
+        }
+        Ok(found)
+    }
+
+    /// Decrypt every entry of the store and index the tags listed in their `tags:` fields
+    ///
+    /// Entries which are not text are skipped.
+    /// See [`ParsedEntry::tags()`] for the tag format.
+    pub fn tag_index(&self) -> Result<TagIndex> {
+        let mut index = TagIndex::default();
+        for file in self.files()? {
+            let entry = match self.parse_entry(&file) {
+                Ok(entry) => entry,
+                Err(PassError::InvalidStoreFormat(..)) => continue,
+                Err(e) => return Err(e),
+            };
+            for tag in entry.tags() {
+                index.insert(tag, file.clone());
+            }
+        }
+        Ok(index)
+    }
+
+    /// Find all entries which have *tag*, sorted by their path
+    ///
+    /// This decrypts every entry of the store; use [`Store::tag_index()`] to answer multiple queries.
+    pub fn entries_with_tag(&self, tag: &str) -> Result<Vec<StoreFileRef>> {
+        Ok(self.tag_index()?.entries_with_tag(tag).to_vec())
+    }
+
+    /// Add *tag* to the entry *file* and save it
+    ///
+    /// Returns whether the tag was added, i.e. `false` if the entry already had it (in which case the entry is
+    /// not rewritten).
+    /// See [`ParsedEntry::add_tag()`].
+    pub fn add_tag(&self, file: &StoreFileRef, tag: &str) -> Result<bool> {
+        let mut entry = self.parse_entry(file)?;
+        let added = entry.add_tag(tag)?;
+        if added {
+            self.write_entry(file, &entry)?;
+        }
+        Ok(added)
+    }
+
+    /// Remove *tag* from the entry *file* and save it
+    ///
+    /// Returns whether the entry had the tag.
+    /// See [`ParsedEntry::remove_tag()`].
+    pub fn remove_tag(&self, file: &StoreFileRef, tag: &str) -> Result<bool> {
+        let mut entry = self.parse_entry(file)?;
+        let removed = entry.remove_tag(tag);
+        if removed {
+            self.write_entry(file, &entry)?;
+        }
+        Ok(removed)
+    }
+
+    /// Decrypt *file* and interpret its content according to common pass conventions
+    pub(crate) fn parse_entry(&self, file: &StoreFileRef) -> Result<ParsedEntry> {
+        file.parse_plaintext(self.read_plaintext(file)?)
+    }
+
+    /// Decrypt *file* while respecting the rate limits and notifying the access logger
+    pub(crate) fn read_plaintext(&self, file: &StoreFileRef) -> Result<Vec<u8>> {
+        self.acquire_decryption(file)?;
+        let plaintext = self.decrypt(file)?;
+        self.record_access(file, AccessOperation::Decrypt)?;
+        Ok(plaintext)
+    }
+
+    /// Decrypt *file* and encrypt it again for the keys of its governing `.gpg-id` file
+    ///
+    /// Files of at least [`streaming_threshold()`](Store::streaming_threshold) bytes are streamed so that
+    /// their plaintext is never completely held in memory.
+    pub(crate) fn reencrypt(&self, file: &StoreFileRef) -> Result<()> {
+        if fs::metadata(&file.path)?.len() < self.streaming_threshold {
+            let plaintext = self.read_plaintext(file)?;
+            self.write_new(file.path.clone(), plaintext)?;
+            return Ok(());
+        }
+
+        self.check_permissions_of(&file.path)?;
+        self.acquire_decryption(file)?;
+        let keys = self.encryption_keys(file)?;
+        trace!("Streaming re-encryption of {}", file.path.display());
+        {
+            let _shared = self.locks.shared();
+            let ciphertext = io::BufReader::new(fs::File::open(&file.path)?);
+            utils::write_atomically_with(&file.path, self.config.umask(), |output| {
+                self.crypto
+                    .reencrypt_stream(&self.gpg_contexts, &keys, ciphertext, output)
+            })?;
+        }
+        self.record_access(file, AccessOperation::Decrypt)?;
+        self.track_in_manifest(&file.path)?;
+        self.record_access(file, AccessOperation::Write)?;
+        Ok(())
+    }
+
+    /// Compare the recipients of every entry against the keys configured in its governing `.gpg-id` file
+    ///
+    /// The recipients are read from the unencrypted packet headers of each file so no decryption is
+    /// necessary.
+    /// Only entries whose recipients differ are returned, sorted by their path.
+    pub fn check_recipients(&self) -> Result<Vec<RecipientMismatch>> {
+        let mut mismatches = Vec::new();
+        for file in self.files()? {
+            if let Some(mismatch) = RecipientMismatch::check(file, &self.crypto)? {
+                mismatches.push(mismatch);
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// Describe which keys can decrypt the entries of every directory of this store
+    ///
+    /// Recipients of the `.gpg-id` files are resolved to keys and user ids via the local keyring.
+    /// No entry is decrypted.
+    /// See the [`access`](crate::access) module for details.
+    pub fn access_map(&self) -> Result<AccessMap> {
+        let _span = span!("access_map", root = %self.root.display());
+        access::access_map(&self.root, &self.crypto)
+    }
+
+    /// Replace the recipient *old_key* with *new_key* in every `.gpg-id` file of this store and re-encrypt
+    /// all entries governed by a changed file or still encrypted for *old_key*
+    ///
+    /// Recipients are matched by key id, fingerprint or any other identifier that resolves to the same key
+    /// as *old_key*.
+    /// If a signing key is configured in *options*, the changed `.gpg-id` files are re-signed.
+    /// Otherwise, their now outdated `.gpg-id.sig` files are listed in the report as stale.
+    /// Large entries are streamed instead of being decrypted into memory (see
+    /// [`Store::with_streaming_threshold()`]).
+    ///
+    /// ## Errors
+    /// [`PassError::GpgKeyNotFoundError`] or [`PassError::UnusableRecipient`] is returned if *new_key* cannot
+    /// be used for encryption.
+    /// If re-encrypting an entry fails, the `.gpg-id` files are already updated.
+    /// Running the rotation again still re-encrypts the remaining entries because they are found by the keys
+    /// in their packet headers, as long as *old_key* is still in the local keyring or given as the id of the
+    /// subkey for which the entries are encrypted.
+    pub fn rotate_recipient(
+        &self,
+        old_key: &str,
+        new_key: &str,
+        options: &RotationOptions,
+    ) -> Result<RotationReport> {
+        let _span = span!("rotate_recipient", root = %self.root.display());
+        let report = keys::rotate_recipient(self, old_key, new_key, options);
+        self.recipients.clear();
+        report
+    }
+
+    /// Check the password of every entry against *policy*
+    ///
+    /// Every entry is decrypted and the age of an entry is the time since its file was last modified.
+    /// Only entries which violate the policy are returned, sorted by their path.
+    /// Entries which are not text are returned as [skipped](PolicyReport::skipped) instead of failing the
+    /// whole audit.
+    pub fn audit_against_policy(&self, policy: &PasswordPolicy) -> Result<Vec<PolicyReport>> {
+        let mut reports = Vec::new();
+        for file in self.files()? {
+            let modified = fs::metadata(&file.path)?.modified()?;
+            let (mut violations, skipped) = match self.parse_entry(&file) {
+                Ok(entry) => (policy.check(entry.password()), false),
+                Err(PassError::InvalidStoreFormat(..)) => {
+                    warning!(
+                        "Skipping the password of {} because it is not text",
+                        file.path.display()
+                    );
+                    (Vec::new(), true)
+                }
+                Err(e) => return Err(e),
+            };
+            // modification times in the future are treated as if the entry was just modified
+            let age = modified.elapsed().unwrap_or_default();
+            violations.extend(policy.check_age(age));
+            if skipped || !violations.is_empty() {
+                reports.push(PolicyReport {
+                    file,
+                    violations,
+                    skipped,
+                });
+            }
+        }
+        Ok(reports)
+    }
+
+    /// Check the names of all entries against *spec* without decrypting any of them
+    ///
+    /// Only entries which do not follow the spec are returned, sorted by their name.
+    #[cfg(feature = "layout")]
+    pub fn validate_layout(&self, spec: &LayoutSpec) -> Result<Vec<LayoutViolation>> {
+        let mut violations = Vec::new();
+        for name in self.names()? {
+            violations.extend(spec.check(&name?));
+        }
+        violations.sort_by(|a, b| a.pass_name().cmp(b.pass_name()));
+        Ok(violations)
+    }
+
+    /// Collect health metrics of this store without decrypting any entry
+    ///
+    /// Entries whose files were not modified for longer than *max_age* are counted as expired.
+    /// The time of the last sync is taken from the `FETCH_HEAD` of the git repository at the store root.
+    pub fn metrics(&self, max_age: Option<Duration>) -> Result<StoreMetrics> {
+        let files = self.entry_files()?;
+        let mut expired_entries = 0;
+        if let Some(max_age) = max_age {
+            for file in &files {
+                let modified = fs::metadata(&file.path)?.modified()?;
+                if modified.elapsed().unwrap_or_default() > max_age {
+                    expired_entries += 1;
+                }
+            }
+        }
+        let last_sync_age = fs::metadata(self.root.join(".git").join("FETCH_HEAD"))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .map(|modified| modified.elapsed().unwrap_or_default());
+        Ok(StoreMetrics {
+            entries_total: files.len() as u64,
+            expired_entries,
+            decryption_errors: self.decryption_errors.get(),
+            last_sync_age,
+        })
+    }
+}
+
+impl RecipientMismatch {
+    /// Compare the recipients of *file* against the keys configured in its governing `.gpg-id` file
+    ///
+    /// The keys are looked up according to *crypto*.
+    pub(crate) fn check(file: StoreFileRef, crypto: &CryptoOptions) -> Result<Option<Self>> {
+        let actual = file.recipient_key_ids()?;
+        let expected = file.encryption_keys_in(crypto)?;
+
+        // recipients are usually encryption subkeys so a key matches if any of its subkeys do
+        let subkey_ids = |key: &gpgme::Key| {
+            key.subkeys()
+                .filter_map(|subkey| subkey.id().ok().map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+
+        let missing = expected
+            .iter()
+            .filter(|key| !subkey_ids(key).iter().any(|id| actual.contains(id)))
+            .map(|key| key.id().unwrap_or_default().to_string())
+            .collect::<Vec<_>>();
+        let unexpected = actual
+            .iter()
+            .filter(|id| !expected.iter().any(|key| subkey_ids(key).contains(id)))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if missing.is_empty() && unexpected.is_empty() {
+            Ok(None)
+        } else {
+            debug!(
+                "Recipients of {} differ from its .gpg-id file",
+                file.path.display()
+            );
+            Ok(Some(Self {
+                entry: file,
+                missing,
+                unexpected,
+            }))
+        }
+    }
+}
+
+/// A password store that is opened in read-only mode
+///
+/// Get an instance of this by calling [`Store::open_read_only()`].
+/// This type only provides the non-mutating operations of [`Store`] and all file handles it hands out are
+/// opened read-only.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ReadOnlyStore {
+    store: Store,
+}
+
+impl ReadOnlyStore {
+    /// The root directory of this store
+    pub fn root(&self) -> &Path {
+        self.store.root()
+    }
+
+    /// The store which is wrapped by this read-only view
+    #[cfg(feature = "http")]
+    pub(crate) fn inner(&self) -> &Store {
+        &self.store
+    }
+
+    /// Refuse to operate on files and directories which other users could have tampered with
+    ///
+    /// See [`Store::with_strict_permissions()`].
+    pub fn with_strict_permissions(mut self, enabled: bool) -> Self {
+        self.store = self.store.with_strict_permissions(enabled);
+        self
+    }
+
+    /// Transparently resolve aliases when retrieving entries
+    ///
+    /// See [`Store::with_alias_resolution()`].
+    pub fn with_alias_resolution(mut self, enabled: bool) -> Self {
+        self.store = self.store.with_alias_resolution(enabled);
+        self
+    }
+
+    /// Resolve names which refer to both a file and a directory according to *prefer*
+    ///
+    /// See [`Store::with_ambiguity()`].
+    pub fn with_ambiguity(mut self, prefer: Prefer) -> Self {
+        self.store = self.store.with_ambiguity(prefer);
+        self
+    }
+
+    /// Persist the index of this store at *path*
+    ///
+    /// See [`Store::with_index()`].
+    pub fn with_index(mut self, path: impl Into<PathBuf>) -> Self {
+        self.store = self.store.with_index(path);
+        self
+    }
+
+    /// Notify *logger* whenever an entry is decrypted through this store
+    ///
+    /// See [`Store::set_access_logger()`].
+    pub fn set_access_logger(&mut self, logger: impl AccessLogger + 'static) {
+        self.store.set_access_logger(logger);
+    }
+
+    /// Run *transformer* on every entry which is retrieved with [`ReadOnlyStore::retrieve_entry()`]
+    ///
+    /// See [`Store::with_transformer()`].
+    pub fn with_transformer(mut self, transformer: impl EntryTransformer + 'static) -> Self {
+        self.store = self.store.with_transformer(transformer);
+        self
+    }
+
+    /// Attach *context* to all access events
+    ///
+    /// See [`Store::with_access_context()`].
+    pub fn with_access_context(mut self, context: impl Into<String>) -> Self {
+        self.store = self.store.with_access_context(context);
+        self
+    }
+
+    /// Consult the agent listening on *socket_path* before decrypting entries
+    ///
+    /// See [`Store::with_agent()`].
+    #[cfg(all(feature = "agent", unix))]
+    pub fn with_agent(mut self, socket_path: impl Into<PathBuf>) -> Self {
+        self.store = self.store.with_agent(socket_path);
+        self
+    }
+
+    /// Memory-map entry files of at least *min_size* bytes instead of reading them into memory
+    ///
+    /// See [`Store::with_mmap_threshold()`].
+    #[cfg(feature = "mmap")]
+    pub fn with_mmap_threshold(mut self, min_size: u64) -> Self {
+        self.store = self.store.with_mmap_threshold(min_size);
+        self
+    }
+
+    /// Refuse decryptions which would exceed *limit*
+    ///
+    /// See [`Store::with_rate_limit()`].
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.store = self.store.with_rate_limit(limit);
+        self
+    }
+
+    /// Only read the direct content of directories which are returned by [`ReadOnlyStore::retrieve()`]
+    ///
+    /// See [`Store::with_shallow_directories()`].
+    pub fn with_shallow_directories(mut self, enabled: bool) -> Self {
+        self.store = self.store.with_shallow_directories(enabled);
+        self
+    }
+
+    /// Treat `*.asc` files as entries in addition to `*.gpg` files
+    ///
+    /// See [`Store::with_asc_entries()`].
+    pub fn with_asc_entries(mut self, enabled: bool) -> Self {
+        self.store = self.store.with_asc_entries(enabled);
+        self
+    }
+
+    /// Convert all line breaks of text entries to *ending* whenever their plaintext is read
+    ///
+    /// See [`Store::with_line_ending()`].
+    pub fn with_line_ending(mut self, ending: Option<LineEnding>) -> Self {
+        self.store = self.store.with_line_ending(ending);
+        self
+    }
+
+    /// Reject plaintext which is not valid UTF-8 when reading it
+    ///
+    /// See [`Store::with_utf8_validation()`].
+    pub fn with_utf8_validation(mut self, enabled: bool) -> Self {
+        self.store = self.store.with_utf8_validation(enabled);
+        self
+    }
+
+    /// Prevent gpg from accessing the network during operations of this store
+    ///
+    /// See [`Store::with_offline()`].
+    pub fn with_offline(mut self, enabled: bool) -> Self {
+        self.store = self.store.with_offline(enabled);
+        self
+    }
+
+    /// Use the keyring in the directory *gpg_home* for all gpg operations of this store
+    ///
+    /// See [`Store::with_gpg_home()`].
+    pub fn with_gpg_home(mut self, gpg_home: impl Into<PathBuf>) -> Self {
+        self.store = self.store.with_gpg_home(gpg_home);
+        self
+    }
+
+    /// Abort gpg operations of the kind *operation* which take longer than *timeout*
+    ///
+    /// See [`Store::with_gpg_timeout()`].
+    pub fn with_gpg_timeout(mut self, operation: GpgOperation, timeout: Duration) -> Self {
+        self.store = self.store.with_gpg_timeout(operation, timeout);
+        self
+    }
+
+    /// Keep up to *size* idle gpg contexts around for reuse by later operations of this store
+    ///
+    /// See [`Store::with_gpg_context_pool_size()`].
+    pub fn with_gpg_context_pool_size(mut self, size: usize) -> Self {
+        self.store = self.store.with_gpg_context_pool_size(size);
+        self
+    }
+
+    /// List all passwords in the store in a flat data structure
+    ///
+    /// See [`Store::list()`].
+    pub fn list(&self) -> Result<HashSet<StoreEntry>> {
+        self.store.list()
+    }
+
+    /// List all entries of the store which match *filter*
+    ///
+    /// See [`Store::list_filtered()`].
+    pub fn list_filtered(&self, filter: &Filter) -> Result<Listing> {
+        self.store.list_filtered(filter)
+    }
+
+    /// Compare the store against its manifest and verify the signature of the manifest
+    ///
+    /// See [`Store::verify_manifest()`].
+    pub fn verify_manifest(&self) -> Result<ManifestReport> {
+        self.store.verify_manifest()
+    }
+
+    /// Whether the entry named *pass_name* is protected
+    ///
+    /// See [`Store::is_protected()`].
+    pub fn is_protected(&self, pass_name: &str) -> Result<bool> {
+        self.store.is_protected(pass_name)
+    }
+
+    /// The names of all protected entries, sorted
+    ///
+    /// See [`Store::protected_entries()`].
+    pub fn protected_entries(&self) -> Result<Vec<String>> {
+        self.store.protected_entries()
+    }
+
+    /// Record the names, modification times and sizes of all entries without decrypting them
+    ///
+    /// See [`Store::listing()`].
+    pub fn listing(&self) -> Result<Listing> {
+        self.store.listing()
+    }
+
+    /// Compare the current entries of the store against the older listing *previous*
+    ///
+    /// See [`Store::changes_since()`].
+    pub fn changes_since(&self, previous: &Listing) -> Result<ListingChanges> {
+        self.store.changes_since(previous)
+    }
+
+    /// Encrypt the content of the entry *pass_name* for the single key *recipient*
+    ///
+    /// See [`Store::share()`].
+    pub fn share(
+        &self,
+        pass_name: &str,
+        recipient: &str,
+        options: &ShareOptions,
+    ) -> Result<SharedEntry> {
+        self.store.share(pass_name, recipient, options)
+    }
+
+    /// Write the encrypted files of the entries *pass_names* into a single bundle
+    ///
+    /// See [`Store::pack()`].
+    pub fn pack(&self, pass_names: &[&str], writer: impl Write) -> Result<()> {
+        self.store.pack(pass_names, writer)
+    }
+
+    /// Iterate over the names of all entries in the store
+    ///
+    /// See [`Store::names()`].
+    pub fn names(&self) -> Result<names::Names> {
+        self.store.names()
+    }
+
+    /// Retrieve the stored entry identified by *pass_name*
+    ///
+    /// See [`Store::retrieve()`].
+    pub fn retrieve(&self, pass_name: impl AsRef<str>) -> Result<StoreEntry> {
+        self.store.retrieve(pass_name)
+    }
+
+    /// Retrieve the entry file identified by *pass_name*
+    ///
+    /// See [`Store::retrieve_file()`].
+    pub fn retrieve_file(&self, pass_name: impl AsRef<str>) -> Result<StoreFileRef> {
+        self.store.retrieve_file(pass_name)
+    }
+
+    /// Retrieve the directory identified by *pass_name*
+    ///
+    /// See [`Store::retrieve_dir()`].
+    pub fn retrieve_dir(&self, pass_name: impl AsRef<str>) -> Result<StoreDirectoryRef> {
+        self.store.retrieve_dir(pass_name)
+    }
+
+    /// Retrieve the entry file identified by *pass_name* and fail if it is a directory
+    ///
+    /// See [`Store::file()`].
+    pub fn file(&self, pass_name: impl AsRef<str>) -> Result<StoreFileRef> {
+        self.store.file(pass_name)
+    }
+
+    /// Retrieve the directory identified by *pass_name* and fail if it is a file
+    ///
+    /// See [`Store::dir()`].
+    pub fn dir(&self, pass_name: impl AsRef<str>) -> Result<StoreDirectoryRef> {
+        self.store.dir(pass_name)
+    }
+
+    /// Retrieve both the file and the directory identified by *pass_name*
+    ///
+    /// See [`Store::retrieve_all()`].
+    pub fn retrieve_all(&self, pass_name: &str) -> Result<Vec<StoreEntry>> {
+        self.store.retrieve_all(pass_name)
+    }
+
+    /// Get a read-only IO handle to the encrypted content of *file*
+    pub fn cipher_io(&self, file: &StoreFileRef) -> Result<CipherFile> {
+        self.store.check_permissions_of(&file.path)?;
+        file.cipher_io_ro()
+    }
+
+    /// Get a read-only IO handle to the plaintext of *file*
+    pub fn plain_io(&self, file: &StoreFileRef) -> Result<RoPlainFile> {
+        self.store.check_permissions_of(&file.path)?;
+        self.store.acquire_decryption(file)?;
+        let plain = RoPlainFile::from_plaintext(self.store.decrypt(file)?);
+        self.store.record_access(file, AccessOperation::Decrypt)?;
+        Ok(plain)
+    }
+
+    /// Decrypt *file* and verify the signatures that were made while encrypting it
+    ///
+    /// See [`Store::verify_signature()`].
+    pub fn verify_signature(&self, file: &StoreFileRef) -> Result<SignatureStatus> {
+        self.store.verify_signature(file)
+    }
+
+    /// List the entries of this store as they were recorded in the git commit *rev*
+    ///
+    /// See [`Store::list_at()`].
+    #[cfg(feature = "git")]
+    pub fn list_at(&self, rev: &str) -> Result<git::PastListing> {
+        self.store.list_at(rev)
+    }
+
+    /// Get a read-only IO handle to the plaintext of *entry* as it was recorded in a past commit
+    ///
+    /// See [`Store::plain_io_at()`].
+    #[cfg(feature = "git")]
+    pub fn plain_io_at(&self, entry: &git::PastEntry) -> Result<RoPlainFile> {
+        self.store.plain_io_at(entry)
+    }
+
+    /// Check whether entries of this store can currently be decrypted
+    ///
+    /// See [`Store::probe_decryption_capability()`].
+    pub fn probe_decryption_capability(&self) -> Result<()> {
+        self.store.probe_decryption_capability()
+    }
+
+    /// Perform a comprehensive consistency check of the store
+    ///
+    /// See [`Store::fsck()`].
+    pub fn fsck(&self) -> Result<FsckReport> {
+        self.store.fsck()
+    }
+
+    /// Compare the recipients of every entry against the keys configured in its governing `.gpg-id` file
+    ///
+    /// See [`Store::check_recipients()`].
+    pub fn check_recipients(&self) -> Result<Vec<RecipientMismatch>> {
+        self.store.check_recipients()
+    }
+
+    /// Describe which keys can decrypt the entries of every directory of this store
+    ///
+    /// See [`Store::access_map()`].
+    pub fn access_map(&self) -> Result<AccessMap> {
+        self.store.access_map()
+    }
+
+    /// Check the password of every entry against *policy*
+    ///
+    /// See [`Store::audit_against_policy()`].
+    pub fn audit_against_policy(&self, policy: &PasswordPolicy) -> Result<Vec<PolicyReport>> {
+        self.store.audit_against_policy(policy)
+    }
+
+    /// Check the names of all entries against *spec* without decrypting any of them
+    ///
+    /// See [`Store::validate_layout()`].
+    #[cfg(feature = "layout")]
+    pub fn validate_layout(&self, spec: &LayoutSpec) -> Result<Vec<LayoutViolation>> {
+        self.store.validate_layout(spec)
+    }
+
+    /// Collect health metrics of this store without decrypting any entry
+    ///
+    /// See [`Store::metrics()`].
+    pub fn metrics(&self, max_age: Option<Duration>) -> Result<StoreMetrics> {
+        self.store.metrics(max_age)
+    }
+
+    /// Get the names and non-secret metadata of all entries without decrypting them
+    ///
+    /// See [`Store::index()`].
+    pub fn index(&self) -> Result<StoreIndex> {
+        self.store.index()
+    }
+
+    /// Get a [`Picker`] for fuzzy matching the names of all entries
+    ///
+    /// See [`Store::picker()`].
+    pub fn picker(&self) -> Result<Picker> {
+        self.store.picker()
+    }
+
+    /// List the names which complete *prefix* by one path component for interactive completion
+    ///
+    /// See [`Store::completion_candidates()`].
+    pub fn completion_candidates(&self, prefix: &str) -> Result<Vec<String>> {
+        self.store.completion_candidates(prefix)
+    }
+
+    /// Decrypt every entry of the store and index the tags listed in their `tags:` fields
+    ///
+    /// See [`Store::tag_index()`].
+    pub fn tag_index(&self) -> Result<TagIndex> {
+        self.store.tag_index()
+    }
+
+    /// Decrypt the entry *pass_name* and run all transformers on it
+    ///
+    /// See [`Store::retrieve_entry()`].
+    pub fn retrieve_entry(&self, pass_name: impl AsRef<str>) -> Result<ParsedEntry> {
+        self.store.retrieve_entry(pass_name)
+    }
+
+    /// Decrypt every entry of the store and collect the passkeys of the relying party *rp_id*
+    ///
+    /// See [`Store::find_passkeys()`].
+    pub fn find_passkeys(&self, rp_id: &str) -> Result<Vec<(StoreFileRef, Passkey)>> {
+        self.store.find_passkeys(rp_id)
+    }
+}
+
+/// How [`inspect_folder()`] descends into subdirectories
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Descend {
+    /// Subdirectories are returned without their content
+    Never,
+    /// Subdirectories are inspected one after another
+    Serially,
+    /// The direct subdirectories are inspected on a bounded number of threads
+    InParallel,
+}
+
+/// Inspect the folder at *path* of the store at *root* and map it and its content to a [`StoreEntry`],
+/// recursing into subdirectories according to *descend*
+fn inspect_folder(
+    root: &Path,
+    path: impl AsRef<Path>,
+    extensions: &[&str],
+    descend: Descend,
+) -> Result<HashSet<StoreEntry>> {
+    let mut entries = HashSet::new();
+    let mut subdirs = Vec::new();
+    for file in fs::read_dir(path)? {
+        let file = file?;
+        let path = file.path();
+        // git repositories (including those of submodules) are not part of the entry hierarchy
+        if path.file_name() == Some(OsStr::new(".git")) {
+            continue;
+        }
+        let file_type = file.file_type()?;
+        if file_type.is_dir() {
+            subdirs.push(path);
+        } else if file_type.is_file() {
+            // files without an entry extension are ignored
+            if walk::has_extension(&path, extensions) {
+                entries.insert(StoreEntry::File(StoreFileRef {
+                    path,
+                    root: root.to_owned(),
+                }));
+            }
+        } else {
+            return Err(PassError::InvalidStoreFormat(
+                path,
+                "File is neither a string nor directory but pass stores can only contain those types of files"
+                    .to_string(),
+            ));
+        }
+    }
+
+    let inspect = |dir: &PathBuf| {
+        let content = match descend {
+            Descend::Never => HashSet::new(),
+            Descend::Serially | Descend::InParallel => {
+                inspect_folder(root, dir, extensions, Descend::Serially)?
+            }
+        };
+        Ok(StoreEntry::Directory(StoreDirectoryRef {
+            path: dir.clone(),
+            root: root.to_owned(),
+            content,
+        }))
+    };
+    let subdirs = match descend {
+        Descend::InParallel => walk::try_map_parallel(&subdirs, inspect)?,
+        Descend::Never | Descend::Serially => subdirs.iter().map(inspect).collect::<Result<_>>()?,
+    };
+    entries.extend(subdirs);
+    Ok(entries)
+}
+
+/// Remove the directory *dir* if it is empty and return whether it was removed
+///
+/// Directories which are not empty or which were already removed by someone else are not removed.
+fn remove_empty_dir(dir: &Path) -> Result<bool> {
+    match fs::remove_dir(dir) {
+        Ok(()) => Ok(true),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::DirectoryNotEmpty | io::ErrorKind::NotFound
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether the file or directory *name* is hidden (like `.git` or `.extensions`)
+fn is_hidden(name: &OsStr) -> bool {
+    name.to_str().is_some_and(|name| name.starts_with('.'))
+}
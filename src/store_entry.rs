@@ -1,13 +1,24 @@
 //! Type definitions and interaction logic for entries in a password store
 
+use crate::credentials::{self, Certificate, SshKey};
+use crate::crypto::CryptoOptions;
 use crate::file_io::{CipherFile, RoPlainFile, RwPlainFile};
-use crate::{utils, PassError, Result};
+#[cfg(feature = "git")]
+use crate::git::{self, Provenance};
+use crate::secure::SecretString;
+use crate::secure_mem::{self, LockedRegion};
+use crate::telemetry::{trace, warning};
+use crate::{
+    export, packets, utils, walk, GpgIdFile, ParsedEntry, PassError, Result, SignatureStatus,
+};
 use std::collections::hash_set::Iter as HashSetIter;
-use std::collections::HashSet;
-use std::fs::File;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
 use std::hash::{Hash, Hasher};
-use std::io::{BufRead, BufReader};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use unicode_normalization::UnicodeNormalization;
 
 /// An entry in the password store
 #[derive(Debug, Eq, PartialEq, Clone, Hash)]
@@ -44,6 +55,8 @@ impl StoreEntry {
 pub struct StoreDirectoryRef {
     /// Absolute path to the referenced directory
     pub path: PathBuf,
+    /// Absolute path to the root of the store which contains the directory
+    pub root: PathBuf,
     /// Other entries that are contained in this directory
     pub content: HashSet<StoreEntry>,
 }
@@ -51,10 +64,14 @@ pub struct StoreDirectoryRef {
 impl StoreDirectoryRef {
     /// Retrieve the name of the store entry
     ///
-    /// The name is represented as a relative path from the store root and can be used to retrieve this
-    /// entry using [`retrieve`](crate::retrieve).
+    /// The name is represented as a relative path from the root of the store which contains this directory
+    /// and can be used to retrieve it from that store.
     pub fn name(&self) -> Result<String> {
-        Ok(utils::path2str(utils::abspath2relpath(&self.path)?)?.to_string())
+        Ok(
+            utils::path2str(utils::abspath2relpath(&self.root, &self.path)?)?
+                .nfc()
+                .collect(),
+        )
     }
 
     /// Verify that *self* references an existing directory
@@ -69,6 +86,113 @@ impl StoreDirectoryRef {
         }
     }
 
+    /// Verify that this directory and all entries which are (recursively) contained in it still match what is
+    /// present on the filesystem
+    ///
+    /// The entries are checked on a bounded number of threads so that this stays fast for large directories.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidStoreFormat`] is returned for the first entry which no longer exists or has changed
+    /// its type.
+    pub fn verify_recursive(&self) -> Result<()> {
+        self.verify()?;
+        let mut entries = Vec::new();
+        let mut pending = vec![self];
+        while let Some(dir) = pending.pop() {
+            for entry in &dir.content {
+                if let StoreEntry::Directory(subdir) = entry {
+                    pending.push(subdir);
+                }
+                entries.push(entry);
+            }
+        }
+        walk::try_map_parallel(&entries, |entry| entry.verify())?;
+        Ok(())
+    }
+
+    /// Decrypt the entries which are directly contained in this directory and map them to environment
+    /// variables
+    ///
+    /// The variable names are derived from the entry names with [`export::variable_name()`] and the values are
+    /// the first lines of the entries.
+    /// Subdirectories are ignored.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidStoreFormat`] is returned if two entries map to the same variable name or an entry
+    /// is not valid UTF-8.
+    pub fn as_env_map(&self) -> Result<BTreeMap<String, SecretString>> {
+        let mut variables = BTreeMap::new();
+        for file in self.files() {
+            let stem = file.path.file_stem().unwrap_or_default();
+            let variable = export::variable_name(utils::path2str(Path::new(stem))?);
+            let password = SecretString::from(file.parse()?.password());
+            if variables.insert(variable.clone(), password).is_some() {
+                return Err(PassError::InvalidStoreFormat(
+                    file.path.to_owned(),
+                    format!("Another entry also maps to the variable {}", variable),
+                ));
+            }
+        }
+        Ok(variables)
+    }
+
+    /// Spawn *command* with the variables of [`as_env_map()`](Self::as_env_map) added to its environment
+    ///
+    /// This is the common pattern of injecting secrets into a deployment or CI job.
+    pub fn spawn_with_env(&self, command: &mut Command) -> Result<Child> {
+        let variables = self.as_env_map()?;
+        command.envs(
+            variables
+                .iter()
+                .map(|(variable, value)| (variable, value.expose())),
+        );
+        Ok(command.spawn()?)
+    }
+
+    /// Write the variables of [`as_env_map()`](Self::as_env_map) as `.env` file into *writer*
+    ///
+    /// Every variable becomes a line like `KEY=value`; values with special characters are double-quoted and
+    /// escaped.
+    pub fn render_dotenv(&self, writer: impl Write) -> Result<()> {
+        export::write_dotenv(writer, &self.as_env_map()?)
+    }
+
+    /// Decrypt the entries which are directly contained in this directory into a Kubernetes secret named
+    /// *name* in *namespace*
+    ///
+    /// The keys of the secret are the entry names and the values their complete content.
+    /// Subdirectories are ignored.
+    /// This is only available with the `k8s` feature.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidField`] is returned if an entry name can not be used as key of a secret.
+    #[cfg(feature = "k8s")]
+    pub fn to_k8s_secret(&self, name: &str, namespace: &str) -> Result<crate::k8s::Secret> {
+        let mut secret = crate::k8s::Secret::new(name, namespace);
+        for file in self.files() {
+            let stem = file.path.file_stem().unwrap_or_default();
+            secret.insert(
+                utils::path2str(Path::new(stem))?,
+                file.plain_io_ro()?.as_ref(),
+            )?;
+        }
+        Ok(secret)
+    }
+
+    /// The files which are directly contained in this directory sorted by their path
+    fn files(&self) -> Vec<&StoreFileRef> {
+        let mut files = self
+            .content
+            .iter()
+            .filter_map(|entry| match entry {
+                StoreEntry::File(file) => Some(file),
+                StoreEntry::Directory(_) => None,
+            })
+            .collect::<Vec<_>>();
+        files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        files
+    }
+
     /// iterate over all the entries contained in the storage hierarchy below this directory
     ///
     /// **Note:** The iterator iterates over all entries even if they are in a subdirectory further down the
@@ -140,27 +264,31 @@ impl<'a> Iterator for StoreDirectoryIter<'a> {
 /// A reference to a file in the password store
 #[derive(Debug, Eq, PartialEq, Clone, Hash)]
 pub struct StoreFileRef {
-    /// Absolute path to the referenced directory
+    /// Absolute path to the referenced file
     pub path: PathBuf,
+    /// Absolute path to the root of the store which contains the file
+    pub root: PathBuf,
 }
 
 impl StoreFileRef {
     /// Retrieve the name of the store entry
     ///
-    /// The name is represented as a relative path from the store root and can be used to retrieve this
-    /// entry using [`retrieve`](crate::retrieve).
+    /// The name is represented as a relative path from the root of the store which contains this file and
+    /// can be used to retrieve it from that store.
     pub fn name(&self) -> Result<String> {
-        let relative_path = utils::path2str(utils::abspath2relpath(&self.path)?)?;
+        let relative_path = utils::path2str(utils::abspath2relpath(&self.root, &self.path)?)?;
 
         Ok(relative_path
             .strip_suffix(".gpg")
+            .or_else(|| relative_path.strip_suffix(".asc"))
             .ok_or_else(|| {
                 PassError::InvalidStoreFormat(
                     self.path.to_owned(),
                     "File does not end with .gpg extension".to_string(),
                 )
             })?
-            .to_string())
+            .nfc()
+            .collect())
     }
 
     /// Retrieve the encryption keys that are used to encrypt this file
@@ -186,15 +314,45 @@ impl StoreFileRef {
     ///     "8497251104B6F45F"
     /// )
     /// ```
+    ///
+    /// ## Errors
+    /// All keys are validated to be usable for encryption.
+    /// If one of them is expired, revoked, disabled or lacks encryption capability,
+    /// [`PassError::UnusableRecipient`] is returned.
+    /// Use [`encryption_keys_unchecked()`](StoreFileRef::encryption_keys_unchecked) to skip this validation.
+    ///
+    /// The default gpg configuration is used to look up the keys.
+    /// Use [`Store::encryption_keys()`](crate::Store::encryption_keys) to apply the options of a specific
+    /// store.
     pub fn encryption_keys(&self) -> Result<Vec<gpgme::Key>> {
-        log::warn!(
+        self.encryption_keys_unchecked()?
+            .into_iter()
+            .map(|key| {
+                utils::check_key_usable(&key)?;
+                Ok(key)
+            })
+            .collect()
+    }
+
+    /// Retrieve the encryption keys that are used to encrypt this file without validating that they are
+    /// actually usable for encryption
+    ///
+    /// See [`encryption_keys()`](StoreFileRef::encryption_keys) for details.
+    pub fn encryption_keys_unchecked(&self) -> Result<Vec<gpgme::Key>> {
+        self.encryption_keys_in(&CryptoOptions::default())
+    }
+
+    /// Retrieve the encryption keys of this file without validating them using a context configured
+    /// according to *crypto*
+    pub(crate) fn encryption_keys_in(&self, crypto: &CryptoOptions) -> Result<Vec<gpgme::Key>> {
+        warning!(
             "Looking for encryption keys for entry at {}",
             self.path.display()
         );
 
-        /// look for a .gpg-id file starting from the given directory path
-        fn look_for_keys_file_from_dir(path: &Path) -> Result<PathBuf> {
-            log::trace!("Looking for .gpg-id file in directory {}", path.display());
+        /// look for a .gpg-id file starting from the given directory path without leaving *root*
+        fn look_for_keys_file_from_dir(path: &Path, root: &Path) -> Result<PathBuf> {
+            trace!("Looking for .gpg-id file in directory {}", path.display());
 
             let gpg_id_path = path.join(".gpg-id");
             if gpg_id_path.exists() {
@@ -209,55 +367,104 @@ impl StoreFileRef {
                 }
             } else {
                 // recursion into parent directory
-                look_for_keys_file_from_dir(path.parent().ok_or_else(|| {
-                    PassError::InvalidStoreFormat(
-                        path.to_owned(),
-                        "Path does not hava a parent but a .gpg-id file has not yet been found"
-                            .to_string(),
-                    )
-                })?)
+                match path.parent() {
+                    Some(parent) if path != root => look_for_keys_file_from_dir(parent, root),
+                    _ => Err(PassError::StoreNotInitialized(root.to_owned())),
+                }
             }
         }
 
         // start search in directory that this file contains
-        let keys_path = look_for_keys_file_from_dir(self.path.parent().ok_or_else(|| {
+        let dir = self.path.parent().ok_or_else(|| {
             PassError::InvalidStoreFormat(
                 self.path.to_owned(),
                 "File does not have a parent which means it is not contained in a password store"
                     .to_string(),
             )
-        })?)?;
+        })?;
+        // .gpg-id files outside of the store must never decide about the recipients of its entries
+        if !dir.starts_with(&self.root) {
+            return Err(PassError::InvalidStoreFormat(
+                self.path.to_owned(),
+                "File is not contained in the root of its store".to_string(),
+            ));
+        }
+        let keys_path = look_for_keys_file_from_dir(dir, &self.root)?;
 
         // extract keys from the file
-        log::trace!(
+        trace!(
             "Found .gpg-id file at {}, inspecting gpg keys from it",
             keys_path.display()
         );
-        let mut gpg_ctx = utils::create_gpg_context()?;
-        let file = File::open(keys_path)?;
-        let buffered_reader = BufReader::new(file);
-        buffered_reader
-            .lines()
-            .map(|maybe_line| match maybe_line {
-                Err(e) => Err(PassError::from(e)),
-                Ok(line) => {
-                    log::trace!("Loading key {}", line);
-                    Ok(gpg_ctx
-                        .get_key(&line)
-                        .map_err(|_| PassError::GpgKeyNotFoundError(line))?)
-                }
+        let mut gpg_ctx = crypto.new_context()?;
+        GpgIdFile::read(keys_path)?
+            .recipients()
+            .iter()
+            .map(|recipient| {
+                trace!("Loading key {}", recipient);
+                gpg_ctx
+                    .get_key(recipient)
+                    .map_err(|_| PassError::GpgKeyNotFoundError(recipient.to_owned()))
             })
             .collect()
     }
 
+    /// Retrieve the ids of the keys for which this file is actually encrypted
+    ///
+    /// The ids are read from the unencrypted packet headers of the file so no decryption is necessary.
+    /// Note that these are usually the ids of encryption subkeys and not of the primary keys listed in
+    /// `.gpg-id` files.
+    pub fn recipient_key_ids(&self) -> Result<Vec<String>> {
+        let ciphertext = fs::read(&self.path)?;
+        packets::recipient_key_ids(&ciphertext)
+            .map_err(|e| PassError::InvalidCiphertext(self.path.to_owned(), e.to_string()))
+    }
+
+    /// Check that this file actually contains an encrypted OpenPGP message in binary or ASCII armored form
+    ///
+    /// Only the structure of the file is inspected, no decryption takes place.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidCiphertext`] is returned if the file is empty or corrupted.
+    pub fn validate_ciphertext(&self) -> Result<()> {
+        let ciphertext = fs::read(&self.path)?;
+        packets::validate_message(&ciphertext)
+            .map_err(|e| PassError::InvalidCiphertext(self.path.to_owned(), e.to_string()))
+    }
+
+    /// Decrypt this file and verify the signatures that were made while encrypting it
+    ///
+    /// Entries are only signed if they were written by a store that has
+    /// [`encrypt_and_sign`](crate::Store::with_encrypt_and_sign) enabled or by other tools that do the same.
+    /// The default gpg configuration is used.
+    /// Use [`Store::verify_signature()`](crate::Store::verify_signature) to apply the options of a specific
+    /// store.
+    pub fn verify_signature(&self) -> Result<SignatureStatus> {
+        CryptoOptions::default().verify_signature(&fs::read(&self.path)?)
+    }
+
     /// Get an IO handle to the encrypted content of this file
     pub fn cipher_io(&self) -> Result<CipherFile> {
-        CipherFile::new(&self.path)
+        CipherFile::new(&self.path, true)
+    }
+
+    /// Get a read-only IO handle to the encrypted content of this file
+    ///
+    /// In contrast to [`cipher_io()`](StoreFileRef::cipher_io), this also works on read-only filesystems.
+    pub fn cipher_io_ro(&self) -> Result<CipherFile> {
+        CipherFile::new(&self.path, false)
     }
 
     /// Get a read-write IO handle to the plaintext content of this file
+    ///
+    /// The handle uses default encryption options.
+    /// Use [`Store::plain_io_rw()`](crate::Store::plain_io_rw) to apply the options of a specific store.
     pub fn plain_io_rw(&self) -> Result<RwPlainFile> {
-        RwPlainFile::new(&self.path, self.encryption_keys()?)
+        RwPlainFile::new(
+            &self.path,
+            self.encryption_keys()?,
+            CryptoOptions::default(),
+        )
     }
 
     /// Get a read-only IO handle to the plaintext of this file
@@ -265,14 +472,95 @@ impl StoreFileRef {
         RoPlainFile::new(&self.path)
     }
 
+    /// Replace the password (the first line) of this file with *password* and keep all following lines
+    /// (like `pass generate --in-place`)
+    ///
+    /// The file is written with default encryption options.
+    /// Use [`Store::replace_password()`](crate::Store::replace_password) to apply the options of a specific
+    /// store.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidField`] is returned if *password* contains a line break.
+    pub fn replace_password(&self, password: &str) -> Result<()> {
+        let mut plain = self.plain_io_rw()?;
+        plain.reserve(password.len() + 1);
+        replace_first_line(plain.as_mut(), password)?;
+        plain.close()
+    }
+
+    /// Decrypt this file and interpret its content according to common pass conventions
+    ///
+    /// The content is returned as it is stored because a file is not associated with a store and therefore
+    /// runs no [transformers](crate::transform), e.g. references are not resolved.
+    /// Use [`Store::retrieve_entry()`](crate::Store::retrieve_entry) for that.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidStoreFormat`] is returned if the content is not valid UTF-8.
+    pub fn parse(&self) -> Result<ParsedEntry> {
+        self.parse_plaintext(self.plain_io_ro()?.as_ref().to_vec())
+    }
+
+    /// Find out who created and last changed this file according to the history of the git repository
+    /// containing it
+    ///
+    /// This is only available with the `git` feature.
+    ///
+    /// ## Errors
+    /// [`PassError::GitError`] is returned if the file is not inside a git repository.
+    #[cfg(feature = "git")]
+    pub fn provenance(&self) -> Result<Provenance> {
+        git::provenance(&self.path)
+    }
+
+    /// Decrypt and [parse](StoreFileRef::parse) this file together with its
+    /// [provenance](StoreFileRef::provenance)
+    ///
+    /// This is only available with the `git` feature.
+    #[cfg(feature = "git")]
+    pub fn parse_with_provenance(&self) -> Result<(ParsedEntry, Provenance)> {
+        Ok((self.parse()?, self.provenance()?))
+    }
+
+    /// Decrypt this file and interpret the first private key it contains (e.g. an OpenSSH private key)
+    ///
+    /// See the [`credentials`](crate::credentials) module for the supported formats.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidStoreFormat`] is returned if the content does not contain a valid private key.
+    pub fn as_ssh_key(&self) -> Result<SshKey> {
+        credentials::parse_ssh_key(self.plain_io_ro()?.as_ref())
+            .map_err(|reason| PassError::InvalidStoreFormat(self.path.to_owned(), reason))
+    }
+
+    /// Decrypt this file and interpret the first X.509 certificate it contains (PEM or DER encoded)
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidStoreFormat`] is returned if the content does not contain a valid certificate.
+    pub fn as_x509(&self) -> Result<Certificate> {
+        credentials::parse_certificate(self.plain_io_ro()?.as_ref())
+            .map_err(|reason| PassError::InvalidStoreFormat(self.path.to_owned(), reason))
+    }
+
+    /// Interpret *plaintext* which was decrypted from this file like [`parse()`](StoreFileRef::parse) does
+    pub(crate) fn parse_plaintext(&self, plaintext: Vec<u8>) -> Result<ParsedEntry> {
+        let content = String::from_utf8(plaintext).map_err(|e| {
+            secure_mem::release(&mut e.into_bytes(), &mut LockedRegion::default());
+            PassError::InvalidStoreFormat(
+                self.path.to_owned(),
+                "Entry content is not valid UTF-8".to_string(),
+            )
+        })?;
+        Ok(ParsedEntry::from(content))
+    }
+
     /// Verify that *self* references an existing file with the expected file extension
+    ///
+    /// ASCII armored `*.asc` files are accepted as well since they are only referenced by stores which are
+    /// configured to contain them.
     pub(crate) fn verify(&self) -> Result<()> {
         if self.path.exists()
             && self.path.is_file()
-            && match self.path.extension() {
-                None => false,
-                Some(extension) => extension == "gpg",
-            }
+            && walk::has_extension(&self.path, walk::GPG_AND_ASC)
         {
             Ok(())
         } else {
@@ -280,3 +568,23 @@ impl StoreFileRef {
         }
     }
 }
+
+/// Replace the first line of *plaintext* with *password*
+///
+/// Like `sed 1c` (which *pass* uses), the new line is always terminated by a newline.
+/// *plaintext* is only reallocated if it can not hold `password.len() + 1` more bytes, so callers reserve
+/// them beforehand.
+pub(crate) fn replace_first_line(plaintext: &mut Vec<u8>, password: &str) -> Result<()> {
+    if password.contains(['\n', '\r']) {
+        return Err(PassError::InvalidField {
+            key: "password".to_string(),
+            reason: "Passwords can not contain line breaks".to_string(),
+        });
+    }
+    let first_line_end = plaintext
+        .iter()
+        .position(|b| *b == b'\n')
+        .map_or(plaintext.len(), |i| i + 1);
+    plaintext.splice(..first_line_end, password.bytes().chain([b'\n']));
+    Ok(())
+}
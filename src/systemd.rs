@@ -0,0 +1,139 @@
+//! Integration with the credential mechanism of systemd
+//!
+//! systemd passes credentials to services as files in the directory named by `$CREDENTIALS_DIRECTORY`
+//! (configured with `LoadCredential=`, `SetCredential=` or `LoadCredentialEncrypted=` in the unit).
+//! [`credential()`] provides a single lookup path for services which looks there first and falls back to the
+//! password store, so the same service works with and without systemd.
+//!
+//! In the other direction, [`write_credentials()`] writes entries as files which can be referenced by
+//! `LoadCredential=` or encrypted with `systemd-creds encrypt`, and [`set_credential()`] renders an entry as
+//! `SetCredential=` line for unit drop-ins.
+//!
+//! ## Credential names
+//! Credentials are plain files in the credentials directory while pass names may contain directories.
+//! Both directions therefore map pass names to credential names with [`credential_name()`]: the name is
+//! [normalized](crate::names::normalize_name) and the `/` between its segments is replaced by `_`, so the
+//! entry `services/db` is the credential `services_db`.
+//! Names which could leave the store (like `..`) and the empty name are rejected so that a credential name
+//! is always a file directly inside of the credentials directory.
+//! Note that this maps `services/db` and `services_db` to the same credential.
+//!
+//! ## Example
+//! ```no_run
+//! let token = libpass::systemd::credential("api-token").unwrap();
+//! ```
+
+use crate::{config, names, utils, PassError, Result, Store};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Environment variable with the directory in which systemd places the credentials of a service
+pub const CREDENTIALS_DIRECTORY_ENV: &str = "CREDENTIALS_DIRECTORY";
+
+/// The umask which makes written credentials readable only by their owner (like systemd does)
+const CREDENTIAL_UMASK: u32 = 0o377;
+
+/// Look up the credential *name* in `$CREDENTIALS_DIRECTORY` and fall back to the entry with the same name
+/// in the default password store (see [`Store::from_env()`])
+///
+/// *name* is a pass name which is mapped to the name of the credential with [`credential_name()`].
+///
+/// ## Errors
+/// [`PassError::EntryNotFound`] is returned if neither systemd nor the store provide the credential and
+/// [`PassError::InvalidPassName`] if *name* is no valid name.
+pub fn credential(name: &str) -> Result<Vec<u8>> {
+    let credentials_dir = env::var_os(CREDENTIALS_DIRECTORY_ENV).map(PathBuf::from);
+    if let Some(content) = read_credential(credentials_dir.as_deref(), name)? {
+        return Ok(content);
+    }
+    credential_from_store(&Store::from_env()?, name)
+}
+
+/// Look up the credential *name* in `$CREDENTIALS_DIRECTORY` and fall back to the entry with the same name
+/// in *store*
+///
+/// See [`credential()`].
+pub fn credential_with(store: &Store, name: &str) -> Result<Vec<u8>> {
+    let credentials_dir = env::var_os(CREDENTIALS_DIRECTORY_ENV).map(PathBuf::from);
+    match read_credential(credentials_dir.as_deref(), name)? {
+        Some(content) => Ok(content),
+        None => credential_from_store(store, name),
+    }
+}
+
+/// Read the credential for the pass name *name* from *credentials_dir* if it exists there
+pub(crate) fn read_credential(
+    credentials_dir: Option<&Path>,
+    name: &str,
+) -> Result<Option<Vec<u8>>> {
+    let path = match credentials_dir {
+        Some(dir) => dir.join(credential_name(name)?),
+        None => return Ok(None),
+    };
+    match fs::read(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn credential_from_store(store: &Store, name: &str) -> Result<Vec<u8>> {
+    store.read_plaintext(&store.file(name)?)
+}
+
+/// The name of the credential for the entry *pass_name*
+///
+/// See the [module documentation](self#credential-names) for how names are mapped.
+///
+/// ## Errors
+/// [`PassError::InvalidPassName`] is returned if *pass_name* is no valid name or refers to the store root.
+pub fn credential_name(pass_name: &str) -> Result<String> {
+    let normalized = names::normalize_name(pass_name)?;
+    if normalized.is_empty() {
+        return Err(PassError::InvalidPassName(
+            pass_name.to_string(),
+            "The store root can not be a credential".to_string(),
+        ));
+    }
+    Ok(normalized.replace('/', "_"))
+}
+
+/// Decrypt the entries *pass_names* of *store* into files of *dir* which are only readable by their owner
+///
+/// The files are named by [`credential_name()`] and contain the complete content of the entries, which is
+/// the input that `LoadCredential=` and `systemd-creds encrypt` expect.
+/// The paths of the written files are returned.
+pub fn write_credentials(store: &Store, pass_names: &[&str], dir: &Path) -> Result<Vec<PathBuf>> {
+    utils::create_dir_all(dir, config::DEFAULT_UMASK)?;
+    pass_names
+        .iter()
+        .map(|pass_name| {
+            let path = dir.join(credential_name(pass_name)?);
+            let content = credential_from_store(store, pass_name)?;
+            utils::write_atomically(&path, &content, CREDENTIAL_UMASK)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Render a `SetCredential=` line which passes *value* as credential *name* to a service
+///
+/// Bytes which systemd would interpret (backslashes, `%` specifiers and non-printable characters) are
+/// escaped.
+/// **Note:** Unit files are usually world-readable so this should only be used in drop-ins with restricted
+/// permissions.
+pub fn set_credential(name: &str, value: &[u8]) -> String {
+    let mut line = format!("SetCredential={}:", name);
+    for b in value {
+        match b {
+            b'\\' => line.push_str("\\\\"),
+            b'%' => line.push_str("%%"),
+            b'\n' => line.push_str("\\n"),
+            b'\t' => line.push_str("\\t"),
+            0x20..=0x7e => line.push(*b as char),
+            b => line.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    line
+}
@@ -0,0 +1,53 @@
+//! Browsing entries by the tags listed in their `tags:` field
+//!
+//! Tags are part of the encrypted content of entries (see [`ParsedEntry::tags()`](crate::ParsedEntry::tags))
+//! so finding entries by tag requires decrypting the whole store.
+//! A [`TagIndex`] does this once so that applications can answer many tag queries from it.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::Store;
+//!
+//! let store = Store::from_env().unwrap();
+//! let index = store.tag_index().unwrap();
+//! for tag in index.tags() {
+//!     println!("{}: {} entries", tag, index.entries_with_tag(tag).len());
+//! }
+//! ```
+
+use crate::StoreFileRef;
+use std::collections::BTreeMap;
+
+/// A mapping from tags to the entries which have them
+///
+/// Get an instance of this by calling [`Store::tag_index()`](crate::Store::tag_index).
+/// The index is a snapshot and is not updated when entries change.
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct TagIndex {
+    entries: BTreeMap<String, Vec<StoreFileRef>>,
+}
+
+impl TagIndex {
+    /// Record that *file* has *tag*
+    pub(crate) fn insert(&mut self, tag: &str, file: StoreFileRef) {
+        let files = self.entries.entry(tag.to_string()).or_default();
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+
+    /// All tags which at least one entry has, sorted alphabetically
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// The entries which have *tag*, sorted by their path
+    pub fn entries_with_tag(&self, tag: &str) -> &[StoreFileRef] {
+        self.entries.get(tag).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Whether no entry has any tag
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
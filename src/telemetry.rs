@@ -0,0 +1,109 @@
+//! Instrumentation of operations for logging and profiling
+//!
+//! All messages of this crate are emitted through the macros of this module.
+//! Without the `tracing` feature, they are emitted via the `log` crate and spans have no effect.
+//! With it, messages are emitted as `tracing` events and expensive operations (gpg operations, reading
+//! ciphertext, listing and synchronization) are wrapped in spans which record their duration in an
+//! `elapsed_us` field so that applications can see where time is spent.
+
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+/// Emit a trace level message
+macro_rules! trace {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        log::trace!($($arg)+);
+    }};
+}
+
+/// Emit a debug level message
+macro_rules! debug {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        log::debug!($($arg)+);
+    }};
+}
+
+/// Emit a warning
+///
+/// This is not called `warn` because that name is taken by the builtin attribute.
+macro_rules! warning {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        log::warn!($($arg)+);
+    }};
+}
+
+/// Emit an error message
+///
+/// Only the git integration reports errors which it cannot return.
+#[cfg(feature = "git")]
+macro_rules! error {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::error!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        log::error!($($arg)+);
+    }};
+}
+
+/// Enter a debug level span named *name* with the given fields which records its duration when it is exited
+///
+/// The span is exited when the returned [`Timed`] guard is dropped.
+macro_rules! span {
+    ($name:literal $(, $($field:tt)+)?) => {{
+        #[cfg(feature = "tracing")]
+        let timed = $crate::telemetry::Timed::enter(tracing::debug_span!(
+            $name,
+            elapsed_us = tracing::field::Empty
+            $(, $($field)+)?
+        ));
+        #[cfg(not(feature = "tracing"))]
+        let timed = $crate::telemetry::Timed::disabled();
+        timed
+    }};
+}
+
+#[cfg(feature = "git")]
+pub(crate) use error;
+pub(crate) use {debug, span, trace, warning};
+
+/// Guard of an entered span which records the time until it is dropped
+#[derive(Debug)]
+#[must_use = "the span is exited immediately if the guard is not kept"]
+pub(crate) struct Timed {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+    #[cfg(feature = "tracing")]
+    start: Instant,
+}
+
+impl Timed {
+    #[cfg(feature = "tracing")]
+    pub(crate) fn enter(span: tracing::Span) -> Self {
+        Self {
+            span: span.entered(),
+            start: Instant::now(),
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn disabled() -> Self {
+        Self {}
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for Timed {
+    fn drop(&mut self) {
+        let elapsed = u64::try_from(self.start.elapsed().as_micros()).unwrap_or(u64::MAX);
+        self.span.record("elapsed_us", elapsed);
+    }
+}
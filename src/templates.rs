@@ -0,0 +1,163 @@
+//! Templates for creating entries with a standardized structure
+//!
+//! Entries in a pass store conventionally contain the password on their first line followed by additional
+//! `key: value` lines like `user: john` or `url: https://example.com`.
+//! A [`Template`] describes which of these fields an entry of a certain kind should contain and renders given
+//! values into this format.
+//! Templates can be kept by name in a [`TemplateRegistry`] and be used to create entries via
+//! [`Store::insert_from_template()`](crate::Store::insert_from_template).
+//!
+//! ## Example
+//! ```
+//! use std::collections::HashMap;
+//! use libpass::templates::{Template, TemplateRegistry};
+//!
+//! let mut registry = TemplateRegistry::default();
+//! registry.register(Template::new("login").required("user").optional("url"));
+//!
+//! let values = HashMap::from([
+//!     ("password".to_string(), "hunter2".to_string()),
+//!     ("user".to_string(), "john".to_string()),
+//! ]);
+//! assert_eq!(
+//!     registry.get("login").unwrap().render(&values).unwrap(),
+//!     "hunter2\nuser: john\n"
+//! );
+//! ```
+
+use crate::{PassError, Result};
+use std::collections::HashMap;
+
+/// Name of the field which holds the password and is always rendered on the first line
+pub const PASSWORD_FIELD: &str = "password";
+
+/// Name of the field which is rendered without a `key: ` prefix as *pass-otp* expects `otpauth://` URIs
+pub const OTP_FIELD: &str = "otp";
+
+/// A single field of a [`Template`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TemplateField {
+    /// Name of the field which is used as key in the rendered entry
+    pub name: String,
+    /// Whether rendering fails if no value is given for this field
+    pub required: bool,
+}
+
+/// Description of the fields that an entry of a certain kind contains
+///
+/// Every template implicitly contains the required [`PASSWORD_FIELD`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Template {
+    name: String,
+    fields: Vec<TemplateField>,
+}
+
+impl Template {
+    /// Create a new template with the given name which only contains the password field
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Add a field which must be given a value when rendering
+    pub fn required(mut self, field: impl Into<String>) -> Self {
+        self.fields.push(TemplateField {
+            name: field.into(),
+            required: true,
+        });
+        self
+    }
+
+    /// Add a field which may be omitted when rendering
+    pub fn optional(mut self, field: impl Into<String>) -> Self {
+        self.fields.push(TemplateField {
+            name: field.into(),
+            required: false,
+        });
+        self
+    }
+
+    /// The name of this template
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The fields of this template in the order in which they are rendered (excluding the password)
+    pub fn fields(&self) -> &[TemplateField] {
+        &self.fields
+    }
+
+    /// Render *values* into the conventional pass entry format
+    ///
+    /// The password is rendered on the first line, followed by one `key: value` line per field with a given
+    /// value in the order in which the fields were added to the template.
+    ///
+    /// ## Errors
+    /// [`PassError::InvalidTemplateValues`] is returned if a required field (including the password) has no
+    /// value, a value is given for a field which is not part of this template or a value spans multiple
+    /// lines.
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<String> {
+        let error = |reason: String| PassError::InvalidTemplateValues {
+            template: self.name.clone(),
+            reason,
+        };
+
+        if let Some(unknown) = values
+            .keys()
+            .find(|key| *key != PASSWORD_FIELD && !self.fields.iter().any(|f| &f.name == *key))
+        {
+            return Err(error(format!("{} is not a field of the template", unknown)));
+        }
+        if let Some((key, _)) = values.iter().find(|(_, value)| value.contains('\n')) {
+            return Err(error(format!("the value of {} spans multiple lines", key)));
+        }
+
+        let password = values
+            .get(PASSWORD_FIELD)
+            .ok_or_else(|| error(format!("required field {} is missing", PASSWORD_FIELD)))?;
+        let mut content = format!("{}\n", password);
+
+        for field in &self.fields {
+            match values.get(&field.name) {
+                Some(value) if field.name == OTP_FIELD => content.push_str(&format!("{}\n", value)),
+                Some(value) => content.push_str(&format!("{}: {}\n", field.name, value)),
+                None if field.required => {
+                    return Err(error(format!("required field {} is missing", field.name)))
+                }
+                None => {}
+            }
+        }
+
+        Ok(content)
+    }
+}
+
+/// A collection of templates which can be looked up by their name
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct TemplateRegistry {
+    templates: HashMap<String, Template>,
+}
+
+impl TemplateRegistry {
+    /// Register *template* under its name, replacing and returning a previously registered one
+    pub fn register(&mut self, template: Template) -> Option<Template> {
+        self.templates.insert(template.name.clone(), template)
+    }
+
+    /// Remove the template with the given name from the registry
+    pub fn unregister(&mut self, name: &str) -> Option<Template> {
+        self.templates.remove(name)
+    }
+
+    /// Look up the template with the given name
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+
+    /// Iterate over all registered templates in arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = &Template> {
+        self.templates.values()
+    }
+}
@@ -0,0 +1,192 @@
+//! Fixtures for integration tests of applications which use this library
+//!
+//! [`StoreFixture`] describes the content of a store which is then built inside a temporary directory.
+//! Entries are encrypted for an embedded test key ([`TEST_KEY`]) unless other recipients are given.
+//! The key is not protected by a passphrase so tests never trigger a pinentry prompt.
+//!
+//! Every fixture gets its own temporary keyring containing the test key (see [`test_gpg_home()`]) so that
+//! building fixtures never modifies the keyring of the current user.
+//!
+//! This module is only available with the `testing` feature.
+//!
+//! ## Example
+//! ```
+//! use libpass::testing::StoreFixture;
+//!
+//! let fixture = StoreFixture::new()
+//!     .with_entry("web/github.com", "hunter2\nuser: john\n")
+//!     .with_entry("team/database", "s3cret\n")
+//!     .with_gpg_id("team", [libpass::testing::TEST_KEY_FINGERPRINT])
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(fixture.store().list().unwrap().len(), 2);
+//! ```
+
+use crate::crypto::CryptoOptions;
+use crate::{config, utils, Config, PassError, Result, Store};
+use std::path::{Component, Path};
+use tempfile::TempDir;
+
+/// The secret key which fixtures are encrypted for by default (ASCII armored, without passphrase)
+pub const TEST_KEY: &str = include_str!("../tests/8497251104B6F45F.key");
+
+/// The fingerprint of [`TEST_KEY`]
+pub const TEST_KEY_FINGERPRINT: &str = "4837F49E22F425EA91725C428497251104B6F45F";
+
+/// Create a temporary gpg home directory whose keyring contains [`TEST_KEY`]
+///
+/// Use it with [`Store::with_gpg_home()`] like `GNUPGHOME` would be used with *pass*.
+/// The directory is deleted when the returned handle is dropped.
+pub fn test_gpg_home() -> Result<TempDir> {
+    let gpg_home = tempfile::tempdir()?;
+    let crypto = CryptoOptions {
+        gpg_home: Some(gpg_home.path().into()),
+        ..CryptoOptions::default()
+    };
+    let mut gpg_ctx = crypto.new_context()?;
+    gpg_ctx.import(TEST_KEY.as_bytes())?;
+    Ok(gpg_home)
+}
+
+/// A declarative description of the content of a store
+///
+/// The root of the store is always initialized for [`TEST_KEY`].
+/// Entries and `.gpg-id` files are created in the order in which they were added.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct StoreFixture {
+    gpg_ids: Vec<(String, Vec<String>)>,
+    entries: Vec<FixtureEntry>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct FixtureEntry {
+    pass_name: String,
+    content: Vec<u8>,
+    recipients: Option<Vec<String>>,
+}
+
+impl StoreFixture {
+    /// Describe an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an entry named *pass_name* with the given plaintext *content*
+    ///
+    /// The entry is encrypted for the keys of its governing `.gpg-id` file.
+    pub fn with_entry(mut self, pass_name: impl Into<String>, content: impl AsRef<[u8]>) -> Self {
+        self.entries.push(FixtureEntry {
+            pass_name: pass_name.into(),
+            content: content.as_ref().to_vec(),
+            recipients: None,
+        });
+        self
+    }
+
+    /// Add an entry which is encrypted for *recipients* regardless of its governing `.gpg-id` file
+    ///
+    /// This allows building stores whose entries do not match their `.gpg-id` files, e.g. to test key
+    /// rotation.
+    pub fn with_entry_for(
+        mut self,
+        pass_name: impl Into<String>,
+        content: impl AsRef<[u8]>,
+        recipients: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.entries.push(FixtureEntry {
+            pass_name: pass_name.into(),
+            content: content.as_ref().to_vec(),
+            recipients: Some(recipients.into_iter().map(Into::into).collect()),
+        });
+        self
+    }
+
+    /// Add a `.gpg-id` file to the directory *dir* which lists *recipients*
+    pub fn with_gpg_id(
+        mut self,
+        dir: impl Into<String>,
+        recipients: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.gpg_ids
+            .push((dir.into(), recipients.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Build the described store inside a new temporary directory
+    ///
+    /// The store uses a new keyring which only contains [`TEST_KEY`] (see [`test_gpg_home()`]).
+    pub fn build(&self) -> Result<TestStore> {
+        let gpg_home = test_gpg_home()?;
+        let dir = tempfile::tempdir()?;
+        write_gpg_id(dir.path(), &[TEST_KEY_FINGERPRINT.to_string()])?;
+        for (gpg_id_dir, recipients) in &self.gpg_ids {
+            let relative = Path::new(gpg_id_dir.trim_matches('/'));
+            if relative.as_os_str().is_empty()
+                || relative
+                    .components()
+                    .any(|component| !matches!(component, Component::Normal(_)))
+            {
+                return Err(PassError::InvalidPassName(
+                    gpg_id_dir.to_string(),
+                    "Directory of nested .gpg-id file must be below the store root".to_string(),
+                ));
+            }
+            write_gpg_id(&dir.path().join(relative), recipients)?;
+        }
+
+        let store = Store::open(dir.path())?.with_gpg_home(gpg_home.path());
+        for entry in &self.entries {
+            match &entry.recipients {
+                None => store.insert(&entry.pass_name, &entry.content)?,
+                Some(recipients) => store
+                    .clone()
+                    .with_config(Config::default().with_keys(recipients))
+                    .insert(&entry.pass_name, &entry.content)?,
+            };
+        }
+
+        Ok(TestStore {
+            dir,
+            gpg_home,
+            store,
+        })
+    }
+}
+
+/// Write a `.gpg-id` file listing *recipients* into *dir*
+fn write_gpg_id(dir: &Path, recipients: &[String]) -> Result<()> {
+    utils::create_dir_all(dir, config::DEFAULT_UMASK)?;
+    let content = recipients.join("\n") + "\n";
+    utils::write_atomically(
+        &dir.join(".gpg-id"),
+        content.as_bytes(),
+        config::DEFAULT_UMASK,
+    )?;
+    Ok(())
+}
+
+/// A store built from a [`StoreFixture`] which is deleted again when this is dropped
+#[derive(Debug)]
+pub struct TestStore {
+    dir: TempDir,
+    gpg_home: TempDir,
+    store: Store,
+}
+
+impl TestStore {
+    /// The built store
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// The temporary directory containing the store
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// The temporary gpg home directory whose keyring the store uses
+    pub fn gpg_home(&self) -> &Path {
+        self.gpg_home.path()
+    }
+}
@@ -1,8 +1,12 @@
 #![allow(clippy::unwrap_used)]
 
-use crate::utils;
+use crate::secure::SecretString;
 use crate::*;
-use std::io::Read;
+use crate::{gpg_id, packets, utils};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
 
 fn set_store_dir() {
     env::set_var(
@@ -11,6 +15,29 @@ fn set_store_dir() {
     );
 }
 
+/// Create a copy of the test store in a temporary directory for tests which modify it
+fn temp_store() -> (tempfile::TempDir, Store) {
+    fn copy_dir(from: &Path, to: &Path) {
+        fs::create_dir_all(to).unwrap();
+        for entry in fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &to.join(entry.file_name()));
+            } else {
+                fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+            }
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    copy_dir(
+        &env::current_dir().unwrap().join("tests/simple"),
+        dir.path(),
+    );
+    let store = Store::open(dir.path()).unwrap();
+    (dir, store)
+}
+
 fn retrieve_file(pass_name: &str) -> StoreFileRef {
     match retrieve(pass_name).unwrap() {
         StoreEntry::File(file) => file,
@@ -40,31 +67,38 @@ fn test_list_entries() {
         dbg!(list()).unwrap(),
         HashSet::from_iter(vec![
             StoreEntry::File(StoreFileRef {
-                path: password_store_dir().unwrap().join("secret-a.gpg")
+                path: password_store_dir().unwrap().join("secret-a.gpg"),
+                root: password_store_dir().unwrap(),
             }),
             StoreEntry::File(StoreFileRef {
-                path: password_store_dir().unwrap().join("secret-b.gpg")
+                path: password_store_dir().unwrap().join("secret-b.gpg"),
+                root: password_store_dir().unwrap(),
             }),
             StoreEntry::File(StoreFileRef {
-                path: password_store_dir().unwrap().join("folder/subsecret-a.gpg")
+                path: password_store_dir().unwrap().join("folder/subsecret-a.gpg"),
+                root: password_store_dir().unwrap(),
             }),
             StoreEntry::File(StoreFileRef {
-                path: password_store_dir().unwrap().join("folder/subsecret-b.gpg")
+                path: password_store_dir().unwrap().join("folder/subsecret-b.gpg"),
+                root: password_store_dir().unwrap(),
             }),
             StoreEntry::File(StoreFileRef {
                 path: password_store_dir()
                     .unwrap()
                     .join("folder/subfolder/generated-a.gpg"),
+                root: password_store_dir().unwrap(),
             }),
             StoreEntry::File(StoreFileRef {
                 path: password_store_dir()
                     .unwrap()
                     .join("folder/subfolder/generated-b.gpg"),
+                root: password_store_dir().unwrap(),
             }),
             StoreEntry::File(StoreFileRef {
                 path: password_store_dir()
                     .unwrap()
-                    .join("folder2/subsecret-a.gpg")
+                    .join("folder2/subsecret-a.gpg"),
+                root: password_store_dir().unwrap(),
             })
         ])
     );
@@ -82,7 +116,8 @@ fn test_retrieve_entry() {
     assert_eq!(
         retrieve("secret-a").unwrap(),
         StoreEntry::File(StoreFileRef {
-            path: password_store_dir().unwrap().join("secret-a.gpg")
+            path: password_store_dir().unwrap().join("secret-a.gpg"),
+            root: password_store_dir().unwrap(),
         })
     );
 
@@ -92,7 +127,8 @@ fn test_retrieve_entry() {
         StoreEntry::File(StoreFileRef {
             path: password_store_dir()
                 .unwrap()
-                .join("folder/subfolder/generated-a.gpg")
+                .join("folder/subfolder/generated-a.gpg"),
+            root: password_store_dir().unwrap(),
         })
     );
 
@@ -134,6 +170,32 @@ fn test_write_plaintext() {
     assert!(dbg!(handle.sync(false)).is_ok())
 }
 
+#[test]
+fn test_sync_only_changed_plaintext() {
+    set_store_dir();
+    let file = retrieve_file("secret-a");
+    let ciphertext = fs::read(&file.path).unwrap();
+    let mut handle = file.plain_io_rw().unwrap();
+
+    // reading does not cause the entry to be re-encrypted
+    let mut content = String::new();
+    handle.read_to_string(&mut content).unwrap();
+    handle.sync(false).unwrap();
+    assert_eq!(fs::read(&file.path).unwrap(), ciphertext);
+
+    // forcing a sync re-encrypts unchanged content with a new session key
+    handle.sync(true).unwrap();
+    let forced = fs::read(&file.path).unwrap();
+    assert_ne!(forced, ciphertext);
+
+    // writing marks the content as changed even if it is the same
+    handle.seek(SeekFrom::Start(0)).unwrap();
+    handle.write_all(content.as_bytes()).unwrap();
+    handle.sync(false).unwrap();
+    assert_ne!(fs::read(&file.path).unwrap(), forced);
+    handle.close().unwrap();
+}
+
 #[test]
 fn test_read_plaintext() {
     set_store_dir();
@@ -168,6 +230,34 @@ fn test_get_entry_name() {
     );
 }
 
+#[test]
+fn test_get_entry_name_outside_default_store() {
+    set_store_dir();
+    let (_dir, store) = temp_store();
+
+    assert_eq!(
+        store.retrieve("secret-a").unwrap().name().unwrap(),
+        "secret-a"
+    );
+    assert_eq!(store.retrieve("folder").unwrap().name().unwrap(), "folder");
+    assert_eq!(
+        store
+            .retrieve_file("folder/subsecret-a")
+            .unwrap()
+            .name()
+            .unwrap(),
+        "folder/subsecret-a"
+    );
+    assert_eq!(
+        store
+            .retrieve_dir("folder/subfolder")
+            .unwrap()
+            .name()
+            .unwrap(),
+        "folder/subfolder"
+    );
+}
+
 #[test]
 fn test_get_encryption_keys() {
     set_store_dir();
@@ -190,3 +280,4309 @@ fn test_get_encryption_keys() {
         "8497251104B6F45F"
     );
 }
+
+#[test]
+fn test_recipient_key_ids() {
+    set_store_dir();
+
+    // the test key encrypts to its subkey
+    assert_eq!(
+        retrieve_file("secret-a").recipient_key_ids().unwrap(),
+        vec!["5574A652EE284AC0".to_string()]
+    );
+
+    // non-encrypted data is rejected
+    assert!(packets::recipient_key_ids(b"").is_err());
+    assert!(packets::recipient_key_ids(b"foobar123\n").is_err());
+}
+
+#[test]
+fn test_check_recipients() {
+    set_store_dir();
+    let store = Store::from_env().unwrap();
+    assert!(store.check_recipients().unwrap().is_empty());
+}
+
+#[test]
+fn test_get_encryption_keys_unchecked() {
+    set_store_dir();
+    let file = retrieve_file("secret-a");
+
+    // the test key is usable so validation does not change the result
+    assert_eq!(
+        file.encryption_keys_unchecked().unwrap()[0].id().unwrap(),
+        file.encryption_keys().unwrap()[0].id().unwrap()
+    );
+
+    // .gpg-id files above the store root are never used
+    let (dir, _) = temp_store();
+    let root = dir.path().join("folder");
+    let file = StoreFileRef {
+        path: root.join("subsecret-a.gpg"),
+        root: root.clone(),
+    };
+    assert!(matches!(
+        file.encryption_keys_unchecked(),
+        Err(PassError::StoreNotInitialized(path)) if path == root
+    ));
+}
+
+#[test]
+fn test_parse_gpg_id_file() {
+    // comments and blank lines are ignored
+    let gpg_id: GpgIdFile = "# comment\n\n8497251104B6F45F # trailing comment\n  \n"
+        .parse()
+        .unwrap();
+    assert_eq!(gpg_id.recipients(), ["8497251104B6F45F"]);
+
+    // multiple key ids per line
+    let gpg_id: GpgIdFile = "8497251104B6F45F 0x5574A652EE284AC0\n".parse().unwrap();
+    assert_eq!(
+        gpg_id.recipients(),
+        ["8497251104B6F45F", "0x5574A652EE284AC0"]
+    );
+
+    // user ids with spaces are kept intact
+    let gpg_id: GpgIdFile = "John Doe <john@example.com>\njane@example.com\n"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        gpg_id.recipients(),
+        ["John Doe <john@example.com>", "jane@example.com"]
+    );
+
+    // space separated fingerprints are joined
+    let gpg_id: GpgIdFile = "4837 F49E 22F4 25EA 9172  5C42 8497 2511 04B6 F45F\n"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        gpg_id.recipients(),
+        ["4837F49E22F425EA91725C428497251104B6F45F"]
+    );
+
+    // the test store file
+    assert_eq!(
+        GpgIdFile::read("tests/simple/.gpg-id")
+            .unwrap()
+            .recipients(),
+        ["4837F49E22F425EA91725C428497251104B6F45F"]
+    );
+}
+
+#[test]
+fn test_write_plaintext_with_valid_trust() {
+    set_store_dir();
+    let store = Store::from_env()
+        .unwrap()
+        .with_encryption_policy(EncryptionPolicy::RequireValidTrust);
+    assert_eq!(
+        store.encryption_policy(),
+        EncryptionPolicy::RequireValidTrust
+    );
+
+    // the test key is ultimately trusted so encryption for it is allowed
+    let mut handle = store.plain_io_rw(&retrieve_file("secret-b")).unwrap();
+    let original_content = handle.as_ref().to_vec();
+    *handle.as_mut() = "hello world".as_bytes().to_vec();
+    assert!(dbg!(handle.sync(false)).is_ok());
+    *handle.as_mut() = original_content;
+    assert!(dbg!(handle.sync(false)).is_ok())
+}
+
+#[test]
+fn test_verify_signature() {
+    set_store_dir();
+
+    // test entries are not signed
+    assert_eq!(
+        retrieve_file("secret-a").verify_signature().unwrap(),
+        SignatureStatus::Unsigned
+    );
+}
+
+#[test]
+fn test_probe_decryption_capability() {
+    set_store_dir();
+    assert!(Store::from_env()
+        .unwrap()
+        .probe_decryption_capability()
+        .is_ok());
+}
+
+#[test]
+fn test_discover_keys() {
+    set_store_dir();
+
+    // the test key is imported with its secret part
+    assert!(keys::secret_keys()
+        .unwrap()
+        .iter()
+        .any(|key| key.id().unwrap() == "8497251104B6F45F"));
+    assert_eq!(
+        keys::find_by_fingerprint("4837F49E22F425EA91725C428497251104B6F45F")
+            .unwrap()
+            .id()
+            .unwrap(),
+        "8497251104B6F45F"
+    );
+    assert!(keys::find_by_email("nobody@example.com")
+        .unwrap()
+        .is_empty());
+
+    assert!(keys::is_store_decryptable(&Store::from_env().unwrap()).unwrap());
+}
+
+#[test]
+fn test_plaintext_io_traits() {
+    set_store_dir();
+    let mut handle = retrieve_file("folder/subsecret-b").plain_io_rw().unwrap();
+    let original_content = handle.as_ref().to_vec();
+
+    // overwrite the beginning and append past the end
+    handle.seek(SeekFrom::Start(0)).unwrap();
+    handle.write_all(b"xy").unwrap();
+    handle.seek(SeekFrom::End(2)).unwrap();
+    handle.write_all(b"z").unwrap();
+    let mut expected = original_content.clone();
+    expected[..2].copy_from_slice(b"xy");
+    expected.extend_from_slice(&[0, 0, b'z']);
+    assert_eq!(handle.as_ref(), &expected);
+
+    // read back from the middle
+    handle.seek(SeekFrom::Start(1)).unwrap();
+    let mut buffer = Vec::new();
+    handle.read_to_end(&mut buffer).unwrap();
+    assert_eq!(buffer, expected[1..]);
+    assert!(handle.seek(SeekFrom::Current(-100)).is_err());
+
+    // restore file content
+    *handle.as_mut() = original_content;
+    assert!(handle.flush().is_ok());
+}
+
+#[test]
+fn test_close_plaintext() {
+    set_store_dir();
+    let mut handle = retrieve_file("folder2/subsecret-a").plain_io_rw().unwrap();
+    handle.set_panic_on_lost_changes(true);
+    assert!(handle.close().is_ok());
+}
+
+#[test]
+fn test_read_only_store() {
+    set_store_dir();
+    let store = Store::open_read_only(password_store_dir().unwrap()).unwrap();
+    let file = match store.retrieve("secret-a").unwrap() {
+        StoreEntry::File(file) => file,
+        StoreEntry::Directory(_) => panic!("not a file"),
+    };
+
+    // reading works but writing is rejected by the read-only file handle
+    let mut cipher_file = store.cipher_io(&file).unwrap();
+    let mut buffer = Vec::new();
+    assert!(cipher_file.as_mut().read_to_end(&mut buffer).is_ok());
+    assert!(cipher_file.as_mut().write_all(&buffer).is_err());
+
+    assert_eq!(
+        store.plain_io(&file).unwrap().as_ref(),
+        "foobar123\n".as_bytes()
+    );
+}
+
+#[test]
+fn test_replace_ciphertext() {
+    set_store_dir();
+    let entry = retrieve_file("folder/subfolder/generated-b");
+    let mut cipher_file = entry.cipher_io().unwrap();
+    let mut original = Vec::new();
+    cipher_file.as_mut().read_to_end(&mut original).unwrap();
+
+    // invalid content is rejected without touching the file
+    assert!(cipher_file
+        .replace_ciphertext("not encrypted".as_bytes())
+        .is_err());
+    assert_eq!(fs::read(&entry.path).unwrap(), original);
+
+    // valid content replaces the file
+    assert!(cipher_file.replace_ciphertext(original.as_slice()).is_ok());
+    assert_eq!(fs::read(&entry.path).unwrap(), original);
+
+    // read-only handles refuse replacement
+    assert!(entry
+        .cipher_io_ro()
+        .unwrap()
+        .replace_ciphertext(original.as_slice())
+        .is_err());
+}
+
+#[test]
+fn test_validate_ciphertext() {
+    set_store_dir();
+    assert!(retrieve_file("secret-a").validate_ciphertext().is_ok());
+
+    // empty and corrupt files are reported
+    assert!(matches!(
+        packets::validate_message(b""),
+        Err("ciphertext is empty")
+    ));
+    assert!(packets::validate_message(&[0x84, 0x8c, 0x03]).is_err());
+}
+
+#[test]
+fn test_fsck() {
+    set_store_dir();
+    let report = Store::from_env().unwrap().fsck().unwrap();
+    assert!(dbg!(&report).is_ok());
+
+    // the README of the test store is not an entry
+    assert_eq!(report.max_severity(), Some(fsck::Severity::Info));
+    assert_eq!(report.issues.len(), 1);
+    assert_eq!(report.issues[0].kind, fsck::FsckIssueKind::OrphanFile);
+    assert!(report.issues[0].path.ends_with("README.md"));
+}
+
+#[test]
+fn test_render_template() {
+    let template = templates::Template::new("login")
+        .required("user")
+        .optional("url")
+        .optional(templates::OTP_FIELD);
+    let values = |pairs: &[(&str, &str)]| {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>()
+    };
+
+    assert_eq!(
+        template
+            .render(&values(&[
+                ("password", "hunter2"),
+                ("user", "john"),
+                ("url", "https://example.com"),
+                ("otp", "otpauth://totp/example"),
+            ]))
+            .unwrap(),
+        "hunter2\nuser: john\nurl: https://example.com\notpauth://totp/example\n"
+    );
+
+    // missing required fields, unknown fields and multiline values are rejected
+    assert!(template.render(&values(&[("user", "john")])).is_err());
+    assert!(template.render(&values(&[("password", "x")])).is_err());
+    assert!(template
+        .render(&values(&[("password", "x"), ("user", "a"), ("foo", "b")]))
+        .is_err());
+    assert!(template
+        .render(&values(&[("password", "x\ny"), ("user", "a")]))
+        .is_err());
+}
+
+#[test]
+fn test_insert_entry() {
+    let (_dir, store) = temp_store();
+
+    // existing entries and invalid names are rejected
+    assert!(matches!(
+        store.insert("secret-a", b"foo"),
+        Err(PassError::EntryAlreadyExists(_))
+    ));
+    assert!(matches!(
+        store.insert("../outside", b"foo"),
+        Err(PassError::InvalidPassName(..))
+    ));
+
+    let file = store
+        .insert("inserted/test-insert-entry", b"hunter2\n")
+        .unwrap();
+    assert!(file.path.starts_with(store.root()));
+    assert_eq!(file.plain_io_ro().unwrap().as_ref(), b"hunter2\n");
+}
+
+#[test]
+fn test_parsed_entry_login() {
+    let entry: ParsedEntry = "hunter2\nuser: john\nurl: https://example.com/login\n"
+        .parse()
+        .unwrap();
+    assert_eq!(entry.password(), "hunter2");
+    assert_eq!(
+        entry.login(),
+        Login {
+            username: Some("john".to_string()),
+            url: Some("https://example.com/login".to_string()),
+        }
+    );
+
+    // username on the second line and url without field
+    let entry: ParsedEntry = "hunter2\njohn\nhttps://example.com\n".parse().unwrap();
+    assert_eq!(entry.login().username.as_deref(), Some("john"));
+    assert_eq!(entry.login().url.as_deref(), Some("https://example.com"));
+
+    // urls are not mistaken for fields or usernames
+    let entry: ParsedEntry = "hunter2\nhttps://example.com\n".parse().unwrap();
+    assert_eq!(entry.fields().count(), 0);
+    assert_eq!(entry.login().username, None);
+
+    // nothing to find
+    assert_eq!(
+        "hunter2".parse::<ParsedEntry>().unwrap().login(),
+        Login::default()
+    );
+}
+
+#[test]
+fn test_url_matching() {
+    use crate::parsed_entry::{domain_matches, url_host};
+
+    assert_eq!(
+        url_host("https://www.Example.com:8080/login?x=y").as_deref(),
+        Some("example.com")
+    );
+    assert_eq!(
+        url_host("john@gist.github.com").as_deref(),
+        Some("gist.github.com")
+    );
+    assert_eq!(url_host("https://"), None);
+
+    assert!(domain_matches(
+        "github.com",
+        "www.github.com",
+        UrlMatch::Exact
+    ));
+    assert!(!domain_matches(
+        "gist.github.com",
+        "github.com",
+        UrlMatch::Exact
+    ));
+    assert!(domain_matches(
+        "gist.github.com",
+        "github.com",
+        UrlMatch::Subdomains
+    ));
+    assert!(!domain_matches(
+        "notgithub.com",
+        "github.com",
+        UrlMatch::Subdomains
+    ));
+    assert!(!domain_matches(
+        "github.com",
+        "folder",
+        UrlMatch::Subdomains
+    ));
+}
+
+#[test]
+fn test_entries_for_url() {
+    let (_dir, store) = temp_store();
+    let by_name = store.insert("web/github.com/john", b"hunter2\n").unwrap();
+    let by_field = store
+        .insert("web/work", b"hunter2\nurl: https://gist.github.com\n")
+        .unwrap();
+    store
+        .insert("web/other", b"hunter2\nurl: https://example.com\n")
+        .unwrap();
+
+    store.insert("web/github.io/me", b"hunter2\n").unwrap();
+
+    assert_eq!(
+        store
+            .entries_for_url("https://gist.github.com/some/gist")
+            .unwrap(),
+        vec![by_field.clone()]
+    );
+    assert_eq!(
+        store.entries_for_url("https://www.github.com").unwrap(),
+        vec![by_name.clone()]
+    );
+    assert!(store.entries_for_url("https://").unwrap().is_empty());
+    // other sites below a shared domain are not offered its credentials
+    assert!(store
+        .entries_for_url("https://attacker.github.io")
+        .unwrap()
+        .is_empty());
+
+    let store = store.with_url_matching(UrlMatch::Subdomains);
+    assert_eq!(
+        store
+            .entries_for_url("https://gist.github.com/some/gist")
+            .unwrap(),
+        vec![by_name, by_field]
+    );
+}
+
+#[test]
+fn test_parsed_entry_edit_fields() {
+    let original = "hunter2\r\n# comment\n  User:   john\r\nunknown line\nurl: https://example.com";
+    let mut entry: ParsedEntry = original.parse().unwrap();
+
+    entry.set_field("user", "jane").unwrap();
+    assert_eq!(
+        entry.content(),
+        "hunter2\r\n# comment\n  User: jane\r\nunknown line\nurl: https://example.com"
+    );
+
+    entry.set_field("email", "jane@example.com").unwrap();
+    assert_eq!(
+        entry.content(),
+        "hunter2\r\n# comment\n  User: jane\r\nunknown line\nurl: https://example.com\r\nemail: jane@example.com\r\n"
+    );
+
+    assert_eq!(
+        entry
+            .remove_field("EMAIL")
+            .as_ref()
+            .map(SecretString::expose),
+        Some("jane@example.com")
+    );
+    assert!(entry.remove_field("email").is_none());
+    entry.set_field("user", "john").unwrap();
+    assert_eq!(
+        entry.remove_field("url").as_ref().map(SecretString::expose),
+        Some("https://example.com")
+    );
+    assert_eq!(
+        entry.content(),
+        "hunter2\r\n# comment\n  User: john\r\nunknown line\n"
+    );
+
+    // the password line is never interpreted as field
+    let mut entry: ParsedEntry = "user: x\n".parse().unwrap();
+    entry.set_field("user", "john").unwrap();
+    assert_eq!(entry.content(), "user: x\nuser: john\n");
+    let mut entry = ParsedEntry::default();
+    entry.set_field("user", "john").unwrap();
+    assert_eq!(entry.password(), "");
+
+    assert!(matches!(
+        entry.set_field("user name", "john"),
+        Err(PassError::InvalidField { .. })
+    ));
+    assert!(matches!(
+        entry.set_field("user", "jo\nhn"),
+        Err(PassError::InvalidField { .. })
+    ));
+}
+
+#[test]
+fn test_store_write_entry() {
+    let (_dir, store) = temp_store();
+    let file = store.insert("edited", b"hunter2\nuser: john\n").unwrap();
+
+    let mut entry = file.parse().unwrap();
+    entry.set_field("url", "https://example.com").unwrap();
+    store.write_entry(&file, &entry).unwrap();
+
+    assert_eq!(file.parse().unwrap(), entry);
+}
+
+#[test]
+fn test_entry_diff() {
+    let old: ParsedEntry = "hunter2\nuser: john\nurl: https://example.com\nsome notes\n"
+        .parse()
+        .unwrap();
+    assert!(diff::diff(&old, &old).is_empty());
+
+    let new: ParsedEntry = "hunter3\nUser: jane\nemail: jane@example.com\nsome notes\n"
+        .parse()
+        .unwrap();
+    let changes = diff::diff(&old, &new);
+    assert_eq!(
+        changes.to_string(),
+        "password changed, User changed, email added, url removed"
+    );
+    assert!(!format!("{:?}", changes).contains("hunter"));
+    assert!(!format!("{:?}", changes).contains("jane"));
+
+    let changes = diff::diff_with_values(&old, &new);
+    assert_eq!(
+        changes.changes[1],
+        diff::Change::FieldChanged {
+            key: "User".to_string(),
+            old: Some("john".to_string()),
+            new: Some("jane".to_string()),
+        }
+    );
+    assert!(!format!("{:?}", changes).contains("hunter"));
+
+    let new: ParsedEntry = "hunter2\nurl: https://example.com\nuser: john\nother notes\n"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        diff::diff(&old, &new).changes,
+        vec![diff::Change::OtherContentChanged]
+    );
+}
+
+#[cfg(feature = "git")]
+fn git(dir: &Path, args: &[&str]) {
+    let output = std::process::Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_git_sync_resolves_conflicts() {
+    use crate::git::ConflictResolution;
+
+    // a remote which a local store and another clone are synchronized with
+    let remote = tempfile::tempdir().unwrap();
+    git(remote.path(), &["init", "--bare", "-b", "main"]);
+    let (_dir, store) = temp_store();
+    git(store.root(), &["init", "-b", "main"]);
+    git(store.root(), &["config", "user.name", "test"]);
+    git(store.root(), &["config", "user.email", "test@example.com"]);
+    git(store.root(), &["add", "-A"]);
+    git(store.root(), &["commit", "-m", "initial"]);
+    git(
+        store.root(),
+        &["remote", "add", "origin", remote.path().to_str().unwrap()],
+    );
+    git(store.root(), &["push", "-u", "origin", "main"]);
+    let other = tempfile::tempdir().unwrap();
+    git(
+        other.path(),
+        &["clone", remote.path().to_str().unwrap(), "."],
+    );
+
+    // conflicting changes on both sides
+    fs::write(other.path().join("secret-a.gpg"), b"remote").unwrap();
+    git(other.path(), &["commit", "-am", "remote change"]);
+    git(other.path(), &["push"]);
+    fs::write(store.root().join("secret-a.gpg"), b"local").unwrap();
+    git(store.root(), &["commit", "-am", "local change"]);
+
+    let mut conflicts = Vec::new();
+    let report = store
+        .git_sync(|conflict| {
+            conflicts.push(conflict.clone());
+            ConflictResolution::KeepOurs
+        })
+        .unwrap();
+
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].pass_name, "secret-a");
+    assert_eq!(conflicts[0].ours.as_deref(), Some(&b"local"[..]));
+    assert_eq!(conflicts[0].theirs.as_deref(), Some(&b"remote"[..]));
+    assert_eq!(report.resolved[0].resolution, ConflictResolution::KeepOurs);
+    assert!(report.pushed);
+    assert_eq!(
+        fs::read(store.root().join("secret-a.gpg")).unwrap(),
+        b"local"
+    );
+
+    // the other clone receives the resolution
+    git(other.path(), &["pull"]);
+    assert_eq!(
+        fs::read(other.path().join("secret-a.gpg")).unwrap(),
+        b"local"
+    );
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_provenance() {
+    let (_dir, store) = temp_store();
+    git(store.root(), &["init", "-b", "main"]);
+    git(store.root(), &["add", "-A"]);
+    git(store.root(), &["commit", "-m", "initial"]);
+    fs::write(store.root().join("secret-a.gpg"), b"changed").unwrap();
+    git(
+        store.root(),
+        &["-c", "user.name=other", "commit", "-am", "change secret-a"],
+    );
+
+    let provenance = store
+        .retrieve_file("secret-a")
+        .unwrap()
+        .provenance()
+        .unwrap();
+    let created = provenance.created.unwrap();
+    let last_changed = provenance.last_changed.unwrap();
+    assert_eq!(
+        (created.author.as_str(), created.summary.as_str()),
+        ("test", "initial")
+    );
+    assert_eq!(
+        (last_changed.author.as_str(), last_changed.summary.as_str()),
+        ("other", "change secret-a")
+    );
+    assert_eq!(last_changed.email, "test@example.com");
+    assert!(last_changed.time >= created.time);
+
+    let provenance = store
+        .retrieve_file("folder/subsecret-a")
+        .unwrap()
+        .provenance()
+        .unwrap();
+    assert_eq!(provenance.created, provenance.last_changed);
+    assert_eq!(provenance.created.unwrap().summary, "initial");
+
+    // entries which were never committed have no provenance
+    fs::copy(
+        store.root().join("secret-b.gpg"),
+        store.root().join("secret-c.gpg"),
+    )
+    .unwrap();
+    let provenance = store
+        .retrieve_file("secret-c")
+        .unwrap()
+        .provenance()
+        .unwrap();
+    assert_eq!(provenance, git::Provenance::default());
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_list_at() {
+    let (_dir, store) = temp_store();
+    git(store.root(), &["init", "-b", "main"]);
+    git(store.root(), &["add", "-A"]);
+    git(store.root(), &["commit", "-m", "initial"]);
+    git(store.root(), &["tag", "initial"]);
+    git(store.root(), &["rm", "-q", "secret-a.gpg"]);
+    fs::write(store.root().join("folder/subsecret-a.gpg"), b"changed").unwrap();
+    git(store.root(), &["commit", "-am", "remove secret-a"]);
+
+    let listing = store.list_at("initial").unwrap();
+    assert_eq!(listing.revision.summary, "initial");
+    assert_eq!(listing.entries.len(), 7);
+    assert!(listing.entry("/folder//subfolder/generated-b").is_some());
+    // removed entries can still be decrypted
+    let removed = listing.entry("secret-a").unwrap();
+    assert_eq!(store.plain_io_at(removed).unwrap().as_ref(), b"foobar123\n");
+    assert_eq!(
+        listing.entry("folder/subsecret-a").unwrap().size,
+        fs::metadata("tests/simple/folder/subsecret-a.gpg")
+            .unwrap()
+            .len()
+    );
+
+    let listing = store.list_at("HEAD").unwrap();
+    assert_eq!(listing.entries.len(), 6);
+    assert!(listing.entry("secret-a").is_none());
+    assert_eq!(listing.entry("folder/subsecret-a").unwrap().size, 7);
+    assert!(store.list_at("does-not-exist").is_err());
+
+    let outside = git::PastEntry {
+        pass_name: "../outside".to_string(),
+        ..removed.clone()
+    };
+    assert!(matches!(
+        store.plain_io_at(&outside),
+        Err(PassError::InvalidPassName(..))
+    ));
+}
+
+#[test]
+fn test_gpg_id_unsigned() {
+    assert_eq!(
+        GpgIdFile::verify_signature("tests/simple/.gpg-id").unwrap(),
+        SignatureStatus::Unsigned
+    );
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_clone_from() {
+    use crate::git::CloneOptions;
+
+    let (_dir, store) = temp_store();
+    git(store.root(), &["init", "-b", "main"]);
+    git(store.root(), &["add", "-A"]);
+    git(store.root(), &["commit", "-m", "initial"]);
+    let url = store.root().to_str().unwrap();
+
+    let target = tempfile::tempdir().unwrap();
+    let path = target.path().join("clone");
+    let clone = Store::clone_from(
+        url,
+        &path,
+        &CloneOptions::default()
+            .with_depth(1)
+            .with_gpg_id_verification(false),
+    )
+    .unwrap();
+    assert_eq!(clone.list().unwrap().len(), store.list().unwrap().len());
+
+    // verification requires signing keys which are identified by their full fingerprint
+    let path = target.path().join("rejected");
+    for options in [
+        CloneOptions::default(),
+        CloneOptions::default().with_signing_keys(["8497251104B6F45F"]),
+        CloneOptions::default().with_signing_keys([""]),
+    ] {
+        assert!(matches!(
+            Store::clone_from(url, &path, &options),
+            Err(PassError::InvalidConfig { .. })
+        ));
+        assert!(!path.exists());
+    }
+
+    // unsigned .gpg-id files are rejected and the clone is removed
+    assert!(matches!(
+        Store::clone_from(
+            url,
+            &path,
+            &CloneOptions::default()
+                .with_signing_keys(["4837F49E22F425EA91725C428497251104B6F45F"])
+        ),
+        Err(PassError::InvalidGpgIdSignature { .. })
+    ));
+    assert!(!path.exists());
+
+    // a rejected clone into a directory which existed but was empty leaves that directory empty
+    fs::create_dir(&path).unwrap();
+    assert!(matches!(
+        Store::clone_from(
+            url,
+            &path,
+            &CloneOptions::default()
+                .with_signing_keys(["4837F49E22F425EA91725C428497251104B6F45F"])
+        ),
+        Err(PassError::InvalidGpgIdSignature { .. })
+    ));
+    assert_eq!(fs::read_dir(&path).unwrap().count(), 0);
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_submodules() {
+    use crate::git::CloneOptions;
+
+    // a shared repository which is mounted into the store as submodule
+    let (_shared_dir, shared) = temp_store();
+    git(shared.root(), &["init", "-b", "main"]);
+    git(shared.root(), &["add", "-A"]);
+    git(shared.root(), &["commit", "-m", "shared"]);
+    let (_dir, store) = temp_store();
+    git(store.root(), &["init", "-b", "main"]);
+    git(
+        store.root(),
+        &[
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "add",
+            shared.root().to_str().unwrap(),
+            "team",
+        ],
+    );
+    git(store.root(), &["add", "-A"]);
+    git(store.root(), &["commit", "-m", "initial"]);
+
+    let target = tempfile::tempdir().unwrap();
+    let clone = Store::clone_from(
+        store.root().to_str().unwrap(),
+        target.path().join("clone"),
+        &CloneOptions::default().with_gpg_id_verification(false),
+    )
+    .unwrap();
+
+    // entries of the submodule are listed but its repository is not
+    let entry = match clone.retrieve("team/secret-a").unwrap() {
+        StoreEntry::File(file) => file,
+        StoreEntry::Directory(_) => panic!("expected a file"),
+    };
+    assert!(clone.retrieve("team/.git").is_err());
+    assert!(clone.list().unwrap().iter().all(|entry| {
+        let path = match entry {
+            StoreEntry::File(file) => &file.path,
+            StoreEntry::Directory(dir) => &dir.path,
+        };
+        !path.components().any(|c| c.as_os_str() == ".git")
+    }));
+    assert_eq!(
+        clone.repository_of(&entry).unwrap(),
+        clone.root().join("team")
+    );
+    assert_eq!(
+        clone.update_submodules().unwrap(),
+        vec![clone.root().join("team")]
+    );
+    assert!(!clone.fsck().unwrap().issues.iter().any(|issue| {
+        issue.kind == fsck::FsckIssueKind::OrphanFile
+            && (issue.path.ends_with(".git") || issue.path.ends_with(".gitmodules"))
+    }));
+}
+
+#[test]
+fn test_config_from_lookup() {
+    let vars = HashMap::from([
+        ("PASSWORD_STORE_DIR", "tests/simple"),
+        ("PASSWORD_STORE_CLIP_TIME", "10"),
+        ("PASSWORD_STORE_UMASK", "027"),
+        ("PASSWORD_STORE_KEY", "8497251104B6F45F 0x1234567890ABCDEF"),
+        ("PASSWORD_STORE_ENABLE_EXTENSIONS", "true"),
+        ("PASSWORD_STORE_CHARACTER_SET", ""),
+    ]);
+    let config = Config::from_lookup(|var| vars.get(var).map(|value| value.to_string())).unwrap();
+
+    assert_eq!(config.store_dir().unwrap(), Path::new("tests/simple"));
+    assert_eq!(config.clip_time(), std::time::Duration::from_secs(10));
+    assert_eq!(config.umask(), 0o027);
+    assert_eq!(config.keys(), ["8497251104B6F45F", "0x1234567890ABCDEF"]);
+    assert!(config.extensions_enabled());
+    assert_eq!(
+        config.extensions_dir().unwrap(),
+        Path::new("tests/simple/.extensions")
+    );
+    // empty and unset variables use the defaults
+    assert_eq!(config.character_set(), "[:punct:][:alnum:]");
+    assert_eq!(config.generated_length(), 25);
+
+    // overrides take precedence
+    assert_eq!(config.with_generated_length(8).generated_length(), 8);
+
+    for (var, value) in [
+        ("PASSWORD_STORE_UMASK", "999"),
+        ("PASSWORD_STORE_CLIP_TIME", "soon"),
+        ("PASSWORD_STORE_GENERATED_LENGTH", "0"),
+    ] {
+        assert!(matches!(
+            Config::from_lookup(|v| (v == var).then(|| value.to_string())),
+            Err(PassError::InvalidConfig { .. })
+        ));
+    }
+
+    // the environment keeps the defaults of invalid variables instead of failing as a whole
+    let (config, invalid) = Config::parse(|var| match var {
+        "PASSWORD_STORE_CLIP_TIME" => Some("soon".to_string()),
+        "PASSWORD_STORE_UMASK" => Some("027".to_string()),
+        _ => None,
+    });
+    assert_eq!(config.umask(), 0o027);
+    assert_eq!(config.clip_time(), Config::default().clip_time());
+    assert!(matches!(invalid[..], [PassError::InvalidConfig { .. }]));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_insert_respects_umask() {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+    let (_dir, store) = temp_store();
+    let file = store.insert("private/entry", b"hunter2\n").unwrap();
+    assert_eq!(mode(&file.path), 0o600);
+    assert_eq!(mode(&store.root().join("private")), 0o700);
+
+    let store = store.with_config(Config::default().with_umask(0o027));
+    let file = store.insert("shared/nested/entry", b"hunter2\n").unwrap();
+    assert_eq!(mode(&file.path), 0o640);
+    assert_eq!(mode(&store.root().join("shared")), 0o750);
+    assert_eq!(mode(&store.root().join("shared/nested")), 0o750);
+
+    // replacing content keeps the permissions of the existing file
+    fs::set_permissions(&file.path, fs::Permissions::from_mode(0o604)).unwrap();
+    let ciphertext = fs::read(store.root().join("secret-a.gpg")).unwrap();
+    file.cipher_io()
+        .unwrap()
+        .replace_ciphertext(&ciphertext[..])
+        .unwrap();
+    assert_eq!(mode(&file.path), 0o604);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_write_atomically_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+    let dir = tempfile::tempdir().unwrap();
+    let nested = dir.path().join("a/b");
+    utils::create_dir_all(&nested, 0o022).unwrap();
+    assert_eq!(mode(&dir.path().join("a")), 0o755);
+    assert_eq!(mode(&nested), 0o755);
+
+    let path = nested.join("file");
+    utils::write_atomically(&path, b"foo", 0o077).unwrap();
+    assert_eq!(mode(&path), 0o600);
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+    utils::write_atomically(&path, b"bar", 0o077).unwrap();
+    assert_eq!(mode(&path), 0o640);
+    assert_eq!(fs::read(&path).unwrap(), b"bar");
+
+    // the content is never written into a file that is more accessible than the result
+    let path = nested.join("other");
+    utils::write_atomically_with(&path, 0o077, |file| -> std::io::Result<()> {
+        assert_eq!(file.metadata()?.permissions().mode() & 0o777, 0o600);
+        file.write_all(b"baz")
+    })
+    .unwrap();
+    assert_eq!(mode(&path), 0o600);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_strict_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (_dir, store) = temp_store();
+    fs::set_permissions(store.root(), fs::Permissions::from_mode(0o700)).unwrap();
+    let store = store.with_strict_permissions(true);
+    for path in [".gpg-id", "secret-a.gpg", "folder/subsecret-a.gpg"] {
+        fs::set_permissions(store.root().join(path), fs::Permissions::from_mode(0o600)).unwrap();
+    }
+    fs::set_permissions(
+        store.root().join("folder"),
+        fs::Permissions::from_mode(0o755),
+    )
+    .unwrap();
+    store.retrieve("secret-a").unwrap();
+
+    // a world-writable entry is only rejected when it is used
+    fs::set_permissions(
+        store.root().join("folder/subsecret-a.gpg"),
+        fs::Permissions::from_mode(0o666),
+    )
+    .unwrap();
+    store.retrieve("secret-a").unwrap();
+    assert!(matches!(
+        store.retrieve("folder/subsecret-a"),
+        Err(PassError::InsecurePermissions { .. })
+    ));
+    assert!(matches!(
+        store.check_permissions(),
+        Err(PassError::InsecurePermissions { .. })
+    ));
+
+    // a group-writable .gpg-id affects all entries
+    fs::set_permissions(
+        store.root().join("folder/subsecret-a.gpg"),
+        fs::Permissions::from_mode(0o600),
+    )
+    .unwrap();
+    fs::set_permissions(
+        store.root().join(".gpg-id"),
+        fs::Permissions::from_mode(0o620),
+    )
+    .unwrap();
+    assert!(matches!(
+        store.retrieve("secret-a"),
+        Err(PassError::InsecurePermissions { .. })
+    ));
+    assert!(store
+        .clone()
+        .with_strict_permissions(false)
+        .retrieve("secret-a")
+        .is_ok());
+}
+
+#[cfg(feature = "secure-mem")]
+#[test]
+fn test_secure_mem_release() {
+    use crate::secure_mem::{self, LockedRegion};
+
+    let mut buffer = b"hunter2".to_vec();
+    let mut region = LockedRegion::default();
+    region.lock(&buffer);
+    // locking a reallocated buffer moves the lock
+    buffer.reserve(4096);
+    region.lock(&buffer);
+
+    secure_mem::release(&mut buffer, &mut region);
+    assert!(buffer.is_empty());
+
+    // growing moves the content into a new allocation which is locked instead
+    let mut buffer = b"hunter2".to_vec();
+    region.lock(&buffer);
+    secure_mem::reserve(&mut buffer, &mut region, 4096);
+    assert_eq!(buffer, b"hunter2");
+    assert!(buffer.capacity() >= 4096 + 7);
+    secure_mem::release(&mut buffer, &mut region);
+
+    let mut text = String::new();
+    for _ in 0..100 {
+        secure_mem::push_str(&mut text, "hunter2");
+    }
+    assert_eq!(text, "hunter2".repeat(100));
+}
+
+#[test]
+fn test_constant_time_eq() {
+    use crate::secure::constant_time_eq;
+
+    assert!(constant_time_eq(b"", b""));
+    assert!(constant_time_eq(b"hunter2", b"hunter2"));
+    assert!(!constant_time_eq(b"hunter2", b"Hunter2"));
+    assert!(!constant_time_eq(b"hunter2", b"hunter"));
+    assert!(!constant_time_eq(b"", b"a"));
+}
+
+#[test]
+fn test_verify_password() {
+    set_store_dir();
+    let entry = retrieve_file("secret-a");
+    assert!(secure::verify_password(&entry, "foobar123").unwrap());
+    assert!(!secure::verify_password(&entry, "foobar123\n").unwrap());
+    assert!(!secure::verify_password(&entry, "foobar").unwrap());
+}
+
+#[test]
+fn test_access_logger() {
+    use crate::audit::{AccessEvent, AccessOperation};
+    use std::sync::{Arc, Mutex};
+
+    let (_dir, store) = temp_store();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut store = store.with_access_context("test suite");
+    let recorded = events.clone();
+    store
+        .set_access_logger(move |event: &AccessEvent| recorded.lock().unwrap().push(event.clone()));
+
+    store.remove("folder/subsecret-a").unwrap();
+    assert!(matches!(
+        store.remove("folder/subsecret-a"),
+        Err(PassError::EntryNotFound(_))
+    ));
+    assert!(store.remove("folder").is_err());
+    assert!(store.root().join("folder").is_dir());
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].pass_name, "folder/subsecret-a");
+    assert_eq!(events[0].operation, AccessOperation::Delete);
+    assert_eq!(events[0].context.as_deref(), Some("test suite"));
+}
+
+#[test]
+fn test_access_logger_decrypt() {
+    use crate::audit::{AccessEvent, AccessOperation};
+    use std::sync::{Arc, Mutex};
+
+    let (_dir, mut store) = temp_store();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    store.set_access_logger(move |event: &AccessEvent| {
+        recorded
+            .lock()
+            .unwrap()
+            .push((event.pass_name.clone(), event.operation))
+    });
+
+    let file = match store.retrieve("secret-a").unwrap() {
+        StoreEntry::File(file) => file,
+        StoreEntry::Directory(_) => panic!("secret-a is not a file"),
+    };
+    let mut plain = store.plain_io_rw(&file).unwrap();
+    plain.as_mut().extend_from_slice(b"user: john\n");
+    plain.close().unwrap();
+    store.insert("new", b"hunter2\n").unwrap();
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            ("secret-a".to_string(), AccessOperation::Decrypt),
+            ("secret-a".to_string(), AccessOperation::Write),
+            ("new".to_string(), AccessOperation::Write),
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "mmap")]
+fn test_mmap_ciphertext() {
+    use crate::file_io::Ciphertext;
+
+    set_store_dir();
+    let file = retrieve_file("secret-a");
+    let size = fs::metadata(&file.path).unwrap().len();
+    assert!(matches!(
+        Ciphertext::load(&file.path, Some(size)).unwrap(),
+        Ciphertext::Mapped(_)
+    ));
+    assert!(matches!(
+        Ciphertext::load(&file.path, Some(size + 1)).unwrap(),
+        Ciphertext::Read(_)
+    ));
+    assert_eq!(
+        &*Ciphertext::load(&file.path, Some(0)).unwrap(),
+        fs::read(&file.path).unwrap().as_slice()
+    );
+
+    let store = Store::from_env().unwrap().with_mmap_threshold(0);
+    assert_eq!(store.mmap_threshold(), Some(0));
+    assert_eq!(store.decrypt(&file).unwrap(), b"foobar123\n");
+}
+
+#[test]
+fn test_gpg_context_pool() {
+    use crate::context_pool::ContextPool;
+
+    let pool = ContextPool::new(1);
+    {
+        let _first = pool.get(false, None).unwrap();
+        let _second = pool.get(false, None).unwrap();
+        assert_eq!(pool.idle(), 0);
+    }
+    // only as many contexts as the pool size are kept
+    assert_eq!(pool.idle(), 1);
+    // reused contexts are configured again
+    let reused = pool.get(true, None).unwrap();
+    assert!(reused.offline());
+    assert_eq!(pool.idle(), 0);
+    drop(reused);
+    assert_eq!(pool.clone().idle(), 1);
+
+    let unpooled = ContextPool::new(0);
+    drop(unpooled.get(false, None).unwrap());
+    assert_eq!(unpooled.idle(), 0);
+
+    set_store_dir();
+    let store = Store::from_env().unwrap();
+    assert_eq!(store.gpg_context_pool_size(), 4);
+    let store = store.with_gpg_context_pool_size(0);
+    assert_eq!(store.gpg_context_pool_size(), 0);
+}
+
+#[test]
+fn test_rate_limit() {
+    use crate::rate_limit::RateLimit;
+    use std::time::Duration;
+
+    set_store_dir();
+    let store = Store::from_env()
+        .unwrap()
+        .with_rate_limit(RateLimit::per_entry(2, Duration::from_secs(60)))
+        .with_rate_limit(RateLimit::global(3, Duration::from_millis(200)));
+    let secret_a = retrieve_file("secret-a");
+    let secret_b = retrieve_file("secret-b");
+
+    store.acquire_decryption(&secret_a).unwrap();
+    // clones are limited together
+    store.clone().acquire_decryption(&secret_a).unwrap();
+    match store.acquire_decryption(&secret_a) {
+        Err(PassError::RateLimited {
+            pass_name,
+            retry_after,
+        }) => {
+            assert_eq!(pass_name, "secret-a");
+            assert!(retry_after > Duration::from_secs(50));
+        }
+        other => panic!("expected rate limit, got {:?}", other),
+    }
+
+    store.acquire_decryption(&secret_b).unwrap();
+    assert!(matches!(
+        store.acquire_decryption(&secret_b),
+        Err(PassError::RateLimited { .. })
+    ));
+    std::thread::sleep(Duration::from_millis(250));
+    store.acquire_decryption(&secret_b).unwrap();
+}
+
+#[cfg(all(feature = "agent", unix))]
+#[test]
+fn test_agent() {
+    use crate::agent::{self, Agent, AgentClient};
+    use std::time::Duration;
+
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("agent").join("agent.sock");
+    let agent = Agent::bind(&socket, Duration::from_millis(300)).unwrap();
+    std::thread::spawn(move || agent.serve());
+
+    let client = AgentClient::new(&socket);
+    assert_eq!(client.get("a").unwrap(), None);
+    client.put("a", b"hunter2").unwrap();
+    assert_eq!(client.get("a").unwrap(), Some(b"hunter2".to_vec()));
+    client.clear().unwrap();
+    assert_eq!(client.get("a").unwrap(), None);
+
+    // entries are served from the agent without decrypting them
+    set_store_dir();
+    let store = Store::open_read_only(password_store_dir().unwrap())
+        .unwrap()
+        .with_agent(&socket);
+    let file = retrieve_file("secret-a");
+    let key = agent::cache_key(&file.path, &fs::read(&file.path).unwrap());
+    client.put(&key, b"cached\n").unwrap();
+    assert_eq!(store.plain_io(&file).unwrap().as_ref(), b"cached\n");
+
+    std::thread::sleep(Duration::from_millis(400));
+    assert_eq!(client.get(&key).unwrap(), None);
+    assert_eq!(key.len(), file.path.display().to_string().len() + 65);
+
+    // the socket is only accessible by the current user
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // connections beyond the limit are closed right away
+    let idle = (0..16)
+        .map(|_| std::os::unix::net::UnixStream::connect(&socket).unwrap())
+        .collect::<Vec<_>>();
+    // connections of earlier requests may still be counted for a moment
+    let refused = (0..50).any(|_| {
+        std::thread::sleep(Duration::from_millis(20));
+        client.get("a").is_err()
+    });
+    assert!(refused);
+    drop(idle);
+    // closed connections are only noticed by the agent after a moment
+    let reconnected = (0..50).any(|_| {
+        std::thread::sleep(Duration::from_millis(20));
+        client.get("a").is_ok_and(|value| value.is_none())
+    });
+    assert!(reconnected);
+}
+
+#[test]
+fn test_warm_up() {
+    let dir = tempfile::tempdir().unwrap();
+    let empty = Store::open(dir.path()).unwrap();
+    assert!(empty.passphrase_cached().unwrap());
+    empty.warm_up().unwrap();
+
+    set_store_dir();
+    Store::from_env().unwrap().warm_up().unwrap();
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_store_fixture() {
+    use crate::testing::{StoreFixture, TEST_KEY_FINGERPRINT};
+
+    let fixture = StoreFixture::new()
+        .with_entry("secret", "hunter2\n")
+        .with_entry_for("team/other", "s3cret\n", [TEST_KEY_FINGERPRINT])
+        .with_gpg_id("team", [TEST_KEY_FINGERPRINT])
+        .build()
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(fixture.path().join("team/.gpg-id")).unwrap(),
+        format!("{}\n", TEST_KEY_FINGERPRINT)
+    );
+    assert_eq!(fixture.store().list().unwrap().len(), 2);
+    assert_eq!(fixture.store().gpg_home(), Some(fixture.gpg_home()));
+    assert_eq!(
+        fixture
+            .store()
+            .retrieve_entry("team/other")
+            .unwrap()
+            .password(),
+        "s3cret"
+    );
+
+    assert!(matches!(
+        StoreFixture::new()
+            .with_gpg_id("../outside", ["key"])
+            .build(),
+        Err(PassError::InvalidPassName(..))
+    ));
+}
+
+#[test]
+fn test_alias() {
+    let (_dir, store) = temp_store();
+    let target = store.insert("services/new-name", b"hunter2\n").unwrap();
+    let alias = store
+        .create_alias("services/old-name", "services/new-name")
+        .unwrap();
+    assert_eq!(
+        alias.plain_io_ro().unwrap().as_ref(),
+        b"@alias: services/new-name\n"
+    );
+    assert!(matches!(
+        store.create_alias("services/other", "services"),
+        Err(PassError::InvalidPassName(..))
+    ));
+    assert!(matches!(
+        store.create_alias("services/other", "missing"),
+        Err(PassError::EntryNotFound(..))
+    ));
+
+    // aliases are only resolved if enabled
+    let retrieve_file = |store: &Store, name| match store.retrieve(name).unwrap() {
+        StoreEntry::File(file) => file,
+        StoreEntry::Directory(_) => panic!("expected a file"),
+    };
+    assert_eq!(retrieve_file(&store, "services/old-name"), alias);
+    let resolving = store.clone().with_alias_resolution(true);
+    assert_eq!(retrieve_file(&resolving, "services/old-name"), target);
+    store
+        .create_alias("older-name", "services/old-name")
+        .unwrap();
+    assert_eq!(retrieve_file(&resolving, "older-name"), target);
+
+    // loops are detected
+    store.create_alias("loop-a", "secret-a").unwrap();
+    store.create_alias("loop-b", "loop-a").unwrap();
+    store.remove("loop-a").unwrap();
+    store.create_alias("loop-a", "loop-b").unwrap();
+    match resolving.retrieve("loop-a") {
+        Err(PassError::AliasLoop(chain)) => assert_eq!(chain, ["loop-a", "loop-b", "loop-a"]),
+        other => panic!("expected an alias loop, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parsed_entry_tags() {
+    let mut entry: ParsedEntry = "hunter2\nuser: john\ntags: work,  email  personal\n"
+        .parse()
+        .unwrap();
+    assert_eq!(entry.tags(), ["work", "email", "personal"]);
+    assert!(entry.has_tag("email"));
+    assert!(!entry.add_tag("work").unwrap());
+    assert!(entry.add_tag("mail").unwrap());
+    assert!(entry.add_tag("two words").is_err());
+    assert_eq!(
+        entry.content(),
+        "hunter2\nuser: john\ntags: work, email, personal, mail\n"
+    );
+
+    assert!(entry.remove_tag("email"));
+    assert!(!entry.remove_tag("email"));
+    for tag in ["work", "personal", "mail"] {
+        entry.remove_tag(tag);
+    }
+    assert_eq!(entry.content(), "hunter2\nuser: john\n");
+
+    let mut untagged: ParsedEntry = "hunter2".parse().unwrap();
+    assert!(untagged.tags().is_empty());
+    untagged.add_tag("work").unwrap();
+    assert_eq!(untagged.content(), "hunter2\ntags: work\n");
+}
+
+#[test]
+fn test_tag_index() {
+    let (_dir, store) = temp_store();
+    let mail = store
+        .insert("mail", b"hunter2\ntags: work, email\n")
+        .unwrap();
+    let chat = store.insert("chat", b"hunter2\ntags: work\n").unwrap();
+
+    let index = store.tag_index().unwrap();
+    assert_eq!(index.tags().collect::<Vec<_>>(), ["email", "work"]);
+    assert_eq!(index.entries_with_tag("email"), std::slice::from_ref(&mail));
+    assert_eq!(index.entries_with_tag("work"), [chat.clone(), mail.clone()]);
+    assert!(index.entries_with_tag("missing").is_empty());
+
+    assert!(store.add_tag(&chat, "email").unwrap());
+    assert!(store.remove_tag(&mail, "email").unwrap());
+    assert_eq!(store.entries_with_tag("email").unwrap(), [chat]);
+}
+
+#[test]
+fn test_persistent_index() {
+    // the default location does not change between builds
+    if let Some(path) = index::default_path(Path::new("/store")) {
+        assert!(path.ends_with("libpass/index-ee30b1f5ac5da177"));
+    }
+
+    let (dir, store) = temp_store();
+    let index_path = dir.path().join("index");
+    let store = store.with_index(&index_path);
+
+    let index = store.index().unwrap();
+    let names = |index: &index::StoreIndex| {
+        index
+            .entries()
+            .iter()
+            .map(|entry| entry.name().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names(&index),
+        [
+            "folder/subfolder/generated-a",
+            "folder/subfolder/generated-b",
+            "folder/subsecret-a",
+            "folder/subsecret-b",
+            "folder2/subsecret-a",
+            "secret-a",
+            "secret-b"
+        ]
+    );
+    assert!(index_path.exists());
+    assert_eq!(index.get("folder/subsecret-a").unwrap().tags(), None);
+
+    // the persisted index is reused and changed directories are read again
+    let mut persisted = index::StoreIndex::load(&index_path);
+    assert_eq!(names(&persisted), names(&index));
+    fs::create_dir(store.root().join("folder/new")).unwrap();
+    fs::copy(
+        store.root().join("secret-a.gpg"),
+        store.root().join("folder/new/entry.gpg"),
+    )
+    .unwrap();
+    persisted = store.index().unwrap();
+    assert_eq!(persisted.len(), 8);
+    assert!(persisted.get("folder/new/entry").is_some());
+
+    // a corrupted index is rebuilt
+    fs::write(&index_path, "garbage").unwrap();
+    assert_eq!(store.index().unwrap().len(), 8);
+}
+
+#[test]
+fn test_persistent_index_tags() {
+    let (dir, store) = temp_store();
+    let store = store.with_index(dir.path().join("index"));
+    store.insert("tagged", b"hunter2\ntags: work\n").unwrap();
+
+    let index = store.index_with_tags().unwrap();
+    assert_eq!(
+        index.get("tagged").unwrap().tags(),
+        Some(["work".to_string()].as_slice())
+    );
+    assert_eq!(index.get("secret-a").unwrap().tags(), Some([].as_slice()));
+    // tags are persisted
+    assert_eq!(store.index().unwrap().entries_with_tag("work").len(), 1);
+}
+
+#[test]
+fn test_error_suggestion() {
+    assert_eq!(
+        Store::open("/does/not/exist").unwrap_err().suggestion(),
+        Some(Suggestion::RunPassInit)
+    );
+    assert_eq!(
+        PassError::EntryAlreadyExists("secret-a".to_string()).suggestion(),
+        Some(Suggestion::ChooseDifferentName)
+    );
+    assert_eq!(
+        PassError::from(gpgme::Error::CARD_NOT_PRESENT).suggestion(),
+        Some(Suggestion::InsertSmartcard)
+    );
+    assert_eq!(
+        PassError::from(gpgme::Error::NO_AGENT).suggestion(),
+        Some(Suggestion::StartGpgAgent)
+    );
+    assert_eq!(
+        PassError::EntryNotFound("secret-a".to_string()).suggestion(),
+        None
+    );
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn test_wire_format() {
+    use crate::wire::*;
+
+    set_store_dir();
+    let store = Store::from_env().unwrap();
+    let listing = Versioned::new(WireListing::from_store(&store).unwrap());
+    let json = serde_json::to_value(&listing).unwrap();
+    assert_eq!(json["version"], SCHEMA_VERSION);
+    assert_eq!(
+        json["data"]["entries"][0],
+        serde_json::json!({"name": "folder/subfolder/generated-a", "kind": "file"})
+    );
+    let decoded: Versioned<WireListing> = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded, listing);
+
+    let error = WireError::from(&store.retrieve("missing").unwrap_err());
+    assert_eq!(error.code, "entry_not_found");
+    let json = serde_json::to_string(&Versioned::new(error.clone())).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Versioned<WireError>>(&json)
+            .unwrap()
+            .into_data()
+            .unwrap(),
+        error
+    );
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn test_wire_format_forward_compatibility() {
+    use crate::wire::*;
+
+    // messages of newer versions may contain unknown fields and enum values
+    let listing: Versioned<WireListing> = serde_json::from_str(
+        r#"{"version": 1, "extra": true, "data": {"entries": [
+            {"name": "a", "kind": "file", "size": 42},
+            {"name": "b", "kind": "symlink"}
+        ], "total": 2}}"#,
+    )
+    .unwrap();
+    let entries = listing.into_data().unwrap().entries;
+    assert_eq!(entries[0].kind, WireEntryKind::File);
+    assert_eq!(entries[1].kind, WireEntryKind::Unknown);
+
+    let error: Versioned<WireError> = serde_json::from_str(
+        r#"{"version": 1, "data": {"code": "quantum_error", "message": "oops", "suggestion": "reboot"}}"#,
+    )
+    .unwrap();
+    assert_eq!(error.data.suggestion, None);
+    let error: Versioned<WireError> = serde_json::from_str(
+        r#"{"version": 1, "data": {"code": "pin_required", "message": "oops", "suggestion": "enter_pin"}}"#,
+    )
+    .unwrap();
+    assert_eq!(error.data.suggestion, Some(Suggestion::EnterPin));
+
+    // incompatible versions are rejected
+    let newer: Versioned<WireListing> =
+        serde_json::from_str(r#"{"version": 2, "data": {"entries": []}}"#).unwrap();
+    assert!(matches!(
+        newer.into_data(),
+        Err(PassError::UnsupportedSchemaVersion(2))
+    ));
+}
+
+#[test]
+fn test_insert_source() {
+    let (_dir, store) = temp_store();
+    let content = |file: &StoreFileRef| file.plain_io_ro().unwrap().as_ref().to_vec();
+
+    // like pass insert, a single line ends with exactly one newline and multi-line input is kept
+    let file = store
+        .insert("single", InsertSource::SingleLine("hunter2\r\n".into()))
+        .unwrap();
+    assert_eq!(content(&file), b"hunter2\n");
+    assert!(matches!(
+        store.insert(
+            "broken",
+            InsertSource::SingleLine("hunter2\nuser: john".into())
+        ),
+        Err(PassError::MalformedEntry(_))
+    ));
+    assert!(!store.root().join("broken.gpg").exists());
+    let file = store.insert("multi", b"hunter2\nuser: john").unwrap();
+    assert_eq!(content(&file), b"hunter2\nuser: john");
+    let file = store
+        .insert("reader", InsertSource::reader(&b"hunter2\n\n"[..]))
+        .unwrap();
+    assert_eq!(content(&file), b"hunter2\n\n");
+
+    let store = store.with_trailing_newline(TrailingNewline::Strip);
+    let file = store
+        .insert("stripped", InsertSource::reader(&b"hunter2\n\n"[..]))
+        .unwrap();
+    assert_eq!(content(&file), b"hunter2");
+    let store = store.with_trailing_newline(TrailingNewline::Ensure);
+    let file = store.insert("ensured", "hunter2").unwrap();
+    assert_eq!(content(&file), b"hunter2\n");
+
+    assert_eq!(
+        format!("{:?}", SecretString::from("hunter2")),
+        "SecretString(..)"
+    );
+}
+
+#[test]
+fn test_overwrite_policy() {
+    let (_dir, store) = temp_store();
+    let content = |file: &StoreFileRef| file.plain_io_ro().unwrap().as_ref().to_vec();
+
+    assert!(matches!(
+        store.insert("secret-a", b"new\n"),
+        Err(PassError::EntryAlreadyExists(_))
+    ));
+    let file = store
+        .insert_with("secret-a", b"new\n", OverwritePolicy::Overwrite)
+        .unwrap();
+    assert_eq!(content(&file), b"new\n");
+
+    let file = store
+        .insert_with("secret-a", b"newer\n", OverwritePolicy::KeepBoth)
+        .unwrap();
+    assert_eq!(store.relative_name(&file.path).unwrap(), "secret-a (2)");
+    let (file, password) = store
+        .generate(
+            "secret-a",
+            &generate::PasswordGenerator::from_config(store.config(), true).unwrap(),
+            OverwritePolicy::KeepBoth,
+        )
+        .unwrap();
+    assert_eq!(store.relative_name(&file.path).unwrap(), "secret-a (3)");
+    assert_eq!(
+        content(&file),
+        format!("{}\n", password.expose()).as_bytes()
+    );
+
+    // copies are placed into existing directories
+    let file = store
+        .copy("secret-a", "folder", OverwritePolicy::Error)
+        .unwrap();
+    assert_eq!(store.relative_name(&file.path).unwrap(), "folder/secret-a");
+    assert_eq!(content(&file), b"new\n");
+    assert!(matches!(
+        store.copy("secret-a", "folder/", OverwritePolicy::Error),
+        Err(PassError::EntryAlreadyExists(_))
+    ));
+    assert!(matches!(
+        store.copy("folder", "other", OverwritePolicy::Error),
+        Err(PassError::InvalidPassName(..))
+    ));
+}
+
+#[test]
+fn test_password_generator() {
+    use generate::PasswordGenerator;
+
+    let generator = PasswordGenerator::new("a-c[:digit:]\\-").unwrap();
+    assert_eq!(
+        generator.characters().unwrap(),
+        ['a', 'b', 'c', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '-']
+    );
+    let password = generator.with_length(64).generate().unwrap();
+    assert_eq!(password.expose().len(), 64);
+    assert!(password
+        .expose()
+        .chars()
+        .all(|c| "abc0123456789-".contains(c)));
+
+    let generator = PasswordGenerator::from_config(&Config::default(), false).unwrap();
+    assert_eq!(generator.length(), 25);
+    assert_eq!(generator.characters().unwrap().len(), 62);
+    for invalid in ["", "[:alnum:", "[:foo:]", "z-a"] {
+        assert!(matches!(
+            PasswordGenerator::new(invalid),
+            Err(PassError::InvalidConfig { .. })
+        ));
+    }
+}
+
+#[test]
+fn test_replace_password() {
+    let (_dir, store) = temp_store();
+    let content = |file: &StoreFileRef| file.plain_io_ro().unwrap().as_ref().to_vec();
+
+    let file = store.insert("with-newline", b"old\nuser: john\n").unwrap();
+    file.replace_password("new").unwrap();
+    assert_eq!(content(&file), b"new\nuser: john\n");
+    let file = store.insert("without-newline", b"old\nuser: john").unwrap();
+    store.replace_password(&file, "new").unwrap();
+    assert_eq!(content(&file), b"new\nuser: john");
+    let file = store.insert("password-only", b"old").unwrap();
+    file.replace_password("new").unwrap();
+    assert_eq!(content(&file), b"new\n");
+    assert!(matches!(
+        file.replace_password("new\nline"),
+        Err(PassError::InvalidField { .. })
+    ));
+}
+
+#[test]
+fn test_replace_first_line() {
+    use crate::store_entry::replace_first_line;
+
+    for (plaintext, expected) in [
+        (&b""[..], &b"new\n"[..]),
+        (b"old", b"new\n"),
+        (b"old\n", b"new\n"),
+        (b"old\r\nurl: example.com", b"new\nurl: example.com"),
+        (b"\nurl: example.com\n", b"new\nurl: example.com\n"),
+    ] {
+        let mut plaintext = plaintext.to_vec();
+        replace_first_line(&mut plaintext, "new").unwrap();
+        assert_eq!(plaintext, expected);
+    }
+}
+
+#[test]
+fn test_passphrase_generator() {
+    use generate::{PasswordGenerator, Wordlist};
+
+    let wordlist = Wordlist::parse("11111\tabacus\n11112\tabdomen\n\n11113\tabide\n").unwrap();
+    assert_eq!(wordlist.words(), ["abacus", "abdomen", "abide"]);
+    let generator = PasswordGenerator::passphrase(wordlist)
+        .with_length(4)
+        .with_separator(" ");
+    assert!(generator.characters().is_none());
+    let passphrase = generator.generate().unwrap();
+    let words = passphrase.expose().split(' ').collect::<Vec<_>>();
+    assert_eq!(words.len(), 4);
+    assert!(words
+        .iter()
+        .all(|word| ["abacus", "abdomen", "abide"].contains(word)));
+
+    assert!(matches!(
+        Wordlist::new(["word", "word"]),
+        Err(PassError::InvalidConfig { .. })
+    ));
+    assert!(matches!(
+        Wordlist::new(["two words", "word"]),
+        Err(PassError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn test_pronounceable_and_pattern_generator() {
+    use generate::PasswordGenerator;
+
+    let generator = PasswordGenerator::pronounceable().with_length(8);
+    let password = generator.generate().unwrap();
+    let password = password.expose().as_bytes();
+    assert_eq!(password.len(), 8);
+    assert!(password
+        .chunks(2)
+        .all(|syllable| !b"aeiou".contains(&syllable[0]) && b"aeiou".contains(&syllable[1])));
+
+    let generator = PasswordGenerator::pattern("AAaa99--\\A!").unwrap();
+    assert_eq!(generator.length(), 10);
+    let password = generator.generate().unwrap();
+    let password = password.expose().as_bytes();
+    assert!(password[..2].iter().all(u8::is_ascii_uppercase));
+    assert!(password[2..4].iter().all(u8::is_ascii_lowercase));
+    assert!(password[4..6].iter().all(u8::is_ascii_digit));
+    assert!(password[6..8].iter().all(u8::is_ascii_punctuation));
+    assert_eq!(&password[8..], b"A!");
+    assert!(
+        (generator.entropy_bits() - (4.0 * 26f64.log2() + 2.0 * 10f64.log2() + 2.0 * 32f64.log2()))
+            .abs()
+            < 1e-9
+    );
+    assert!(matches!(
+        PasswordGenerator::pattern("aa\\"),
+        Err(PassError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn test_password_policy() {
+    use policy::{CharacterClass, PasswordPolicy, PolicyViolation};
+
+    let policy = PasswordPolicy::default()
+        .with_min_length(8)
+        .with_required_class(CharacterClass::Uppercase)
+        .with_required_class(CharacterClass::Symbol)
+        .with_forbidden_substring("ACME");
+    assert!(policy.check("Correct-Horse").is_empty());
+    assert_eq!(
+        policy.check("acme123"),
+        [
+            PolicyViolation::TooShort {
+                min_length: 8,
+                length: 7
+            },
+            PolicyViolation::MissingClass(CharacterClass::Uppercase),
+            PolicyViolation::MissingClass(CharacterClass::Symbol),
+            PolicyViolation::ForbiddenSubstring("ACME".to_string()),
+        ]
+    );
+
+    let (_dir, store) = temp_store();
+    let policy = PasswordPolicy::default()
+        .with_min_length(10)
+        .with_max_age(std::time::Duration::from_secs(3600));
+    store.insert("weak", b"hunter2\nuser: john\n").unwrap();
+    store.insert("strong", b"correct-horse-battery\n").unwrap();
+    let reports = store.audit_against_policy(&policy).unwrap();
+    let violations = |name: &str| {
+        reports
+            .iter()
+            .find(|report| store.relative_name(&report.file.path).unwrap() == name)
+            .map(|report| report.violations.clone())
+    };
+    assert_eq!(
+        violations("weak").unwrap(),
+        [PolicyViolation::TooShort {
+            min_length: 10,
+            length: 7
+        }]
+    );
+    assert_eq!(violations("strong"), None);
+
+    // entries which are not text do not fail the whole audit
+    store.insert("binary", b"\xff\xfe\x00\n").unwrap();
+    let reports = Store::open_read_only(store.root())
+        .unwrap()
+        .audit_against_policy(&policy)
+        .unwrap();
+    let binary = reports
+        .iter()
+        .find(|report| store.relative_name(&report.file.path).unwrap() == "binary")
+        .unwrap();
+    assert!(binary.skipped);
+    assert!(binary.violations.is_empty());
+    assert!(
+        !reports
+            .iter()
+            .find(|report| store.relative_name(&report.file.path).unwrap() == "weak")
+            .unwrap()
+            .skipped
+    );
+}
+
+#[test]
+fn test_credentials() {
+    use credentials::{parse_certificate, parse_ssh_key, SshKeyFormat};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let key = parse_ssh_key(include_bytes!("../tests/credentials/id_ed25519")).unwrap();
+    assert_eq!(key.format, SshKeyFormat::OpenSsh);
+    assert_eq!(key.key_type.as_deref(), Some("ssh-ed25519"));
+    assert!(!key.encrypted);
+    assert!(key
+        .public_key
+        .unwrap()
+        .starts_with("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5"));
+    let key = parse_ssh_key(include_bytes!("../tests/credentials/id_ed25519_protected")).unwrap();
+    assert!(key.encrypted);
+    let key = parse_ssh_key(include_bytes!(
+        "../tests/credentials/rsa_legacy_protected.pem"
+    ))
+    .unwrap();
+    assert_eq!(key.format, SshKeyFormat::Pem);
+    assert_eq!(key.key_type.as_deref(), Some("ssh-rsa"));
+    assert!(key.encrypted);
+    assert!(parse_ssh_key(b"foobar123\n").is_err());
+
+    // the certificate may follow other content of the entry
+    let mut content = b"user: admin\n".to_vec();
+    content.extend_from_slice(include_bytes!("../tests/credentials/cert.pem"));
+    let certificate = parse_certificate(&content).unwrap();
+    assert_eq!(certificate.subject, "C=DE, O=Example Org, CN=example.com");
+    assert_eq!(certificate.issuer, certificate.subject);
+    assert_eq!(
+        certificate.not_before,
+        UNIX_EPOCH + Duration::from_secs(1792162790)
+    );
+    assert_eq!(
+        certificate.not_after,
+        UNIX_EPOCH + Duration::from_secs(2107522790)
+    );
+    assert!(certificate.is_expired_at(UNIX_EPOCH + Duration::from_secs(2107522791)));
+    assert!(parse_certificate(b"foobar123\n").is_err());
+
+    let (_dir, store) = temp_store();
+    let file = store
+        .insert(
+            "ssh/deploy",
+            include_bytes!("../tests/credentials/id_ed25519"),
+        )
+        .unwrap();
+    assert_eq!(
+        file.as_ssh_key().unwrap().key_type.as_deref(),
+        Some("ssh-ed25519")
+    );
+    assert!(matches!(
+        file.as_x509(),
+        Err(PassError::InvalidStoreFormat(..))
+    ));
+}
+
+#[test]
+fn test_env_export() {
+    let (_dir, store) = temp_store();
+    store
+        .insert("deploy/db-password", b"hunter2\nuser: app\n")
+        .unwrap();
+    store.insert("deploy/api.token", b"s3cr3t\n").unwrap();
+    store.insert("deploy/nested/ignored", b"nested\n").unwrap();
+    let dir = match store.retrieve("deploy").unwrap() {
+        StoreEntry::Directory(dir) => dir,
+        StoreEntry::File(_) => panic!("expected a directory"),
+    };
+
+    let variables = dir.as_env_map().unwrap();
+    assert_eq!(
+        variables.keys().collect::<Vec<_>>(),
+        ["API_TOKEN", "DB_PASSWORD"]
+    );
+    assert_eq!(variables["DB_PASSWORD"].expose(), "hunter2");
+
+    let output = dir
+        .spawn_with_env(
+            std::process::Command::new("sh")
+                .args(["-c", "echo $API_TOKEN"])
+                .stdout(std::process::Stdio::piped()),
+        )
+        .unwrap()
+        .wait_with_output()
+        .unwrap();
+    assert_eq!(output.stdout, b"s3cr3t\n");
+
+    store.insert("deploy/db_password", b"other\n").unwrap();
+    let dir = match store.retrieve("deploy").unwrap() {
+        StoreEntry::Directory(dir) => dir,
+        StoreEntry::File(_) => panic!("expected a directory"),
+    };
+    assert!(matches!(
+        dir.as_env_map(),
+        Err(PassError::InvalidStoreFormat(..))
+    ));
+}
+
+#[test]
+fn test_dotenv() {
+    let variables = export::parse_dotenv(
+        "# comment\n\nexport DB_HOST=db.example.com # primary\nDB_PASSWORD=\"hun\\\"ter $2\"\nTOKEN='a#b c'\n",
+    )
+    .unwrap();
+    let variables = variables
+        .iter()
+        .map(|(variable, value)| (variable.as_str(), value.expose()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        variables,
+        [
+            ("DB_HOST", "db.example.com"),
+            ("DB_PASSWORD", "hun\"ter $2"),
+            ("TOKEN", "a#b c")
+        ]
+    );
+    assert!(matches!(
+        export::parse_dotenv("NO_VALUE\n"),
+        Err(PassError::InvalidField { .. })
+    ));
+
+    let (_dir, store) = temp_store();
+    let files = store
+        .import_dotenv(
+            "app",
+            "DB_HOST=db.example.com\nDB_PASSWORD=\"hun\\\"ter $2\"\n",
+            OverwritePolicy::Error,
+        )
+        .unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[1].plain_io_ro().unwrap().as_ref(), b"hun\"ter $2\n");
+    let dir = match store.retrieve("app").unwrap() {
+        StoreEntry::Directory(dir) => dir,
+        StoreEntry::File(_) => panic!("expected a directory"),
+    };
+    let mut rendered = Vec::new();
+    dir.render_dotenv(&mut rendered).unwrap();
+    assert_eq!(
+        String::from_utf8(rendered).unwrap(),
+        "DB_HOST=db.example.com\nDB_PASSWORD=\"hun\\\"ter \\$2\"\n"
+    );
+}
+
+#[cfg(feature = "k8s")]
+#[test]
+fn test_k8s_secret() {
+    let mut secret = k8s::Secret::new("app", "default");
+    secret.insert("db-password", b"hunter2\n").unwrap();
+    assert!(matches!(
+        secret.insert("db-password", b"other\n"),
+        Err(PassError::InvalidField { .. })
+    ));
+    assert!(matches!(
+        secret.insert("db password", b"other\n"),
+        Err(PassError::InvalidField { .. })
+    ));
+    assert_eq!(secret.data["db-password"], "aHVudGVyMgo=");
+    let json: serde_json::Value = serde_json::from_str(&secret.to_json().unwrap()).unwrap();
+    assert_eq!(json["apiVersion"], "v1");
+    assert_eq!(json["type"], "Opaque");
+    assert_eq!(json["metadata"]["namespace"], "default");
+    assert!(secret
+        .to_yaml()
+        .unwrap()
+        .contains("db-password: aHVudGVyMgo="));
+
+    let (_dir, store) = temp_store();
+    let dir = match store.retrieve("folder").unwrap() {
+        StoreEntry::Directory(dir) => dir,
+        StoreEntry::File(_) => panic!("expected a directory"),
+    };
+    let secret = dir.to_k8s_secret("app", "default").unwrap();
+    assert_eq!(
+        secret.data.keys().collect::<Vec<_>>(),
+        ["subsecret-a", "subsecret-b"]
+    );
+}
+
+#[test]
+fn test_systemd_credentials() {
+    use std::os::unix::fs::PermissionsExt;
+    use systemd::{credential_name, read_credential, set_credential, write_credentials};
+
+    let (dir, store) = temp_store();
+    store
+        .insert("services/db", b"hunter2\nuser: app\n")
+        .unwrap();
+
+    let credentials_dir = dir.path().join("credentials");
+    let written = write_credentials(&store, &["services/db"], &credentials_dir).unwrap();
+    assert_eq!(written, [credentials_dir.join("services_db")]);
+    assert_eq!(
+        fs::metadata(&written[0]).unwrap().permissions().mode() & 0o777,
+        0o400
+    );
+    assert_eq!(
+        read_credential(Some(&credentials_dir), "/services//db").unwrap(),
+        Some(b"hunter2\nuser: app\n".to_vec())
+    );
+    assert_eq!(
+        read_credential(Some(&credentials_dir), "missing").unwrap(),
+        None
+    );
+    assert_eq!(read_credential(None, "services_db").unwrap(), None);
+
+    // names are mapped to file names inside of the credentials directory
+    assert_eq!(credential_name("services/./db/").unwrap(), "services_db");
+    for name in ["", "/", "..", "services/../../db"] {
+        assert!(matches!(
+            credential_name(name),
+            Err(PassError::InvalidPassName(..))
+        ));
+    }
+    assert!(matches!(
+        read_credential(Some(&credentials_dir), ".."),
+        Err(PassError::InvalidPassName(..))
+    ));
+    assert!(write_credentials(&store, &[".."], &credentials_dir).is_err());
+
+    assert_eq!(
+        set_credential("token", b"50%\\off\n\x01"),
+        "SetCredential=token:50%%\\\\off\\n\\x01"
+    );
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_http_server() {
+    use crate::http::HttpServer;
+
+    fn get(addr: std::net::SocketAddr, path: &str, token: &str) -> String {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\n\r\n",
+            path, token
+        )
+        .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    let store = Store::open_read_only(env::current_dir().unwrap().join("tests/simple")).unwrap();
+    let server = HttpServer::bind("127.0.0.1:0", store)
+        .unwrap()
+        .with_client("dashboard", "t0ken")
+        .with_authorizer(|request| {
+            request.client == "dashboard" && request.pass_name == "secret-a"
+        });
+    let addr = server.local_addr().unwrap();
+    let requests = [
+        ("/v1/entries", "wrong"),
+        ("/v1/entries", "t0ken"),
+        ("/v1/entries/secret%2Da", "t0ken"),
+        ("/v1/entries/missing", "t0ken"),
+        ("/v1/secrets/secret-b", "t0ken"),
+        ("/v1/secrets/secret-a", "t0ken"),
+        ("/v1/secrets/.//secret-a.gpg", "t0ken"),
+        ("/v1/secrets/secret-a/../secret-b", "t0ken"),
+    ];
+    let client = std::thread::spawn(move || {
+        requests
+            .iter()
+            .map(|(path, token)| get(addr, path, token))
+            .collect::<Vec<_>>()
+    });
+    for _ in 0..requests.len() {
+        server.handle_next().unwrap();
+    }
+    let responses = client.join().unwrap();
+
+    assert!(responses[0].starts_with("HTTP/1.1 401 "));
+    assert!(responses[1].starts_with("HTTP/1.1 200 "));
+    assert!(responses[1].contains(r#"{"name":"secret-a","kind":"file"}"#));
+    assert!(responses[2].ends_with(r#"{"version":1,"data":{"name":"secret-a","kind":"file"}}"#));
+    assert!(responses[3].starts_with("HTTP/1.1 404 "));
+    assert!(responses[3].contains(r#""code":"entry_not_found""#));
+    assert!(responses[4].starts_with("HTTP/1.1 403 "));
+    assert!(responses[5].starts_with("HTTP/1.1 200 "));
+    assert!(responses[5].ends_with("\r\n\r\nfoobar123\n"));
+    // the authorizer sees normalized names
+    assert!(responses[6].starts_with("HTTP/1.1 200 "));
+    assert!(responses[7].starts_with("HTTP/1.1 400 "));
+}
+
+#[test]
+fn test_metrics() {
+    use std::time::Duration;
+
+    let (dir, store) = temp_store();
+    let metrics = store.metrics(None).unwrap();
+    assert_eq!(metrics.entries_total, 7);
+    assert_eq!(metrics.expired_entries, 0);
+    assert_eq!(metrics.last_sync_age, None);
+    assert_eq!(
+        store.metrics(Some(Duration::ZERO)).unwrap().expired_entries,
+        7
+    );
+
+    fs::create_dir(dir.path().join(".git")).unwrap();
+    fs::write(dir.path().join(".git").join("FETCH_HEAD"), "").unwrap();
+    assert!(store.metrics(None).unwrap().last_sync_age.is_some());
+
+    fs::write(dir.path().join("broken.gpg"), b"not a message").unwrap();
+    let file = match store.retrieve("broken").unwrap() {
+        StoreEntry::File(file) => file,
+        StoreEntry::Directory(_) => panic!("expected a file"),
+    };
+    assert!(store.decrypt(&file).is_err());
+    let metrics = store.clone().metrics(None).unwrap();
+    assert_eq!(metrics.decryption_errors, 1);
+    assert!(metrics
+        .render_prometheus()
+        .contains("# TYPE pass_decryption_errors_total counter\npass_decryption_errors_total 1\n"));
+}
+
+#[test]
+fn test_completion() {
+    use completion::{render, Shell};
+
+    let (_dir, store) = temp_store();
+    assert_eq!(
+        store.completion_candidates("").unwrap(),
+        ["folder/", "folder2/", "secret-a", "secret-b"]
+    );
+    assert_eq!(
+        store.completion_candidates("folder/sub").unwrap(),
+        [
+            "folder/subfolder/",
+            "folder/subsecret-a",
+            "folder/subsecret-b"
+        ]
+    );
+    assert!(store.completion_candidates("missing/").unwrap().is_empty());
+
+    store.insert("web/my site:8080", b"hunter2\n").unwrap();
+    let candidates = store.completion_candidates("web/").unwrap();
+    assert_eq!(render(Shell::Bash, &candidates), "web/my\\ site:8080\n");
+    assert_eq!(
+        render(Shell::Zsh, &candidates),
+        "web/my site\\:8080:Entry\n"
+    );
+    assert_eq!(
+        render(Shell::Fish, &["folder/".to_string()]),
+        "folder/\tDirectory\n"
+    );
+}
+
+#[test]
+fn test_picker() {
+    use picker::Picker;
+
+    let (_dir, store) = temp_store();
+    let matches = store.picker().unwrap().query("sa");
+    assert_eq!(
+        matches
+            .iter()
+            .take(3)
+            .map(|m| m.name.as_str())
+            .collect::<Vec<_>>(),
+        ["secret-a", "folder/subsecret-a", "folder2/subsecret-a"]
+    );
+    assert_eq!(matches[0].positions, [0, 7]);
+
+    let picker = Picker::new(["web/github.com", "work/signhere", "Games/GH"]).with_limit(2);
+    let matches = picker.query("gh");
+    assert_eq!(
+        matches.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(),
+        ["Games/GH", "web/github.com"]
+    );
+    assert_eq!(matches[0].positions, [6, 7]);
+    assert!(matches[0].score > matches[1].score);
+    // smart case
+    assert_eq!(picker.query("GH").len(), 1);
+    assert!(picker.query("xyz").is_empty());
+    assert_eq!(Picker::new(["b", "a"]).query("").len(), 2);
+}
+
+#[test]
+fn test_diff_stores() {
+    use diff::StoreComparison;
+
+    let (_dir_a, a) = temp_store();
+    let (dir_b, b) = temp_store();
+    assert!(diff_stores(&a, &b, StoreComparison::Ciphertext)
+        .unwrap()
+        .is_empty());
+
+    fs::remove_file(dir_b.path().join("secret-b.gpg")).unwrap();
+    fs::copy(
+        dir_b.path().join("secret-a.gpg"),
+        dir_b.path().join("folder/copied.gpg"),
+    )
+    .unwrap();
+    fs::copy(
+        dir_b.path().join("secret-a.gpg"),
+        dir_b.path().join("folder2/subsecret-a.gpg"),
+    )
+    .unwrap();
+    let diff = diff_stores(&a, &b, StoreComparison::Ciphertext).unwrap();
+    assert_eq!(diff.added, ["folder/copied"]);
+    assert_eq!(diff.removed, ["secret-b"]);
+    assert_eq!(diff.changed, ["folder2/subsecret-a"]);
+
+    // both entries contain the same plaintext
+    let diff = diff_stores(&a, &b, StoreComparison::Plaintext).unwrap();
+    assert!(diff.changed.is_empty());
+}
+
+#[test]
+fn test_merge_from() {
+    use merge::{MergeConflict, MergeResolution};
+
+    assert_eq!(
+        merge::union_lines(b"hunter2\nuser: a\n", b"other\nuser: a\nurl: x"),
+        b"hunter2\nuser: a\nother\nurl: x"
+    );
+
+    let (_dir_a, ours) = temp_store();
+    let (dir_b, theirs) = temp_store();
+    fs::copy(
+        dir_b.path().join("secret-a.gpg"),
+        dir_b.path().join("folder/copied.gpg"),
+    )
+    .unwrap();
+    // re-encrypted but with the same content, so not conflicting
+    fs::copy(
+        dir_b.path().join("secret-a.gpg"),
+        dir_b.path().join("folder2/subsecret-a.gpg"),
+    )
+    .unwrap();
+    // the same key but named differently, so the copy is encrypted again for the recipients of ours
+    fs::write(dir_b.path().join("folder2/.gpg-id"), "8497251104B6F45F\n").unwrap();
+    fs::copy(
+        dir_b.path().join("secret-a.gpg"),
+        dir_b.path().join("folder2/recipients.gpg"),
+    )
+    .unwrap();
+    let report = ours
+        .merge_from(&theirs, |_: &MergeConflict| -> MergeResolution {
+            panic!("no conflict expected")
+        })
+        .unwrap();
+    assert_eq!(report.copied, ["folder/copied", "folder2/recipients"]);
+    assert!(report.resolved.is_empty());
+    assert!(ours.retrieve("folder/copied").is_ok());
+    assert_eq!(
+        fs::read(ours.root().join("folder/copied.gpg")).unwrap(),
+        fs::read(dir_b.path().join("folder/copied.gpg")).unwrap()
+    );
+    assert_ne!(
+        fs::read(ours.root().join("folder2/recipients.gpg")).unwrap(),
+        fs::read(dir_b.path().join("folder2/recipients.gpg")).unwrap()
+    );
+
+    theirs
+        .insert_with("secret-b", b"changed\n", OverwritePolicy::Overwrite)
+        .unwrap();
+    let report = ours.merge_from(&theirs, MergeResolution::KeepBoth).unwrap();
+    assert_eq!(
+        report.resolved,
+        [("secret-b".to_string(), MergeResolution::KeepBoth)]
+    );
+    assert!(ours.retrieve("secret-b (2)").is_ok());
+}
+
+#[test]
+fn test_snapshot_restore() {
+    let (dir, store) = temp_store();
+    let backups = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join(".git")).unwrap();
+
+    // the store must not be copied into itself
+    for dest in [dir.path().to_owned(), dir.path().join("snapshots/new")] {
+        assert!(matches!(
+            store.snapshot(&dest),
+            Err(PassError::IOError { source, .. }) if source.kind() == std::io::ErrorKind::InvalidInput
+        ));
+    }
+    assert!(!dir.path().join("snapshots").exists());
+
+    let first = store.snapshot(backups.path()).unwrap();
+    let second = store.snapshot(backups.path()).unwrap();
+    assert_ne!(first.path(), second.path());
+    assert_eq!(
+        snapshot::list(backups.path()).unwrap(),
+        [first.clone(), second]
+    );
+    assert!(first.path().join("secret-a.gpg").is_file());
+    assert!(first.path().join(".gpg-id").is_file());
+    assert!(!first.path().join(".git").exists());
+    assert_eq!(first.store().unwrap().list().unwrap().len(), 7);
+
+    let secret_a = dir.path().join("secret-a.gpg");
+    let original = fs::read(&secret_a).unwrap();
+    // modifications through the library never change snapshots
+    let file = StoreFileRef {
+        path: secret_a.clone(),
+        root: dir.path().to_owned(),
+    };
+    file.cipher_io()
+        .unwrap()
+        .replace_ciphertext(&fs::read(dir.path().join("secret-b.gpg")).unwrap()[..])
+        .unwrap();
+    fs::remove_file(dir.path().join("folder/subsecret-a.gpg")).unwrap();
+    assert_eq!(
+        fs::read(first.path().join("secret-a.gpg")).unwrap(),
+        original
+    );
+
+    let restored = store.restore(&first, &["secret-a", "folder"]).unwrap();
+    assert_eq!(restored.len(), 5);
+    assert_eq!(fs::read(&secret_a).unwrap(), original);
+    assert!(dir.path().join("folder/subsecret-a.gpg").is_file());
+    assert!(matches!(
+        store.restore(&first, &["missing"]),
+        Err(PassError::EntryNotFound(_))
+    ));
+}
+
+#[test]
+fn test_backup() {
+    use snapshot::BackupIssue;
+
+    let (dir, store) = temp_store();
+    let backups = tempfile::tempdir().unwrap();
+    assert!(matches!(
+        store.backup(dir.path().join("backups")),
+        Err(PassError::IOError { source, .. }) if source.kind() == std::io::ErrorKind::InvalidInput
+    ));
+    assert!(!dir.path().join("backups").exists());
+
+    let first = store.backup(backups.path()).unwrap();
+    assert_eq!(first.entries().len(), 7);
+    assert!(first.verify().unwrap().is_empty());
+
+    // identical files are only stored once
+    let blob_count = || {
+        fs::read_dir(backups.path().join("blobs"))
+            .unwrap()
+            .map(|dir| fs::read_dir(dir.unwrap().path()).unwrap().count())
+            .sum::<usize>()
+    };
+    let blobs = blob_count();
+    let second = store.backup(backups.path()).unwrap();
+    assert_eq!(blob_count(), blobs);
+    assert_eq!(
+        snapshot::list_backups(backups.path()).unwrap(),
+        [first.clone(), second]
+    );
+
+    let secret_a = dir.path().join("secret-a.gpg");
+    let original = fs::read(&secret_a).unwrap();
+    fs::remove_file(&secret_a).unwrap();
+    fs::remove_dir_all(dir.path().join("folder")).unwrap();
+    let restored = store
+        .restore_backup(&first, &["secret-a", "folder"])
+        .unwrap();
+    assert_eq!(restored.len(), 5);
+    assert_eq!(fs::read(&secret_a).unwrap(), original);
+    assert!(matches!(
+        store.restore_backup(&first, &["missing"]),
+        Err(PassError::EntryNotFound(_))
+    ));
+
+    let blob = fs::read_dir(backups.path().join("blobs"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let blob = fs::read_dir(blob).unwrap().next().unwrap().unwrap().path();
+    fs::write(&blob, b"corrupted").unwrap();
+    let issues = first.verify().unwrap();
+    assert_eq!(issues.len(), 1);
+    assert!(matches!(issues[0], BackupIssue::CorruptedBlob { .. }));
+    fs::remove_file(&blob).unwrap();
+    assert!(matches!(
+        first.verify().unwrap()[0],
+        BackupIssue::MissingBlob { .. }
+    ));
+}
+
+#[test]
+fn test_civil_from_days() {
+    for days in [-719468, -1, 0, 1, 18993, 20742, 2932896] {
+        let (year, month, day) = utils::civil_from_days(days);
+        assert_eq!(utils::days_from_civil(year, month, day), days);
+    }
+    assert_eq!(utils::civil_from_days(20742), (2026, 10, 16));
+}
+
+#[test]
+fn test_replace_gpg_id_recipient() {
+    let is_old = |recipient: &str| recipient == "AAAA1111BBBB2222";
+
+    // comments and other recipients are kept
+    let content = "# team\nAAAA1111BBBB2222 # old key\njane@example.com\n";
+    assert_eq!(
+        gpg_id::replace_recipient(content, is_old, "CCCC3333DDDD4444").unwrap(),
+        "# team\nCCCC3333DDDD4444 # old key\njane@example.com\n"
+    );
+
+    // the new key is not listed twice
+    let content = "AAAA1111BBBB2222 CCCC3333DDDD4444\n";
+    assert_eq!(
+        gpg_id::replace_recipient(content, is_old, "CCCC3333DDDD4444").unwrap(),
+        "CCCC3333DDDD4444\n"
+    );
+    let content = "AAAA1111BBBB2222\nCCCC3333DDDD4444\n";
+    assert_eq!(
+        gpg_id::replace_recipient(content, is_old, "CCCC3333DDDD4444").unwrap(),
+        "CCCC3333DDDD4444\n"
+    );
+
+    // nothing to replace
+    assert_eq!(
+        gpg_id::replace_recipient("jane@example.com\n", is_old, "CCCC3333DDDD4444"),
+        None
+    );
+}
+
+#[test]
+fn test_store_view() {
+    let (_dir, store) = temp_store();
+    let view = store.view(["folder/subfolder/", "folder2"]).unwrap();
+    assert_eq!(view.subpaths(), ["folder/subfolder", "folder2"]);
+
+    let mut names = view
+        .list()
+        .unwrap()
+        .iter()
+        .map(|entry| match entry {
+            StoreEntry::File(file) => names::path_to_name(view.root(), &file.path).unwrap(),
+            StoreEntry::Directory(_) => panic!("list() returned a directory"),
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "folder/subfolder/generated-a",
+            "folder/subfolder/generated-b",
+            "folder2/subsecret-a"
+        ]
+    );
+
+    assert!(view.is_accessible("/folder/subfolder/generated-a"));
+    assert!(!view.is_accessible("folder/sub"));
+    assert!(!view.is_accessible("folder/subfolder/../../secret-a"));
+    assert!(view.retrieve("folder/subfolder").is_ok());
+    assert!(matches!(
+        view.retrieve("folder"),
+        Err(PassError::AccessDenied(name)) if name == "folder"
+    ));
+    assert!(matches!(
+        view.retrieve("secret-a"),
+        Err(PassError::AccessDenied(_))
+    ));
+    assert!(matches!(
+        view.insert("secret-c", "foo"),
+        Err(PassError::AccessDenied(_))
+    ));
+    assert!(matches!(
+        view.copy("folder2/subsecret-a", "secret-c", OverwritePolicy::Error),
+        Err(PassError::AccessDenied(_))
+    ));
+    assert!(matches!(
+        view.remove("secret-a"),
+        Err(PassError::AccessDenied(_))
+    ));
+    assert!(view.root().join("secret-a.gpg").exists());
+
+    view.remove("folder2/subsecret-a").unwrap();
+    assert!(!view.root().join("folder2/subsecret-a.gpg").exists());
+}
+
+#[test]
+fn test_access_map() {
+    let (_dir, store) = temp_store();
+    fs::write(
+        store.root().join("folder2/.gpg-id"),
+        "# ops team\nnobody@example.invalid\n",
+    )
+    .unwrap();
+
+    let access = store.access_map().unwrap();
+    assert_eq!(
+        access
+            .directories()
+            .iter()
+            .map(|dir| dir.name.as_str())
+            .collect::<Vec<_>>(),
+        ["", "folder", "folder/subfolder", "folder2"]
+    );
+
+    let root = access.directory("/").unwrap();
+    assert_eq!(root.gpg_id, Some(store.root().join(".gpg-id")));
+    assert_eq!(
+        root.recipients,
+        ["4837F49E22F425EA91725C428497251104B6F45F"]
+    );
+    let subfolder = access.directory("folder/subfolder/").unwrap();
+    assert_eq!(subfolder.gpg_id, root.gpg_id);
+    assert_eq!(subfolder.keys, root.keys);
+
+    let folder2 = access.directory("folder2").unwrap();
+    assert_eq!(folder2.gpg_id, Some(store.root().join("folder2/.gpg-id")));
+    assert_eq!(folder2.recipients, ["nobody@example.invalid"]);
+    assert!(folder2.keys.is_empty());
+    assert_eq!(folder2.unresolved, ["nobody@example.invalid"]);
+    assert!(access.directory("missing").is_none());
+}
+
+#[test]
+fn test_effective_recipients() {
+    use std::time::{Duration, SystemTime};
+
+    let (_dir, store) = temp_store();
+    let root_recipients = ["4837F49E22F425EA91725C428497251104B6F45F"];
+    assert_eq!(
+        store.effective_recipients("secret-a").unwrap(),
+        root_recipients
+    );
+    assert_eq!(
+        store.effective_recipients("folder2/new-entry").unwrap(),
+        root_recipients
+    );
+
+    // a .gpg-id file of a directory which was not resolved before takes precedence
+    let gpg_id = store.root().join("folder/.gpg-id");
+    fs::write(&gpg_id, "jane@example.com\n").unwrap();
+    assert_eq!(
+        store
+            .effective_recipients("folder/subfolder/generated-a")
+            .unwrap(),
+        ["jane@example.com"]
+    );
+
+    // modified files are read again
+    fs::write(&gpg_id, "john@example.com\n").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&gpg_id)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(60))
+        .unwrap();
+    assert_eq!(
+        store.effective_recipients("folder/subsecret-a").unwrap(),
+        ["john@example.com"]
+    );
+
+    // a .gpg-id file which is created between a resolved directory and its governing file takes precedence
+    assert_eq!(
+        store
+            .effective_recipients("folder2/deep/nested/new-entry")
+            .unwrap(),
+        root_recipients
+    );
+    fs::create_dir_all(store.root().join("folder2/deep")).unwrap();
+    fs::write(
+        store.root().join("folder2/deep/.gpg-id"),
+        "jane@example.com\n",
+    )
+    .unwrap();
+    assert_eq!(
+        store
+            .effective_recipients("folder2/deep/nested/new-entry")
+            .unwrap(),
+        ["jane@example.com"]
+    );
+
+    // configured keys override .gpg-id files
+    let store = store.with_config(Config::default().with_keys(["0x1234567890ABCDEF"]));
+    assert_eq!(
+        store.effective_recipients("folder/subsecret-a").unwrap(),
+        ["0x1234567890ABCDEF"]
+    );
+}
+
+#[test]
+fn test_store_not_initialized() {
+    // a .gpg-id file outside of the store does not govern its entries
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join(".gpg-id"), "jane@example.com\n").unwrap();
+    fs::create_dir(dir.path().join("store")).unwrap();
+    let store = Store::open(dir.path().join("store")).unwrap();
+
+    assert!(matches!(
+        store.effective_recipients("web/github.com"),
+        Err(PassError::StoreNotInitialized(root)) if root == store.root()
+    ));
+    assert!(matches!(
+        store.insert("web/github.com", "foo"),
+        Err(PassError::StoreNotInitialized(_))
+    ));
+    assert!(matches!(
+        store.insert_with_keys("web/github.com", "foo", Vec::<String>::new()),
+        Err(PassError::StoreNotInitialized(_))
+    ));
+    assert!(store
+        .insert_with_keys("web/github.com", "foo", ["nobody@example.invalid"])
+        .is_err());
+    assert!(!store.root().join(".gpg-id").exists());
+    assert!(!store.root().join("web").exists());
+}
+
+#[test]
+fn test_ambiguity() {
+    let (_dir, store) = temp_store();
+    fs::copy(
+        store.root().join("secret-a.gpg"),
+        store.root().join("folder.gpg"),
+    )
+    .unwrap();
+
+    assert_eq!(store.ambiguity(), Prefer::Error);
+    assert!(matches!(
+        store.retrieve("folder"),
+        Err(PassError::AmbiguousPassName(_))
+    ));
+    assert!(matches!(
+        store.retrieve("folder/"),
+        Ok(StoreEntry::Directory(_))
+    ));
+
+    let entries = store.retrieve_all("folder").unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(matches!(&entries[0], StoreEntry::File(file) if file.path.ends_with("folder.gpg")));
+    assert!(matches!(&entries[1], StoreEntry::Directory(dir) if dir.path.ends_with("folder")));
+    assert_eq!(store.retrieve_all("secret-a").unwrap().len(), 1);
+    assert!(matches!(
+        store.retrieve_all("missing"),
+        Err(PassError::EntryNotFound(_))
+    ));
+
+    let store = store.with_ambiguity(Prefer::File);
+    assert!(matches!(store.retrieve("folder"), Ok(StoreEntry::File(_))));
+    let store = store.with_ambiguity(Prefer::Directory);
+    assert!(matches!(
+        store.retrieve("folder"),
+        Ok(StoreEntry::Directory(_))
+    ));
+}
+
+#[test]
+fn test_retrieve_kind() {
+    let (_dir, store) = temp_store();
+    fs::copy(
+        store.root().join("secret-a.gpg"),
+        store.root().join("folder.gpg"),
+    )
+    .unwrap();
+
+    // the file extension is tolerated
+    assert!(matches!(
+        store.retrieve("secret-a.gpg"),
+        Ok(StoreEntry::File(file)) if file.path == store.root().join("secret-a.gpg")
+    ));
+    assert!(matches!(
+        store.retrieve("folder/subsecret-a.gpg/"),
+        Err(PassError::EntryNotFound(_))
+    ));
+    assert!(matches!(
+        store.retrieve("missing.gpg"),
+        Err(PassError::EntryNotFound(name)) if name == "missing.gpg"
+    ));
+
+    // typed variants ignore entries of the other kind
+    assert_eq!(
+        store.retrieve_file("folder").unwrap().path,
+        store.root().join("folder.gpg")
+    );
+    assert_eq!(
+        store.retrieve_dir("folder").unwrap().path,
+        store.root().join("folder")
+    );
+    assert_eq!(
+        store.retrieve_file("secret-a.gpg").unwrap().path,
+        store.root().join("secret-a.gpg")
+    );
+    assert!(matches!(
+        store.retrieve_file("folder/subfolder"),
+        Err(PassError::EntryNotFound(_))
+    ));
+    assert!(matches!(
+        store.retrieve_dir("secret-a"),
+        Err(PassError::EntryNotFound(_))
+    ));
+}
+
+#[test]
+fn test_typed_retrieve() {
+    let (_dir, store) = temp_store();
+    assert_eq!(
+        store.file("folder/subsecret-a").unwrap().path,
+        store.root().join("folder/subsecret-a.gpg")
+    );
+    assert_eq!(
+        store.dir("folder/subfolder").unwrap().path,
+        store.root().join("folder/subfolder")
+    );
+    assert!(matches!(
+        store.file("/folder/subfolder/"),
+        Err(PassError::NotAFile(name)) if name == "folder/subfolder"
+    ));
+    assert!(matches!(
+        store.dir("secret-a"),
+        Err(PassError::NotADirectory(name)) if name == "secret-a"
+    ));
+    assert!(matches!(
+        store.file("missing"),
+        Err(PassError::EntryNotFound(_))
+    ));
+    assert!(matches!(
+        store.dir("missing"),
+        Err(PassError::EntryNotFound(_))
+    ));
+}
+
+#[test]
+fn test_names() {
+    let (_dir, store) = temp_store();
+    fs::create_dir(store.root().join(".git")).unwrap();
+    fs::write(store.root().join(".git/ignored.gpg"), b"").unwrap();
+    fs::write(store.root().join("folder/notes.txt"), b"").unwrap();
+
+    let mut names = store.names().unwrap().collect::<Result<Vec<_>>>().unwrap();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "folder/subfolder/generated-a",
+            "folder/subfolder/generated-b",
+            "folder/subsecret-a",
+            "folder/subsecret-b",
+            "folder2/subsecret-a",
+            "secret-a",
+            "secret-b",
+        ]
+    );
+}
+
+#[test]
+fn test_changes_since() {
+    let (_dir, store) = temp_store();
+    let listing = store.listing().unwrap();
+    assert_eq!(listing.len(), 7);
+    assert_eq!(listing.root(), store.root());
+    assert!(store.changes_since(&listing).unwrap().is_empty());
+
+    fs::remove_file(store.root().join("secret-b.gpg")).unwrap();
+    fs::copy(
+        store.root().join("secret-a.gpg"),
+        store.root().join("folder/new.gpg"),
+    )
+    .unwrap();
+    fs::OpenOptions::new()
+        .append(true)
+        .open(store.root().join("folder2/subsecret-a.gpg"))
+        .unwrap()
+        .write_all(b"\n")
+        .unwrap();
+
+    let changes = store.changes_since(&listing).unwrap();
+    assert_eq!(changes.added, ["folder/new"]);
+    assert_eq!(changes.removed, ["secret-b"]);
+    assert_eq!(changes.modified, ["folder2/subsecret-a"]);
+    assert!(changes.listing.get("folder/new").is_some());
+    assert!(store.changes_since(&changes.listing).unwrap().is_empty());
+}
+
+#[test]
+fn test_pack_unpack() {
+    let (_dir, store) = temp_store();
+    let mut bundle = Vec::new();
+    store.pack(&["folder/", "secret-a"], &mut bundle).unwrap();
+    assert!(matches!(
+        store.pack(&["missing"], Vec::new()),
+        Err(PassError::EntryNotFound(_))
+    ));
+
+    let target_dir = tempfile::tempdir().unwrap();
+    let target = Store::open(target_dir.path()).unwrap();
+    let mut unpacked = target
+        .unpack(bundle.as_slice())
+        .unwrap()
+        .into_iter()
+        .map(|file| names::path_to_name(target.root(), &file.path).unwrap())
+        .collect::<Vec<_>>();
+    unpacked.sort_unstable();
+    assert_eq!(
+        unpacked,
+        [
+            "folder/subfolder/generated-a",
+            "folder/subfolder/generated-b",
+            "folder/subsecret-a",
+            "folder/subsecret-b",
+            "secret-a",
+        ]
+    );
+    assert_eq!(
+        fs::read(target.root().join(".gpg-id")).unwrap(),
+        fs::read(store.root().join(".gpg-id")).unwrap()
+    );
+    assert_eq!(
+        fs::read(target.root().join("secret-a.gpg")).unwrap(),
+        fs::read(store.root().join("secret-a.gpg")).unwrap()
+    );
+    assert!(matches!(
+        target.unpack(bundle.as_slice()),
+        Err(PassError::EntryAlreadyExists(_))
+    ));
+
+    let last = bundle.len() - 1;
+    bundle[last] ^= 0xff;
+    let other_dir = tempfile::tempdir().unwrap();
+    let other = Store::open(other_dir.path()).unwrap();
+    assert!(matches!(
+        other.unpack(bundle.as_slice()),
+        Err(PassError::InvalidBundle(_))
+    ));
+    assert!(!other.root().join(".gpg-id").exists());
+
+    // a .gpg-id file must not take over entries which exist already
+    bundle[last] ^= 0xff;
+    fs::copy(
+        store.root().join("secret-b.gpg"),
+        other.root().join("existing.gpg"),
+    )
+    .unwrap();
+    assert!(matches!(
+        other.unpack(bundle.as_slice()),
+        Err(PassError::InvalidBundle(_))
+    ));
+    assert!(!other.root().join(".gpg-id").exists());
+    assert!(!other.root().join("secret-a.gpg").exists());
+}
+
+#[test]
+fn test_share() {
+    let (_dir, store) = temp_store();
+    let fingerprint = "4837F49E22F425EA91725C428497251104B6F45F";
+    let options = share::ShareOptions::default().with_password_only(true);
+    assert!(matches!(
+        store.share("missing", fingerprint, &options),
+        Err(PassError::EntryNotFound(_))
+    ));
+    assert!(matches!(
+        store.share("folder", fingerprint, &options),
+        Err(PassError::NotAFile(_))
+    ));
+
+    let shared = store.share("secret-a", fingerprint, &options).unwrap();
+    assert_eq!(shared.pass_name, "secret-a");
+    assert_eq!(shared.recipient.fingerprint, fingerprint);
+    assert!(shared.password_only);
+    assert!(shared.armored.starts_with("-----BEGIN PGP MESSAGE-----"));
+}
+
+#[test]
+fn test_armor() {
+    let (_dir, store) = temp_store();
+    assert!(!store.armor());
+    assert!(store.clone().with_armor(true).armor());
+
+    // recipients are read from armored ciphertext as well
+    let binary = fs::read(store.root().join("secret-a.gpg")).unwrap();
+    let encoded = utils::base64_encode(&binary);
+    let mut armored = "-----BEGIN PGP MESSAGE-----\n\n".to_string();
+    for line in encoded.as_bytes().chunks(64) {
+        armored.push_str(std::str::from_utf8(line).unwrap());
+        armored.push('\n');
+    }
+    armored.push_str("=abcd\n-----END PGP MESSAGE-----\n");
+    assert_eq!(
+        packets::recipient_key_ids(armored.as_bytes()).unwrap(),
+        packets::recipient_key_ids(&binary).unwrap()
+    );
+
+    // .asc files are only entries if configured
+    fs::write(store.root().join("folder/armored.asc"), &armored).unwrap();
+    assert!(store.retrieve("folder/armored").is_err());
+    assert!(!store
+        .listing()
+        .unwrap()
+        .entries()
+        .contains_key("folder/armored"));
+    let store = store.with_asc_entries(true);
+    let file = store.file("folder/armored").unwrap();
+    assert_eq!(file.path, store.root().join("folder/armored.asc"));
+    assert_eq!(
+        file.recipient_key_ids().unwrap(),
+        packets::recipient_key_ids(&binary).unwrap()
+    );
+    assert!(store
+        .listing()
+        .unwrap()
+        .entries()
+        .contains_key("folder/armored"));
+    assert!(store
+        .names()
+        .unwrap()
+        .any(|name| name.unwrap() == "folder/armored"));
+    assert_eq!(store.list().unwrap().len(), 8);
+    match store.retrieve("folder").unwrap() {
+        StoreEntry::Directory(dir) => assert_eq!(dir.content.len(), 4),
+        StoreEntry::File(_) => panic!("folder is not a directory"),
+    }
+}
+
+#[test]
+fn test_compression() {
+    let (_dir, store) = temp_store();
+    assert_eq!(store.compression(), Compression::Default);
+    let store = store.with_compression(Compression::None);
+    assert_eq!(store.compression(), Compression::None);
+
+    let options = crypto::CryptoOptions::default();
+    assert!(!options
+        .flags(&[])
+        .unwrap()
+        .contains(gpgme::EncryptFlags::NO_COMPRESS));
+    let options = crypto::CryptoOptions {
+        compression: Compression::None,
+        ..options
+    };
+    assert!(options
+        .flags(&[])
+        .unwrap()
+        .contains(gpgme::EncryptFlags::NO_COMPRESS));
+}
+
+#[test]
+fn test_shallow_directories() {
+    let (_dir, store) = temp_store();
+    let subfolder_content = |store: &Store| match store.retrieve("folder").unwrap() {
+        StoreEntry::Directory(dir) => {
+            dir.verify_recursive().unwrap();
+            dir.content
+                .iter()
+                .find_map(|entry| match entry {
+                    StoreEntry::Directory(subdir) => Some(subdir.content.len()),
+                    StoreEntry::File(_) => None,
+                })
+                .unwrap()
+        }
+        StoreEntry::File(_) => panic!("folder is not a directory"),
+    };
+    assert_eq!(subfolder_content(&store), 2);
+    let shallow = store.clone().with_shallow_directories(true);
+    assert!(shallow.shallow_directories());
+    assert_eq!(subfolder_content(&shallow), 0);
+
+    // removed entries are noticed
+    let root = match store.retrieve("/").unwrap() {
+        StoreEntry::Directory(dir) => dir,
+        StoreEntry::File(_) => panic!("the root is not a directory"),
+    };
+    fs::remove_file(store.root().join("folder/subfolder/generated-a.gpg")).unwrap();
+    assert!(matches!(
+        root.verify_recursive(),
+        Err(PassError::InvalidStoreFormat(..))
+    ));
+}
+
+#[test]
+fn test_list_filtered() {
+    use std::time::{Duration, SystemTime};
+
+    let (_dir, store) = temp_store();
+    let names = |filter: &filter::Filter| {
+        store
+            .list_filtered(filter)
+            .unwrap()
+            .entries()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&filter::Filter::new()).len(), 7);
+    assert_eq!(
+        names(
+            &filter::Filter::new()
+                .under("folder/subfolder")
+                .under("folder2/")
+        ),
+        [
+            "folder/subfolder/generated-a",
+            "folder/subfolder/generated-b",
+            "folder2/subsecret-a"
+        ]
+    );
+    assert!(names(&filter::Filter::new().under("missing/")).is_empty());
+    assert!(names(&filter::Filter::new().extension("asc")).is_empty());
+    assert_eq!(names(&filter::Filter::new().extension("gpg")).len(), 7);
+
+    let future = SystemTime::now() + Duration::from_secs(60);
+    assert!(names(&filter::Filter::new().modified_since(future)).is_empty());
+    assert_eq!(
+        names(
+            &filter::Filter::new()
+                .under("folder2")
+                .modified_before(future)
+        ),
+        ["folder2/subsecret-a"]
+    );
+    assert!(matches!(
+        store.list_filtered(&filter::Filter::new().under("../outside")),
+        Err(PassError::InvalidPassName(..))
+    ));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn test_listing_serde() {
+    let (_dir, store) = temp_store();
+    let listing = store.listing().unwrap();
+    let json = serde_json::to_string(&wire::Versioned::new(&listing)).unwrap();
+    let parsed: wire::Versioned<listing::Listing> = serde_json::from_str(&json).unwrap();
+    let parsed = parsed.into_data().unwrap();
+    assert_eq!(parsed, listing);
+    assert!(store.changes_since(&parsed).unwrap().is_empty());
+}
+
+#[test]
+fn test_config_to_env() {
+    let config = Config::default()
+        .with_keys(["A", "B"])
+        .with_umask(0o022)
+        .with_extensions_dir("/tmp/extensions");
+    let env = config.to_env();
+    let parsed = Config::from_lookup(|var| {
+        env.iter()
+            .find(|(name, _)| *name == var)
+            .map(|(_, value)| value.clone())
+    })
+    .unwrap();
+    assert_eq!(parsed, config);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pass_cli() {
+    use std::os::unix::fs::PermissionsExt;
+
+    assert_eq!(
+        compat::strip_ansi("\x1b[1m\x1b[93mpassword\x1b[39m\x1b[22m"),
+        "password"
+    );
+    let tree = "Password Store\n├── folder\n│   ├── sub\n│   │   └── a\n│   └── b\n└── c\n";
+    assert_eq!(compat::parse_tree(tree), ["folder/sub/a", "folder/b", "c"]);
+
+    let (dir, store) = temp_store();
+    let program = dir.path().join("fake-pass");
+    fs::write(
+        &program,
+        "#!/bin/sh\ncase \"$1\" in\n\
+         show) printf 'secret\\nuser: %s\\n' \"$PASSWORD_STORE_DIR\" ;;\n\
+         ls) printf 'Password Store\\n|-- folder\\n|   `-- a\\n`-- b\\n' ;;\n\
+         env) env ;;\n\
+         cat) cat ;;\n\
+         *) echo \"unknown command $1\" >&2; exit 2 ;;\n\
+         esac\n",
+    )
+    .unwrap();
+    fs::set_permissions(&program, fs::Permissions::from_mode(0o700)).unwrap();
+
+    let pass = compat::PassCli::new(&store).with_program(&program);
+    let entry = pass.show("secret-a").unwrap();
+    assert_eq!(entry.password(), "secret");
+    assert_eq!(entry.field("user").unwrap(), store.root().to_string_lossy());
+    assert_eq!(pass.names().unwrap(), ["folder/a", "b"]);
+    match pass.run(&["missing"], None) {
+        Err(PassError::PassCliFailed { status, stderr }) => {
+            assert_eq!(status, Some(2));
+            assert_eq!(stderr, "unknown command missing");
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // only the settings of the store are passed on
+    env::set_var("PASSWORD_STORE_LIBPASS_TEST_LEAK", "1");
+    let env = String::from_utf8(pass.run(&["env"], None).unwrap()).unwrap();
+    env::remove_var("PASSWORD_STORE_LIBPASS_TEST_LEAK");
+    assert!(!env.contains("PASSWORD_STORE_LIBPASS_TEST_LEAK"));
+    assert!(env.contains(&format!("PASSWORD_STORE_DIR={}", store.root().display())));
+
+    // input which exceeds the pipe buffers does not dead-lock with the output of the program
+    let input = vec![b'a'; 1 << 20];
+    assert_eq!(pass.run(&["cat"], Some(&input)).unwrap(), input);
+}
+
+#[test]
+fn test_store_registry() {
+    use crate::registry::StoreRegistry;
+
+    let (_root_dir, root) = temp_store();
+    let (_mount_dir, mounted) = temp_store();
+    let registry = StoreRegistry::new()
+        .with_root(root.clone())
+        .with_mount("/folder/", mounted.clone())
+        .unwrap();
+    assert!(StoreRegistry::new().with_mount("/", root.clone()).is_err());
+    assert!(matches!(
+        StoreRegistry::new().resolve("secret-a"),
+        Err(PassError::EntryNotFound(_))
+    ));
+
+    let (store, name) = registry.resolve("folder/subfolder/generated-a").unwrap();
+    assert_eq!(store.root(), mounted.root());
+    assert_eq!(name, "subfolder/generated-a");
+    let (store, name) = registry.resolve("folder2/subsecret-a").unwrap();
+    assert_eq!(store.root(), root.root());
+    assert_eq!(name, "folder2/subsecret-a");
+    match registry.retrieve("folder/folder/subsecret-a").unwrap() {
+        StoreEntry::File(file) => {
+            assert_eq!(file.path, mounted.root().join("folder/subsecret-a.gpg"))
+        }
+        other => panic!("unexpected entry {:?}", other),
+    }
+
+    let names = registry.names().unwrap();
+    assert_eq!(names.len(), 10);
+    assert!(names.contains(&"secret-a".to_string()));
+    assert!(names.contains(&"folder/secret-a".to_string()));
+    assert!(!names.contains(&"folder/subsecret-a".to_string()));
+}
+
+#[test]
+#[cfg(feature = "gopass")]
+fn test_gopass_config() {
+    use crate::registry::StoreRegistry;
+
+    let (root_dir, root) = temp_store();
+    let (mount_dir, mounted) = temp_store();
+    let config_dir = tempfile::tempdir().unwrap();
+    let config = config_dir.path().join("config.yml");
+    fs::write(
+        &config,
+        format!(
+            "autoclip: true\nroot:\n  path: gpgcli-gitcli-fs+file://{}\nmounts:\n  work:\n    path: {}\n",
+            root_dir.path().display(),
+            mount_dir.path().display()
+        ),
+    )
+    .unwrap();
+
+    let registry = StoreRegistry::from_gopass_config(&config).unwrap();
+    assert_eq!(registry.root().unwrap().root(), root.root());
+    assert_eq!(registry.mounts().len(), 1);
+    assert_eq!(registry.mounts()["work"].root(), mounted.root());
+    assert_eq!(registry.resolve("work/secret-a").unwrap().1, "secret-a");
+
+    fs::write(&config, "mounts: [work]\n").unwrap();
+    assert!(matches!(
+        StoreRegistry::from_gopass_config(&config),
+        Err(PassError::InvalidConfig { .. })
+    ));
+}
+
+#[test]
+fn test_parsed_entry_gopass() {
+    let content = "hunter2\nuser: john\n---\nurl: https://example.com\nnested:\n  key: value\n";
+    let entry: ParsedEntry = content.parse().unwrap();
+    assert_eq!(entry.mode(), ParseMode::Pass);
+    assert_eq!(entry.field("key"), Some("value"));
+
+    let mut entry = entry.with_mode(ParseMode::Gopass);
+    assert_eq!(entry.fields().collect::<Vec<_>>(), [("user", "john")]);
+    assert_eq!(entry.field("key"), None);
+    assert_eq!(entry.login().username.as_deref(), Some("john"));
+    entry.set_field("user", "jane").unwrap();
+    entry.set_field("email", "jane@example.com").unwrap();
+    assert!(entry.remove_field("url").is_none());
+    assert_eq!(
+        entry.content(),
+        "hunter2\nuser: jane\nemail: jane@example.com\n---\nurl: https://example.com\nnested:\n  key: value\n"
+    );
+
+    // entries without a YAML document are interpreted like in the pass mode
+    let plain = "hunter2\nuser: john\n";
+    let mut entry = ParsedEntry::from(plain.to_string()).with_mode(ParseMode::Gopass);
+    entry.set_field("url", "https://example.com").unwrap();
+    assert_eq!(
+        entry.content(),
+        "hunter2\nuser: john\nurl: https://example.com\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "gopass")]
+fn test_parsed_entry_yaml() {
+    let mut entry: ParsedEntry = "hunter2\r\nuser: john\r\n---\r\nurl: https://example.com\n"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        entry.yaml_value("url").unwrap(),
+        Some(serde_yaml::Value::from("https://example.com"))
+    );
+    entry.set_yaml_value("otp", "JBSWY3DPEHPK3PXP").unwrap();
+    assert_eq!(
+        entry.content(),
+        "hunter2\r\nuser: john\r\n---\r\nurl: https://example.com\r\notp: JBSWY3DPEHPK3PXP\r\n"
+    );
+    assert_eq!(
+        entry.remove_yaml_value("url").unwrap(),
+        Some(serde_yaml::Value::from("https://example.com"))
+    );
+    assert_eq!(entry.remove_yaml_value("url").unwrap(), None);
+    assert_eq!(entry.yaml().unwrap().unwrap().len(), 1);
+
+    let mut entry: ParsedEntry = "hunter2".parse().unwrap();
+    assert_eq!(entry.yaml().unwrap(), None);
+    entry.set_yaml_value("port", 22).unwrap();
+    assert_eq!(entry.content(), "hunter2\n---\nport: 22\n");
+
+    let entry: ParsedEntry = "hunter2\n---\n- a list\n".parse().unwrap();
+    assert!(matches!(entry.yaml(), Err(PassError::MalformedEntry(_))));
+}
+
+#[test]
+fn test_manifest() {
+    use crate::manifest::ManifestIssue;
+
+    let (dir, store) = temp_store();
+    assert!(matches!(
+        store.verify_manifest(),
+        Err(PassError::InvalidStoreFormat(..))
+    ));
+    let manifest = store.update_manifest().unwrap();
+    assert_eq!(manifest.files().len(), 8);
+    assert!(manifest.hash(".gpg-id").is_some());
+    assert!(manifest.hash("folder/subfolder/generated-a.gpg").is_some());
+    let report = store.verify_manifest().unwrap();
+    assert!(report.issues.is_empty());
+    assert_eq!(report.signature, SignatureStatus::Unsigned);
+    // unsigned manifests could have been rewritten by whoever changed the store
+    assert!(!report.trusted_signature);
+    assert!(!report.is_intact());
+
+    let store = store.with_manifest_maintenance(true);
+    store.remove("secret-b").unwrap();
+    assert!(store.verify_manifest().unwrap().issues.is_empty());
+
+    // removing .gpg-id files while pruning updates the manifest as well
+    fs::create_dir(dir.path().join("empty")).unwrap();
+    fs::write(dir.path().join("empty/.gpg-id"), "8497251104B6F45F\n").unwrap();
+    store.update_manifest().unwrap();
+    assert_eq!(store.prune_empty_dirs(|_| true).unwrap(), ["empty"]);
+    assert!(store.verify_manifest().unwrap().issues.is_empty());
+
+    fs::write(dir.path().join("secret-a.gpg"), "tampered").unwrap();
+    fs::remove_file(dir.path().join("folder2/subsecret-a.gpg")).unwrap();
+    fs::write(dir.path().join("new.gpg"), "unknown").unwrap();
+    let report = store.verify_manifest().unwrap();
+    assert!(!report.is_intact());
+    assert_eq!(
+        report.issues,
+        [
+            ManifestIssue::Missing {
+                file: "folder2/subsecret-a.gpg".to_string()
+            },
+            ManifestIssue::Unlisted {
+                file: "new.gpg".to_string()
+            },
+            ManifestIssue::Modified {
+                file: "secret-a.gpg".to_string()
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_obfuscated_store() {
+    let (dir, store) = temp_store();
+    let mut store = store.obfuscated().unwrap();
+    let copy = store.clone();
+    assert!(store.names().is_empty());
+    assert_eq!(store.obfuscate_existing().unwrap().len(), 7);
+    assert!(!dir.path().join("folder").exists());
+    assert_eq!(store.store().names().unwrap().count(), 7);
+    assert!(!store
+        .store()
+        .names()
+        .unwrap()
+        .any(|name| name.unwrap().contains("secret")));
+
+    let file = store.retrieve_file("folder/subsecret-a").unwrap();
+    assert_eq!(store.name_of(&file).as_deref(), Some("folder/subsecret-a"));
+    assert_eq!(file.plain_io_ro().unwrap().as_ref(), b"foobar123\n");
+
+    store.insert("web/github.com", "hunter2").unwrap();
+    store
+        .rename("web/github.com", "web/gitlab.com", OverwritePolicy::Error)
+        .unwrap();
+    store.remove("secret-b").unwrap();
+    assert!(matches!(
+        store.insert("secret-a", "hunter2"),
+        Err(PassError::EntryAlreadyExists(_))
+    ));
+
+    // clones share the name map
+    assert_eq!(copy, store);
+    assert!(copy.contains("web/gitlab.com"));
+
+    let reopened = Store::open(dir.path()).unwrap().obfuscated().unwrap();
+    assert_eq!(reopened.names(), store.names());
+    assert!(reopened.contains("web/gitlab.com"));
+    assert!(matches!(
+        reopened.retrieve_file("secret-b"),
+        Err(PassError::EntryNotFound(_))
+    ));
+}
+
+#[test]
+fn test_concurrent_store_access() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Store>();
+    assert_send_sync::<ReadOnlyStore>();
+    assert_send_sync::<registry::StoreRegistry>();
+    assert_send_sync::<obfuscation::ObfuscatedStore>();
+
+    let (_dir, store) = temp_store();
+    let store = store.with_manifest_maintenance(true);
+    store.update_manifest().unwrap();
+
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..20 {
+                    for name in store.names().unwrap() {
+                        store.retrieve(name.unwrap()).unwrap();
+                    }
+                }
+            });
+        }
+        for writer in 0..2 {
+            let store = &store;
+            scope.spawn(move || {
+                for i in 0..10 {
+                    let path = store
+                        .root()
+                        .join(format!("concurrent/writer-{}/entry-{}.gpg", writer, i));
+                    store.write_ciphertext(path, b"ciphertext").unwrap();
+                }
+            });
+        }
+        // stands in for a sync which rewrites the tree
+        scope.spawn(|| {
+            for _ in 0..10 {
+                let _exclusive = store.locks().exclusive();
+                let before = store.names().unwrap().count();
+                std::thread::yield_now();
+                assert_eq!(store.names().unwrap().count(), before);
+            }
+        });
+    });
+
+    assert_eq!(store.names().unwrap().count(), 7 + 20);
+    // no update of the manifest got lost
+    assert!(store.verify_manifest().unwrap().issues.is_empty());
+}
+
+#[test]
+fn test_gpg_timeout() {
+    use crate::crypto::{with_timeout, MAX_TIMEOUT_WORKERS};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::time::Duration;
+
+    let (_dir, store) = temp_store();
+    assert_eq!(store.gpg_timeout(GpgOperation::Decrypt), None);
+    let store = store.with_gpg_timeout(GpgOperation::Decrypt, Duration::from_secs(5));
+    assert_eq!(
+        store.gpg_timeout(GpgOperation::Decrypt),
+        Some(Duration::from_secs(5))
+    );
+    assert_eq!(store.gpg_timeout(GpgOperation::KeyLookup), None);
+
+    let result = with_timeout(GpgOperation::Encrypt, Duration::from_secs(5), |_| Ok(42));
+    assert_eq!(result.unwrap(), 42);
+
+    // a hung operation is abandoned and learns that it was cancelled once it returns
+    let (sender, receiver) = mpsc::channel();
+    let result = with_timeout(
+        GpgOperation::Decrypt,
+        Duration::from_millis(10),
+        move |cancellation| {
+            std::thread::sleep(Duration::from_millis(200));
+            sender.send(cancellation.is_cancelled()).unwrap();
+            Ok(Vec::<u8>::new())
+        },
+    );
+    assert!(matches!(
+        result,
+        Err(PassError::Timeout {
+            operation: GpgOperation::Decrypt,
+            ..
+        })
+    ));
+    assert!(receiver.recv().unwrap());
+
+    // hung operations can not pile up threads
+    let (release, hung) = mpsc::channel::<()>();
+    let hung = Arc::new(Mutex::new(hung));
+    for _ in 0..MAX_TIMEOUT_WORKERS {
+        let hung = hung.clone();
+        let result = with_timeout(
+            GpgOperation::Decrypt,
+            Duration::from_millis(10),
+            move |_| {
+                let _ = hung.lock().unwrap().recv();
+                Ok(())
+            },
+        );
+        assert!(matches!(result, Err(PassError::Timeout { .. })));
+    }
+    let result = with_timeout(GpgOperation::Decrypt, Duration::from_millis(10), |_| Ok(42));
+    assert!(matches!(result, Err(PassError::Timeout { .. })));
+    drop(release);
+    let result = with_timeout(GpgOperation::Decrypt, Duration::from_secs(5), |_| Ok(42));
+    assert_eq!(result.unwrap(), 42);
+}
+
+#[test]
+fn test_offline() {
+    let (_dir, store) = temp_store();
+    assert!(!store.offline());
+    let store = store.with_offline(true);
+    assert!(store.offline());
+    assert_eq!(store.access_map().unwrap().directories().len(), 4);
+    let read_only = Store::open_read_only(store.root())
+        .unwrap()
+        .with_offline(true);
+    assert_eq!(read_only.access_map().unwrap(), store.access_map().unwrap());
+
+    // all contexts which operations use are offline
+    assert!(store.crypto().new_context().unwrap().offline());
+    assert!(store
+        .crypto()
+        .context(&context_pool::ContextPool::new(1))
+        .unwrap()
+        .offline());
+    // entries can still be written and read with local keys only
+    store.insert("offline", b"hunter2\n").unwrap();
+    assert_eq!(
+        store.retrieve_entry("offline").unwrap().password(),
+        "hunter2"
+    );
+}
+
+#[test]
+fn test_secure_delete() {
+    use crate::scrub::{CommandHook, RemovedEntry};
+    use std::sync::{Arc, Mutex};
+
+    let (dir, store) = temp_store();
+    let store = store.with_secure_delete(true);
+    assert!(store.secure_delete());
+    let removed = Arc::new(Mutex::new(Vec::new()));
+    let seen = removed.clone();
+    let store = store.with_scrub_hook(move |root: &Path, entry: &RemovedEntry| {
+        assert!(!root.join(&entry.path).exists());
+        seen.lock().unwrap().push(entry.clone());
+        Ok(())
+    });
+
+    // keep a second link to observe that the content is overwritten before unlinking
+    let path = dir.path().join("folder/subsecret-a.gpg");
+    let mut file = fs::File::open(&path).unwrap();
+    let length = file.metadata().unwrap().len();
+    store.remove("folder/subsecret-a").unwrap();
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).unwrap();
+    assert_eq!(content.len() as u64, length);
+    assert!(content.iter().all(|&b| b == 0));
+    assert_eq!(
+        *removed.lock().unwrap(),
+        [RemovedEntry {
+            pass_name: "folder/subsecret-a".to_string(),
+            path: Path::new("folder").join("subsecret-a.gpg"),
+        }]
+    );
+
+    // hard-linked files are shared with snapshots and are not overwritten
+    let original = fs::read(dir.path().join("secret-a.gpg")).unwrap();
+    fs::hard_link(
+        dir.path().join("secret-a.gpg"),
+        dir.path().join("linked.bak"),
+    )
+    .unwrap();
+    store.remove("secret-a").unwrap();
+    assert_eq!(fs::read(dir.path().join("linked.bak")).unwrap(), original);
+
+    #[cfg(unix)]
+    {
+        let store = store.with_scrub_hook(CommandHook::new(
+            "sh",
+            ["-c", "printf %s \"$0\" > .scrubbed"],
+        ));
+        store.remove("secret-b").unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join(".scrubbed")).unwrap(),
+            "secret-b.gpg"
+        );
+        assert_eq!(removed.lock().unwrap().len(), 3);
+
+        let store = store.with_scrub_hook(CommandHook::new("false", Vec::<String>::new()));
+        assert!(store.remove("folder2/subsecret-a").is_err());
+        assert!(!dir.path().join("folder2/subsecret-a.gpg").exists());
+    }
+}
+
+#[test]
+fn test_protection() {
+    let (dir, store) = temp_store();
+    assert!(matches!(
+        store.protect("missing"),
+        Err(PassError::EntryNotFound(_))
+    ));
+    store.protect("folder/subsecret-a").unwrap();
+    store.protect("secret-a").unwrap();
+    store.protect("secret-a").unwrap();
+    assert!(store.is_protected("folder/subsecret-a").unwrap());
+    assert!(!store.is_protected("secret-b").unwrap());
+    assert_eq!(
+        store.protected_entries().unwrap(),
+        ["folder/subsecret-a", "secret-a"]
+    );
+    assert!(dir.path().join(protection::PROTECTION_FILE).is_file());
+
+    assert!(matches!(
+        store.remove("secret-a"),
+        Err(PassError::EntryProtected(name)) if name == "secret-a"
+    ));
+    assert!(matches!(
+        store.copy("secret-b", "secret-a", OverwritePolicy::Overwrite),
+        Err(PassError::EntryProtected(_))
+    ));
+    // restoring replaces the encrypted file directly
+    let snapshots = tempfile::tempdir().unwrap();
+    let snapshot = store.snapshot(snapshots.path()).unwrap();
+    assert!(matches!(
+        store.restore(&snapshot, &["secret-a"]),
+        Err(PassError::EntryProtected(_))
+    ));
+    // entries next to protected ones are not affected
+    store.remove("secret-b").unwrap();
+    assert!(dir.path().join("secret-a.gpg").is_file());
+
+    let maintenance = store.clone().with_override_protection(true);
+    assert!(maintenance.override_protection());
+    maintenance.remove("secret-a").unwrap();
+    assert!(!dir.path().join("secret-a.gpg").exists());
+
+    assert!(store.unprotect("secret-a").unwrap());
+    assert!(!store.unprotect("secret-a").unwrap());
+    assert!(store.unprotect("folder/subsecret-a").unwrap());
+    assert!(!dir.path().join(protection::PROTECTION_FILE).exists());
+    store.remove("folder/subsecret-a").unwrap();
+}
+
+#[test]
+fn test_pass_name() {
+    let name: PassName = "/web//github.com/".parse().unwrap();
+    assert_eq!(name, "web/github.com");
+    assert_eq!(name.file_name(), Some("github.com"));
+    assert_eq!(name.parent().unwrap(), "web");
+    assert_eq!(name.parent().unwrap().parent(), Some(PassName::root()));
+    assert_eq!(PassName::root().parent(), None);
+    assert_eq!(
+        name.segments().collect::<Vec<_>>(),
+        vec!["web", "github.com"]
+    );
+
+    let web = PassName::new("web").unwrap();
+    assert_eq!(web.join("github.com/").unwrap(), name);
+    assert_eq!(PassName::root().join("web").unwrap(), web);
+    assert!(web.join("../secret").is_err());
+    assert!(PassName::new("../secret").is_err());
+
+    assert!(name.starts_with(&web));
+    assert!(name.starts_with(&PassName::root()));
+    assert!(!PassName::new("webmail").unwrap().starts_with(&web));
+    assert_eq!(name.strip_prefix(&web).unwrap(), "github.com");
+    assert_eq!(web.strip_prefix(&web), Some(PassName::root()));
+
+    let (_dir, store) = temp_store();
+    let name = PassName::new("folder/subsecret-a").unwrap();
+    let file = store.retrieve_file(&name).unwrap();
+    assert_eq!(store.name_of(&file).unwrap(), name);
+
+    let new = PassName::new("folder").unwrap().join("new").unwrap();
+    store.insert(&new, "hunter2").unwrap();
+    assert_eq!(store.retrieve_entry(&new).unwrap().password(), "hunter2");
+    store.remove(new).unwrap();
+    assert!(store.file("folder/new").is_err());
+}
+
+#[cfg(feature = "layout")]
+#[test]
+fn test_validate_layout() {
+    use crate::layout::{LayoutSpec, LayoutViolation};
+
+    let (_dir, store) = temp_store();
+    assert!(store
+        .validate_layout(&LayoutSpec::new())
+        .unwrap()
+        .is_empty());
+
+    let spec = LayoutSpec::new()
+        .with_pattern("<top>")
+        .unwrap()
+        .with_pattern("folder/<name>")
+        .unwrap()
+        .with_pattern("folder/subfolder/**")
+        .unwrap()
+        .with_placeholder("top", "secret-[a-z]")
+        .unwrap()
+        .with_placeholder("name", "subsecret-a")
+        .unwrap();
+    assert_eq!(
+        store.validate_layout(&spec).unwrap(),
+        [
+            LayoutViolation::InvalidSegment {
+                pass_name: "folder/subsecret-b".to_string(),
+                pattern: "folder/<name>".to_string(),
+                placeholder: "name".to_string(),
+                segment: "subsecret-b".to_string(),
+            },
+            LayoutViolation::Unmatched("folder2/subsecret-a".to_string()),
+        ]
+    );
+    assert_eq!(spec.check("folder/subfolder"), None);
+    assert_eq!(
+        spec.check("secret-1").map(|v| v.pass_name().to_string()),
+        Some("secret-1".to_string())
+    );
+
+    assert!(matches!(
+        LayoutSpec::new().with_pattern("sites/<domain>.com"),
+        Err(PassError::InvalidLayoutRule { .. })
+    ));
+    assert!(LayoutSpec::new().with_pattern("/").is_err());
+    assert!(LayoutSpec::new().with_placeholder("domain", "(").is_err());
+}
+
+#[test]
+fn test_prune_empty_dirs() {
+    let (_dir, store) = temp_store();
+    let store = store.with_auto_prune(true);
+    store.remove("folder2/subsecret-a").unwrap();
+    assert!(!store.root().join("folder2").exists());
+
+    fs::create_dir_all(store.root().join("empty/nested")).unwrap();
+    fs::create_dir_all(store.root().join("team")).unwrap();
+    fs::copy(
+        store.root().join(".gpg-id"),
+        store.root().join("team/.gpg-id"),
+    )
+    .unwrap();
+    fs::create_dir_all(store.root().join("notes")).unwrap();
+    fs::write(store.root().join("notes/README"), b"keep me").unwrap();
+
+    // directories with a .gpg-id file are only removed after confirmation
+    let mut asked = Vec::new();
+    let pruned = store
+        .prune_empty_dirs(|name| {
+            asked.push(name.to_string());
+            false
+        })
+        .unwrap();
+    assert_eq!(pruned, ["empty/nested", "empty"]);
+    assert_eq!(asked, ["team"]);
+    assert!(store.root().join("team/.gpg-id").exists());
+    assert!(store.root().join("notes/README").exists());
+
+    assert_eq!(store.prune_empty_dirs(|_| true).unwrap(), ["team"]);
+    assert!(store.root().join(".gpg-id").exists());
+    assert_eq!(store.list().unwrap().len(), 6);
+}
+
+#[test]
+fn test_line_endings() {
+    assert_eq!(
+        LineEnding::detect("pw\r\nuser: john\n"),
+        Some(LineEnding::CrLf)
+    );
+    assert_eq!(
+        LineEnding::detect("pw\nuser: john\r\n"),
+        Some(LineEnding::Lf)
+    );
+    assert_eq!(LineEnding::detect("pw"), None);
+    assert_eq!(LineEnding::CrLf.apply("a\nb\r\nc"), "a\r\nb\r\nc");
+    assert_eq!(LineEnding::Lf.apply("a\r\nb\r\n"), "a\nb\n");
+
+    // new fields use the line ending of the entry
+    let mut entry: ParsedEntry = "pw\r\nuser: john\r\n".parse().unwrap();
+    entry.set_field("url", "https://example.com").unwrap();
+    assert_eq!(
+        entry.content(),
+        "pw\r\nuser: john\r\nurl: https://example.com\r\n"
+    );
+
+    let (_dir, store) = temp_store();
+    store.insert("raw", "pw\r\nuser: john\r\n").unwrap();
+    let store = store
+        .with_line_ending(Some(LineEnding::Lf))
+        .with_utf8_validation(true);
+    // content which was written before is normalized on every path which reads it
+    assert_eq!(
+        store.retrieve_entry("raw").unwrap().content(),
+        "pw\nuser: john\n"
+    );
+    let copied = store.copy("raw", "copied", OverwritePolicy::Error).unwrap();
+    assert_eq!(store.read_plaintext(&copied).unwrap(), b"pw\nuser: john\n");
+
+    assert!(matches!(
+        store.insert("binary", &[0xff, 0xfe, b'\n'][..]),
+        Err(PassError::MalformedEntry(_))
+    ));
+    assert!(!store.root().join("binary.gpg").exists());
+
+    let file = store.insert("crlf", "pw\r\nuser: john\r\n").unwrap();
+    assert_eq!(
+        store.plain_io_rw(&file).unwrap().as_ref(),
+        b"pw\nuser: john\n"
+    );
+}
+
+#[test]
+fn test_streaming_reencryption() {
+    use crate::crypto::{self, STREAM_CHUNK_SIZE};
+
+    // chunks arrive in order and the end is reached once the writing half is gone
+    let (mut writer, mut reader) = crypto::pipe();
+    let content = (0..3 * STREAM_CHUNK_SIZE + 17)
+        .map(|i| i as u8)
+        .collect::<Vec<_>>();
+    let producer = {
+        let content = content.clone();
+        std::thread::spawn(move || writer.write_all(&content))
+    };
+    let mut received = Vec::new();
+    reader.read_to_end(&mut received).unwrap();
+    producer.join().unwrap().unwrap();
+    assert_eq!(received, content);
+
+    let (mut writer, reader) = crypto::pipe();
+    drop(reader);
+    assert_eq!(
+        writer.write(b"foobar123").unwrap_err().kind(),
+        std::io::ErrorKind::BrokenPipe
+    );
+
+    let (_dir, store) = temp_store();
+    assert_eq!(store.streaming_threshold(), DEFAULT_STREAMING_THRESHOLD);
+    let store = store.with_streaming_threshold(0);
+    let file = store.file("secret-a").unwrap();
+    let before = fs::read(&file.path).unwrap();
+    store.reencrypt(&file).unwrap();
+    assert_ne!(fs::read(&file.path).unwrap(), before);
+    assert_eq!(store.read_plaintext(&file).unwrap(), b"foobar123\n");
+}
+
+#[test]
+fn test_split_and_merge_entries() {
+    use crate::split::{self, SplitMapping};
+
+    let mapping = SplitMapping::new()
+        .with_entry("db-admin", "admin-password")
+        .with_renamed_field("db-admin", "admin-user", "user")
+        // another spelling of the same target
+        .with_field("/db-admin", "url");
+    let mut entry: ParsedEntry =
+        "hunter2\nuser: john\nadmin-user: root\n# shared\nadmin-password: secret\nurl: db.local\n"
+            .parse()
+            .unwrap();
+    let targets = split::split(&mut entry, &mapping).unwrap();
+    assert_eq!(entry.content(), "hunter2\nuser: john\n# shared\n");
+    assert_eq!(targets.len(), 1);
+    assert_eq!(targets[0].0, "db-admin");
+    assert_eq!(
+        targets[0].1.content(),
+        "secret\nuser: root\nurl: db.local\n"
+    );
+
+    // a missing field leaves the entry untouched
+    let mut unchanged = entry.clone();
+    assert!(matches!(
+        split::split(&mut unchanged, &mapping),
+        Err(PassError::InvalidField { .. })
+    ));
+    assert_eq!(unchanged, entry);
+
+    let work: ParsedEntry = "hunter2\nuser: john\nurl: mail.example.com\n"
+        .parse()
+        .unwrap();
+    let private: ParsedEntry =
+        "letmein\nuser: jdoe\nurl: mail.example.com\nrecovery codes follow\n"
+            .parse()
+            .unwrap();
+    let merged = split::merge([("mail/work", &work), ("mail/private", &private)]);
+    assert_eq!(
+        merged.content(),
+        "hunter2\nuser: john\nurl: mail.example.com\npassword: letmein\nuser-private: jdoe\n\
+         recovery codes follow\n"
+    );
+
+    let (_dir, store) = temp_store();
+    store
+        .insert(
+            "mail/work",
+            "hunter2\nuser: john\nadmin-user: root\nadmin-password: secret\n",
+        )
+        .unwrap();
+    let created = store
+        .split_entry(
+            "mail/work",
+            &SplitMapping::new()
+                .with_entry("mail/admin", "admin-password")
+                .with_renamed_field("mail/admin", "admin-user", "user"),
+        )
+        .unwrap();
+    assert_eq!(created, vec![store.file("mail/admin").unwrap()]);
+    assert_eq!(
+        store.file("mail/admin").unwrap().parse().unwrap().content(),
+        "secret\nuser: root\n"
+    );
+    assert!(matches!(
+        store.split_entry(
+            "mail/work",
+            &SplitMapping::new().with_entry("secret-a", "user")
+        ),
+        Err(PassError::EntryAlreadyExists(_))
+    ));
+
+    store
+        .merge_entries(&["mail/work", "/mail//admin"], "mail/work")
+        .unwrap();
+    assert_eq!(
+        store.file("mail/work").unwrap().parse().unwrap().content(),
+        "hunter2\nuser: john\npassword: secret\nuser-admin: root\n"
+    );
+    assert!(matches!(
+        store.file("mail/admin"),
+        Err(PassError::EntryNotFound(_))
+    ));
+    assert!(matches!(
+        store.merge_entries(&["mail/work"], "secret-a"),
+        Err(PassError::EntryAlreadyExists(_))
+    ));
+}
+
+#[test]
+fn test_otp_insert_uri() {
+    let (_dir, store) = temp_store();
+    let uri = "otpauth://totp/example?secret=JBSWY3DPEHPK3PXP";
+    store.insert("web/example", "hunter2\nuser: john").unwrap();
+    otp::insert_uri(&store, "web/example", uri).unwrap();
+    assert_eq!(
+        store
+            .file("web/example")
+            .unwrap()
+            .parse()
+            .unwrap()
+            .content(),
+        format!("hunter2\nuser: john\n{}\n", uri)
+    );
+    assert!(matches!(
+        otp::insert_uri(&store, "web/example", uri),
+        Err(PassError::InvalidField { .. })
+    ));
+    assert!(matches!(
+        otp::insert_uri(&store, "secret-b", "https://example.com"),
+        Err(PassError::InvalidField { .. })
+    ));
+
+    otp::insert_uri(&store, "totp/example", uri).unwrap();
+    assert_eq!(
+        store
+            .file("totp/example")
+            .unwrap()
+            .parse()
+            .unwrap()
+            .content(),
+        format!("{}\n", uri)
+    );
+
+    #[cfg(feature = "otp-qr")]
+    {
+        assert!(matches!(
+            otp::import_from_image(&store, "secret-b", b"not an image".as_slice()),
+            Err(PassError::NoOtpQrCode(_))
+        ));
+        assert!(matches!(
+            otp::uri_from_image(Path::new("tests/missing.png")),
+            Err(PassError::IOError { .. })
+        ));
+
+        let image = env::current_dir().unwrap().join("tests/otp-qr.png");
+        let imported = otp::import_from_image(&store, "qr/example", &image).unwrap();
+        assert_eq!(imported.expose(), uri);
+        assert_eq!(
+            store.retrieve_entry("qr/example").unwrap().content(),
+            format!("{}\n", uri)
+        );
+    }
+}
+
+#[test]
+fn test_passkeys() {
+    use crate::passkey::Passkey;
+
+    let passkey = Passkey::new(
+        vec![0xf0, 0x9f, 0xac, 0x64],
+        "example.com",
+        vec![0xa5, 0x01, 0x02],
+    )
+    .with_user_handle(b"123456".to_vec())
+    .with_user_name("john@example.com");
+    let mut entry: ParsedEntry = "hunter2\nuser: john\n".parse().unwrap();
+    assert!(entry.add_passkey(&passkey).unwrap());
+    assert!(!entry.add_passkey(&passkey).unwrap());
+    assert_eq!(
+        entry.content(),
+        "hunter2\nuser: john\npasskey: 8J-sZA\npasskey-rp: example.com\npasskey-public-key: pQEC\n\
+         passkey-user-handle: MTIzNDU2\npasskey-user: john@example.com\n"
+    );
+    assert_eq!(entry.passkeys().unwrap(), vec![passkey.clone()]);
+
+    let other = Passkey::new(b"other".to_vec(), "login.example.org", b"key".to_vec());
+    entry.add_passkey(&other).unwrap();
+    assert_eq!(
+        entry.passkeys().unwrap(),
+        vec![passkey.clone(), other.clone()]
+    );
+    assert!(entry.remove_passkey(&passkey.credential_id));
+    assert!(!entry.remove_passkey(&passkey.credential_id));
+    assert_eq!(entry.passkeys().unwrap(), vec![other.clone()]);
+    assert_eq!(entry.field("user"), Some("john"));
+
+    assert!(matches!(
+        entry.add_passkey(&Passkey::new(b"id".to_vec(), "", b"key".to_vec())),
+        Err(PassError::InvalidField { .. })
+    ));
+    let malformed: ParsedEntry = "hunter2\npasskey: 8J-sZA\nuser: john\npasskey-rp: example.com\n"
+        .parse()
+        .unwrap();
+    assert!(matches!(
+        malformed.passkeys(),
+        Err(PassError::MalformedEntry(_))
+    ));
+
+    let (_dir, store) = temp_store();
+    store.insert("web/example", entry.content()).unwrap();
+    assert_eq!(
+        store.find_passkeys("LOGIN.example.org").unwrap(),
+        vec![(store.file("web/example").unwrap(), other)]
+    );
+    assert!(store.find_passkeys("example.com").unwrap().is_empty());
+}
+
+#[test]
+fn test_entry_transformers() {
+    use crate::transform::TransformContext;
+
+    let (_dir, store) = temp_store();
+    store
+        .insert("web/example", "hunter2\nuser: {{name}}\n")
+        .unwrap();
+    let unchanged = store.retrieve_entry("web/example").unwrap();
+    assert_eq!(unchanged.content(), "hunter2\nuser: {{name}}\n");
+
+    let copy = store.clone();
+    let store = store
+        .with_transformer(
+            |context: &TransformContext<'_>, entry: ParsedEntry| -> Result<ParsedEntry> {
+                let name = context.pass_name().rsplit('/').next().unwrap_or_default();
+                Ok(ParsedEntry::from(entry.content().replace("{{name}}", name)))
+            },
+        )
+        .with_transformer(
+            |_: &TransformContext<'_>, entry: ParsedEntry| -> Result<ParsedEntry> {
+                Ok(ParsedEntry::from(entry.content().to_uppercase()))
+            },
+        );
+    assert_eq!(
+        store.retrieve_entry("web/example").unwrap().content(),
+        "HUNTER2\nUSER: EXAMPLE\n"
+    );
+    // the stored content is left untouched
+    assert_eq!(
+        store.file("web/example").unwrap().parse().unwrap(),
+        unchanged
+    );
+    assert_eq!(copy.retrieve_entry("web/example").unwrap(), unchanged);
+
+    let copy = copy.with_transformer(
+        |_: &TransformContext<'_>, _: ParsedEntry| -> Result<ParsedEntry> {
+            Err(PassError::MalformedEntry("rejected".to_string()))
+        },
+    );
+    assert!(matches!(
+        copy.retrieve_entry("web/example"),
+        Err(PassError::MalformedEntry(_))
+    ));
+}
+
+#[test]
+fn test_reference_resolution() {
+    use crate::reference::ReferenceResolver;
+
+    let (_dir, store) = temp_store();
+    store.insert("shared/db", "s3cret\nuser: admin\n").unwrap();
+    store.insert("shared/backup", "!ref shared/db\n").unwrap();
+    store
+        .insert(
+            "servers/db",
+            "!ref shared/db\nuser: john\nbackup: !ref shared/backup\n",
+        )
+        .unwrap();
+    store.insert("loop-a", "!ref loop-b\n").unwrap();
+    store.insert("loop-b", "!ref loop-a\n").unwrap();
+    store.insert("dangling", "!ref missing\n").unwrap();
+    store
+        .insert("spaced", "pw\n  user:\t!ref shared/db  \r\n")
+        .unwrap();
+
+    let shallow = store
+        .clone()
+        .with_transformer(ReferenceResolver::new().with_max_depth(1));
+    let store = store.with_transformer(ReferenceResolver::new());
+
+    let entry = store.retrieve_entry("servers/db").unwrap();
+    assert_eq!(entry.content(), "s3cret\nuser: john\nbackup: s3cret\n");
+    assert_eq!(entry.password(), "s3cret");
+    // the stored content keeps the references
+    assert_eq!(
+        store
+            .file("servers/db")
+            .unwrap()
+            .parse()
+            .unwrap()
+            .password(),
+        "!ref shared/db"
+    );
+
+    match store.retrieve_entry("loop-a") {
+        Err(PassError::ReferenceLoop(chain)) => {
+            assert_eq!(chain, ["loop-a", "loop-b", "loop-a"])
+        }
+        other => panic!("expected a reference loop, got {:?}", other),
+    }
+    match shallow.retrieve_entry("servers/db") {
+        Err(PassError::ReferenceTooDeep { chain, max_depth }) => {
+            assert_eq!(chain, ["servers/db", "shared/backup", "shared/db"]);
+            assert_eq!(max_depth, 1);
+        }
+        other => panic!("expected too deeply nested references, got {:?}", other),
+    }
+    assert_eq!(
+        shallow.retrieve_entry("shared/backup").unwrap().password(),
+        "s3cret"
+    );
+    assert!(matches!(
+        store.retrieve_entry("dangling"),
+        Err(PassError::EntryNotFound(_))
+    ));
+
+    // only the reference itself is replaced
+    assert_eq!(
+        store.retrieve_entry("spaced").unwrap().content(),
+        "pw\n  user:\ts3cret  \r\n"
+    );
+}
+
+#[test]
+fn test_normalize_name() {
+    assert_eq!(normalize_name("/").unwrap(), "");
+    assert_eq!(normalize_name("folder/").unwrap(), "folder");
+    assert_eq!(
+        normalize_name("//folder/./sub//secret").unwrap(),
+        "folder/sub/secret"
+    );
+    assert_eq!(normalize_name("ünïcödé").unwrap(), "ünïcödé");
+    assert!(normalize_name("folder/../../secret").is_err());
+    assert!(normalize_name("secret\0").is_err());
+
+    let root = Path::new("/store");
+    assert!(names::file_path(root, "folder/").is_err());
+    assert!(names::file_path(root, "/").is_err());
+    assert_eq!(
+        names::file_path(root, "/folder/secret").unwrap(),
+        root.join("folder/secret.gpg")
+    );
+
+    set_store_dir();
+    assert!(matches!(
+        retrieve("../simple/secret-a"),
+        Err(PassError::InvalidPassName(..))
+    ));
+    assert!(matches!(
+        retrieve("secret-a/"),
+        Err(PassError::EntryNotFound(..))
+    ));
+    assert!(retrieve("./folder//subsecret-a").is_ok());
+
+    // the internals of git repositories are no entries
+    assert!(normalize_name("sub/.GIT/config").is_err());
+    assert_eq!(normalize_name(".gitignore").unwrap(), ".gitignore");
+    let (dir, store) = temp_store();
+    fs::create_dir(dir.path().join(".git")).unwrap();
+    fs::write(dir.path().join(".git/config.gpg"), b"").unwrap();
+    assert!(matches!(
+        store.retrieve(".git"),
+        Err(PassError::InvalidPassName(..))
+    ));
+    assert!(matches!(
+        store.retrieve(".git/config"),
+        Err(PassError::InvalidPassName(..))
+    ));
+}
+
+#[test]
+fn test_normalize_name_unicode() {
+    // "é" as "e" followed by a combining acute accent
+    let decomposed = "cafe\u{301}/secret";
+    assert_eq!(normalize_name(decomposed).unwrap(), "caf\u{e9}/secret");
+    assert!(normalize_name("secret\nother").is_err());
+    assert!(normalize_name("secret\u{7f}").is_err());
+
+    // entries which were created with decomposed names (like on macOS) are found by their composed name
+    let (_dir, store) = temp_store();
+    fs::create_dir(store.root().join("cafe\u{301}")).unwrap();
+    fs::copy(
+        store.root().join("secret-a.gpg"),
+        store.root().join("cafe\u{301}").join("secret.gpg"),
+    )
+    .unwrap();
+    let file = match store.retrieve("caf\u{e9}/secret").unwrap() {
+        StoreEntry::File(file) => file,
+        StoreEntry::Directory(_) => panic!("expected a file"),
+    };
+    assert_eq!(
+        file.path,
+        store.root().join(decomposed).with_extension("gpg")
+    );
+    assert!(store.list().unwrap().iter().any(|entry| match entry {
+        StoreEntry::File(file) => {
+            names::path_to_name(store.root(), &file.path).unwrap() == "caf\u{e9}/secret"
+        }
+        StoreEntry::Directory(_) => false,
+    }));
+
+    // lookups in listings and indexes accept both spellings
+    let listing = store.listing().unwrap();
+    assert!(listing.get("caf\u{e9}/secret").is_some());
+    assert!(listing.get(decomposed).is_some());
+    let index = store.index().unwrap();
+    assert!(index.get("caf\u{e9}/secret").is_some());
+    assert!(index.get(decomposed).is_some());
+}
+
+mod names_model {
+    use crate::{names, PassError, Store, StoreEntry};
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+    use std::fs;
+    use std::path::Path;
+
+    proptest! {
+        #[test]
+        fn normalized_names_are_canonical(name in r"[a-bä/.\\ ]{0,24}") {
+            match names::normalize_name(&name) {
+                Ok(normalized) => {
+                    prop_assert_eq!(names::normalize_name(&normalized).unwrap(), normalized.clone());
+                    prop_assert!(!normalized.starts_with('/') && !normalized.ends_with('/'));
+                    prop_assert!(normalized.split('/').all(|segment| !matches!(segment, "" | "." | ".."))
+                        || normalized.is_empty());
+
+                    let root = Path::new("/store");
+                    let dir = names::dir_path(root, &normalized).unwrap();
+                    prop_assert!(dir.starts_with(root));
+                    prop_assert_eq!(names::path_to_name(root, &dir).unwrap(), normalized.clone());
+                    if let Ok(file) = names::file_path(root, &normalized) {
+                        prop_assert!(file.starts_with(root));
+                        prop_assert_eq!(names::path_to_name(root, &file).unwrap(), normalized);
+                    }
+                }
+                Err(_) => prop_assert!(name.split('/').any(|segment| segment == "..")),
+            }
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn retrieve_matches_model(
+            entries in prop::collection::hash_set("[ab]{1,2}(/[ab]{1,2}){0,2}", 0..8),
+            query in "/?(\\./)?[ab]{0,2}(//?[ab]{1,2}){0,2}/?",
+        ) {
+            let dir = tempfile::tempdir().unwrap();
+            for entry in &entries {
+                let path = dir.path().join(format!("{}.gpg", entry));
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, b"").unwrap();
+            }
+            let store = Store::open(dir.path()).unwrap();
+
+            let normalized = names::normalize_name(&query).unwrap();
+            let is_dir = normalized.is_empty()
+                || entries.iter().any(|entry| entry.starts_with(&format!("{}/", normalized)));
+            let is_file = !query.ends_with('/') && entries.contains(&normalized);
+            let files = |entry: &StoreEntry| match entry {
+                StoreEntry::File(file) => HashSet::from([file.path.clone()]),
+                StoreEntry::Directory(dir) => dir
+                    .iter()
+                    .filter_map(|entry| match entry {
+                        StoreEntry::File(file) => Some(file.path.clone()),
+                        StoreEntry::Directory(_) => None,
+                    })
+                    .collect(),
+            };
+
+            match (store.retrieve(&query), is_dir, is_file) {
+                (Err(PassError::AmbiguousPassName(_)), true, true) => {}
+                (Err(PassError::EntryNotFound(_)), false, false) => {}
+                (Ok(entry @ StoreEntry::Directory(_)), true, false) => {
+                    let expected = entries
+                        .iter()
+                        .filter(|entry| normalized.is_empty() || entry.starts_with(&format!("{}/", normalized)))
+                        .map(|entry| store.root().join(format!("{}.gpg", entry)))
+                        .collect::<HashSet<_>>();
+                    prop_assert_eq!(files(&entry), expected);
+                }
+                (Ok(entry @ StoreEntry::File(_)), false, true) => {
+                    prop_assert_eq!(
+                        files(&entry),
+                        HashSet::from([store.root().join(format!("{}.gpg", normalized))])
+                    );
+                }
+                (result, is_dir, is_file) => prop_assert!(
+                    false,
+                    "{:?} for query {:?} (dir: {}, file: {})",
+                    result, query, is_dir, is_file
+                ),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_list_matches_retrieve() {
+    let dir = tempfile::tempdir().unwrap();
+    for i in 0..200 {
+        let entry_dir = dir.path().join(format!("group-{}/site-{}", i % 7, i % 31));
+        fs::create_dir_all(&entry_dir).unwrap();
+        fs::write(entry_dir.join(format!("entry-{}.gpg", i)), b"").unwrap();
+        fs::write(entry_dir.join("notes.txt"), b"").unwrap();
+    }
+    fs::create_dir_all(dir.path().join(".git/objects")).unwrap();
+    fs::write(dir.path().join(".git/objects/ignored.gpg"), b"").unwrap();
+
+    let store = Store::open(dir.path()).unwrap();
+    let listed = store.list().unwrap();
+    assert_eq!(listed.len(), 200);
+    match store.retrieve("/").unwrap() {
+        StoreEntry::Directory(root) => {
+            assert_eq!(listed, root.iter().cloned().collect::<HashSet<_>>())
+        }
+        StoreEntry::File(_) => panic!("store root is not a directory"),
+    }
+}
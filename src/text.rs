@@ -0,0 +1,107 @@
+//! Normalization of the plaintext of entries
+//!
+//! Editors on different platforms silently convert line breaks so that entries which are shared between
+//! Windows and Unix machines change although their content did not.
+//! [`Store::with_line_ending()`](crate::Store::with_line_ending) converts the line breaks of text entries to
+//! one style whenever their plaintext is read or written and
+//! [`Store::with_utf8_validation()`](crate::Store::with_utf8_validation) rejects content which is not text.
+
+use crate::secure_mem::{self, LockedRegion};
+use crate::{PassError, Result};
+
+/// A style of line breaks
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum LineEnding {
+    /// `\n` as used on Unix
+    #[default]
+    Lf,
+    /// `\r\n` as used on Windows
+    CrLf,
+}
+
+impl LineEnding {
+    /// The characters which end a line in this style
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+
+    /// The style of the first line break in *text* or `None` if it does not contain any
+    pub fn detect(text: &str) -> Option<Self> {
+        let end = text.find('\n')?;
+        match text[..end].ends_with('\r') {
+            true => Some(Self::CrLf),
+            false => Some(Self::Lf),
+        }
+    }
+
+    /// Convert all line breaks (`\n` and `\r\n`) of *text* to this style
+    pub fn apply(self, text: &str) -> String {
+        let mut converted = String::with_capacity(text.len());
+        for line in text.split_inclusive('\n') {
+            match line.strip_suffix('\n') {
+                Some(line) => {
+                    secure_mem::push_str(&mut converted, line.strip_suffix('\r').unwrap_or(line));
+                    secure_mem::push_str(&mut converted, self.as_str());
+                }
+                None => secure_mem::push_str(&mut converted, line),
+            }
+        }
+        converted
+    }
+}
+
+/// How the plaintext of entries is checked and normalized when it is read or written
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub(crate) struct TextOptions {
+    pub(crate) line_ending: Option<LineEnding>,
+    pub(crate) require_utf8: bool,
+}
+
+impl TextOptions {
+    /// Check *plaintext* and convert its line breaks according to these options
+    ///
+    /// `None` is returned if the content is already normalized.
+    /// Content which is not valid UTF-8 is left unchanged unless UTF-8 is required.
+    ///
+    /// ## Errors
+    /// [`PassError::MalformedEntry`] is returned if UTF-8 is required but *plaintext* is not valid UTF-8.
+    pub(crate) fn normalize(&self, plaintext: &[u8]) -> Result<Option<Vec<u8>>> {
+        let text = match std::str::from_utf8(plaintext) {
+            Ok(text) => text,
+            Err(_) if self.require_utf8 => {
+                return Err(PassError::MalformedEntry(
+                    "The content is not valid UTF-8".to_string(),
+                ))
+            }
+            Err(_) => return Ok(None),
+        };
+        match self.line_ending {
+            Some(ending) if needs_conversion(text, ending) => {
+                Ok(Some(ending.apply(text).into_bytes()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Like [`normalize()`](TextOptions::normalize) but take ownership of *plaintext* and zero it if it had to
+    /// be converted or is rejected
+    pub(crate) fn apply(&self, mut plaintext: Vec<u8>) -> Result<Vec<u8>> {
+        let normalized = self.normalize(&plaintext);
+        if !matches!(normalized, Ok(None)) {
+            secure_mem::release(&mut plaintext, &mut LockedRegion::default());
+        }
+        Ok(normalized?.unwrap_or(plaintext))
+    }
+}
+
+/// Whether *text* contains a line break which is not in the style *ending*
+fn needs_conversion(text: &str, ending: LineEnding) -> bool {
+    let crlf = text.matches("\r\n").count();
+    match ending {
+        LineEnding::Lf => crlf > 0,
+        LineEnding::CrLf => text.matches('\n').count() > crlf,
+    }
+}
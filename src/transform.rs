@@ -0,0 +1,138 @@
+//! Post-processing of decrypted entries when they are retrieved
+//!
+//! Transformers which are registered with [`Store::with_transformer()`](crate::Store::with_transformer) run
+//! in order on every entry which is retrieved with [`Store::retrieve_entry()`](crate::Store::retrieve_entry).
+//! Each one receives the result of the previous one, so features like template expansion can be composed.
+//! The stored content of entries is never changed by transformers.
+//!
+//! Any closure taking a [`TransformContext`] and a [`ParsedEntry`] can be used as transformer.
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::transform::TransformContext;
+//! use libpass::{ParsedEntry, Result, Store};
+//!
+//! // hide comment lines from frontends
+//! let store = Store::from_env().unwrap().with_transformer(
+//!     |_: &TransformContext<'_>, entry: ParsedEntry| -> Result<ParsedEntry> {
+//!         let content = entry
+//!             .content()
+//!             .split_inclusive('\n')
+//!             .filter(|line| !line.starts_with('#'))
+//!             .collect::<String>();
+//!         Ok(ParsedEntry::from(content).with_mode(entry.mode()))
+//!     },
+//! );
+//! let entry = store.retrieve_entry("github.com").unwrap();
+//! ```
+
+#[cfg(doc)]
+use crate::PassError;
+use crate::{ParsedEntry, Result, Store};
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// A post-processing step which is applied to decrypted entries
+pub trait EntryTransformer: Send + Sync {
+    /// Transform *entry* which was retrieved as described by *context*
+    fn transform(&self, context: &TransformContext<'_>, entry: ParsedEntry) -> Result<ParsedEntry>;
+}
+
+impl<F> EntryTransformer for F
+where
+    F: Fn(&TransformContext<'_>, ParsedEntry) -> Result<ParsedEntry> + Send + Sync,
+{
+    fn transform(&self, context: &TransformContext<'_>, entry: ParsedEntry) -> Result<ParsedEntry> {
+        self(context, entry)
+    }
+}
+
+/// Information about the entry which is being transformed
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct TransformContext<'a> {
+    store: &'a Store,
+    chain: &'a [String],
+}
+
+impl<'a> TransformContext<'a> {
+    /// Describe the transformation of the last entry of *chain*, which must not be empty
+    pub(crate) fn new(store: &'a Store, chain: &'a [String]) -> Self {
+        debug_assert!(!chain.is_empty());
+        Self { store, chain }
+    }
+
+    /// The name of the entry which is being transformed
+    pub fn pass_name(&self) -> &'a str {
+        self.chain.last().map(String::as_str).unwrap_or_default()
+    }
+
+    /// The store from which the entry was retrieved
+    pub fn store(&self) -> &'a Store {
+        self.store
+    }
+
+    /// The names of the entries which are being retrieved, starting with the one which was requested and
+    /// ending with [`pass_name()`](TransformContext::pass_name)
+    ///
+    /// The chain is longer than one entry while a transformer retrieves other entries with
+    /// [`retrieve_entry()`](TransformContext::retrieve_entry).
+    pub fn chain(&self) -> &'a [String] {
+        self.chain
+    }
+
+    /// How many entries are retrieved on behalf of the entry which was requested
+    pub fn depth(&self) -> usize {
+        self.chain.len().saturating_sub(1)
+    }
+
+    /// Retrieve the entry *pass_name* like [`Store::retrieve_entry()`] does, running all transformers on it
+    ///
+    /// ## Errors
+    /// [`PassError::ReferenceLoop`] is returned if the entry is already being retrieved further up the
+    /// [chain](TransformContext::chain).
+    pub fn retrieve_entry(&self, pass_name: &str) -> Result<ParsedEntry> {
+        self.store.retrieve_entry_in(pass_name, self.chain)
+    }
+}
+
+/// The transformers of a store
+///
+/// Transformers are arbitrary closures which can not be compared, so all instances are equal.
+#[derive(Clone, Default)]
+pub(crate) struct Transformers(Vec<Arc<dyn EntryTransformer>>);
+
+impl Transformers {
+    pub(crate) fn add(&mut self, transformer: Arc<dyn EntryTransformer>) {
+        self.0.push(transformer);
+    }
+
+    /// Run all transformers in order on *entry*, stopping at the first one which fails
+    pub(crate) fn run(
+        &self,
+        context: &TransformContext<'_>,
+        entry: ParsedEntry,
+    ) -> Result<ParsedEntry> {
+        self.0.iter().try_fold(entry, |entry, transformer| {
+            transformer.transform(context, entry)
+        })
+    }
+}
+
+impl Debug for Transformers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Transformers").field(&self.0.len()).finish()
+    }
+}
+
+impl PartialEq for Transformers {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Transformers {}
+
+impl Hash for Transformers {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
@@ -1,10 +1,13 @@
 //! General utilities used internally
 
-use crate::{password_store_dir, PassError, Result};
+use crate::{PassError, Result};
 
-use std::io;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use directories::UserDirs;
 use gpgme::{Context, Protocol};
@@ -30,14 +33,183 @@ pub(crate) fn canonicalize_path<P: AsRef<Path>>(path: &P) -> io::Result<PathBuf>
     }
 }
 
+/// Counter which makes the names of temporary files unique between threads of the same process
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Replace the file at *path* with *content* without ever leaving it in a partially written state
+///
+/// The content is written to a temporary file in the same directory which is then renamed to *path*.
+/// If *path* already exists, its permissions are kept. Otherwise the file is created with permissions that
+/// are derived from *umask* (see [`create_dir_all()`]).
+pub(crate) fn write_atomically(path: &Path, content: &[u8], umask: u32) -> io::Result<()> {
+    write_atomically_with(path, umask, |file| file.write_all(content))
+}
+
+/// Like [`write_atomically()`] but let *write* produce the content, e.g. by streaming it into the file
+///
+/// *path* is left untouched if *write* fails.
+pub(crate) fn write_atomically_with<E: From<io::Error>>(
+    path: &Path,
+    umask: u32,
+    write: impl FnOnce(&mut File) -> std::result::Result<(), E>,
+) -> std::result::Result<(), E> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path does not name a file"))?;
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let permissions = match fs::metadata(path) {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => file_permissions(umask),
+        Err(e) => return Err(e.into()),
+    };
+
+    let result = create_new_file(&tmp_path, permissions.as_ref())
+        .map_err(E::from)
+        .and_then(|mut tmp_file| {
+            // the process umask may have cleared bits during creation which the file should have
+            if let Some(permissions) = permissions {
+                if tmp_file.metadata()?.permissions() != permissions {
+                    tmp_file.set_permissions(permissions)?;
+                }
+            }
+            write(&mut tmp_file)?;
+            Ok(tmp_file.sync_all()?)
+        })
+        .and_then(|_| Ok(fs::rename(&tmp_path, path)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Create a new file at *path* which is never accessible with more than *permissions*
+///
+/// The permissions are passed to the `open` call so that there is no window in which the file is readable by
+/// others before its permissions are restricted.
+fn create_new_file(path: &Path, permissions: Option<&fs::Permissions>) -> io::Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    if let Some(permissions) = permissions {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(permissions.mode() & 0o7777);
+    }
+    #[cfg(not(unix))]
+    let _ = permissions;
+    options.open(path)
+}
+
+/// Recursively create the directory at *path* and all of its missing parents
+///
+/// Like *pass* does with `PASSWORD_STORE_UMASK`, new directories get the permissions `0777 & !umask` and new
+/// files get `0666 & !umask` regardless of the umask of the current process.
+/// Permissions are only applied on unix systems.
+pub(crate) fn create_dir_all(path: &Path, umask: u32) -> io::Result<()> {
+    if path.is_dir() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent, umask)?;
+    }
+    match fs::create_dir(path) {
+        Ok(()) => match dir_permissions(umask) {
+            Some(permissions) => fs::set_permissions(path, permissions),
+            None => Ok(()),
+        },
+        // another process may have created it concurrently
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists && path.is_dir() => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(unix)]
+fn file_permissions(umask: u32) -> Option<fs::Permissions> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(fs::Permissions::from_mode(0o666 & !umask))
+}
+
+#[cfg(unix)]
+fn dir_permissions(umask: u32) -> Option<fs::Permissions> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(fs::Permissions::from_mode(0o777 & !umask))
+}
+
+#[cfg(not(unix))]
+fn file_permissions(_umask: u32) -> Option<fs::Permissions> {
+    None
+}
+
+#[cfg(not(unix))]
+fn dir_permissions(_umask: u32) -> Option<fs::Permissions> {
+    None
+}
+
 /// Create a gpgme context that is initialized as we need it
-pub(crate) fn create_gpg_context() -> Result<Context> {
-    Ok(Context::from_protocol(Protocol::OpenPgp)?)
+///
+/// See [`configure_gpg_context()`] for *offline* and *gpg_home*.
+/// Use [`CryptoOptions::new_context()`](crate::crypto::CryptoOptions::new_context) instead of calling this
+/// directly so that the options of a store are applied.
+pub(crate) fn create_gpg_context(offline: bool, gpg_home: Option<&Path>) -> Result<Context> {
+    let mut gpg_ctx = Context::from_protocol(Protocol::OpenPgp)?;
+    configure_gpg_context(&mut gpg_ctx, offline, gpg_home)?;
+    Ok(gpg_ctx)
+}
+
+/// Prevent *gpg_ctx* from accessing the network if *offline* is set and let it use the keyring in
+/// *gpg_home* (like `GNUPGHOME` does) or the default keyring if it is `None`
+pub(crate) fn configure_gpg_context(
+    gpg_ctx: &mut Context,
+    offline: bool,
+    gpg_home: Option<&Path>,
+) -> Result<()> {
+    gpg_ctx.set_offline(offline);
+    let gpg_home = gpg_home
+        .map(|path| {
+            path.to_str().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The gpg home directory is not valid UTF-8",
+                )
+            })
+        })
+        .transpose()?;
+    gpg_ctx.set_engine_info(None::<&str>, gpg_home)?;
+    Ok(())
 }
 
-/// Transform an absolute path to a path that is relative to the password store root
-pub(crate) fn abspath2relpath(path: &Path) -> Result<&Path> {
-    path.strip_prefix(password_store_dir()?).map_err(|_| {
+/// Check that *key* is usable as a recipient for encryption operations
+pub(crate) fn check_key_usable(key: &gpgme::Key) -> Result<()> {
+    let reason = if key.is_revoked() {
+        "key is revoked"
+    } else if key.is_expired() {
+        "key is expired"
+    } else if key.is_disabled() {
+        "key is disabled"
+    } else if key.is_invalid() {
+        "key is invalid"
+    } else if !key.can_encrypt() {
+        "key has no usable encryption capability"
+    } else {
+        return Ok(());
+    };
+
+    Err(PassError::UnusableRecipient {
+        key_id: key.id().unwrap_or_default().to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+/// Transform an absolute path to a path that is relative to the root of its store at *root*
+pub(crate) fn abspath2relpath<'a>(root: &Path, path: &'a Path) -> Result<&'a Path> {
+    path.strip_prefix(root).map_err(|_| {
         PassError::InvalidStoreFormat(
             path.to_owned(),
             "Path is not inside password store".to_string(),
@@ -50,3 +222,93 @@ pub(crate) fn path2str(path: &Path) -> Result<&str> {
     path.to_str()
         .ok_or_else(|| PassError::PathDecodingError(path.to_owned()))
 }
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The URL and filename safe alphabet of base64 (RFC 4648, section 5) as used by WebAuthn
+const BASE64URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Decode standard base64 with optional padding or return `None` if *encoded* contains other characters
+pub(crate) fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    decode_with(encoded, BASE64_ALPHABET)
+}
+
+/// Encode *data* as standard base64 with padding
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    encode_with(data, BASE64_ALPHABET, true)
+}
+
+/// Decode URL safe base64 with optional padding or return `None` if *encoded* contains other characters
+pub(crate) fn base64url_decode(encoded: &str) -> Option<Vec<u8>> {
+    decode_with(encoded, BASE64URL_ALPHABET)
+}
+
+/// Encode *data* as URL safe base64 without padding
+pub(crate) fn base64url_encode(data: &[u8]) -> String {
+    encode_with(data, BASE64URL_ALPHABET, false)
+}
+
+fn decode_with(encoded: &str, alphabet: &[u8; 64]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(encoded.len() / 4 * 3);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in encoded.bytes().filter(|c| *c != b'=') {
+        let value = alphabet.iter().position(|a| *a == c)? as u32;
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn encode_with(data: &[u8], alphabet: &[u8; 64], padding: bool) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let buffer = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |buffer, (i, b)| buffer | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(alphabet[(buffer >> (18 - 6 * i)) as usize & 0x3f] as char),
+                false if padding => out.push('='),
+                false => {}
+            }
+        }
+    }
+    out
+}
+
+/// The number of days between the unix epoch and the given date of the proleptic Gregorian calendar
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The date of the proleptic Gregorian calendar which is the given number of days after the unix epoch
+///
+/// This is the inverse of [`days_from_civil()`].
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,199 @@
+//! Access to a subset of the entries of a store
+//!
+//! Large organizations often only grant parts of a shared store to a service, e.g. `team-a/` to the
+//! deployment daemon of team a.
+//! A [`StoreView`] wraps a [`Store`] and confines listing, retrieving, inserting and removing entries to a
+//! set of subpaths.
+//! Attempts to access anything outside of them fail with [`PassError::AccessDenied`].
+//!
+//! ## Example
+//! ```no_run
+//! use libpass::{PassError, Store};
+//!
+//! let view = Store::from_env().unwrap().view(["team-a", "shared/ci"]).unwrap();
+//! assert!(view.retrieve("team-a/database").is_ok());
+//! assert!(matches!(view.retrieve("team-b/database"), Err(PassError::AccessDenied(_))));
+//! ```
+
+use crate::file_io::RwPlainFile;
+use crate::generate::PasswordGenerator;
+use crate::secure::SecretString;
+use crate::telemetry::debug;
+use crate::{
+    names, InsertSource, OverwritePolicy, PassError, Result, Store, StoreEntry, StoreFileRef,
+};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// The path of the file or directory referenced by *entry*
+fn entry_path(entry: &StoreEntry) -> &Path {
+    match entry {
+        StoreEntry::File(file) => &file.path,
+        StoreEntry::Directory(dir) => &dir.path,
+    }
+}
+
+/// A store whose operations are restricted to a set of subpaths
+///
+/// Get an instance of this by calling [`Store::view()`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct StoreView {
+    store: Store,
+    subpaths: Vec<String>,
+}
+
+impl StoreView {
+    /// Restrict *store* to the entries below *subpaths*
+    pub(crate) fn new<S: AsRef<str>>(
+        store: Store,
+        subpaths: impl IntoIterator<Item = S>,
+    ) -> Result<Self> {
+        let mut normalized = subpaths
+            .into_iter()
+            .map(|subpath| names::normalize_name(subpath.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        normalized.sort_unstable();
+        normalized.dedup();
+        Ok(Self {
+            store,
+            subpaths: normalized,
+        })
+    }
+
+    /// The root directory of the underlying store
+    pub fn root(&self) -> &Path {
+        self.store.root()
+    }
+
+    /// The normalized subpaths to which this view is restricted, sorted alphabetically
+    pub fn subpaths(&self) -> &[String] {
+        &self.subpaths
+    }
+
+    /// Whether *pass_name* lies inside of one of the subpaths of this view
+    ///
+    /// Names are normalized before comparing them (see [`names::normalize_name()`]) and invalid names are never
+    /// accessible.
+    pub fn is_accessible(&self, pass_name: &str) -> bool {
+        match names::normalize_name(pass_name) {
+            Ok(name) => self.contains(&name),
+            Err(_) => false,
+        }
+    }
+
+    /// Whether the normalized *name* lies inside of one of the subpaths
+    fn contains(&self, name: &str) -> bool {
+        self.subpaths.iter().any(|subpath| {
+            subpath.is_empty()
+                || name == subpath
+                || name
+                    .strip_prefix(subpath.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+
+    /// Fail with [`PassError::AccessDenied`] if *pass_name* is not accessible
+    fn check_name(&self, pass_name: &str) -> Result<()> {
+        let name = names::normalize_name(pass_name)?;
+        if self.contains(&name) {
+            Ok(())
+        } else {
+            debug!("Denying access to {} outside of the store view", name);
+            Err(PassError::AccessDenied(name))
+        }
+    }
+
+    /// Fail with [`PassError::AccessDenied`] if the file or directory at *path* is not accessible
+    fn check_path(&self, path: &Path) -> Result<()> {
+        self.check_name(&names::path_to_name(self.store.root(), path)?)
+    }
+
+    /// List all passwords inside of this view in a flat data structure
+    ///
+    /// See [`Store::list()`].
+    pub fn list(&self) -> Result<HashSet<StoreEntry>> {
+        let root = self.store.root();
+        Ok(self
+            .store
+            .list()?
+            .into_iter()
+            .filter(|entry| {
+                names::path_to_name(root, entry_path(entry)).is_ok_and(|name| self.contains(&name))
+            })
+            .collect())
+    }
+
+    /// Retrieve the stored entry identified by *pass_name*
+    ///
+    /// See [`Store::retrieve()`].
+    /// Aliases which resolve to an entry outside of this view are not accessible either.
+    pub fn retrieve(&self, pass_name: &str) -> Result<StoreEntry> {
+        self.check_name(pass_name)?;
+        let entry = self.store.retrieve(pass_name)?;
+        self.check_path(entry_path(&entry))?;
+        Ok(entry)
+    }
+
+    /// Get a read-write IO handle to the plaintext content of *file*
+    ///
+    /// See [`Store::plain_io_rw()`].
+    pub fn plain_io_rw(&self, file: &StoreFileRef) -> Result<RwPlainFile> {
+        self.check_path(&file.path)?;
+        self.store.plain_io_rw(file)
+    }
+
+    /// Insert a new entry named *pass_name*
+    ///
+    /// See [`Store::insert()`].
+    pub fn insert<'a>(
+        &self,
+        pass_name: &str,
+        content: impl Into<InsertSource<'a>>,
+    ) -> Result<StoreFileRef> {
+        self.insert_with(pass_name, content, OverwritePolicy::Error)
+    }
+
+    /// Insert a new entry named *pass_name* and handle an existing entry according to *policy*
+    ///
+    /// See [`Store::insert_with()`].
+    pub fn insert_with<'a>(
+        &self,
+        pass_name: &str,
+        content: impl Into<InsertSource<'a>>,
+        policy: OverwritePolicy,
+    ) -> Result<StoreFileRef> {
+        self.check_name(pass_name)?;
+        self.store.insert_with(pass_name, content, policy)
+    }
+
+    /// Generate a password and store it in a new entry named *pass_name*
+    ///
+    /// See [`Store::generate()`].
+    pub fn generate(
+        &self,
+        pass_name: &str,
+        generator: &PasswordGenerator,
+        policy: OverwritePolicy,
+    ) -> Result<(StoreFileRef, SecretString)> {
+        self.check_name(pass_name)?;
+        self.store.generate(pass_name, generator, policy)
+    }
+
+    /// Copy the entry *from* to a new entry named *to*
+    ///
+    /// See [`Store::copy()`].
+    /// Both names must be accessible.
+    pub fn copy(&self, from: &str, to: &str, policy: OverwritePolicy) -> Result<StoreFileRef> {
+        self.check_name(from)?;
+        self.check_name(to)?;
+        self.store.copy(from, to, policy)
+    }
+
+    /// Remove the entry named *pass_name*
+    ///
+    /// See [`Store::remove()`].
+    pub fn remove(&self, pass_name: &str) -> Result<()> {
+        self.check_name(pass_name)?;
+        self.store.remove(pass_name)
+    }
+}
@@ -0,0 +1,195 @@
+//! Fast traversal of the store hierarchy
+//!
+//! Listing large stores is dominated by reading directories so these are read in parallel by a small pool of
+//! threads which share a queue of directories that still need to be read.
+
+use crate::{PassError, Result};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+/// Upper bound for the number of threads reading directories concurrently
+const MAX_THREADS: usize = 8;
+
+/// Directories which still need to be read and the state of the threads reading them
+#[derive(Debug, Default)]
+struct Queue {
+    dirs: Vec<PathBuf>,
+    /// Number of threads currently reading a directory (which may add more directories)
+    busy: usize,
+    error: Option<PassError>,
+}
+
+/// The file extension of entry files
+pub(crate) const GPG: &[&str] = &["gpg"];
+/// The file extensions of entry files in stores which also contain ASCII armored `*.asc` entries
+pub(crate) const GPG_AND_ASC: &[&str] = &["gpg", "asc"];
+
+/// Collect the paths of all entry files (`*.gpg`) below *root*
+///
+/// Like [`retrieve()`](crate::Store::retrieve), git repositories are skipped and anything that is neither a
+/// file nor a directory is an error.
+/// The order of the returned paths is unspecified.
+pub(crate) fn entry_files(root: &Path) -> Result<Vec<PathBuf>> {
+    entry_files_with(root, GPG)
+}
+
+/// Collect the paths of all files below *root* which have one of the given *extensions*
+///
+/// See [`entry_files()`].
+pub(crate) fn entry_files_with(root: &Path, extensions: &[&str]) -> Result<Vec<PathBuf>> {
+    let (mut files, dirs) = read_dir(root, extensions)?;
+    let threads = thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(MAX_THREADS)
+        .min(dirs.len());
+    if threads <= 1 {
+        let mut dirs = dirs;
+        while let Some(dir) = dirs.pop() {
+            let (dir_files, subdirs) = read_dir(&dir, extensions)?;
+            files.extend(dir_files);
+            dirs.extend(subdirs);
+        }
+        return Ok(files);
+    }
+
+    let queue = Mutex::new(Queue {
+        dirs,
+        ..Default::default()
+    });
+    let changed = Condvar::new();
+    thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| scope.spawn(|| work(&queue, &changed, extensions)))
+            .collect::<Vec<_>>();
+        for worker in workers {
+            match worker.join() {
+                Ok(worker_files) => files.extend(worker_files),
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        }
+    });
+
+    match queue
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .error
+    {
+        Some(e) => Err(e),
+        None => Ok(files),
+    }
+}
+
+/// Apply *f* to all *items* on a bounded number of threads
+///
+/// The results keep the order of *items* and if *f* fails for some items, the first of these errors is
+/// returned.
+pub(crate) fn try_map_parallel<T: Sync, R: Send>(
+    items: &[T],
+    f: impl Fn(&T) -> Result<R> + Sync,
+) -> Result<Vec<R>> {
+    let threads = thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(MAX_THREADS)
+        .min(items.len());
+    if threads <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let f = &f;
+    thread::scope(|scope| {
+        let workers = items
+            .chunks(items.len().div_ceil(threads))
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Result<Vec<_>>>()))
+            .collect::<Vec<_>>();
+        let mut results = Vec::with_capacity(items.len());
+        for worker in workers {
+            match worker.join() {
+                Ok(chunk_results) => results.extend(chunk_results?),
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        }
+        Ok(results)
+    })
+}
+
+/// Read directories from *queue* until all directories were read or an error occurred
+fn work(queue: &Mutex<Queue>, changed: &Condvar, extensions: &[&str]) -> Vec<PathBuf> {
+    let lock = || {
+        queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    };
+    let mut files = Vec::new();
+    loop {
+        let dir = {
+            let mut state = lock();
+            loop {
+                if state.error.is_some() {
+                    return files;
+                }
+                if let Some(dir) = state.dirs.pop() {
+                    state.busy += 1;
+                    break dir;
+                }
+                if state.busy == 0 {
+                    return files;
+                }
+                state = changed
+                    .wait(state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+        };
+
+        let result = read_dir(&dir, extensions);
+        let mut state = lock();
+        state.busy -= 1;
+        match result {
+            Ok((dir_files, subdirs)) => {
+                files.extend(dir_files);
+                state.dirs.extend(subdirs);
+            }
+            Err(e) => {
+                state.error.get_or_insert(e);
+            }
+        }
+        changed.notify_all();
+    }
+}
+
+/// Read the entry files with one of the given *extensions* and subdirectories which are directly contained
+/// in *dir*
+fn read_dir(dir: &Path, extensions: &[&str]) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            // git repositories (including those of submodules) are not part of the entry hierarchy
+            if path.file_name() != Some(OsStr::new(".git")) {
+                dirs.push(path);
+            }
+        } else if file_type.is_file() {
+            if has_extension(&path, extensions) {
+                files.push(path);
+            }
+        } else {
+            return Err(PassError::InvalidStoreFormat(
+                path,
+                "File is neither a string nor directory but pass stores can only contain those types of files"
+                    .to_string(),
+            ));
+        }
+    }
+    Ok((files, dirs))
+}
+
+/// Whether *path* has one of the given *extensions*
+pub(crate) fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .is_some_and(|extension| extensions.iter().any(|candidate| extension == *candidate))
+}
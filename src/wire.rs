@@ -0,0 +1,205 @@
+//! A stable, versioned serialization format for embedding this library behind a daemon or other IPC
+//!
+//! The types of this module mirror entries, listings and errors of the library in a form which only contains
+//! plain data and can be serialized with any serde format.
+//! Every message is wrapped into a [`Versioned`] envelope which carries the [`SCHEMA_VERSION`].
+//!
+//! The schema version is only incremented for incompatible changes.
+//! Compatible changes (like new fields, entry kinds or error codes) keep the version so clients have to
+//! ignore what they do not know: unknown fields are ignored and unknown enum values are deserialized as an
+//! `Unknown` variant or `None`.
+//!
+//! This module is only available with the `serde` feature.
+//!
+//! ## Example
+//! ```
+//! use libpass::wire::{Versioned, WireListing};
+//! use libpass::Store;
+//!
+//! # let store = Store::open(std::env::current_dir().unwrap().join("tests/simple")).unwrap();
+//! let listing = Versioned::new(WireListing::from_store(&store).unwrap());
+//! let json = serde_json::to_string(&listing).unwrap();
+//!
+//! // in the client
+//! let listing: Versioned<WireListing> = serde_json::from_str(&json).unwrap();
+//! let listing = listing.into_data().unwrap();
+//! assert!(listing.entries.iter().any(|entry| entry.name == "secret-a"));
+//! ```
+
+use crate::{PassError, Result, Store, StoreEntry, Suggestion};
+use serde::de::value::StrDeserializer;
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// The version of the schema which is produced and understood by this version of the library
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A message of the wire format together with the schema version it was produced with
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    /// The schema version of *data*
+    pub version: u32,
+    /// The actual message
+    pub data: T,
+}
+
+impl<T> Versioned<T> {
+    /// Wrap *data* into an envelope of the current [`SCHEMA_VERSION`]
+    pub fn new(data: T) -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            data,
+        }
+    }
+
+    /// Unwrap the message after checking that its schema version is understood
+    ///
+    /// ## Errors
+    /// [`PassError::UnsupportedSchemaVersion`] is returned if the message was produced with a newer schema
+    /// version.
+    pub fn into_data(self) -> Result<T> {
+        if self.version > SCHEMA_VERSION {
+            return Err(PassError::UnsupportedSchemaVersion(self.version));
+        }
+        Ok(self.data)
+    }
+}
+
+/// The kind of an entry
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireEntryKind {
+    /// An entry which holds content
+    File,
+    /// A directory containing other entries
+    Directory,
+    /// A kind which was introduced by a newer version of the schema
+    #[serde(other)]
+    Unknown,
+}
+
+/// An entry of a store
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct WireEntry {
+    /// The name of the entry relative to the store root
+    pub name: String,
+    /// Whether the entry is a file or a directory
+    pub kind: WireEntryKind,
+}
+
+impl WireEntry {
+    /// Describe *entry* of *store*
+    pub fn from_entry(store: &Store, entry: &StoreEntry) -> Result<Self> {
+        let (path, kind) = match entry {
+            StoreEntry::File(file) => (&file.path, WireEntryKind::File),
+            StoreEntry::Directory(dir) => (&dir.path, WireEntryKind::Directory),
+        };
+        Ok(Self {
+            name: store.relative_name(path)?,
+            kind,
+        })
+    }
+}
+
+/// The flat listing of all entries of a store
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
+pub struct WireListing {
+    /// The entries sorted by name
+    pub entries: Vec<WireEntry>,
+}
+
+impl WireListing {
+    /// List all entries of *store* like [`Store::list()`]
+    pub fn from_store(store: &Store) -> Result<Self> {
+        let mut entries = store
+            .list()?
+            .iter()
+            .map(|entry| WireEntry::from_entry(store, entry))
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        Ok(Self { entries })
+    }
+}
+
+/// An error which occurred while serving a request
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct WireError {
+    /// A stable code identifying the kind of error like `entry_not_found`
+    ///
+    /// New codes may be introduced without changing the schema version.
+    pub code: String,
+    /// A human readable description of the error
+    pub message: String,
+    /// A remediation which could resolve the error, see [`PassError::suggestion()`]
+    #[serde(default, deserialize_with = "lenient_suggestion")]
+    pub suggestion: Option<Suggestion>,
+}
+
+impl From<&PassError> for WireError {
+    fn from(error: &PassError) -> Self {
+        Self {
+            code: error_code(error).to_string(),
+            message: error.to_string(),
+            suggestion: error.suggestion(),
+        }
+    }
+}
+
+/// The stable code of *error*
+fn error_code(error: &PassError) -> &'static str {
+    match error {
+        PassError::PasswordStoreNotFound(_) => "password_store_not_found",
+        PassError::InvalidStoreFormat(..) => "invalid_store_format",
+        PassError::StoreNotInitialized(_) => "store_not_initialized",
+        PassError::InsecurePermissions { .. } => "insecure_permissions",
+        PassError::InvalidConfig { .. } => "invalid_config",
+        PassError::AmbiguousPassName(_) => "ambiguous_pass_name",
+        PassError::EntryNotFound(_) => "entry_not_found",
+        PassError::NotAFile(_) => "not_a_file",
+        PassError::NotADirectory(_) => "not_a_directory",
+        PassError::EntryAlreadyExists(_) => "entry_already_exists",
+        PassError::EntryProtected(_) => "entry_protected",
+        PassError::InvalidPassName(..) => "invalid_pass_name",
+        PassError::InvalidField { .. } => "invalid_field",
+        PassError::InvalidTemplateValues { .. } => "invalid_template_values",
+        PassError::MalformedEntry(_) => "malformed_entry",
+        PassError::PathDecodingError(_) => "path_decoding_error",
+        PassError::InvalidCiphertext(..) => "invalid_ciphertext",
+        PassError::GpgKeyNotFoundError(_) => "gpg_key_not_found",
+        PassError::UnusableRecipient { .. } => "unusable_recipient",
+        PassError::IOError { .. } => "io_error",
+        PassError::CardMissing { .. } => "card_missing",
+        PassError::PinRequired { .. } => "pin_required",
+        PassError::AgentUnavailable { .. } => "agent_unavailable",
+        PassError::InvalidGpgIdSignature { .. } => "invalid_gpg_id_signature",
+        PassError::RateLimited { .. } => "rate_limited",
+        PassError::Timeout { .. } => "timeout",
+        PassError::AccessDenied(_) => "access_denied",
+        PassError::InvalidBundle(_) => "invalid_bundle",
+        PassError::PassCliFailed { .. } => "pass_cli_failed",
+        PassError::AliasLoop(_) => "alias_loop",
+        PassError::ReferenceLoop(_) => "reference_loop",
+        PassError::ReferenceTooDeep { .. } => "reference_too_deep",
+        #[cfg(feature = "layout")]
+        PassError::InvalidLayoutRule { .. } => "invalid_layout_rule",
+        #[cfg(feature = "otp-qr")]
+        PassError::NoOtpQrCode(_) => "no_otp_qr_code",
+        PassError::UnsupportedSchemaVersion(_) => "unsupported_schema_version",
+        #[cfg(feature = "git")]
+        PassError::GitError(_) => "git_error",
+        #[cfg(feature = "git")]
+        PassError::GitConflict(_) => "git_conflict",
+        PassError::GpgError { .. } => "gpg_error",
+    }
+}
+
+/// Deserialize a suggestion, mapping suggestions which were introduced by newer versions to `None`
+fn lenient_suggestion<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Suggestion>, D::Error> {
+    let value = Option::<String>::deserialize(deserializer)?;
+    Ok(value.and_then(|value| {
+        let value: StrDeserializer<'_, D::Error> = value.as_str().into_deserializer();
+        Suggestion::deserialize(value).ok()
+    }))
+}